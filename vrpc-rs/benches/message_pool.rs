@@ -0,0 +1,49 @@
+//! Compare allocating a message per receive with recycling them from a pool
+//!
+//! The pool keeps only the `etype` buffer, so this measures the saving on
+//! that allocation.
+//!
+//! Run with: cargo bench --bench message_pool
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use serde_json::json;
+use std::hint::black_box;
+use vuer_rpc::{Message, MessagePool};
+
+const MESSAGES: usize = 100_000;
+
+fn fill(msg: &mut Message, i: usize) {
+    msg.etype.push_str("CAMERA_MOVE");
+    msg.ts = i as i64;
+    msg.value = Some(json!(i));
+}
+
+fn bench_message_pool(c: &mut Criterion) {
+    let mut group = c.benchmark_group("100k messages");
+
+    group.bench_function("allocate", |b| {
+        b.iter(|| {
+            for i in 0..MESSAGES {
+                let mut msg = Message::default();
+                fill(&mut msg, i);
+                black_box(&msg);
+            }
+        })
+    });
+
+    let pool = MessagePool::new(64);
+    group.bench_function("pool", |b| {
+        b.iter(|| {
+            for i in 0..MESSAGES {
+                let mut msg = pool.acquire();
+                fill(&mut msg, i);
+                black_box(&*msg);
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_message_pool);
+criterion_main!(benches);
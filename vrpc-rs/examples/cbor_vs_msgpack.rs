@@ -0,0 +1,67 @@
+//! Payload size comparison between CBOR and MessagePack
+//!
+//! Run with: cargo run --release --example cbor_vs_msgpack --features cbor
+
+use serde::Serialize;
+use serde_json::json;
+use std::time::Instant;
+use vuer_rpc::cbor_backend::{deserialize_cbor, serialize_cbor};
+use vuer_rpc::prelude::*;
+
+const ITERATIONS: u32 = 1000;
+
+fn compare<T>(label: &str, value: &T) -> Result<()>
+where
+    T: Serialize + serde::de::DeserializeOwned,
+{
+    let msgpack = serialize(value)?;
+    let cbor = serialize_cbor(value)?;
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        let _: T = deserialize(&serialize(value)?)?;
+    }
+    let msgpack_time = start.elapsed() / ITERATIONS;
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        let _: T = deserialize_cbor(&serialize_cbor(value)?)?;
+    }
+    let cbor_time = start.elapsed() / ITERATIONS;
+
+    println!("{}:", label);
+    println!("   MessagePack: {:>8} bytes, {:?} per roundtrip", msgpack.len(), msgpack_time);
+    println!("   CBOR:        {:>8} bytes, {:?} per roundtrip", cbor.len(), cbor_time);
+    println!(
+        "   CBOR / MessagePack: {:.3}\n",
+        cbor.len() as f64 / msgpack.len() as f64
+    );
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    println!("=== CBOR vs MessagePack ===\n");
+
+    let tensor = ZData::new("numpy.ndarray")
+        .with_binary(vec![7u8; 64 * 1024])
+        .with_dtype("float32")
+        .with_shape(vec![128, 128]);
+    compare("Single 64KB tensor", &tensor)?;
+
+    let batch: Vec<ZData> = (0..100)
+        .map(|i| {
+            ZData::new("numpy.ndarray")
+                .with_binary(vec![i as u8; 256])
+                .with_dtype("uint8")
+                .with_shape(vec![16, 16])
+                .with_field("frame", json!(i))
+        })
+        .collect();
+    compare("Batch of 100 small tensors", &batch)?;
+
+    let msg = Message::new("CAMERA_MOVE")
+        .with_data(json!({"position": [1.0, 2.0, 3.0], "rotation": [0.0, 0.5, 0.0, 1.0]}));
+    compare("Small event message", &msg)?;
+
+    Ok(())
+}
@@ -0,0 +1,361 @@
+//! Borrowed views of VMP messages
+//!
+//! Author: Ge Yang
+//!
+//! These types point into the MessagePack buffer they were decoded from
+//! instead of copying strings and binary data out of it. Decode them with
+//! [`crate::deserializer::deserialize_borrowed`], and convert to the owned
+//! types when the data has to outlive the buffer.
+
+use crate::types::{Message, Timestamp};
+use crate::zdata::{ZData, ZDataChecksum};
+use serde::de::{Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Dynamic value borrowing its strings and binary data
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValueRef<'a> {
+    Nil,
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Str(&'a str),
+    Bin(&'a [u8]),
+    Array(Vec<ValueRef<'a>>),
+    Map(Vec<(ValueRef<'a>, ValueRef<'a>)>),
+}
+
+impl<'a> ValueRef<'a> {
+    /// The value under string key `key`, if this is a map
+    pub fn get(&self, key: &str) -> Option<&ValueRef<'a>> {
+        match self {
+            ValueRef::Map(entries) => entries
+                .iter()
+                .find(|(k, _)| matches!(k, ValueRef::Str(s) if *s == key))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// The string, if this is one
+    pub fn as_str(&self) -> Option<&'a str> {
+        match self {
+            ValueRef::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// The binary data, if this is binary
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        match self {
+            ValueRef::Bin(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Copy into a JSON value, as the owned [`Message`] fields hold them
+    ///
+    /// Binary becomes an array of byte values, and map keys that are not
+    /// strings are written as their JSON text.
+    pub fn to_json(&self) -> serde_json::Value {
+        use serde_json::Value;
+
+        match self {
+            ValueRef::Nil => Value::Null,
+            ValueRef::Bool(b) => Value::Bool(*b),
+            ValueRef::Int(n) => Value::from(*n),
+            ValueRef::UInt(n) => Value::from(*n),
+            ValueRef::Float(n) => Value::from(*n),
+            ValueRef::Str(s) => Value::from(*s),
+            ValueRef::Bin(b) => Value::from(b.to_vec()),
+            ValueRef::Array(items) => items.iter().map(ValueRef::to_json).collect(),
+            ValueRef::Map(entries) => entries
+                .iter()
+                .map(|(k, v)| {
+                    let key = match k {
+                        ValueRef::Str(s) => s.to_string(),
+                        other => other.to_json().to_string(),
+                    };
+                    (key, v.to_json())
+                })
+                .collect(),
+        }
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for ValueRef<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ValueRefVisitor<'a>(std::marker::PhantomData<&'a ()>);
+
+        impl<'de: 'a, 'a> Visitor<'de> for ValueRefVisitor<'a> {
+            type Value = ValueRef<'a>;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a value borrowed from the input")
+            }
+
+            fn visit_unit<E>(self) -> Result<Self::Value, E> {
+                Ok(ValueRef::Nil)
+            }
+
+            fn visit_none<E>(self) -> Result<Self::Value, E> {
+                Ok(ValueRef::Nil)
+            }
+
+            fn visit_some<D: Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+                ValueRef::deserialize(d)
+            }
+
+            fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E> {
+                Ok(ValueRef::Bool(v))
+            }
+
+            fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E> {
+                Ok(ValueRef::Int(v))
+            }
+
+            fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E> {
+                Ok(ValueRef::UInt(v))
+            }
+
+            fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E> {
+                Ok(ValueRef::Float(v))
+            }
+
+            fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Self::Value, E> {
+                Ok(ValueRef::Str(v))
+            }
+
+            fn visit_borrowed_bytes<E>(self, v: &'de [u8]) -> Result<Self::Value, E> {
+                Ok(ValueRef::Bin(v))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(1024));
+                while let Some(item) = seq.next_element()? {
+                    items.push(item);
+                }
+                Ok(ValueRef::Array(items))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut entries = Vec::with_capacity(map.size_hint().unwrap_or(0).min(1024));
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(ValueRef::Map(entries))
+            }
+        }
+
+        deserializer.deserialize_any(ValueRefVisitor(std::marker::PhantomData))
+    }
+}
+
+fn to_json_map(map: &HashMap<&str, ValueRef<'_>>) -> HashMap<String, serde_json::Value> {
+    map.iter().map(|(k, v)| (k.to_string(), v.to_json())).collect()
+}
+
+/// Borrowed counterpart of [`Message`]
+///
+/// Dispatching on `etype` needs no copies at all; payloads are decoded into
+/// [`ValueRef`]s that still point into the input.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct MessageRef<'a> {
+    /// Timestamp in milliseconds
+    #[serde(deserialize_with = "crate::types::deserialize_timestamp")]
+    pub ts: Timestamp,
+
+    /// Time in milliseconds after which the message is stale
+    pub expires_at: Option<Timestamp>,
+
+    /// Event type or queue name
+    pub etype: &'a str,
+
+    /// Response type (RPC only)
+    pub rtype: Option<&'a str>,
+
+    /// Positional arguments (RPC)
+    #[serde(borrow)]
+    pub args: Option<Vec<ValueRef<'a>>>,
+
+    /// Keyword arguments (RPC)
+    #[serde(borrow)]
+    pub kwargs: Option<HashMap<&'a str, ValueRef<'a>>>,
+
+    /// Server payload
+    #[serde(borrow)]
+    pub data: Option<ValueRef<'a>>,
+
+    /// Client payload
+    #[serde(borrow)]
+    pub value: Option<ValueRef<'a>>,
+
+    /// Identifier linking this message to the one that caused it
+    pub correlation_id: Option<&'a str>,
+
+    /// Cross-cutting metadata such as trace IDs or auth tokens
+    #[serde(borrow)]
+    pub metadata: Option<HashMap<&'a str, ValueRef<'a>>>,
+
+    /// Protocol version the sender encoded this message with
+    pub vmp_version: Option<&'a str>,
+
+    /// Success flag (RPC response only)
+    pub ok: Option<bool>,
+
+    /// Error message (RPC response only)
+    pub error: Option<&'a str>,
+
+    /// Category of the error (RPC response only)
+    pub error_kind: Option<crate::error::ErrorKind>,
+
+    /// Final chunk flag (streaming RPC only)
+    pub is_final: Option<bool>,
+
+    /// Attempt number that produced this response (retried RPC only)
+    pub attempt: Option<u32>,
+}
+
+impl MessageRef<'_> {
+    /// Copy into an owned [`Message`]
+    pub fn to_owned(&self) -> Message {
+        Message {
+            ts: self.ts,
+            expires_at: self.expires_at,
+            etype: self.etype.to_string(),
+            rtype: self.rtype.map(str::to_string),
+            args: self.args.as_ref().map(|args| args.iter().map(ValueRef::to_json).collect()),
+            kwargs: self.kwargs.as_ref().map(to_json_map),
+            data: self.data.as_ref().map(ValueRef::to_json),
+            value: self.value.as_ref().map(ValueRef::to_json),
+            correlation_id: self.correlation_id.map(str::to_string),
+            metadata: self.metadata.as_ref().map(to_json_map),
+            vmp_version: self.vmp_version.map(str::to_string),
+            ok: self.ok,
+            error: self.error.map(str::to_string),
+            error_kind: self.error_kind.clone(),
+            is_final: self.is_final,
+            attempt: self.attempt,
+            raw_data: None,
+        }
+    }
+}
+
+/// Borrowed counterpart of [`ZData`], with the binary data left in place
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct ZDataRef<'a> {
+    /// Type identifier
+    pub ztype: &'a str,
+
+    /// Binary data
+    #[serde(borrow, default, with = "serde_bytes")]
+    pub b: Option<&'a [u8]>,
+
+    /// Element data type
+    #[serde(default)]
+    pub dtype: Option<&'a str>,
+
+    /// Shape dimensions
+    #[serde(default)]
+    pub shape: Option<Vec<usize>>,
+
+    /// Byte strides per dimension
+    #[serde(default)]
+    pub strides: Option<Vec<isize>>,
+
+    /// Protocol version the sender encoded this ZData with
+    #[serde(default)]
+    pub vmp_version: Option<&'a str>,
+
+    /// Checksum of the binary data
+    #[serde(default)]
+    pub checksum: Option<ZDataChecksum>,
+
+    /// Additional fields for custom types
+    #[serde(borrow, flatten)]
+    pub extra: HashMap<&'a str, ValueRef<'a>>,
+}
+
+impl ZDataRef<'_> {
+    /// Copy into an owned [`ZData`]
+    pub fn to_owned(&self) -> ZData {
+        let mut zdata = ZData::new(self.ztype);
+        zdata.b = self.b.map(bytes::Bytes::copy_from_slice);
+        zdata.dtype = self.dtype.map(str::to_string);
+        zdata.shape = self.shape.clone();
+        zdata.strides = self.strides.clone();
+        zdata.vmp_version = self.vmp_version.map(str::to_string);
+        zdata.checksum = self.checksum.clone();
+        zdata.extra = self.extra.iter().map(|(k, v)| (k.to_string(), v.to_json())).collect();
+        zdata
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deserializer::deserialize_borrowed;
+    use crate::serializer::{serialize, serialize_message};
+    use serde::Serialize;
+    use serde_json::json;
+
+    fn points_into(inner: &[u8], outer: &[u8]) -> bool {
+        let range = outer.as_ptr_range();
+        range.contains(&inner.as_ptr()) && inner.as_ptr_range().end <= range.end
+    }
+
+    #[test]
+    fn test_message_ref_to_owned() {
+        let mut msg = Message::new("CLICK")
+            .with_data(json!({"x": 1, "tags": ["a", "b"], "nested": {"ok": true}}))
+            .with_rtype("CLICK_ACK")
+            .with_correlation("req-1")
+            .with_metadata("trace_id", json!("t-1"));
+        msg.args = Some(vec![json!(1.5), json!(null)]);
+        msg.kwargs = Some(HashMap::from([("k".to_string(), json!(-3))]));
+
+        let bytes = serialize_message(&msg).unwrap();
+        let borrowed: MessageRef = deserialize_borrowed(&bytes).unwrap();
+        assert_eq!(borrowed.etype, "CLICK");
+        assert!(points_into(borrowed.etype.as_bytes(), &bytes));
+        assert_eq!(borrowed.data.as_ref().unwrap().get("x"), Some(&ValueRef::UInt(1)));
+        assert_eq!(borrowed.to_owned(), msg);
+    }
+
+    #[test]
+    fn test_binary_payload_is_not_copied() {
+        #[derive(Serialize)]
+        struct Envelope<'a> {
+            ts: Timestamp,
+            etype: &'a str,
+            data: &'a ZData,
+        }
+
+        let zdata = ZData::new("numpy.ndarray")
+            .with_binary(vec![7u8; 1 << 20])
+            .with_dtype("uint8")
+            .with_shape(vec![1 << 20])
+            .with_field("label", json!("frame"));
+
+        // As sent by implementations that write ZData payloads as bin
+        let bytes = serialize(&Envelope {
+            ts: 1,
+            etype: "FRAME",
+            data: &zdata,
+        })
+        .unwrap();
+        let borrowed: MessageRef = deserialize_borrowed(&bytes).unwrap();
+        let b = borrowed.data.as_ref().and_then(|d| d.get("b")).and_then(ValueRef::as_bytes);
+        assert!(points_into(b.unwrap(), &bytes));
+
+        let bytes = serialize(&zdata).unwrap();
+        let borrowed: ZDataRef = deserialize_borrowed(&bytes).unwrap();
+        assert_eq!(borrowed.b.map(<[u8]>::len), Some(1 << 20));
+        assert!(points_into(borrowed.b.unwrap(), &bytes));
+        assert_eq!(borrowed.extra["label"], ValueRef::Str("frame"));
+        assert_eq!(borrowed.to_owned(), zdata);
+    }
+}
@@ -3,13 +3,16 @@
 //! Author: Ge Yang
 
 use crate::error::{Result, VmpError};
+use crate::type_registry::TypeRegistry;
 use crate::zdata::{ZData, ZDataConversion};
 
 #[cfg(feature = "ndarray")]
-use ndarray::{Array, ArrayD, IxDyn};
+use crate::zdata::contiguous_strides;
+#[cfg(feature = "ndarray")]
+use ndarray::{Array, ArrayD, IxDyn, ShapeBuilder};
 
 #[cfg(feature = "image")]
-use image::{DynamicImage, ImageFormat};
+use image::{ColorType, DynamicImage, ImageFormat};
 
 /// NumPy-compatible ndarray support
 #[cfg(feature = "ndarray")]
@@ -24,6 +27,25 @@ impl<T: Clone> NumpyArray<T> {
     }
 }
 
+#[cfg(all(feature = "ndarray", feature = "compression"))]
+impl NumpyArray<f32> {
+    /// Encode to ZData with the binary buffer compressed using zstd
+    ///
+    /// The algorithm is recorded in the `compression` extra field so that
+    /// `from_zdata` can decompress transparently.
+    pub fn to_zdata_compressed(&self, level: i32) -> Result<ZData> {
+        let mut zdata = self.to_zdata()?;
+        let raw = zdata.b.take().unwrap_or_default();
+
+        let compressed = zstd::encode_all(&raw[..], level)
+            .map_err(|e| VmpError::TypeConversion(format!("zstd compression failed: {}", e)))?;
+
+        Ok(zdata
+            .with_binary(compressed)
+            .with_field("compression", serde_json::json!("zstd")))
+    }
+}
+
 #[cfg(feature = "ndarray")]
 impl ZDataConversion for NumpyArray<f32> {
     fn ztype() -> &'static str {
@@ -31,10 +53,14 @@ impl ZDataConversion for NumpyArray<f32> {
     }
 
     fn to_zdata(&self) -> Result<ZData> {
-        // Convert array to bytes
-        let bytes = self.array.as_slice().ok_or_else(|| {
-            VmpError::TypeConversion("Array is not contiguous".to_string())
-        })?;
+        // Fortran-ordered arrays (e.g. transposes) are sent in memory order
+        let (bytes, fortran) = match (self.array.as_slice(), self.array.t().to_slice()) {
+            (Some(bytes), _) => (bytes, false),
+            (None, Some(bytes)) => (bytes, true),
+            (None, None) => {
+                return Err(VmpError::TypeConversion("Array is not contiguous".to_string()));
+            }
+        };
 
         let byte_vec: Vec<u8> = bytes
             .iter()
@@ -42,11 +68,13 @@ impl ZDataConversion for NumpyArray<f32> {
             .collect();
 
         let shape: Vec<usize> = self.array.shape().to_vec();
+        let strides = contiguous_strides(&shape, size_of::<f32>(), fortran);
 
         Ok(ZData::new("numpy.ndarray")
             .with_binary(byte_vec)
             .with_dtype("float32")
-            .with_shape(shape))
+            .with_shape(shape)
+            .with_strides(strides))
     }
 
     fn from_zdata(zdata: &ZData) -> Result<Self> {
@@ -57,9 +85,7 @@ impl ZDataConversion for NumpyArray<f32> {
             )));
         }
 
-        let bytes = zdata.b.as_ref().ok_or_else(|| {
-            VmpError::MissingField("Binary data missing from ZData".to_string())
-        })?;
+        let bytes = zdata.decompressed()?;
 
         let shape = zdata.shape.as_ref().ok_or_else(|| {
             VmpError::MissingField("Shape missing from ZData".to_string())
@@ -82,8 +108,17 @@ impl ZDataConversion for NumpyArray<f32> {
             .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
             .collect();
 
-        let array = Array::from_shape_vec(IxDyn(shape), floats)
-            .map_err(|e| VmpError::TypeConversion(e.to_string()))?;
+        let array = if zdata.is_c_contiguous() {
+            Array::from_shape_vec(IxDyn(shape), floats)
+        } else if zdata.is_fortran_contiguous() {
+            Array::from_shape_vec(IxDyn(shape).f(), floats)
+        } else {
+            return Err(VmpError::TypeConversion(format!(
+                "Unsupported non-contiguous strides {:?}",
+                zdata.strides.as_deref().unwrap_or_default()
+            )));
+        };
+        let array = array.map_err(|e| VmpError::TypeConversion(e.to_string()))?;
 
         Ok(Self::new(array))
     }
@@ -100,37 +135,470 @@ pub struct ImageData {
     pub format: ImageFormat,
 }
 
+/// Encoder settings for lossy image formats
+#[cfg(feature = "image")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageEncodeOptions {
+    /// JPEG quality from 1 (smallest) to 100 (best)
+    pub quality: u8,
+
+    /// Encode WebP losslessly (the image crate has no lossy WebP encoder)
+    pub lossless: bool,
+}
+
+#[cfg(feature = "image")]
+impl Default for ImageEncodeOptions {
+    fn default() -> Self {
+        Self {
+            quality: 75,
+            lossless: true,
+        }
+    }
+}
+
+#[cfg(feature = "image")]
+impl ImageEncodeOptions {
+    /// Create options with the given quality
+    pub fn with_quality(quality: u8) -> Self {
+        Self {
+            quality,
+            ..Self::default()
+        }
+    }
+}
+
 #[cfg(feature = "image")]
 impl ImageData {
     pub fn new(image: DynamicImage, format: ImageFormat) -> Self {
         Self { image, format }
     }
-}
 
-#[cfg(feature = "image")]
-impl ZDataConversion for ImageData {
-    fn ztype() -> &'static str {
-        "image"
+    /// `format` strings of the encoded image formats that an "image" ZData
+    /// can carry, such as "png" or "tiff"
+    ///
+    /// Raw pixel buffers use the separate "raw" format.
+    pub fn supported_formats() -> &'static [&'static str] {
+        &IMAGE_FORMAT_NAMES
     }
 
-    fn to_zdata(&self) -> Result<ZData> {
-        let mut bytes = Vec::new();
-        let mut cursor = std::io::Cursor::new(&mut bytes);
+    /// Encode the uninterpreted pixel buffer as a raw "image" ZData
+    ///
+    /// The buffer is written in HWC order with shape `[height, width,
+    /// channels]`, the layout the Python client uses for NumPy images.
+    /// 8-bit grayscale, RGB and RGBA images are written as "uint8" and their
+    /// 16-bit counterparts as little-endian "uint16". Other color types are
+    /// converted to 8-bit RGBA if they carry alpha and RGB otherwise.
+    pub fn to_raw_zdata(&self) -> Result<ZData> {
+        let u16_bytes =
+            |data: &[u16]| -> Vec<u8> { data.iter().flat_map(|v| v.to_le_bytes()).collect() };
 
-        self.image
-            .write_to(&mut cursor, self.format)
-            .map_err(|e| VmpError::TypeConversion(e.to_string()))?;
+        let (dtype, channels, buffer) = match &self.image {
+            DynamicImage::ImageLuma8(img) => ("uint8", 1, img.as_raw().clone()),
+            DynamicImage::ImageRgb8(img) => ("uint8", 3, img.as_raw().clone()),
+            DynamicImage::ImageRgba8(img) => ("uint8", 4, img.as_raw().clone()),
+            DynamicImage::ImageLuma16(img) => ("uint16", 1, u16_bytes(img.as_raw())),
+            DynamicImage::ImageRgb16(img) => ("uint16", 3, u16_bytes(img.as_raw())),
+            DynamicImage::ImageRgba16(img) => ("uint16", 4, u16_bytes(img.as_raw())),
+            img if img.color().has_alpha() => ("uint8", 4, img.to_rgba8().into_raw()),
+            img => ("uint8", 3, img.to_rgb8().into_raw()),
+        };
+
+        let shape = vec![
+            self.image.height() as usize,
+            self.image.width() as usize,
+            channels,
+        ];
+
+        Ok(ZData::new("image")
+            .with_binary(buffer)
+            .with_dtype(dtype)
+            .with_shape(shape)
+            .with_field("format", serde_json::json!(RAW_IMAGE_FORMAT)))
+    }
 
-        let format_str = match self.format {
-            ImageFormat::Png => "png",
-            ImageFormat::Jpeg => "jpeg",
-            ImageFormat::WebP => "webp",
-            _ => "unknown",
+    /// Build an image from a raw uint8 or uint16 HWC pixel buffer
+    ///
+    /// The shape may be `[H, W]` or `[H, W, C]` with 1, 3 or 4 channels.
+    /// uint16 samples are little-endian.
+    /// Raw images have no encoded format, so `format` is set to PNG, which
+    /// is used if the image is encoded again with `to_zdata`.
+    fn from_raw_zdata(zdata: &ZData, bytes: &[u8]) -> Result<Self> {
+        let dtype = zdata.dtype.as_deref().ok_or_else(|| {
+            VmpError::MissingField("Dtype missing from raw image ZData".to_string())
+        })?;
+        let sample_size = match dtype {
+            "uint8" => 1,
+            "uint16" => 2,
+            _ => {
+                return Err(VmpError::TypeConversion(format!(
+                    "Raw images must have dtype uint8 or uint16, got {}",
+                    dtype
+                )));
+            }
         };
 
+        let shape = zdata.shape.as_ref().ok_or_else(|| {
+            VmpError::MissingField("Shape missing from raw image ZData".to_string())
+        })?;
+        let (height, width, channels) = match shape.as_slice() {
+            [h, w] => (*h, *w, 1),
+            [h, w, c] => (*h, *w, *c),
+            _ => {
+                return Err(VmpError::TypeConversion(format!(
+                    "Raw image shape must be [H, W] or [H, W, C], got {:?}",
+                    shape
+                )));
+            }
+        };
+
+        // The shape comes from the peer, so its product may not fit
+        let expected = height
+            .checked_mul(width)
+            .and_then(|n| n.checked_mul(channels))
+            .and_then(|n| n.checked_mul(sample_size))
+            .ok_or_else(|| {
+                VmpError::InvalidMessage(format!("Raw image shape {:?} is too large", shape))
+            })?;
+        if bytes.len() != expected {
+            return Err(VmpError::TypeConversion(format!(
+                "Raw image buffer has {} bytes but shape {:?} requires {}",
+                bytes.len(),
+                shape,
+                expected
+            )));
+        }
+
+        if !matches!(channels, 1 | 3 | 4) {
+            return Err(VmpError::TypeConversion(format!(
+                "Raw images must have 1, 3 or 4 channels, got {}",
+                channels
+            )));
+        }
+
+        let (Ok(w), Ok(h)) = (u32::try_from(width), u32::try_from(height)) else {
+            return Err(VmpError::InvalidMessage(format!(
+                "Raw image shape {:?} is too large",
+                shape
+            )));
+        };
+        let image = if sample_size == 1 {
+            let samples = bytes.to_vec();
+            match channels {
+                1 => image::ImageBuffer::from_raw(w, h, samples).map(DynamicImage::ImageLuma8),
+                3 => image::ImageBuffer::from_raw(w, h, samples).map(DynamicImage::ImageRgb8),
+                _ => image::ImageBuffer::from_raw(w, h, samples).map(DynamicImage::ImageRgba8),
+            }
+        } else {
+            let samples: Vec<u16> = bytes
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect();
+            match channels {
+                1 => image::ImageBuffer::from_raw(w, h, samples).map(DynamicImage::ImageLuma16),
+                3 => image::ImageBuffer::from_raw(w, h, samples).map(DynamicImage::ImageRgb16),
+                _ => image::ImageBuffer::from_raw(w, h, samples).map(DynamicImage::ImageRgba16),
+            }
+        }
+        .ok_or_else(|| {
+            VmpError::TypeConversion(format!("Invalid raw image dimensions {:?}", shape))
+        })?;
+
+        Ok(Self::new(image, ImageFormat::Png))
+    }
+
+    /// Resize to exactly `width` x `height`, keeping the format
+    pub fn resize(
+        &self,
+        width: u32,
+        height: u32,
+        filter: image::imageops::FilterType,
+    ) -> ImageData {
+        Self::new(self.image.resize_exact(width, height, filter), self.format)
+    }
+
+    /// Scale down to fit within `max_dimension` on both axes, preserving the
+    /// aspect ratio and keeping the format
+    pub fn thumbnail(&self, max_dimension: u32) -> ImageData {
+        Self::new(self.image.thumbnail(max_dimension, max_dimension), self.format)
+    }
+
+    /// The image as 8-bit RGB, returned as `(width, height, bytes)` with the
+    /// pixels in row-major order
+    pub fn to_rgb_bytes(&self) -> Result<(u32, u32, Vec<u8>)> {
+        let rgb = self.image.to_rgb8();
+        Ok((rgb.width(), rgb.height(), rgb.into_raw()))
+    }
+
+    /// Build an image from a row-major 8-bit RGB buffer
+    ///
+    /// `format` is used when the image is encoded with `to_zdata`.
+    pub fn from_rgb_bytes(
+        width: u32,
+        height: u32,
+        data: Vec<u8>,
+        format: ImageFormat,
+    ) -> Result<ImageData> {
+        let expected = width as u64 * height as u64 * 3;
+        if data.len() as u64 != expected {
+            return Err(VmpError::TypeConversion(format!(
+                "RGB buffer has {} bytes but {}x{} requires {}",
+                data.len(),
+                width,
+                height,
+                expected
+            )));
+        }
+
+        let image = image::RgbImage::from_raw(width, height, data).ok_or_else(|| {
+            VmpError::TypeConversion(format!("Invalid RGB image dimensions {}x{}", width, height))
+        })?;
+        Ok(Self::new(DynamicImage::ImageRgb8(image), format))
+    }
+
+    /// Bits per channel of the underlying image (8, 16 or 32)
+    pub fn bit_depth(&self) -> u16 {
+        let color = self.image.color();
+        color.bits_per_pixel() / color.channel_count() as u16
+    }
+
+    /// Borrow the samples of a 16-bit single-channel (depth) image
+    ///
+    /// Returns the row-major buffer with its width and height, or `None`
+    /// if the image is not `DynamicImage::ImageLuma16`.
+    pub fn as_depth_u16(&self) -> Option<(&[u16], u32, u32)> {
+        self.image
+            .as_luma16()
+            .map(|img| (img.as_raw().as_slice(), img.width(), img.height()))
+    }
+
+    /// Encode the image in `self.format` into a caller-owned buffer
+    ///
+    /// The buffer is cleared first, so its allocation can be reused across
+    /// frames without stale bytes from a previous encode.
+    pub fn encode_into(&self, buf: &mut Vec<u8>) -> Result<()> {
+        let format_str = image_format_to_str(self.format)?;
+        if !self.format.writing_enabled() {
+            return Err(VmpError::TypeConversion(format!(
+                "Encoding {} images is not supported by the image crate",
+                format_str
+            )));
+        }
+        self.encodable_color()?;
+
+        buf.clear();
+        self.image
+            .write_to(&mut std::io::Cursor::new(buf), self.format)
+            .map_err(|e| VmpError::TypeConversion(e.to_string()))
+    }
+
+    /// Encode to ZData, reusing `buf` as the binary payload
+    ///
+    /// The buffer is moved into the ZData without copying; take it back
+    /// with `zdata.b.take().map(Vec::from)` to reuse it for the next frame.
+    pub fn to_zdata_with_buffer(&self, mut buf: Vec<u8>) -> Result<ZData> {
+        self.encode_into(&mut buf)?;
+
         Ok(ZData::new("image")
+            .with_binary(buf)
+            .with_field("format", serde_json::json!(image_format_to_str(self.format)?))
+            .with_field("bit_depth", serde_json::json!(self.bit_depth()))
+            .with_field("color", serde_json::json!(self.encodable_color()?)))
+    }
+
+    /// Check that the target format can represent the image's color type
+    /// and return the `color` string recorded alongside the encoded bytes
+    fn encodable_color(&self) -> Result<&'static str> {
+        let color = self.image.color();
+        if color.has_alpha() && self.format == ImageFormat::Jpeg {
+            return Err(VmpError::TypeConversion(format!(
+                "JPEG cannot store the alpha channel of a {:?} image; \
+                 use PNG or WebP, or convert the image to RGB first",
+                color
+            )));
+        }
+        color_type_to_str(color)
+    }
+
+    /// Encode to ZData with explicit encoder settings
+    ///
+    /// JPEG honors `quality` and records it in the `quality` extra field.
+    /// WebP records `lossless`. Other formats have no tunable settings
+    /// and encode exactly like `to_zdata`.
+    pub fn to_zdata_with_options(&self, options: &ImageEncodeOptions) -> Result<ZData> {
+        if !(1..=100).contains(&options.quality) {
+            return Err(VmpError::TypeConversion(format!(
+                "Image quality must be between 1 and 100, got {}",
+                options.quality
+            )));
+        }
+
+        let mut bytes = Vec::new();
+        let color = self.encodable_color()?;
+        let zdata = match self.format {
+            ImageFormat::Jpeg => {
+                let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(
+                    &mut bytes,
+                    options.quality,
+                );
+                self.image
+                    .write_with_encoder(encoder)
+                    .map_err(|e| VmpError::TypeConversion(e.to_string()))?;
+                ZData::new("image").with_field("quality", serde_json::json!(options.quality))
+            }
+            ImageFormat::WebP => {
+                if !options.lossless {
+                    return Err(VmpError::TypeConversion(
+                        "Lossy WebP encoding is not supported by the image crate".to_string(),
+                    ));
+                }
+                let encoder = image::codecs::webp::WebPEncoder::new_lossless(&mut bytes);
+                self.image
+                    .write_with_encoder(encoder)
+                    .map_err(|e| VmpError::TypeConversion(e.to_string()))?;
+                ZData::new("image").with_field("lossless", serde_json::json!(true))
+            }
+            _ => return self.to_zdata(),
+        };
+
+        let format_str = image_format_to_str(self.format)?;
+        Ok(zdata
             .with_binary(bytes)
-            .with_field("format", serde_json::json!(format_str)))
+            .with_field("format", serde_json::json!(format_str))
+            .with_field("bit_depth", serde_json::json!(self.bit_depth()))
+            .with_field("color", serde_json::json!(color)))
+    }
+}
+
+/// Image formats that can travel in an "image" ZData, paired with the
+/// string recorded in its `format` field
+///
+/// This table is the single source of truth for both encoding and
+/// decoding, so the two directions cannot drift apart.
+#[cfg(feature = "image")]
+const IMAGE_FORMATS: &[(ImageFormat, &str)] = &[
+    (ImageFormat::Png, "png"),
+    (ImageFormat::Jpeg, "jpeg"),
+    (ImageFormat::WebP, "webp"),
+    (ImageFormat::Bmp, "bmp"),
+    (ImageFormat::Tiff, "tiff"),
+    (ImageFormat::Gif, "gif"),
+    (ImageFormat::Ico, "ico"),
+    (ImageFormat::Qoi, "qoi"),
+];
+
+/// The `format` strings of [`IMAGE_FORMATS`], in the same order
+#[cfg(feature = "image")]
+const IMAGE_FORMAT_NAMES: [&str; IMAGE_FORMATS.len()] = {
+    let mut names = [""; IMAGE_FORMATS.len()];
+    let mut i = 0;
+    while i < names.len() {
+        names[i] = IMAGE_FORMATS[i].1;
+        i += 1;
+    }
+    names
+};
+
+/// Color types that can be restored from an "image" ZData, paired with the
+/// string recorded in its `color` field
+#[cfg(feature = "image")]
+const IMAGE_COLORS: &[(ColorType, &str)] = &[
+    (ColorType::L8, "luma8"),
+    (ColorType::La8, "lumaa8"),
+    (ColorType::Rgb8, "rgb8"),
+    (ColorType::Rgba8, "rgba8"),
+    (ColorType::L16, "luma16"),
+    (ColorType::La16, "lumaa16"),
+    (ColorType::Rgb16, "rgb16"),
+    (ColorType::Rgba16, "rgba16"),
+    (ColorType::Rgb32F, "rgb32f"),
+    (ColorType::Rgba32F, "rgba32f"),
+];
+
+/// Map a color type to its ZData `color` string
+#[cfg(feature = "image")]
+fn color_type_to_str(color: ColorType) -> Result<&'static str> {
+    IMAGE_COLORS
+        .iter()
+        .find(|(c, _)| *c == color)
+        .map(|(_, name)| *name)
+        .ok_or_else(|| VmpError::TypeConversion(format!("Unsupported color type: {:?}", color)))
+}
+
+/// Map a ZData `color` string back to a color type
+#[cfg(feature = "image")]
+fn color_type_from_str(name: &str) -> Result<ColorType> {
+    IMAGE_COLORS
+        .iter()
+        .find(|(_, n)| *n == name)
+        .map(|(c, _)| *c)
+        .ok_or_else(|| VmpError::TypeConversion(format!("Unsupported color type: {}", name)))
+}
+
+/// Convert a decoded image to the given color type, if it differs
+#[cfg(feature = "image")]
+fn convert_color(image: DynamicImage, color: ColorType) -> DynamicImage {
+    if image.color() == color {
+        return image;
+    }
+    match color {
+        ColorType::L8 => DynamicImage::ImageLuma8(image.to_luma8()),
+        ColorType::La8 => DynamicImage::ImageLumaA8(image.to_luma_alpha8()),
+        ColorType::Rgb8 => DynamicImage::ImageRgb8(image.to_rgb8()),
+        ColorType::Rgba8 => DynamicImage::ImageRgba8(image.to_rgba8()),
+        ColorType::L16 => DynamicImage::ImageLuma16(image.to_luma16()),
+        ColorType::La16 => DynamicImage::ImageLumaA16(image.to_luma_alpha16()),
+        ColorType::Rgb16 => DynamicImage::ImageRgb16(image.to_rgb16()),
+        ColorType::Rgba16 => DynamicImage::ImageRgba16(image.to_rgba16()),
+        ColorType::Rgb32F => DynamicImage::ImageRgb32F(image.to_rgb32f()),
+        ColorType::Rgba32F => DynamicImage::ImageRgba32F(image.to_rgba32f()),
+        _ => image,
+    }
+}
+
+/// `format` value of an "image" ZData carrying an uninterpreted pixel buffer
+#[cfg(feature = "image")]
+const RAW_IMAGE_FORMAT: &str = "raw";
+
+/// Map an image format to its ZData `format` string
+#[cfg(feature = "image")]
+fn image_format_to_str(format: ImageFormat) -> Result<&'static str> {
+    IMAGE_FORMATS
+        .iter()
+        .find(|(f, _)| *f == format)
+        .map(|(_, name)| *name)
+        .ok_or_else(|| {
+            VmpError::TypeConversion(format!("Unsupported image format: {:?}", format))
+        })
+}
+
+/// Map a ZData `format` string back to an image format
+#[cfg(feature = "image")]
+fn image_format_from_str(name: &str) -> Result<ImageFormat> {
+    IMAGE_FORMATS
+        .iter()
+        .find(|(_, n)| *n == name)
+        .map(|(f, _)| *f)
+        .ok_or_else(|| VmpError::TypeConversion(format!("Unsupported image format: {}", name)))
+}
+
+/// Whether an "image" ZData carries a raw pixel buffer rather than encoded
+/// bytes. Payloads without a format but with dtype and shape are raw.
+#[cfg(feature = "image")]
+fn is_raw_image(zdata: &ZData) -> bool {
+    match zdata.get_field("format").and_then(|v| v.as_str()) {
+        Some(f) => f == RAW_IMAGE_FORMAT,
+        None => zdata.dtype.is_some() && zdata.shape.is_some(),
+    }
+}
+
+#[cfg(feature = "image")]
+impl ZDataConversion for ImageData {
+    fn ztype() -> &'static str {
+        "image"
+    }
+
+    fn to_zdata(&self) -> Result<ZData> {
+        self.to_zdata_with_buffer(Vec::new())
     }
 
     fn from_zdata(zdata: &ZData) -> Result<Self> {
@@ -141,32 +609,32 @@ impl ZDataConversion for ImageData {
             )));
         }
 
-        let bytes = zdata.b.as_ref().ok_or_else(|| {
-            VmpError::MissingField("Binary data missing from ZData".to_string())
-        })?;
+        let bytes = zdata.decompressed()?;
 
-        let format_str = zdata
-            .get_field("format")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| {
-                VmpError::MissingField("Format missing from ZData".to_string())
-            })?;
+        if is_raw_image(zdata) {
+            return Self::from_raw_zdata(zdata, &bytes);
+        }
 
-        let format = match format_str {
-            "png" => ImageFormat::Png,
-            "jpeg" => ImageFormat::Jpeg,
-            "webp" => ImageFormat::WebP,
-            _ => {
-                return Err(VmpError::TypeConversion(format!(
-                    "Unsupported image format: {}",
-                    format_str
-                )))
-            }
+        // Older clients omit the format field, so sniff it from the bytes
+        let format = match zdata.get_field("format").and_then(|v| v.as_str()) {
+            Some(format_str) => image_format_from_str(format_str)?,
+            None => image::guess_format(&bytes).map_err(|_| {
+                VmpError::MissingField(
+                    "Format missing from ZData and could not be auto-detected from the \
+                     image bytes"
+                        .to_string(),
+                )
+            })?,
         };
 
-        let image = image::load_from_memory_with_format(bytes, format)
+        let mut image = image::load_from_memory_with_format(&bytes, format)
             .map_err(|e| VmpError::TypeConversion(e.to_string()))?;
 
+        // Decoders may pick a different variant than the one that was encoded
+        if let Some(color) = zdata.get_field("color").and_then(|v| v.as_str()) {
+            image = convert_color(image, color_type_from_str(color)?);
+        }
+
         Ok(Self::new(image, format))
     }
 
@@ -175,6 +643,319 @@ impl ZDataConversion for ImageData {
     }
 }
 
+/// Pre-encoded video frame (H.264, VP9, ...)
+///
+/// The encoded bitstream travels in `b` untouched; no codec is linked.
+#[cfg(feature = "video")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct VideoFrame {
+    codec: String,
+    pts: i64,
+    keyframe: bool,
+    width: Option<u32>,
+    height: Option<u32>,
+    data: Vec<u8>,
+}
+
+#[cfg(feature = "video")]
+impl VideoFrame {
+    /// Create a non-key frame with the given codec, presentation timestamp
+    /// in microseconds and encoded bytes
+    pub fn new(codec: impl Into<String>, pts: i64, data: Vec<u8>) -> Self {
+        Self {
+            codec: codec.into(),
+            pts,
+            keyframe: false,
+            width: None,
+            height: None,
+            data,
+        }
+    }
+
+    /// Mark the frame as a keyframe
+    pub fn with_keyframe(mut self, keyframe: bool) -> Self {
+        self.keyframe = keyframe;
+        self
+    }
+
+    /// Set the frame dimensions in pixels
+    pub fn with_dimensions(mut self, width: u32, height: u32) -> Self {
+        self.width = Some(width);
+        self.height = Some(height);
+        self
+    }
+
+    /// Codec name, e.g. "h264" or "vp9"
+    pub fn codec(&self) -> &str {
+        &self.codec
+    }
+
+    /// Presentation timestamp in microseconds
+    pub fn pts(&self) -> i64 {
+        self.pts
+    }
+
+    /// Whether the frame can be decoded without previous frames
+    pub fn is_keyframe(&self) -> bool {
+        self.keyframe
+    }
+
+    /// Frame width in pixels, if known
+    pub fn width(&self) -> Option<u32> {
+        self.width
+    }
+
+    /// Frame height in pixels, if known
+    pub fn height(&self) -> Option<u32> {
+        self.height
+    }
+
+    /// Encoded bitstream
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Consume the frame and return the encoded bitstream
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+#[cfg(feature = "video")]
+impl ZDataConversion for VideoFrame {
+    fn ztype() -> &'static str {
+        "video.frame"
+    }
+
+    fn to_zdata(&self) -> Result<ZData> {
+        let mut zdata = ZData::new("video.frame")
+            .with_binary(self.data.clone())
+            .with_field("codec", serde_json::json!(self.codec))
+            .with_field("pts", serde_json::json!(self.pts))
+            .with_field("keyframe", serde_json::json!(self.keyframe));
+
+        if let Some(width) = self.width {
+            zdata = zdata.with_field("width", serde_json::json!(width));
+        }
+        if let Some(height) = self.height {
+            zdata = zdata.with_field("height", serde_json::json!(height));
+        }
+
+        Ok(zdata)
+    }
+
+    fn from_zdata(zdata: &ZData) -> Result<Self> {
+        if !zdata.is_type("video.frame") {
+            return Err(VmpError::TypeConversion(format!(
+                "Expected video.frame, got {}",
+                zdata.ztype
+            )));
+        }
+
+        let data = zdata.decompressed()?.into_owned();
+
+        let codec = zdata
+            .get_field("codec")
+            .ok_or_else(|| VmpError::MissingField("Codec missing from ZData".to_string()))?
+            .as_str()
+            .ok_or_else(|| VmpError::TypeConversion("Video codec must be a string".to_string()))?;
+
+        let pts = zdata
+            .get_field("pts")
+            .ok_or_else(|| VmpError::MissingField("Pts missing from ZData".to_string()))?
+            .as_i64()
+            .ok_or_else(|| VmpError::TypeConversion("Video pts must be an integer".to_string()))?;
+
+        let keyframe = match zdata.get_field("keyframe") {
+            Some(v) => v.as_bool().ok_or_else(|| {
+                VmpError::TypeConversion("Video keyframe flag must be a boolean".to_string())
+            })?,
+            None => false,
+        };
+
+        let dimension = |key: &str| -> Result<Option<u32>> {
+            zdata
+                .get_field(key)
+                .map(|v| {
+                    v.as_u64().and_then(|n| u32::try_from(n).ok()).ok_or_else(|| {
+                        VmpError::TypeConversion(format!("Video {} must be a u32", key))
+                    })
+                })
+                .transpose()
+        };
+
+        Ok(Self {
+            codec: codec.to_string(),
+            pts,
+            keyframe,
+            width: dimension("width")?,
+            height: dimension("height")?,
+            data,
+        })
+    }
+
+    fn is_available() -> bool {
+        true
+    }
+}
+
+/// Register every built-in type enabled by the current feature set
+pub fn register_all() {
+    register_all_into(&crate::type_registry::GLOBAL_TYPE_REGISTRY);
+}
+
+/// Register every built-in type enabled by the current feature set in
+/// `registry`
+#[cfg_attr(not(any(feature = "ndarray", feature = "image")), allow(unused_variables))]
+pub fn register_all_into(registry: &TypeRegistry) {
+    #[cfg(feature = "ndarray")]
+    register_ndarray_into(registry);
+    #[cfg(feature = "image")]
+    register_image_into(registry);
+}
+
+/// Keys of the JSON form produced by the "numpy.ndarray" decoder
+#[cfg(feature = "ndarray")]
+const NDARRAY_JSON_KEYS: &[&str] = &["dtype", "shape", "data"];
+
+/// Register "numpy.ndarray" in the global type registry
+///
+/// Decoded arrays become `{"dtype": "float32", "shape": [...], "data": [...]}`,
+/// with `data` holding the elements in row-major order. Objects of exactly
+/// that shape are encoded back to "numpy.ndarray" ZData.
+#[cfg(feature = "ndarray")]
+pub fn register_ndarray() {
+    register_ndarray_into(&crate::type_registry::GLOBAL_TYPE_REGISTRY);
+}
+
+/// Register "numpy.ndarray" in `registry`, as [`register_ndarray`] does
+#[cfg(feature = "ndarray")]
+pub fn register_ndarray_into(registry: &TypeRegistry) {
+    registry.register(
+        NumpyArray::<f32>::ztype(),
+        |value| ndarray_from_json(value)?.to_zdata(),
+        |zdata| ndarray_to_json(&NumpyArray::<f32>::from_zdata(zdata)?),
+        Some(std::sync::Arc::new(|value| has_exact_keys(value, NDARRAY_JSON_KEYS))),
+    );
+}
+
+#[cfg(feature = "ndarray")]
+fn ndarray_to_json(array: &NumpyArray<f32>) -> Result<serde_json::Value> {
+    let data: Vec<f32> = array.array.iter().copied().collect();
+    Ok(serde_json::json!({
+        "dtype": "float32",
+        "shape": array.array.shape(),
+        "data": data,
+    }))
+}
+
+#[cfg(feature = "ndarray")]
+fn ndarray_from_json(value: &serde_json::Value) -> Result<NumpyArray<f32>> {
+    let dtype = value.get("dtype").and_then(|v| v.as_str());
+    if dtype != Some("float32") {
+        return Err(VmpError::TypeConversion(format!(
+            "Expected dtype float32, got {:?}",
+            dtype
+        )));
+    }
+
+    let shape: Vec<usize> = serde_json::from_value(json_field(value, "shape")?.clone())?;
+    let data: Vec<f32> = serde_json::from_value(json_field(value, "data")?.clone())?;
+
+    let array = Array::from_shape_vec(IxDyn(&shape), data)
+        .map_err(|e| VmpError::TypeConversion(e.to_string()))?;
+
+    Ok(NumpyArray::new(array))
+}
+
+/// Keys of the JSON form produced by the "image" decoder
+#[cfg(feature = "image")]
+const IMAGE_JSON_KEYS: &[&str] = &["format", "width", "height", "channels", "bit_depth", "data"];
+
+/// Register "image" in the global type registry
+///
+/// Decoded images become
+/// `{"format": "png", "width": W, "height": H, "channels": C, "bit_depth": 8, "data": "..."}`,
+/// where `data` is the base64-encoded image in `format`. Encoded payloads
+/// are passed through untouched; raw pixel buffers are re-encoded as PNG.
+/// Objects of exactly that shape are encoded back to "image" ZData, using
+/// `format` and `data` only.
+#[cfg(feature = "image")]
+pub fn register_image() {
+    register_image_into(&crate::type_registry::GLOBAL_TYPE_REGISTRY);
+}
+
+/// Register "image" in `registry`, as [`register_image`] does
+#[cfg(feature = "image")]
+pub fn register_image_into(registry: &TypeRegistry) {
+    registry.register(
+        ImageData::ztype(),
+        image_from_json,
+        image_to_json,
+        Some(std::sync::Arc::new(|value| has_exact_keys(value, IMAGE_JSON_KEYS))),
+    );
+}
+
+#[cfg(feature = "image")]
+fn image_to_json(zdata: &ZData) -> Result<serde_json::Value> {
+    use base64::Engine;
+
+    let image = ImageData::from_zdata(zdata)?;
+    let encoded = if is_raw_image(zdata) {
+        image.to_zdata()?.b.map(Vec::from).unwrap_or_default()
+    } else {
+        zdata.decompressed()?.into_owned()
+    };
+
+    Ok(serde_json::json!({
+        "format": image_format_to_str(image.format)?,
+        "width": image.image.width(),
+        "height": image.image.height(),
+        "channels": image.image.color().channel_count(),
+        "bit_depth": image.bit_depth(),
+        "data": base64::engine::general_purpose::STANDARD.encode(encoded),
+    }))
+}
+
+#[cfg(feature = "image")]
+fn image_from_json(value: &serde_json::Value) -> Result<ZData> {
+    use base64::Engine;
+
+    let format = json_field(value, "format")?
+        .as_str()
+        .ok_or_else(|| VmpError::TypeConversion("Image format must be a string".to_string()))?;
+    let data = json_field(value, "data")?
+        .as_str()
+        .ok_or_else(|| VmpError::TypeConversion("Image data must be a string".to_string()))?;
+
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(data)
+        .map_err(|e| VmpError::TypeConversion(format!("Base64 decode error: {}", e)))?;
+
+    let zdata = ZData::new(ImageData::ztype())
+        .with_binary(bytes)
+        .with_field("format", serde_json::json!(format));
+
+    // Decode once to reject payloads that are not valid images
+    let image = ImageData::from_zdata(&zdata)?;
+    Ok(zdata.with_field("bit_depth", serde_json::json!(image.bit_depth())))
+}
+
+#[cfg(any(feature = "ndarray", feature = "image"))]
+fn json_field<'a>(value: &'a serde_json::Value, key: &str) -> Result<&'a serde_json::Value> {
+    value
+        .get(key)
+        .ok_or_else(|| VmpError::MissingField(format!("{} missing from JSON value", key)))
+}
+
+/// Whether `value` is an object with exactly the given keys
+#[cfg(any(feature = "ndarray", feature = "image"))]
+fn has_exact_keys(value: &serde_json::Value, keys: &[&str]) -> bool {
+    value
+        .as_object()
+        .is_some_and(|map| map.len() == keys.len() && keys.iter().all(|k| map.contains_key(*k)))
+}
+
 /// Type conversion fallback for unavailable types
 ///
 /// This provides helpful error messages when a type is not available
@@ -192,6 +973,11 @@ impl TypeConversionFallback {
         cfg!(feature = "image")
     }
 
+    /// Check if video frame support is available
+    pub fn is_video_available() -> bool {
+        cfg!(feature = "video")
+    }
+
     /// Get a helpful error message for a missing type
     pub fn missing_type_error(ztype: &str) -> VmpError {
         match ztype {
@@ -209,6 +995,13 @@ impl TypeConversionFallback {
                         .to_string(),
                 )
             }
+            "video.frame" if !Self::is_video_available() => {
+                VmpError::TypeConversion(
+                    "Video frame support requires the 'video' feature. \
+                     Add 'features = [\"video\"]' to your Cargo.toml dependency."
+                        .to_string(),
+                )
+            }
             _ => VmpError::TypeNotRegistered(format!(
                 "Type '{}' is not available. It may require a feature flag or external dependency.",
                 ztype
@@ -237,6 +1030,77 @@ mod tests {
         assert_eq!(restored.array.shape(), &[2, 3]);
     }
 
+    #[test]
+    #[cfg(feature = "ndarray")]
+    fn test_numpy_array_strides() {
+        let array = Array::from_shape_vec(IxDyn(&[2, 3]), vec![1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0])
+            .unwrap();
+        let zdata = NumpyArray::new(array.clone()).to_zdata().unwrap();
+        assert_eq!(zdata.strides, Some(vec![12, 4]));
+        assert!(zdata.is_c_contiguous());
+
+        // A transpose is Fortran-contiguous and keeps its memory order
+        let transposed = array.reversed_axes();
+        let zdata = NumpyArray::new(transposed.clone()).to_zdata().unwrap();
+        assert_eq!(zdata.shape, Some(vec![3, 2]));
+        assert_eq!(zdata.strides, Some(vec![4, 12]));
+        assert!(zdata.is_fortran_contiguous() && !zdata.is_c_contiguous());
+
+        let bytes = crate::serializer::serialize(&zdata).unwrap();
+        let received: ZData = crate::deserializer::deserialize(&bytes).unwrap();
+        assert_eq!(received.strides, zdata.strides);
+        assert_eq!(NumpyArray::from_zdata(&received).unwrap().array, transposed);
+
+        let sliced = zdata.with_strides(vec![8, 24]);
+        assert!(NumpyArray::from_zdata(&sliced).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "ndarray")]
+    fn test_numpy_array_uncompressed_unchanged() {
+        let array = Array::from_shape_vec(IxDyn(&[2, 2]), vec![1.0f32, 2.0, 3.0, 4.0]).unwrap();
+        let zdata = NumpyArray::new(array.clone()).to_zdata().unwrap();
+
+        assert!(zdata.get_field("compression").is_none());
+        assert_eq!(zdata.b.as_ref().unwrap().len(), 16);
+
+        let restored = NumpyArray::from_zdata(&zdata).unwrap();
+        assert_eq!(restored.array, array);
+    }
+
+    #[test]
+    #[cfg(feature = "ndarray")]
+    fn test_numpy_array_unknown_compression() {
+        let array = Array::from_shape_vec(IxDyn(&[2]), vec![1.0f32, 2.0]).unwrap();
+        let zdata = NumpyArray::new(array)
+            .to_zdata()
+            .unwrap()
+            .with_field("compression", serde_json::json!("brotli"));
+
+        let err = NumpyArray::<f32>::from_zdata(&zdata).err().unwrap();
+        assert!(matches!(err, VmpError::TypeConversion(_)));
+        assert!(err.to_string().contains("brotli"));
+    }
+
+    #[test]
+    #[cfg(all(feature = "ndarray", feature = "compression"))]
+    fn test_numpy_array_compressed_roundtrip() {
+        let data: Vec<f32> = (0..1024).map(|i| (i % 8) as f32).collect();
+        let array = Array::from_shape_vec(IxDyn(&[32, 32]), data).unwrap();
+        let numpy_array = NumpyArray::new(array.clone());
+
+        let plain = numpy_array.to_zdata().unwrap();
+        let compressed = numpy_array.to_zdata_compressed(3).unwrap();
+
+        assert_eq!(compressed.get_field("compression").unwrap(), "zstd");
+        assert_eq!(compressed.dtype, plain.dtype);
+        assert_eq!(compressed.shape, plain.shape);
+        assert!(compressed.b.as_ref().unwrap().len() < plain.b.as_ref().unwrap().len());
+
+        let restored = NumpyArray::from_zdata(&compressed).unwrap();
+        assert_eq!(restored.array, array);
+    }
+
     #[test]
     #[cfg(feature = "image")]
     fn test_image_conversion() {
@@ -257,9 +1121,588 @@ mod tests {
         assert_eq!(restored.format, ImageFormat::Png);
     }
 
+    #[test]
+    #[cfg(feature = "image")]
+    fn test_image_color_type_roundtrip() {
+        use image::{ImageBuffer, Luma, LumaA, Rgba};
+
+        let images = [
+            DynamicImage::ImageLuma8(ImageBuffer::from_fn(8, 6, |x, y| Luma([(x * y) as u8]))),
+            DynamicImage::ImageLumaA8(ImageBuffer::from_fn(8, 6, |x, y| {
+                LumaA([x as u8 * 30, y as u8 * 40])
+            })),
+            DynamicImage::ImageRgba8(ImageBuffer::from_fn(8, 6, |x, y| {
+                Rgba([x as u8 * 30, y as u8 * 40, 200, (x + y) as u8 * 10])
+            })),
+        ];
+
+        for (image, color) in images.into_iter().zip(["luma8", "lumaa8", "rgba8"]) {
+            let zdata = ImageData::new(image.clone(), ImageFormat::Png).to_zdata().unwrap();
+            assert_eq!(zdata.get_field("color").unwrap(), color);
+
+            let restored = ImageData::from_zdata(&zdata).unwrap();
+            assert_eq!(restored.image.color(), image.color());
+            assert_eq!(restored.image, image);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn test_image_color_type_restored() {
+        use image::{ImageBuffer, Luma};
+
+        // An 8-bit RGB PNG that was recorded as grayscale comes back as Luma8
+        let gray = ImageBuffer::from_fn(4, 4, |x, _| Luma([x as u8 * 50]));
+        let rgb = DynamicImage::ImageLuma8(gray.clone()).to_rgb8();
+        let zdata = ImageData::new(DynamicImage::ImageRgb8(rgb), ImageFormat::Png)
+            .to_zdata()
+            .unwrap()
+            .with_field("color", serde_json::json!("luma8"));
+
+        let restored = ImageData::from_zdata(&zdata).unwrap();
+        assert_eq!(restored.image, DynamicImage::ImageLuma8(gray));
+
+        let zdata = zdata.with_field("color", serde_json::json!("cmyk"));
+        assert!(ImageData::from_zdata(&zdata).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn test_image_alpha_jpeg_rejected() {
+        use image::{ImageBuffer, LumaA, Rgba};
+
+        for image in [
+            DynamicImage::ImageRgba8(ImageBuffer::from_pixel(4, 4, Rgba([1, 2, 3, 4]))),
+            DynamicImage::ImageLumaA8(ImageBuffer::from_pixel(4, 4, LumaA([1, 2]))),
+        ] {
+            let image_data = ImageData::new(image, ImageFormat::Jpeg);
+            let err = image_data.to_zdata().unwrap_err();
+            assert!(err.to_string().contains("alpha"));
+            assert!(image_data.to_zdata_with_options(&ImageEncodeOptions::default()).is_err());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn test_image_format_auto_detection() {
+        use image::{ImageBuffer, Rgb};
+
+        let img = DynamicImage::ImageRgb8(ImageBuffer::from_fn(16, 16, |x, y| {
+            Rgb([x as u8 * 16, y as u8 * 16, 128])
+        }));
+
+        for format in [ImageFormat::Png, ImageFormat::Jpeg] {
+            let mut zdata = ImageData::new(img.clone(), format).to_zdata().unwrap();
+            zdata.extra.shift_remove("format");
+
+            let restored = ImageData::from_zdata(&zdata).unwrap();
+            assert_eq!(restored.format, format);
+            assert_eq!((restored.image.width(), restored.image.height()), (16, 16));
+        }
+
+        let zdata = ZData::new("image").with_binary(vec![0, 1, 2, 3]);
+        let result = ImageData::from_zdata(&zdata);
+        assert!(
+            matches!(result, Err(VmpError::MissingField(msg)) if msg.contains("auto-detected"))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn test_image_encode_reuses_buffer() {
+        use image::{ImageBuffer, Rgb};
+
+        let large = DynamicImage::ImageRgb8(ImageBuffer::from_fn(64, 64, |x, y| {
+            Rgb([(x * 4) as u8, (y * 4) as u8, ((x ^ y) * 4) as u8])
+        }));
+        let small = DynamicImage::ImageRgb8(ImageBuffer::from_pixel(2, 2, Rgb([9, 9, 9])));
+
+        let mut buf = Vec::new();
+        for image in [large.clone(), small.clone(), large] {
+            let image_data = ImageData::new(image.clone(), ImageFormat::Png);
+            image_data.encode_into(&mut buf).unwrap();
+
+            let expected = image_data.to_zdata().unwrap();
+            assert_eq!(&buf, expected.b.as_ref().unwrap());
+
+            let decoded = image::load_from_memory_with_format(&buf, ImageFormat::Png).unwrap();
+            assert_eq!(decoded, image);
+        }
+
+        // The buffer moves into the ZData and can be taken back for the next frame
+        let capacity = buf.capacity();
+        let image_data = ImageData::new(small.clone(), ImageFormat::Png);
+        let mut zdata = image_data.to_zdata_with_buffer(buf).unwrap();
+        assert_eq!(zdata, image_data.to_zdata().unwrap());
+
+        let buf = Vec::from(zdata.b.take().unwrap());
+        assert_eq!(buf.capacity(), capacity);
+        assert_eq!(ImageData::from_zdata(&image_data.to_zdata().unwrap()).unwrap().image, small);
+    }
+
+    #[test]
+    #[cfg(all(feature = "image", feature = "compression"))]
+    fn test_image_compressed_payload() {
+        use image::{ImageBuffer, Rgb};
+
+        let img = DynamicImage::ImageRgb8(ImageBuffer::from_pixel(8, 8, Rgb([10, 20, 30])));
+        let zdata = ImageData::new(img.clone(), ImageFormat::Bmp)
+            .to_zdata()
+            .unwrap()
+            .with_compression("gzip")
+            .unwrap();
+
+        assert_eq!(ImageData::from_zdata(&zdata).unwrap().image, img);
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn test_image_jpeg_quality() {
+        use image::{ImageBuffer, Rgb};
+
+        let img = DynamicImage::ImageRgb8(ImageBuffer::from_fn(128, 128, |x, y| {
+            Rgb([(x * 2) as u8, (y * 2) as u8, ((x * y) % 256) as u8])
+        }));
+        let image_data = ImageData::new(img, ImageFormat::Jpeg);
+
+        let low = image_data
+            .to_zdata_with_options(&ImageEncodeOptions::with_quality(30))
+            .unwrap();
+        let high = image_data
+            .to_zdata_with_options(&ImageEncodeOptions::with_quality(95))
+            .unwrap();
+
+        assert_eq!(low.get_field("quality").unwrap(), 30);
+        assert_eq!(high.get_field("quality").unwrap(), 95);
+
+        let low_len = low.b.as_ref().unwrap().len();
+        let high_len = high.b.as_ref().unwrap().len();
+        assert!(low_len * 2 < high_len, "{} vs {}", low_len, high_len);
+
+        for zdata in [low, high] {
+            let restored = ImageData::from_zdata(&zdata).unwrap();
+            assert_eq!(restored.format, ImageFormat::Jpeg);
+            assert_eq!(restored.image.width(), 128);
+        }
+
+        // Plain to_zdata keeps the default encoder and records no quality
+        let plain = image_data.to_zdata().unwrap();
+        assert!(plain.get_field("quality").is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn test_image_encode_options_validation() {
+        let img = DynamicImage::new_rgb8(8, 8);
+
+        let jpeg = ImageData::new(img.clone(), ImageFormat::Jpeg);
+        assert!(jpeg.to_zdata_with_options(&ImageEncodeOptions::with_quality(0)).is_err());
+        assert!(jpeg.to_zdata_with_options(&ImageEncodeOptions::with_quality(101)).is_err());
+
+        let webp = ImageData::new(img, ImageFormat::WebP);
+        let zdata = webp.to_zdata_with_options(&ImageEncodeOptions::default()).unwrap();
+        assert_eq!(zdata.get_field("lossless").unwrap(), true);
+
+        let lossy = ImageEncodeOptions {
+            lossless: false,
+            ..ImageEncodeOptions::default()
+        };
+        assert!(webp.to_zdata_with_options(&lossy).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn test_raw_image_roundtrip() {
+        use image::{ImageBuffer, Luma, Rgb, Rgba};
+
+        let images = [
+            (
+                DynamicImage::ImageLuma8(ImageBuffer::from_fn(5, 3, |x, y| Luma([(x * 10 + y) as u8]))),
+                1,
+            ),
+            (
+                DynamicImage::ImageRgb8(ImageBuffer::from_fn(5, 3, |x, y| {
+                    Rgb([x as u8, y as u8, (x + y) as u8])
+                })),
+                3,
+            ),
+            (
+                DynamicImage::ImageRgba8(ImageBuffer::from_fn(5, 3, |x, y| {
+                    Rgba([x as u8, y as u8, 7, (x * y) as u8])
+                })),
+                4,
+            ),
+        ];
+
+        for (img, channels) in images {
+            let zdata = ImageData::new(img.clone(), ImageFormat::Png)
+                .to_raw_zdata()
+                .unwrap();
+            assert_eq!(zdata.dtype.as_deref(), Some("uint8"));
+            assert_eq!(zdata.shape, Some(vec![3, 5, channels]));
+            assert_eq!(zdata.b.as_ref().unwrap().len(), 3 * 5 * channels);
+
+            let restored = ImageData::from_zdata(&zdata).unwrap();
+            assert_eq!(restored.image, img);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn test_raw_image_from_python_layout() {
+        // No format field, as the Python client sends it; HWC row-major
+        let zdata = ZData::new("image")
+            .with_binary(vec![255, 0, 0, 0, 255, 0, 0, 0, 255, 9, 9, 9])
+            .with_dtype("uint8")
+            .with_shape(vec![2, 2, 3]);
+
+        let restored = ImageData::from_zdata(&zdata).unwrap();
+        let rgb = restored.image.as_rgb8().unwrap();
+        assert_eq!(rgb.get_pixel(1, 0).0, [0, 255, 0]);
+        assert_eq!(rgb.get_pixel(1, 1).0, [9, 9, 9]);
+
+        // [H, W] is grayscale
+        let gray = ZData::new("image")
+            .with_binary(vec![1, 2, 3, 4, 5, 6])
+            .with_dtype("uint8")
+            .with_shape(vec![2, 3]);
+        let restored = ImageData::from_zdata(&gray).unwrap();
+        assert_eq!(restored.image.as_luma8().unwrap().get_pixel(2, 1).0, [6]);
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn test_raw_image_invalid() {
+        let mismatch = ZData::new("image")
+            .with_binary(vec![0; 11])
+            .with_dtype("uint8")
+            .with_shape(vec![2, 2, 3]);
+        let err = ImageData::from_zdata(&mismatch).err().unwrap();
+        assert!(err.to_string().contains("11 bytes"));
+
+        let channels = ZData::new("image")
+            .with_binary(vec![0; 8])
+            .with_dtype("uint8")
+            .with_shape(vec![2, 2, 2]);
+        assert!(ImageData::from_zdata(&channels).is_err());
+
+        let dtype = ZData::new("image")
+            .with_binary(vec![0; 16])
+            .with_dtype("float32")
+            .with_shape(vec![2, 2, 1]);
+        assert!(ImageData::from_zdata(&dtype).is_err());
+
+        // Dimensions whose product overflows are rejected, not multiplied
+        let huge = ZData::new("image")
+            .with_binary(vec![0; 12])
+            .with_dtype("uint16")
+            .with_shape(vec![usize::MAX / 2, 3, 4]);
+        let err = ImageData::from_zdata(&huge).err().unwrap();
+        assert!(matches!(err, VmpError::InvalidMessage(m) if m.contains("too large")));
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn test_depth_image_roundtrip() {
+        use image::{ImageBuffer, Luma};
+
+        // Gradient covering the full 16-bit range
+        let depth: ImageBuffer<Luma<u16>, Vec<u16>> =
+            ImageBuffer::from_fn(64, 32, |x, y| Luma([((y * 64 + x) * 32) as u16 + 31]));
+        let samples = depth.as_raw().clone();
+        assert_eq!(samples.iter().max(), Some(&65535));
+
+        let image_data = ImageData::new(DynamicImage::ImageLuma16(depth), ImageFormat::Png);
+        assert_eq!(image_data.bit_depth(), 16);
+
+        for zdata in [image_data.to_zdata().unwrap(), image_data.to_raw_zdata().unwrap()] {
+            let restored = ImageData::from_zdata(&zdata).unwrap();
+            assert!(matches!(restored.image, DynamicImage::ImageLuma16(_)));
+
+            let (buffer, width, height) = restored.as_depth_u16().unwrap();
+            assert_eq!((width, height), (64, 32));
+            assert_eq!(buffer, samples.as_slice());
+        }
+
+        let zdata = image_data.to_zdata().unwrap();
+        assert_eq!(zdata.get_field("bit_depth").unwrap(), 16);
+
+        let raw = image_data.to_raw_zdata().unwrap();
+        assert_eq!(raw.dtype.as_deref(), Some("uint16"));
+        assert_eq!(raw.shape, Some(vec![32, 64, 1]));
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn test_rgb16_image_roundtrip() {
+        use image::{ImageBuffer, Rgb};
+
+        let img: ImageBuffer<Rgb<u16>, Vec<u16>> =
+            ImageBuffer::from_fn(8, 8, |x, y| Rgb([x as u16 * 8000, y as u16 * 8000, 65535]));
+        let image_data = ImageData::new(DynamicImage::ImageRgb16(img.clone()), ImageFormat::Png);
+
+        let restored = ImageData::from_zdata(&image_data.to_zdata().unwrap()).unwrap();
+        assert_eq!(restored.image.as_rgb16().unwrap(), &img);
+        assert!(restored.as_depth_u16().is_none());
+
+        let rgb8 = ImageData::new(DynamicImage::new_rgb8(2, 2), ImageFormat::Png);
+        assert_eq!(rgb8.bit_depth(), 8);
+        assert_eq!(rgb8.to_zdata().unwrap().get_field("bit_depth").unwrap(), 8);
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn test_image_format_mapping() {
+        for (format, name) in IMAGE_FORMATS {
+            assert_eq!(image_format_to_str(*format).unwrap(), *name);
+            assert_eq!(image_format_from_str(name).unwrap(), *format);
+        }
+
+        assert!(image_format_to_str(ImageFormat::Dds).is_err());
+        assert!(image_format_from_str("unknown").is_err());
+
+        let supported = ImageData::supported_formats();
+        assert_eq!(supported.len(), IMAGE_FORMATS.len());
+        assert!(supported.contains(&"tiff") && supported.contains(&"bmp"));
+        for name in supported {
+            assert!(image_format_from_str(name).is_ok());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn test_image_conversion_bmp_tiff() {
+        use image::{ImageBuffer, Rgb};
+
+        let img = DynamicImage::ImageRgb8(ImageBuffer::from_fn(16, 8, |x, y| {
+            Rgb([(x * 16) as u8, (y * 32) as u8, 128])
+        }));
+
+        for (format, name) in [(ImageFormat::Bmp, "bmp"), (ImageFormat::Tiff, "tiff")] {
+            let zdata = ImageData::new(img.clone(), format).to_zdata().unwrap();
+            assert_eq!(zdata.get_field("format").unwrap().as_str().unwrap(), name);
+
+            let restored = ImageData::from_zdata(&zdata).unwrap();
+            assert_eq!(restored.format, format);
+            assert_eq!(restored.image.to_rgb8(), img.to_rgb8());
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn test_image_resize_and_thumbnail() {
+        use image::imageops::FilterType;
+
+        let image_data = ImageData::new(DynamicImage::new_rgb8(1000, 500), ImageFormat::Jpeg);
+
+        let resized = image_data.resize(64, 48, FilterType::Triangle);
+        assert_eq!((resized.image.width(), resized.image.height()), (64, 48));
+        assert_eq!(resized.format, ImageFormat::Jpeg);
+
+        let thumb = image_data.thumbnail(128);
+        let (w, h) = (thumb.image.width(), thumb.image.height());
+        assert!(w <= 128 && h <= 128);
+        assert_eq!((w, h), (128, 64));
+        assert_eq!(thumb.format, ImageFormat::Jpeg);
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn test_image_rgb_bytes_roundtrip() {
+        use image::{ImageBuffer, Rgb};
+
+        let img = DynamicImage::ImageRgb8(ImageBuffer::from_fn(7, 5, |x, y| {
+            Rgb([(x * 30) as u8, (y * 50) as u8, (x + y) as u8])
+        }));
+        let (width, height, data) = ImageData::new(img.clone(), ImageFormat::Png)
+            .to_rgb_bytes()
+            .unwrap();
+        assert_eq!((width, height, data.len()), (7, 5, 7 * 5 * 3));
+
+        let restored = ImageData::from_rgb_bytes(width, height, data, ImageFormat::Png).unwrap();
+        assert_eq!(restored.image, img);
+        assert_eq!(restored.format, ImageFormat::Png);
+
+        let err = ImageData::from_rgb_bytes(7, 5, vec![0; 100], ImageFormat::Png).err();
+        assert!(matches!(err, Some(VmpError::TypeConversion(_))));
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn test_image_conversion_unsupported_format() {
+        let img = DynamicImage::new_rgb8(4, 4);
+
+        let err = ImageData::new(img, ImageFormat::Dds).to_zdata().err().unwrap();
+        assert!(matches!(err, VmpError::TypeConversion(_)));
+    }
+
+    /// Options using a fresh registry holding the built-in types, so tests
+    /// do not depend on what others put in the global one
+    #[cfg(any(feature = "ndarray", feature = "image"))]
+    fn local_registry_options() -> (
+        crate::serializer::SerializeOptions,
+        crate::deserializer::DeserializeOptions,
+    ) {
+        let registry = TypeRegistry::new();
+        register_all_into(&registry);
+        let ser = crate::serializer::SerializeOptions {
+            type_registry: Some(registry.clone()),
+            ..Default::default()
+        };
+        let de = crate::deserializer::DeserializeOptions {
+            type_registry: Some(registry),
+            ..Default::default()
+        };
+        (ser, de)
+    }
+
+    #[test]
+    #[cfg(feature = "ndarray")]
+    fn test_registered_ndarray_message_roundtrip() {
+        use crate::deserializer::{decode_value_recursive, deserialize_message_with_options};
+        use crate::serializer::{encode_value_recursive, serialize_message_with_options};
+        use crate::types::Message;
+
+        let (ser, de) = local_registry_options();
+        let array = Array::from_shape_vec(IxDyn(&[2, 2]), vec![0.5f32, 1.0, 1.5, 2.0]).unwrap();
+        let zdata = NumpyArray::new(array.clone()).to_zdata().unwrap();
+        let msg = Message::new("ARRAY").with_data(serde_json::to_value(&zdata).unwrap());
+
+        let bytes = serialize_message_with_options(&msg, &ser).unwrap();
+        let restored = deserialize_message_with_options(&bytes, &de).unwrap();
+
+        let decoded = decode_value_recursive(&restored.data.unwrap(), &de).unwrap();
+        assert_eq!(
+            decoded,
+            serde_json::json!({"dtype": "float32", "shape": [2, 2], "data": [0.5, 1.0, 1.5, 2.0]})
+        );
+
+        // The decoded form is recognized and encoded back to ZData
+        let encoded = encode_value_recursive(&decoded, &ser).unwrap();
+        let zdata: ZData = serde_json::from_value(encoded).unwrap();
+        assert_eq!(NumpyArray::from_zdata(&zdata).unwrap().array, array);
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn test_registered_image_message_roundtrip() {
+        use crate::deserializer::{decode_value_recursive, deserialize_message_with_options};
+        use crate::serializer::{encode_value_recursive, serialize_message_with_options};
+        use crate::types::Message;
+        use base64::Engine;
+        use image::{ImageBuffer, Rgb};
+
+        let (ser, de) = local_registry_options();
+
+        let img = ImageBuffer::from_fn(4, 3, |x, y| Rgb([x as u8 * 60, y as u8 * 80, 7]));
+        let original = DynamicImage::ImageRgb8(img);
+
+        for zdata in [
+            ImageData::new(original.clone(), ImageFormat::Png).to_zdata().unwrap(),
+            ImageData::new(original.clone(), ImageFormat::Png).to_raw_zdata().unwrap(),
+        ] {
+            let msg = Message::new("FRAME").with_data(serde_json::to_value(&zdata).unwrap());
+
+            let bytes = serialize_message_with_options(&msg, &ser).unwrap();
+            let restored = deserialize_message_with_options(&bytes, &de).unwrap();
+
+            let decoded = decode_value_recursive(&restored.data.unwrap(), &de).unwrap();
+            assert_eq!(decoded["format"], "png");
+            assert_eq!(decoded["width"], 4);
+            assert_eq!(decoded["height"], 3);
+            assert_eq!(decoded["channels"], 3);
+            assert_eq!(decoded["bit_depth"], 8);
+
+            let png = base64::engine::general_purpose::STANDARD
+                .decode(decoded["data"].as_str().unwrap())
+                .unwrap();
+            let pixels = image::load_from_memory_with_format(&png, ImageFormat::Png).unwrap();
+            assert_eq!(pixels, original);
+
+            // The decoded form is recognized and encoded back to ZData
+            let encoded = encode_value_recursive(&decoded, &ser).unwrap();
+            let zdata: ZData = serde_json::from_value(encoded).unwrap();
+            assert_eq!(ImageData::from_zdata(&zdata).unwrap().image, original);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn test_registered_image_rejects_invalid_json() {
+        let registry = TypeRegistry::new();
+        register_image_into(&registry);
+
+        let value = serde_json::json!({
+            "format": "png",
+            "width": 1,
+            "height": 1,
+            "channels": 3,
+            "bit_depth": 8,
+            "data": "bm90IGFuIGltYWdl",
+        });
+        assert!(registry.encode("image", &value).is_err());
+    }
+
     #[test]
     fn test_type_conversion_fallback() {
         assert!(TypeConversionFallback::is_ndarray_available() == cfg!(feature = "ndarray"));
         assert!(TypeConversionFallback::is_image_available() == cfg!(feature = "image"));
+        assert!(TypeConversionFallback::is_video_available() == cfg!(feature = "video"));
+    }
+
+    #[test]
+    #[cfg(feature = "video")]
+    fn test_video_frame_roundtrip() {
+        let frame = VideoFrame::new("h264", 33_366, vec![0, 0, 0, 1, 0x65, 0x88])
+            .with_keyframe(true)
+            .with_dimensions(1920, 1080);
+
+        let zdata = frame.to_zdata().unwrap();
+        assert_eq!(zdata.ztype, "video.frame");
+        assert_eq!(zdata.get_field("codec").unwrap(), "h264");
+        assert_eq!(zdata.get_field("pts").unwrap(), 33_366);
+
+        let bytes = crate::serializer::zdata_to_bytes(&zdata).unwrap();
+        let restored: ZData = crate::deserializer::deserialize(&bytes).unwrap();
+        let restored = VideoFrame::from_zdata(&restored).unwrap();
+        assert_eq!(restored, frame);
+        assert_eq!(restored.width(), Some(1920));
+        assert!(restored.is_keyframe());
+
+        // Optional fields are omitted when unset
+        let frame = VideoFrame::new("vp9", -5, vec![1, 2, 3]);
+        let zdata = frame.to_zdata().unwrap();
+        assert!(zdata.get_field("width").is_none());
+        assert_eq!(VideoFrame::from_zdata(&zdata).unwrap(), frame);
+    }
+
+    #[test]
+    #[cfg(feature = "video")]
+    fn test_video_frame_from_python_map() {
+        let zdata: ZData = serde_json::from_value(serde_json::json!({
+            "ztype": "video.frame",
+            "b": [0, 0, 1, 0xb3],
+            "codec": "vp9",
+            "pts": 1_000_000,
+            "keyframe": false,
+            "width": 640,
+            "height": 480,
+        }))
+        .unwrap();
+
+        let frame = VideoFrame::from_zdata(&zdata).unwrap();
+        assert_eq!(frame.codec(), "vp9");
+        assert_eq!(frame.pts(), 1_000_000);
+        assert!(!frame.is_keyframe());
+        assert_eq!((frame.width(), frame.height()), (Some(640), Some(480)));
+        assert_eq!(frame.data(), &[0, 0, 1, 0xb3]);
+
+        let mut missing_pts = zdata.clone();
+        missing_pts.extra.shift_remove("pts");
+        assert!(matches!(VideoFrame::from_zdata(&missing_pts), Err(VmpError::MissingField(_))));
+
+        let mut missing_codec = zdata;
+        missing_codec.extra.shift_remove("codec");
+        assert!(matches!(VideoFrame::from_zdata(&missing_codec), Err(VmpError::MissingField(_))));
     }
 }
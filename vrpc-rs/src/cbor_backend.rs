@@ -0,0 +1,268 @@
+//! CBOR serialization backend for VMP
+//!
+//! Author: Ge Yang
+
+use crate::deserializer::{
+    check_component_depth, check_input_size, decode_message_payloads, DeserializeOptions,
+};
+use crate::error::{Result, VmpError};
+use crate::serializer::{prepare_component, prepare_message, Base64Variant, SerializeOptions};
+use crate::types::{check_protocol_version, Message, VuerComponent};
+use base64::Engine;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Serialize a value to CBOR
+///
+/// ZData binary fields are written as native CBOR byte strings.
+pub fn serialize_cbor<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+    serialize_cbor_with_options(value, &SerializeOptions::default())
+}
+
+/// Serialize a value to CBOR with custom options
+///
+/// Only `max_message_size` applies here; the encoding options take effect
+/// through the message and component functions.
+pub fn serialize_cbor_with_options<T: Serialize>(
+    value: &T,
+    options: &SerializeOptions,
+) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    ciborium::into_writer(value, &mut buf)
+        .map_err(|e| VmpError::Serialization(format!("CBOR encode error: {}", e)))?;
+    if options.max_message_size.is_some_and(|limit| buf.len() > limit) {
+        return Err(VmpError::Serialization("message exceeds max_message_size".to_string()));
+    }
+    Ok(buf)
+}
+
+/// Deserialize a value from CBOR
+pub fn deserialize_cbor<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    ciborium::from_reader(bytes)
+        .map_err(|e| VmpError::Deserialization(format!("CBOR decode error: {}", e)))
+}
+
+/// Serialize to base64-encoded CBOR
+pub fn serialize_cbor_to_base64<T: Serialize>(value: &T) -> Result<String> {
+    serialize_cbor_to_base64_with(value, Base64Variant::StandardPad)
+}
+
+/// Serialize to CBOR encoded with the given base64 variant
+pub fn serialize_cbor_to_base64_with<T: Serialize>(
+    value: &T,
+    variant: Base64Variant,
+) -> Result<String> {
+    Ok(variant.engine().encode(serialize_cbor(value)?))
+}
+
+/// Deserialize from base64-encoded CBOR
+pub fn deserialize_cbor_from_base64<T: DeserializeOwned>(encoded: &str) -> Result<T> {
+    deserialize_cbor_from_base64_with(encoded, Base64Variant::StandardPad)
+}
+
+/// Deserialize from CBOR encoded with the given base64 variant
+pub fn deserialize_cbor_from_base64_with<T: DeserializeOwned>(
+    encoded: &str,
+    variant: Base64Variant,
+) -> Result<T> {
+    let bytes = variant
+        .engine()
+        .decode(encoded)
+        .map_err(|e| VmpError::Deserialization(format!("Base64 decode error: {}", e)))?;
+    deserialize_cbor(&bytes)
+}
+
+/// Serialize a message to CBOR
+///
+/// Payloads are encoded with the global type registry as in
+/// [`crate::serializer::serialize_message`].
+pub fn serialize_cbor_message(message: &Message) -> Result<Vec<u8>> {
+    serialize_cbor_message_with_options(message, &SerializeOptions::default())
+}
+
+/// Serialize a message to CBOR with custom options
+pub fn serialize_cbor_message_with_options(
+    message: &Message,
+    options: &SerializeOptions,
+) -> Result<Vec<u8>> {
+    serialize_cbor_with_options(&*prepare_message(message, options)?, options)
+}
+
+/// Deserialize a message from CBOR
+///
+/// Messages from a newer major protocol version are rejected.
+pub fn deserialize_cbor_message(bytes: &[u8]) -> Result<Message> {
+    let message: Message = deserialize_cbor(bytes)?;
+    if let Some(version) = &message.vmp_version {
+        check_protocol_version(version)?;
+    }
+    Ok(message)
+}
+
+/// Deserialize a message from CBOR, decoding ZData in its payloads with
+/// [`crate::deserializer::decode_value_recursive`]
+pub fn deserialize_cbor_message_with_options(
+    bytes: &[u8],
+    options: &DeserializeOptions,
+) -> Result<Message> {
+    check_input_size(bytes.len(), options)?;
+    let mut message = deserialize_cbor_message(bytes)?;
+    decode_message_payloads(&mut message, options)?;
+    Ok(message)
+}
+
+/// Serialize a Vuer component tree to CBOR
+///
+/// Props are encoded with the global type registry as in
+/// [`crate::serializer::serialize_component`].
+pub fn serialize_cbor_component(component: &VuerComponent) -> Result<Vec<u8>> {
+    serialize_cbor_component_with_options(component, &SerializeOptions::default())
+}
+
+/// Serialize a Vuer component tree to CBOR with custom options
+pub fn serialize_cbor_component_with_options(
+    component: &VuerComponent,
+    options: &SerializeOptions,
+) -> Result<Vec<u8>> {
+    serialize_cbor_with_options(&*prepare_component(component, options)?, options)
+}
+
+/// Deserialize a Vuer component tree from CBOR
+pub fn deserialize_cbor_component(bytes: &[u8]) -> Result<VuerComponent> {
+    deserialize_cbor_component_with_options(bytes, &DeserializeOptions::default())
+}
+
+/// Deserialize a Vuer component tree from CBOR, rejecting trees nested
+/// deeper than `options.max_depth`
+pub fn deserialize_cbor_component_with_options(
+    bytes: &[u8],
+    options: &DeserializeOptions,
+) -> Result<VuerComponent> {
+    check_input_size(bytes.len(), options)?;
+    let component: VuerComponent = deserialize_cbor(bytes)?;
+    check_component_depth(&component, options)?;
+    Ok(component)
+}
+
+/// Whether `bytes` look like a CBOR-encoded VMP value
+///
+/// Only the first byte is checked: VMP values are maps, which CBOR starts
+/// with 0xa0-0xbb (or 0xbf when the length is not known up front), while
+/// MessagePack maps start with 0x80-0x8f, 0xde or 0xdf and positional
+/// MessagePack structs with 0x90-0x9f, 0xdc or 0xdd. The check says nothing
+/// about other values: a MessagePack string (0xa0-0xbf) also passes, so
+/// only use it on input already known to hold a VMP message or component.
+pub fn is_cbor(bytes: &[u8]) -> bool {
+    matches!(bytes.first(), Some(0xa0..=0xbb | 0xbf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zdata::ZData;
+    use serde_json::json;
+
+    #[test]
+    fn test_message_roundtrip() {
+        let msg = Message::new("UPDATE")
+            .with_data(json!({"position": [1.0, 2.5, -3.0], "label": "cube"}))
+            .with_correlation("req-1");
+
+        let bytes = serialize_cbor_message(&msg).unwrap();
+        assert!(is_cbor(&bytes));
+        assert!(!is_cbor(&crate::serializer::serialize_message(&msg).unwrap()));
+        assert_eq!(deserialize_cbor_message(&bytes).unwrap(), msg);
+
+        // MessagePack arrays, including a 15-element fixarray, are not CBOR maps
+        assert!(!is_cbor(&rmp_serde::to_vec(&[0u8; 15]).unwrap()));
+        assert!(!is_cbor(&rmp_serde::to_vec(&[0u8; 3]).unwrap()));
+    }
+
+    #[test]
+    fn test_component_roundtrip() {
+        let scene = VuerComponent::new("scene")
+            .with_prop("background", json!("#000"))
+            .with_child(VuerComponent::new("box").with_key("a"));
+
+        let bytes = serialize_cbor_component(&scene).unwrap();
+        assert_eq!(deserialize_cbor_component(&bytes).unwrap(), scene);
+    }
+
+    #[test]
+    fn test_zdata_binary_is_byte_string() {
+        let payload = vec![0xaa; 32];
+        let zdata = ZData::new("custom.Blob")
+            .with_binary(payload.clone())
+            .with_shape(vec![32])
+            .with_field("meta", json!({"source": "camera"}));
+
+        let bytes = serialize_cbor(&zdata).unwrap();
+        // Major type 2 (byte string) with a one-byte length, then the raw bytes
+        let mut header = vec![0x58, 32];
+        header.extend_from_slice(&payload);
+        assert!(bytes.windows(header.len()).any(|w| w == header));
+
+        let restored: ZData = deserialize_cbor(&bytes).unwrap();
+        assert_eq!(restored, zdata);
+    }
+
+    #[test]
+    fn test_message_registry_roundtrip() {
+        let registry = crate::type_registry::TypeRegistry::new();
+        registry.register(
+            "test.CborPoint",
+            |value| Ok(ZData::new("test.CborPoint").with_field("xy", value["cbor_point"].clone())),
+            |zdata| Ok(json!({"cbor_point": zdata.get_field("xy").unwrap()})),
+            Some(std::sync::Arc::new(|value: &serde_json::Value| {
+                value.get("cbor_point").is_some()
+            })),
+        );
+
+        let encode = SerializeOptions {
+            type_registry: Some(registry.clone()),
+            ..Default::default()
+        };
+        let decode = DeserializeOptions {
+            type_registry: Some(registry),
+            ..Default::default()
+        };
+
+        let msg = Message::new("MOVE").with_value(json!({"cbor_point": [1, 2]}));
+        let bytes = serialize_cbor_message_with_options(&msg, &encode).unwrap();
+        let wire = deserialize_cbor_message(&bytes).unwrap();
+        assert_eq!(wire.value, Some(json!({"ztype": "test.CborPoint", "xy": [1, 2]})));
+        assert_eq!(deserialize_cbor_message_with_options(&bytes, &decode).unwrap(), msg);
+    }
+
+    #[test]
+    fn test_base64_roundtrip() {
+        let zdata = ZData::new("custom.Blob").with_binary(vec![1, 2, 3]);
+        let encoded = serialize_cbor_to_base64(&zdata).unwrap();
+        assert_eq!(deserialize_cbor_from_base64::<ZData>(&encoded).unwrap(), zdata);
+
+        let encoded = serialize_cbor_to_base64_with(&zdata, Base64Variant::UrlSafe).unwrap();
+        let restored: ZData =
+            deserialize_cbor_from_base64_with(&encoded, Base64Variant::UrlSafe).unwrap();
+        assert_eq!(restored, zdata);
+    }
+
+    #[test]
+    fn test_component_max_depth() {
+        let mut tree = VuerComponent::new("leaf");
+        for _ in 0..4 {
+            tree = VuerComponent::new("group").with_child(tree);
+        }
+        let bytes = serialize_cbor_component(&tree).unwrap();
+        let options = DeserializeOptions {
+            max_depth: Some(3),
+            ..Default::default()
+        };
+        assert!(deserialize_cbor_component_with_options(&bytes, &options).is_err());
+    }
+
+    #[test]
+    fn test_invalid_cbor() {
+        let err = deserialize_cbor::<Message>(&[0xff, 0x00]).unwrap_err();
+        assert!(matches!(err, VmpError::Deserialization(_)));
+    }
+}
@@ -0,0 +1,94 @@
+//! Message deduplication by content hash
+//!
+//! Author: Ge Yang
+
+use crate::error::Result;
+use crate::types::Message;
+use lru::LruCache;
+use std::num::NonZeroUsize;
+
+/// Remembers the content hashes of recently seen messages
+///
+/// Retransmitted messages that differ only in `ts` are recognized as
+/// duplicates. Once `capacity` hashes are stored, the least recently seen
+/// one is forgotten.
+pub struct MessageDeduplicator {
+    seen: LruCache<[u8; 32], ()>,
+}
+
+impl MessageDeduplicator {
+    /// Create a deduplicator remembering up to `capacity` messages
+    /// (at least one)
+    pub fn new(capacity: usize) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            seen: LruCache::new(capacity),
+        }
+    }
+
+    /// Record a message, returning `true` if its content was not seen before
+    /// and `false` if it is a duplicate
+    ///
+    /// Fails, recording nothing, if the message cannot be hashed.
+    pub fn check_and_insert(&mut self, msg: &Message) -> Result<bool> {
+        Ok(self.seen.put(msg.content_hash()?, ()).is_none())
+    }
+
+    /// Number of hashes currently remembered
+    pub fn len(&self) -> usize {
+        self.seen.len()
+    }
+
+    /// Whether no messages have been recorded
+    pub fn is_empty(&self) -> bool {
+        self.seen.is_empty()
+    }
+
+    /// Forget all recorded messages
+    pub fn clear(&mut self) {
+        self.seen.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_duplicate_with_different_ts() {
+        let mut dedup = MessageDeduplicator::new(16);
+
+        let mut msg = Message::new("CLICK").with_value(json!({"x": 10}));
+        msg.ts = 1_000;
+        assert!(dedup.check_and_insert(&msg).unwrap());
+
+        let mut retransmit = msg.clone();
+        retransmit.ts = 2_000;
+        assert!(!dedup.check_and_insert(&retransmit).unwrap());
+
+        let other = Message::new("CLICK").with_value(json!({"x": 11}));
+        assert!(dedup.check_and_insert(&other).unwrap());
+        assert_eq!(dedup.len(), 2);
+    }
+
+    #[test]
+    fn test_capacity_evicts_oldest() {
+        let mut dedup = MessageDeduplicator::new(2);
+
+        let messages: Vec<Message> = (0..3)
+            .map(|i| Message::new("CLICK").with_value(json!(i)))
+            .collect();
+        for msg in &messages {
+            assert!(dedup.check_and_insert(msg).unwrap());
+        }
+        assert_eq!(dedup.len(), 2);
+
+        // The first message was evicted, so it is treated as new again
+        assert!(dedup.check_and_insert(&messages[0]).unwrap());
+        assert!(!dedup.check_and_insert(&messages[2]).unwrap());
+
+        dedup.clear();
+        assert!(dedup.is_empty());
+    }
+}
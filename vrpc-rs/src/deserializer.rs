@@ -2,13 +2,47 @@
 //!
 //! Author: Ge Yang
 
-use crate::error::{Result, VmpError};
 use base64::Engine;
-use crate::type_registry::GLOBAL_TYPE_REGISTRY;
-use crate::types::{Message, VuerComponent};
-use crate::zdata::ZData;
+use crate::error::{Result, VmpError};
+use crate::serializer::Base64Variant;
+use crate::type_registry::{TypeRegistry, UnknownOrKnown, GLOBAL_TYPE_REGISTRY};
+use crate::types::{
+    check_protocol_version, is_expired, Message, Timestamp, VmpEnvelope, VuerComponent,
+};
+use crate::validator::{RULE_NONEMPTY_ETYPE, RULE_RPC_HAS_RTYPE};
+use crate::zdata::{unwrap_unknown, wrap_unknown, ZData, ZDATA_RESERVED_KEYS};
 use serde::de::DeserializeOwned;
+use serde::Deserialize;
 use serde_json::Value;
+use std::io::Read;
+
+/// Default nesting limit for [`DeserializeOptions::max_depth`]
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// Default limit for [`DeserializeOptions::max_bytes`] (64MB)
+pub const DEFAULT_MAX_BYTES: usize = 64 * 1024 * 1024;
+
+/// Default limit for [`DeserializeOptions::max_binary_bytes`] (256MB)
+pub const DEFAULT_MAX_BINARY_BYTES: usize = 256 * 1024 * 1024;
+
+/// Default limit for [`DeserializeOptions::max_shape_elements`]
+pub const DEFAULT_MAX_SHAPE_ELEMENTS: usize = 256 * 1024 * 1024;
+
+/// How [`decode_value_recursive`] handles ZData it cannot decode
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnknownTypePolicy {
+    /// Return the ZData map, with its extra fields decoded
+    #[default]
+    Passthrough,
+
+    /// Wrap the original map under [`crate::zdata::UNKNOWN_TYPE_KEY`], so it
+    /// can be recovered with `UnknownType::from_wrapped` and re-serialized
+    /// unchanged
+    Wrap,
+
+    /// Fail with `VmpError::TypeNotRegistered`
+    Error,
+}
 
 /// Deserialization options
 #[derive(Debug, Clone)]
@@ -19,8 +53,73 @@ pub struct DeserializeOptions {
     /// Validate message structure
     pub validate: bool,
 
-    /// Use the global type registry for custom types
+    /// Use the type registry for custom types
     pub use_type_registry: bool,
+
+    /// Registry consulted instead of [`GLOBAL_TYPE_REGISTRY`] when
+    /// `use_type_registry` is set
+    pub type_registry: Option<TypeRegistry>,
+
+    /// Maximum nesting depth for decoded values and component trees
+    /// (`None` for no limit)
+    pub max_depth: Option<usize>,
+
+    /// Largest serialized message accepted, in bytes (`None` for no limit)
+    ///
+    /// Input longer than this is rejected before any of it is parsed, and
+    /// the same limit caps each frame read by [`read_framed_with_options`]
+    /// and [`crate::stream::MessageStream`] and each message read by
+    /// [`MessageReader`].
+    pub max_bytes: Option<usize>,
+
+    /// Reject messages whose `expires_at` time has passed
+    pub reject_expired: bool,
+
+    /// Reject structs carrying fields their Rust type does not model,
+    /// instead of dropping them
+    pub strict: bool,
+
+    /// Reject ZData whose stored checksum does not match its data
+    pub verify_checksum: bool,
+
+    /// Maximum size of a single ZData binary buffer (`None` for no limit)
+    pub max_binary_bytes: Option<usize>,
+
+    /// Maximum number of elements described by a ZData shape
+    /// (`None` for no limit)
+    pub max_shape_elements: Option<usize>,
+
+    /// What to do with ZData whose type is not registered
+    pub unknown_types: UnknownTypePolicy,
+
+    /// Keep ZData whose type is not registered, as `unknown_types` says
+    ///
+    /// Unset, unknown types fail with `VmpError::TypeNotRegistered`
+    /// whatever `unknown_types` is, as if it were
+    /// [`UnknownTypePolicy::Error`].
+    pub preserve_unknown_types: bool,
+
+    /// Check decoded ZData with [`ZData::validate_with`]
+    pub validate_zdata: bool,
+
+    /// Accept ZData with unrecognized dtypes when `validate_zdata` is set
+    pub allow_unknown_dtypes: bool,
+}
+
+impl DeserializeOptions {
+    /// The registry selected by `type_registry`
+    pub(crate) fn registry(&self) -> &TypeRegistry {
+        self.type_registry.as_ref().unwrap_or(&GLOBAL_TYPE_REGISTRY)
+    }
+
+    /// `unknown_types`, overridden by `preserve_unknown_types`
+    fn unknown_type_policy(&self) -> UnknownTypePolicy {
+        if self.preserve_unknown_types {
+            self.unknown_types
+        } else {
+            UnknownTypePolicy::Error
+        }
+    }
 }
 
 impl Default for DeserializeOptions {
@@ -29,6 +128,18 @@ impl Default for DeserializeOptions {
             recursive: true,
             validate: true,
             use_type_registry: true,
+            type_registry: None,
+            max_depth: Some(DEFAULT_MAX_DEPTH),
+            max_bytes: Some(DEFAULT_MAX_BYTES),
+            reject_expired: false,
+            strict: false,
+            verify_checksum: false,
+            max_binary_bytes: Some(DEFAULT_MAX_BINARY_BYTES),
+            max_shape_elements: Some(DEFAULT_MAX_SHAPE_ELEMENTS),
+            unknown_types: UnknownTypePolicy::Passthrough,
+            preserve_unknown_types: true,
+            validate_zdata: true,
+            allow_unknown_dtypes: true,
         }
     }
 }
@@ -41,67 +152,786 @@ pub fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
 /// Deserialize with custom options
 pub fn deserialize_with_options<T: DeserializeOwned>(
     bytes: &[u8],
-    _options: &DeserializeOptions,
+    options: &DeserializeOptions,
 ) -> Result<T> {
-    let value = rmp_serde::from_slice(bytes)
-        .map_err(|e| VmpError::Deserialization(e.to_string()))?;
-    Ok(value)
+    check_input_size(bytes.len(), options)?;
+    if options.strict {
+        check_unknown_fields::<T>(bytes, options)?;
+    }
+    let mut deserializer = wire_deserializer(bytes, options);
+    T::deserialize(&mut deserializer).map_err(|e| locate_error::<T>(bytes, e))
+}
+
+/// Wire nesting limit of the second pass made by [`locate_error`]
+const LOCATE_MAX_DEPTH: usize = 128;
+
+/// Decode `bytes` again, tracking the field path and byte offset, to turn
+/// `err` into a `VmpError::DeserializationDetailed`
+///
+/// Tracking costs stack space per level, so the second pass gives up below
+/// [`LOCATE_MAX_DEPTH`] and `err` is returned without a location.
+fn locate_error<T: DeserializeOwned>(bytes: &[u8], err: rmp_serde::decode::Error) -> VmpError {
+    let mut deserializer = rmp_serde::Deserializer::new(std::io::Cursor::new(bytes));
+    deserializer.set_max_depth(LOCATE_MAX_DEPTH);
+    match serde_path_to_error::deserialize::<_, T>(&mut deserializer) {
+        Err(e) if !matches!(e.inner(), rmp_serde::decode::Error::DepthLimitExceeded) => {
+            VmpError::DeserializationDetailed {
+                path: e.path().to_string(),
+                offset: deserializer.position(),
+                message: e.into_inner().to_string(),
+            }
+        }
+        _ => VmpError::Deserialization(err.to_string()),
+    }
+}
+
+/// Reject a map with keys that are not fields of `T`
+///
+/// Types that do not deserialize as a plain struct, such as those with
+/// flattened fields, and input that is not a map are not checked.
+fn check_unknown_fields<T: DeserializeOwned>(
+    bytes: &[u8],
+    options: &DeserializeOptions,
+) -> Result<()> {
+    let Some((name, fields)) = struct_fields::<T>() else {
+        return Ok(());
+    };
+    let mut deserializer = wire_deserializer(bytes, options);
+    let Ok(keys) =
+        std::collections::BTreeMap::<String, serde::de::IgnoredAny>::deserialize(&mut deserializer)
+    else {
+        return Ok(());
+    };
+    match keys.keys().find(|key| !fields.contains(&key.as_str())) {
+        Some(key) => Err(VmpError::InvalidMessage(format!(
+            "unknown field `{}` in {}",
+            key, name
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Name and field names of a struct deriving `Deserialize`, captured from
+/// the call its derived impl makes to `deserialize_struct`
+fn struct_fields<T: DeserializeOwned>() -> Option<(&'static str, &'static [&'static str])> {
+    use serde::de::{Error, Visitor};
+
+    type Fields = Option<(&'static str, &'static [&'static str])>;
+
+    struct Introspect<'a>(&'a mut Fields);
+
+    impl<'de> serde::Deserializer<'de> for Introspect<'_> {
+        type Error = serde::de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(
+            self,
+            _visitor: V,
+        ) -> std::result::Result<V::Value, Self::Error> {
+            Err(Error::custom("not a struct"))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            name: &'static str,
+            fields: &'static [&'static str],
+            _visitor: V,
+        ) -> std::result::Result<V::Value, Self::Error> {
+            *self.0 = Some((name, fields));
+            Err(Error::custom("fields captured"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map enum identifier ignored_any
+        }
+    }
+
+    let mut fields = None;
+    let _ = T::deserialize(Introspect(&mut fields));
+    fields
+}
+
+/// A MessagePack deserializer whose nesting limit follows `options.max_depth`
+///
+/// The wire limit is four times `max_depth`. Each level of a component tree
+/// is a map holding a `children` array, so takes two levels on the wire;
+/// the rest is headroom, so input nested just past `max_depth` still
+/// decodes far enough to fail the depth check that reports where the limit
+/// was crossed. The wire limit itself only keeps hostile input from
+/// exhausting the stack.
+fn wire_deserializer<'a>(
+    bytes: &'a [u8],
+    options: &DeserializeOptions,
+) -> rmp_serde::Deserializer<rmp_serde::decode::ReadRefReader<'a, [u8]>> {
+    let mut deserializer = rmp_serde::Deserializer::from_read_ref(bytes);
+    if let Some(max_depth) = options.max_depth {
+        deserializer.set_max_depth(max_depth.saturating_mul(4));
+    }
+    deserializer
+}
+
+/// Routing fields of a message, read by [`peek_fields`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PeekedFields {
+    /// Event type
+    pub etype: String,
+    /// Response type (RPC only)
+    pub rtype: Option<String>,
+    /// Timestamp in milliseconds
+    pub ts: Timestamp,
+}
+
+/// Read just the `etype` of a serialized message
+pub fn peek_etype(bytes: &[u8]) -> Result<String> {
+    peek_fields(bytes).map(|fields| fields.etype)
+}
+
+/// Read the `etype`, `rtype` and `ts` of a serialized message without
+/// decoding the rest
+///
+/// The values of other fields are skipped over in place, so large payloads
+/// cost neither allocation nor copying. Only messages written as named maps
+/// can be peeked, with missing fields taking the same defaults as in
+/// [`deserialize_message`]; positional arrays fail with
+/// `VmpError::Deserialization`, since their positions depend on which
+/// optional fields the sender set.
+pub fn peek_fields(bytes: &[u8]) -> Result<PeekedFields> {
+    /// `ts` normalized as in a full decode
+    #[derive(Deserialize)]
+    struct Ts(#[serde(deserialize_with = "crate::types::deserialize_timestamp")] Timestamp);
+
+    struct PeekVisitor;
+
+    impl<'de> serde::de::Visitor<'de> for PeekVisitor {
+        type Value = PeekedFields;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a message map")
+        }
+
+        fn visit_map<A: serde::de::MapAccess<'de>>(
+            self,
+            mut map: A,
+        ) -> std::result::Result<Self::Value, A::Error> {
+            let mut fields = PeekedFields::default();
+            while let Some(key) = map.next_key::<&str>()? {
+                match key {
+                    "etype" => fields.etype = map.next_value()?,
+                    "rtype" => fields.rtype = map.next_value()?,
+                    "ts" => fields.ts = map.next_value::<Ts>()?.0,
+                    _ => {
+                        map.next_value::<serde::de::IgnoredAny>()?;
+                    }
+                }
+            }
+            Ok(fields)
+        }
+
+        fn visit_seq<A: serde::de::SeqAccess<'de>>(
+            self,
+            _seq: A,
+        ) -> std::result::Result<Self::Value, A::Error> {
+            Err(serde::de::Error::custom(
+                "positional messages cannot be peeked; encode with struct_map",
+            ))
+        }
+    }
+
+    let mut deserializer = rmp_serde::Deserializer::from_read_ref(bytes);
+    serde::Deserializer::deserialize_any(&mut deserializer, PeekVisitor)
+        .map_err(|e| VmpError::Deserialization(format!("cannot peek message: {}", e)))
+}
+
+/// Deserialize a message into the [`VmpEnvelope`] variant its fields
+/// point to
+///
+/// The bytes are decoded once and classified in this order: `ok` or
+/// `error` make an `RpcResponse`, `rtype` with `args` or `kwargs` an
+/// `RpcRequest`, `value` a `ClientEvent` and `data` a `ServerEvent`. Null
+/// fields count as absent. A frame matching none of these, or more than one
+/// (such as an RPC request that also has `ok`, or an event with both `data`
+/// and `value`), comes back as a plain `Message`. Payloads are left as they
+/// are.
+pub fn deserialize_envelope(bytes: &[u8]) -> Result<VmpEnvelope> {
+    let map: serde_json::Map<String, Value> =
+        deserialize_with_options(bytes, &DeserializeOptions::default())?;
+    if let Some(version) = map.get("vmp_version").and_then(Value::as_str) {
+        check_protocol_version(version)?;
+    }
+
+    let has = |key: &str| map.get(key).is_some_and(|v| !v.is_null());
+    let is_response = has("ok") || has("error");
+    let is_request = has("rtype") && (has("args") || has("kwargs"));
+    let (has_value, has_data) = (has("value"), has("data"));
+
+    let value = Value::Object(map);
+    let envelope = match (is_response, is_request) {
+        (true, true) => VmpEnvelope::Message(serde_json::from_value(value)?),
+        (true, false) => VmpEnvelope::RpcResponse(serde_json::from_value(value)?),
+        (false, true) => VmpEnvelope::RpcRequest(serde_json::from_value(value)?),
+        (false, false) => match (has_value, has_data) {
+            (true, false) => VmpEnvelope::ClientEvent(serde_json::from_value(value)?),
+            (false, true) => VmpEnvelope::ServerEvent(serde_json::from_value(value)?),
+            _ => VmpEnvelope::Message(serde_json::from_value(value)?),
+        },
+    };
+    Ok(envelope)
+}
+
+/// Deserialize a value that borrows from `bytes`, such as
+/// [`crate::borrowed::MessageRef`], without copying strings or binary data
+pub fn deserialize_borrowed<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T> {
+    rmp_serde::from_slice(bytes).map_err(|e| VmpError::Deserialization(e.to_string()))
+}
+
+/// Reject input longer than `options.max_bytes`
+pub(crate) fn check_input_size(len: usize, options: &DeserializeOptions) -> Result<()> {
+    match options.max_bytes {
+        Some(max) if len > max => Err(VmpError::InvalidMessage(format!(
+            "input of {} bytes exceeds max_bytes of {}",
+            len, max
+        ))),
+        _ => Ok(()),
+    }
 }
 
 /// Deserialize a message from MessagePack
+///
+/// Messages from a newer major protocol version are rejected, as are
+/// messages that fail [`validate_message`]. Registered types in the
+/// payloads are decoded.
 pub fn deserialize_message(bytes: &[u8]) -> Result<Message> {
-    deserialize(bytes)
+    deserialize_message_with_options(bytes, &DeserializeOptions::default())
+}
+
+/// Deserialize a message written by
+/// [`crate::serializer::serialize_message_named`] or in positional form
+///
+/// The MessagePack marker tells a named map from a positional array, so
+/// both are read as by [`deserialize_message`].
+pub fn deserialize_message_named(bytes: &[u8]) -> Result<Message> {
+    deserialize_message(bytes)
+}
+
+/// Deserialize a message, running [`validate_message_with_options`] when
+/// `options.validate` is set
+///
+/// The protocol version is checked even with `validate` unset. The `data`,
+/// `value`, `args` and `kwargs` payloads are then passed through
+/// [`decode_value_recursive`], following `options.recursive` and
+/// `options.use_type_registry` as for component props.
+pub fn deserialize_message_with_options(
+    bytes: &[u8],
+    options: &DeserializeOptions,
+) -> Result<Message> {
+    let message: Message = deserialize_with_options(bytes, options)?;
+    finish_message(message, options)
+}
+
+/// Validate a freshly read message and decode its payloads
+fn finish_message(mut message: Message, options: &DeserializeOptions) -> Result<Message> {
+    if options.validate {
+        validate_message_with_options(&message, options)?;
+    } else if let Some(version) = &message.vmp_version {
+        check_protocol_version(version)?;
+    }
+    decode_message_payloads(&mut message, options)?;
+    Ok(message)
+}
+
+/// Read one message framed by [`crate::serializer::frame_message`]
+///
+/// Returns `Ok(None)` if the stream ends cleanly before the next frame.
+pub fn read_framed<R: Read>(reader: &mut R) -> Result<Option<Message>> {
+    read_framed_with_options(reader, &DeserializeOptions::default())
+}
+
+/// Read one framed message, rejecting frames larger than
+/// `options.max_bytes`
+///
+/// Frames are read by [`crate::framing::read_framed_with_limit`], so a
+/// stream that ends partway through a frame is a `VmpError::Io` of kind
+/// `UnexpectedEof`. The payload is decoded as by
+/// [`deserialize_message_with_options`].
+pub fn read_framed_with_options<R: Read>(
+    reader: &mut R,
+    options: &DeserializeOptions,
+) -> Result<Option<Message>> {
+    crate::framing::read_framed_with_limit(reader, options.max_bytes)?
+        .map(|buf| deserialize_message_with_options(&buf, options))
+        .transpose()
+}
+
+/// Iterate over back-to-back MessagePack messages read from `reader`
+pub fn deserialize_stream<R: Read>(reader: R) -> MessageReader<R> {
+    MessageReader::new(reader)
+}
+
+/// Iterator decoding concatenated messages from a reader, one at a time
+///
+/// Iteration ends cleanly when the input ends between two messages. After
+/// an error, which names the byte offset where the failed message started,
+/// no further items are produced, since the position of the next message is
+/// unknown.
+pub struct MessageReader<R> {
+    reader: std::io::BufReader<R>,
+    offset: u64,
+    options: DeserializeOptions,
+    failed: bool,
+}
+
+impl<R: Read> MessageReader<R> {
+    /// Read messages from `reader` with the default options
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader: std::io::BufReader::new(reader),
+            offset: 0,
+            options: DeserializeOptions::default(),
+            failed: false,
+        }
+    }
+
+    /// Fail on any message longer than `max_bytes`, before reading past it
+    ///
+    /// Sets `max_bytes` in the options, which holds
+    /// [`DEFAULT_MAX_BYTES`] unless changed.
+    pub fn with_max_message_size(mut self, max_bytes: usize) -> Self {
+        self.options.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Validate and decode each message as [`deserialize_message_with_options`]
+    /// does, with `max_bytes` capping the size of each message
+    pub fn with_options(mut self, options: DeserializeOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Number of bytes consumed from the input so far, including any read
+    /// by a message that failed to decode
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    fn read_message(&mut self) -> Result<Option<Message>> {
+        use std::io::BufRead;
+
+        if self.reader.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+
+        let start = self.offset;
+        let mut limited = LimitedReader {
+            inner: &mut self.reader,
+            read: 0,
+            limit: self.options.max_bytes,
+            exceeded: false,
+        };
+        let decoded = rmp_serde::decode::from_read::<_, Message>(&mut limited);
+        self.offset += limited.read as u64;
+        let message = match decoded {
+            Ok(message) => message,
+            Err(_) if limited.exceeded => {
+                return Err(VmpError::InvalidMessage(format!(
+                    "message at byte offset {} exceeds max_bytes",
+                    start
+                )));
+            }
+            Err(e) => {
+                return Err(VmpError::Deserialization(format!(
+                    "message at byte offset {}: {}",
+                    start, e
+                )));
+            }
+        };
+
+        finish_message(message, &self.options).map(Some)
+    }
+}
+
+impl<R: Read> Iterator for MessageReader<R> {
+    type Item = Result<Message>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let item = self.read_message().transpose();
+        self.failed = matches!(item, Some(Err(_)));
+        item
+    }
+}
+
+/// Reader that counts bytes and fails once more than `limit` are requested
+struct LimitedReader<'a, R> {
+    inner: &'a mut R,
+    read: usize,
+    limit: Option<usize>,
+    exceeded: bool,
+}
+
+impl<R: Read> Read for LimitedReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let buf = match self.limit {
+            Some(limit) if self.read >= limit && !buf.is_empty() => {
+                self.exceeded = true;
+                return Err(std::io::Error::other("max_bytes exceeded"));
+            }
+            Some(limit) => {
+                let end = buf.len().min(limit - self.read);
+                &mut buf[..end]
+            }
+            None => buf,
+        };
+        let n = self.inner.read(buf)?;
+        self.read += n;
+        Ok(n)
+    }
+}
+
+/// Deserialize a batch written by [`crate::serializer::serialize_batch`]
+///
+/// Payloads are left as they are; use [`deserialize_batch_with_options`] to
+/// decode them. If an element cannot be decoded, the error names its index.
+pub fn deserialize_batch(bytes: &[u8]) -> Result<Vec<Message>> {
+    read_batch(bytes, &DeserializeOptions::default())
+}
+
+fn read_batch(bytes: &[u8], options: &DeserializeOptions) -> Result<Vec<Message>> {
+    struct BatchVisitor;
+
+    impl<'de> serde::de::Visitor<'de> for BatchVisitor {
+        type Value = Vec<Message>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("an array of messages")
+        }
+
+        fn visit_seq<A: serde::de::SeqAccess<'de>>(
+            self,
+            mut seq: A,
+        ) -> std::result::Result<Self::Value, A::Error> {
+            let mut messages = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(1024));
+            loop {
+                match seq.next_element::<Message>() {
+                    Ok(Some(message)) => messages.push(message),
+                    Ok(None) => return Ok(messages),
+                    Err(e) => {
+                        return Err(serde::de::Error::custom(format!(
+                            "batch element {}: {}",
+                            messages.len(),
+                            e
+                        )));
+                    }
+                }
+            }
+        }
+    }
+
+    let mut deserializer = wire_deserializer(bytes, options);
+    let messages = serde::Deserializer::deserialize_seq(&mut deserializer, BatchVisitor)
+        .map_err(|e| VmpError::Deserialization(e.to_string()))?;
+    for (index, message) in messages.iter().enumerate() {
+        if let Some(version) = &message.vmp_version {
+            check_protocol_version(version).map_err(|e| {
+                VmpError::InvalidMessage(format!("batch element {}: {}", index, e))
+            })?;
+        }
+    }
+    Ok(messages)
+}
+
+/// Deserialize a batch, decoding ZData in the `data`, `value`, `args` and
+/// `kwargs` payloads of each message with [`decode_value_recursive`]
+pub fn deserialize_batch_with_options(
+    bytes: &[u8],
+    options: &DeserializeOptions,
+) -> Result<Vec<Message>> {
+    check_input_size(bytes.len(), options)?;
+    let mut messages = read_batch(bytes, options)?;
+    for (index, message) in messages.iter_mut().enumerate() {
+        decode_message_payloads(message, options).map_err(|e| {
+            VmpError::Deserialization(format!("batch element {}: {}", index, e))
+        })?;
+    }
+    Ok(messages)
+}
+
+pub(crate) fn decode_message_payloads(
+    message: &mut Message,
+    options: &DeserializeOptions,
+) -> Result<()> {
+    let decode = |value: &mut Value| -> Result<()> {
+        *value = decode_value_recursive_owned(value.take(), options)?;
+        Ok(())
+    };
+    message.data.iter_mut().try_for_each(decode)?;
+    message.value.iter_mut().try_for_each(decode)?;
+    message.args.iter_mut().flatten().try_for_each(decode)?;
+    message.kwargs.iter_mut().flat_map(|k| k.values_mut()).try_for_each(decode)
 }
 
 /// Deserialize a Vuer component from MessagePack
 pub fn deserialize_component(bytes: &[u8]) -> Result<VuerComponent> {
-    deserialize(bytes)
+    deserialize_component_with_options(bytes, &DeserializeOptions::default())
+}
+
+/// Deserialize a Vuer component, rejecting trees nested deeper than
+/// `options.max_depth`
+///
+/// Every prop in the tree, children included, is decoded as by
+/// [`decode_value_recursive`]. `tag` and `children` are fields, not props,
+/// and are never decoded.
+pub fn deserialize_component_with_options(
+    bytes: &[u8],
+    options: &DeserializeOptions,
+) -> Result<VuerComponent> {
+    let mut component: VuerComponent = deserialize_with_options(bytes, options)?;
+    check_component_depth(&component, options)?;
+    decode_component_props(&mut component, options)?;
+    Ok(component)
+}
+
+/// Decode the props of every component in the tree
+fn decode_component_props(
+    component: &mut VuerComponent,
+    options: &DeserializeOptions,
+) -> Result<()> {
+    let mut stack = vec![component];
+    while let Some(node) = stack.pop() {
+        for value in node.props.values_mut() {
+            *value = decode_value_recursive_owned(value.take(), options)?;
+        }
+        stack.extend(node.children.iter_mut().flatten());
+    }
+    Ok(())
+}
+
+/// Reject trees nested deeper than `options.max_depth`
+pub(crate) fn check_component_depth(
+    component: &VuerComponent,
+    options: &DeserializeOptions,
+) -> Result<()> {
+    if let Some(max_depth) = options.max_depth {
+        // Walk the tree iteratively so the check itself cannot overflow
+        let mut stack = vec![(component, 1)];
+        while let Some((node, depth)) = stack.pop() {
+            if depth > max_depth {
+                return Err(max_depth_exceeded(max_depth, depth));
+            }
+            for child in node.children.iter().flatten() {
+                stack.push((child, depth + 1));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn max_depth_exceeded(max_depth: usize, depth: usize) -> VmpError {
+    VmpError::InvalidMessage(format!("max depth of {} exceeded at depth {}", max_depth, depth))
 }
 
 /// Recursively decode a JSON value, converting ZData objects
 pub fn decode_value_recursive(value: &Value, options: &DeserializeOptions) -> Result<Value> {
-    if !options.recursive {
-        return Ok(value.clone());
+    decode_value_recursive_owned(value.clone(), options)
+}
+
+/// Like [`decode_value_recursive`], but consumes `value` and rewrites it in
+/// place, so parts that need no decoding are never copied
+pub fn decode_value_recursive_owned(
+    mut value: Value,
+    options: &DeserializeOptions,
+) -> Result<Value> {
+    if options.recursive {
+        decode_value_at_depth(&mut value, options, 0)?;
+    }
+    Ok(value)
+}
+
+fn decode_value_at_depth(
+    value: &mut Value,
+    options: &DeserializeOptions,
+    depth: usize,
+) -> Result<()> {
+    if matches!(value, Value::Object(_) | Value::Array(_))
+        && let Some(max) = options.max_depth
+        && depth >= max
+    {
+        return Err(max_depth_exceeded(max, depth));
+    }
+
+    // Unknown types wrapped by an earlier pass are already decoded
+    if unwrap_unknown(value).is_some() {
+        return Ok(());
     }
 
     match value {
         Value::Object(map) => {
             // Check if this is a ZData object
             if map.contains_key("ztype") {
-                let zdata: ZData = serde_json::from_value(value.clone())?;
+                // Wrapped unknown types keep the map exactly as received
+                let policy = options.unknown_type_policy();
+                let original = matches!(policy, UnknownTypePolicy::Wrap)
+                    .then(|| map.clone());
 
-                // Try to decode using type registry
-                if options.use_type_registry && GLOBAL_TYPE_REGISTRY.is_registered(&zdata.ztype) {
-                    return GLOBAL_TYPE_REGISTRY.decode(&zdata);
+                // Decode nested custom types in the extra fields first, so the
+                // registry decoder sees already-decoded inner values
+                for (key, val) in map.iter_mut() {
+                    if !ZDATA_RESERVED_KEYS.contains(&key.as_str()) {
+                        decode_value_at_depth(val, options, depth + 1)?;
+                    }
+                }
+                let zdata = ZData::deserialize(&*value)?;
+                validate_zdata(&zdata, options)?;
+                if options.validate_zdata {
+                    zdata.validate_with(options.allow_unknown_dtypes)?;
+                }
+
+                if options.verify_checksum {
+                    zdata.verify_checksum()?;
+                }
+
+                // Registered decoders get the payload already decompressed, so
+                // its inflated size is held to max_binary_bytes as well
+                let registry = options.use_type_registry.then(|| options.registry());
+                let mut zdata = zdata;
+                if zdata.get_field("compression").is_some()
+                    && registry.is_some_and(|registry| registry.is_registered(&zdata.ztype))
+                {
+                    let raw = zdata.decompressed_with_limit(options.max_binary_bytes)?.into_owned();
+                    zdata.b = Some(raw.into());
+                    zdata.extra.shift_remove("compression");
                 }
 
-                // Return as-is if not registered
-                return Ok(value.clone());
+                // Try to decode using type registry
+                let zdata = if let Some(registry) = registry {
+                    match registry.try_decode_unknown(&zdata)? {
+                        UnknownOrKnown::Known(decoded) => {
+                            *value = decoded;
+                            return Ok(());
+                        }
+                        UnknownOrKnown::Unknown(zdata) => *zdata,
+                    }
+                } else {
+                    zdata
+                };
+
+                return match (policy, original) {
+                    (UnknownTypePolicy::Error, _) => Err(VmpError::TypeNotRegistered(zdata.ztype)),
+                    (_, Some(original)) => {
+                        *value = wrap_unknown(original);
+                        Ok(())
+                    }
+                    _ => Ok(()),
+                };
             }
 
             // Recursively process object fields
-            let mut result = serde_json::Map::new();
-            for (key, val) in map {
-                let decoded = decode_value_recursive(val, options)?;
-                result.insert(key.clone(), decoded);
+            for val in map.values_mut() {
+                decode_value_at_depth(val, options, depth + 1)?;
             }
-            Ok(Value::Object(result))
+            Ok(())
         }
         Value::Array(arr) => {
             // Recursively process array elements
-            let decoded: Result<Vec<Value>> = arr
-                .iter()
-                .map(|v| decode_value_recursive(v, options))
-                .collect();
-            Ok(Value::Array(decoded?))
+            for val in arr.iter_mut() {
+                decode_value_at_depth(val, options, depth + 1)?;
+            }
+            Ok(())
         }
-        _ => Ok(value.clone()),
+        _ => Ok(()),
     }
 }
 
+/// Deserialize a value from JSON text written by
+/// [`crate::serializer::serialize_json`]
+///
+/// Base64 `b` fields of ZData are turned back into bytes, then the value goes
+/// through the same recursive ZData decoding as message payloads. ZData of
+/// registered types is replaced by the decoded value, so to read a ZData
+/// itself, unset `use_type_registry` with [`deserialize_json_with_options`].
+pub fn deserialize_json<T: DeserializeOwned>(s: &str) -> Result<T> {
+    deserialize_json_with_options(s, &DeserializeOptions::default())
+}
+
+/// Deserialize from JSON text with custom options
+pub fn deserialize_json_with_options<T: DeserializeOwned>(
+    s: &str,
+    options: &DeserializeOptions,
+) -> Result<T> {
+    check_input_size(s.len(), options)?;
+    let mut value: Value = serde_json::from_str(s)?;
+    decode_binary_fields(&mut value)?;
+    let value = decode_value_recursive_owned(value, options)?;
+    Ok(serde_json::from_value(value)?)
+}
+
+/// Turn the base64 strings of ZData `b` fields back into byte arrays
+pub(crate) fn decode_binary_fields(value: &mut Value) -> Result<()> {
+    match value {
+        Value::Object(map) => {
+            if map.contains_key("ztype")
+                && let Some(b) = map.get_mut("b")
+                && let Value::String(encoded) = b
+            {
+                let bytes = base64::engine::general_purpose::STANDARD
+                    .decode(encoded.as_bytes())
+                    .map_err(|e| {
+                        VmpError::Deserialization(format!("Base64 decode error: {}", e))
+                    })?;
+                *b = Value::from(bytes);
+            }
+            map.values_mut().try_for_each(decode_binary_fields)
+        }
+        Value::Array(items) => items.iter_mut().try_for_each(decode_binary_fields),
+        _ => Ok(()),
+    }
+}
+
+/// Check a ZData against the size limits in `options`
+pub fn validate_zdata(zdata: &ZData, options: &DeserializeOptions) -> Result<()> {
+    if let (Some(max), Some(b)) = (options.max_binary_bytes, &zdata.b)
+        && b.len() > max
+    {
+        return Err(VmpError::InvalidMessage(format!(
+            "{} ZData binary size {} exceeds max_binary_bytes ({})",
+            zdata.ztype,
+            b.len(),
+            max
+        )));
+    }
+
+    if let (Some(max), Some(shape)) = (options.max_shape_elements, &zdata.shape) {
+        let elements = shape.iter().try_fold(1usize, |acc, &dim| acc.checked_mul(dim));
+        if elements.is_none_or(|n| n > max) {
+            return Err(VmpError::InvalidMessage(format!(
+                "{} ZData shape {:?} exceeds max_shape_elements ({})",
+                zdata.ztype, shape, max
+            )));
+        }
+    }
+
+    Ok(())
+}
+
 /// Deserialize from base64-encoded MessagePack
 pub fn deserialize_from_base64<T: DeserializeOwned>(encoded: &str) -> Result<T> {
-    let bytes = base64::engine::general_purpose::STANDARD
+    deserialize_from_base64_with(encoded, Base64Variant::StandardPad)
+}
+
+/// Deserialize from unpadded URL-safe base64-encoded MessagePack
+pub fn deserialize_from_base64_url<T: DeserializeOwned>(encoded: &str) -> Result<T> {
+    deserialize_from_base64_with(encoded, Base64Variant::UrlSafe)
+}
+
+/// Deserialize from MessagePack encoded with the given base64 variant
+pub fn deserialize_from_base64_with<T: DeserializeOwned>(
+    encoded: &str,
+    variant: Base64Variant,
+) -> Result<T> {
+    let bytes = variant
+        .engine()
         .decode(encoded)
         .map_err(|e| VmpError::Deserialization(format!("Base64 decode error: {}", e)))?;
     deserialize(&bytes)
@@ -113,23 +943,28 @@ pub fn bytes_to_zdata(bytes: &[u8]) -> Result<ZData> {
 }
 
 /// Validate message structure
+///
+/// Checks the protocol version, [`RULE_NONEMPTY_ETYPE`] and
+/// [`RULE_RPC_HAS_RTYPE`], stopping at the first failure. Use
+/// [`crate::validator::MessageValidator`] for other rules or to collect every
+/// violation.
 pub fn validate_message(msg: &Message) -> Result<()> {
-    if msg.etype.is_empty() {
-        return Err(VmpError::InvalidMessage(
-            "Message etype cannot be empty".to_string(),
-        ));
+    validate_message_with_options(msg, &DeserializeOptions::default())
+}
+
+/// Validate message structure, also rejecting expired messages when
+/// `options.reject_expired` is set
+pub fn validate_message_with_options(msg: &Message, options: &DeserializeOptions) -> Result<()> {
+    if options.reject_expired && is_expired(msg) {
+        return Err(VmpError::InvalidMessage("Message has expired".to_string()));
     }
 
-    // RPC requests must have rtype
-    if msg.args.is_some() || msg.kwargs.is_some() {
-        if msg.rtype.is_none() {
-            return Err(VmpError::InvalidMessage(
-                "RPC request must have rtype field".to_string(),
-            ));
-        }
+    if let Some(version) = &msg.vmp_version {
+        check_protocol_version(version)?;
     }
 
-    Ok(())
+    RULE_NONEMPTY_ETYPE(msg)?;
+    RULE_RPC_HAS_RTYPE(msg)
 }
 
 #[cfg(test)]
@@ -176,6 +1011,67 @@ mod tests {
         assert!(validate_message(&invalid_msg).is_err());
     }
 
+    #[test]
+    fn test_deserialize_message_validates() {
+        let check = |msg: &Message, options: &DeserializeOptions| {
+            deserialize_message_with_options(&serialize_message(msg).unwrap(), options)
+        };
+        let strict = DeserializeOptions::default();
+        let permissive = DeserializeOptions {
+            validate: false,
+            ..Default::default()
+        };
+
+        let empty = Message::new("");
+        let err = deserialize_message(&serialize_message(&empty).unwrap()).unwrap_err();
+        assert!(matches!(err, VmpError::InvalidMessage(m) if m.contains("etype")));
+        assert_eq!(check(&empty, &permissive).unwrap(), empty);
+
+        let mut no_rtype = Message::new("RENDER");
+        no_rtype.args = Some(vec![json!(1)]);
+        let err = check(&no_rtype, &strict).unwrap_err();
+        assert!(matches!(err, VmpError::InvalidMessage(m) if m.contains("rtype")));
+        assert_eq!(check(&no_rtype, &permissive).unwrap(), no_rtype);
+
+        // The version check is not part of what `validate` turns off
+        let future = Message::new("TEST").with_vmp_version("2.0");
+        assert!(check(&future, &permissive).is_err());
+    }
+
+    #[test]
+    fn test_protocol_version() {
+        let roundtrip = |msg: &Message| deserialize_message(&serialize_message(msg).unwrap());
+
+        let absent = Message::new("TEST");
+        assert!(roundtrip(&absent).unwrap().vmp_version.is_none());
+
+        for version in ["0.9", crate::types::PROTOCOL_VERSION, "1.7"] {
+            let msg = Message::new("TEST").with_vmp_version(version);
+            assert_eq!(roundtrip(&msg).unwrap(), msg);
+            assert!(validate_message(&msg).is_ok());
+        }
+
+        let future = Message::new("TEST").with_vmp_version("2.0");
+        let err = roundtrip(&future).unwrap_err();
+        assert!(matches!(err, VmpError::InvalidMessage(msg) if msg.contains("newer")));
+        assert!(validate_message(&future).is_err());
+        assert!(validate_message(&Message::new("TEST").with_vmp_version("v1")).is_err());
+
+        // Stamping sets the current version
+        let options = crate::serializer::SerializeOptions {
+            stamp_version: true,
+            ..Default::default()
+        };
+        let bytes = crate::serializer::serialize_message_with_options(&absent, &options).unwrap();
+        let stamped = deserialize_message(&bytes).unwrap();
+        assert_eq!(stamped.vmp_version.as_deref(), Some(crate::types::PROTOCOL_VERSION));
+
+        let zdata = json!({"ztype": "custom.Blob", "vmp_version": "2.1"});
+        assert!(decode_value_recursive(&zdata, &DeserializeOptions::default()).is_err());
+        let zdata = json!({"ztype": "custom.Blob", "vmp_version": "1.0"});
+        assert!(decode_value_recursive(&zdata, &DeserializeOptions::default()).is_ok());
+    }
+
     #[test]
     fn test_decode_value_recursive() {
         let value = json!({
@@ -189,4 +1085,965 @@ mod tests {
         let decoded = decode_value_recursive(&value, &options).unwrap();
         assert_eq!(decoded, value);
     }
+
+    fn nested_component(depth: usize) -> VuerComponent {
+        let mut component = VuerComponent::new("leaf");
+        for _ in 1..depth {
+            component = VuerComponent::new("group").with_child(component);
+        }
+        component
+    }
+
+    #[test]
+    fn test_component_max_depth() {
+        let component = nested_component(200);
+        let bytes = crate::serializer::serialize_component(&component).unwrap();
+
+        let err = deserialize_component(&bytes).unwrap_err();
+        let expected = format!("max depth of {0} exceeded at depth {1}", DEFAULT_MAX_DEPTH, 129);
+        assert!(matches!(err, VmpError::InvalidMessage(msg) if msg == expected));
+
+        let options = DeserializeOptions {
+            max_depth: Some(300),
+            ..Default::default()
+        };
+        let deserialized = deserialize_component_with_options(&bytes, &options).unwrap();
+        assert_eq!(component, deserialized);
+    }
+
+    #[test]
+    fn test_deserialize_component_decodes_props() {
+        let registry = TypeRegistry::new();
+        registry.register(
+            "test.PropArray",
+            |value| {
+                let bytes: Vec<u8> = serde_json::from_value(value.clone())?;
+                Ok(ZData::new("test.PropArray").with_binary(bytes))
+            },
+            |zdata| Ok(json!(zdata.b.as_deref().unwrap())),
+            None,
+        );
+        let decode = DeserializeOptions {
+            type_registry: Some(registry),
+            ..Default::default()
+        };
+
+        let vertices = ZData::new("test.PropArray").with_binary(vec![1, 2, 3]);
+        let vertices = serde_json::to_value(&vertices).unwrap();
+        let scene = VuerComponent::new("scene").with_child(
+            VuerComponent::new("group").with_child(
+                VuerComponent::new("mesh")
+                    .with_prop("vertices", vertices.clone())
+                    .with_prop("style", json!({"color": "red"})),
+            ),
+        );
+        let options = crate::serializer::SerializeOptions {
+            use_type_registry: false,
+            ..Default::default()
+        };
+        let bytes = crate::serializer::serialize_component_with_options(&scene, &options).unwrap();
+
+        let restored = deserialize_component_with_options(&bytes, &decode).unwrap();
+        let group = &restored.children.as_ref().unwrap()[0];
+        let mesh = &group.children.as_ref().unwrap()[0];
+        assert_eq!(mesh.tag, "mesh");
+        assert_eq!(mesh.props.len(), 2);
+        assert_eq!(mesh.props["vertices"], json!([1, 2, 3]));
+        assert_eq!(mesh.props["style"], json!({"color": "red"}));
+        assert!(!group.props.contains_key("tag") && !group.props.contains_key("children"));
+
+        let options = DeserializeOptions {
+            recursive: false,
+            ..decode
+        };
+        let raw = deserialize_component_with_options(&bytes, &options).unwrap();
+        assert_eq!(raw, scene);
+    }
+
+    #[test]
+    fn test_unknown_type_policies() {
+        let original = json!({
+            "ztype": "custom.Mesh",
+            "b": [1, 2, 3],
+            "vertices": 3,
+        });
+        let value = json!({"mesh": original.clone()});
+
+        let passthrough = decode_value_recursive(&value, &DeserializeOptions::default()).unwrap();
+        assert_eq!(passthrough, value);
+
+        let options = DeserializeOptions {
+            unknown_types: UnknownTypePolicy::Wrap,
+            ..Default::default()
+        };
+        let wrapped = decode_value_recursive(&value, &options).unwrap();
+        assert_ne!(wrapped["mesh"], original);
+        let unknown = crate::zdata::UnknownType::from_wrapped(&wrapped["mesh"]).unwrap();
+        assert_eq!(unknown.ztype(), "custom.Mesh");
+        assert_eq!(unknown.as_zdata().b.as_deref(), Some(&[1, 2, 3][..]));
+        assert!(crate::zdata::UnknownType::from_wrapped(&value).is_none());
+
+        let reencoded = crate::serializer::encode_value_recursive(
+            &wrapped,
+            &crate::serializer::SerializeOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(reencoded, value);
+
+        let options = DeserializeOptions {
+            unknown_types: UnknownTypePolicy::Error,
+            ..Default::default()
+        };
+        let err = decode_value_recursive(&value, &options).unwrap_err();
+        assert!(matches!(err, VmpError::TypeNotRegistered(ztype) if ztype == "custom.Mesh"));
+    }
+
+    #[test]
+    fn test_deserialize_message_decodes_payloads() {
+        let registry = TypeRegistry::new();
+        registry.register(
+            "datetime",
+            |value| Ok(ZData::new("datetime").with_field("iso", value.clone())),
+            |zdata| {
+                zdata
+                    .get_field("iso")
+                    .cloned()
+                    .ok_or_else(|| VmpError::MissingField("iso".to_string()))
+            },
+            None,
+        );
+        let decode = DeserializeOptions {
+            type_registry: Some(registry),
+            ..Default::default()
+        };
+        let when = serde_json::to_value(
+            ZData::new("datetime").with_field("iso", json!("2025-01-20T12:00:00Z")),
+        )
+        .unwrap();
+        let mut msg = Message::new("SCHEDULE")
+            .with_data(json!({"when": when.clone(), "other": {"ztype": "custom.Thing"}}));
+        msg.args = Some(vec![when.clone()]);
+        msg.kwargs = Some([("at".to_string(), when.clone())].into());
+        msg.rtype = Some("SCHEDULED".to_string());
+        let bytes = serialize_message(&msg).unwrap();
+
+        // The global registry does not know the type
+        let plain = deserialize_message(&bytes).unwrap();
+        assert_eq!(plain.data.unwrap()["when"], when);
+
+        let mut decoded = deserialize_message_with_options(&bytes, &decode).unwrap();
+        let iso = json!("2025-01-20T12:00:00Z");
+        assert_eq!(decoded.data.as_ref().unwrap()["when"], iso);
+        assert_eq!(decoded.data.as_ref().unwrap()["other"], json!({"ztype": "custom.Thing"}));
+        assert_eq!(decoded.args.as_ref().unwrap()[0], iso);
+        assert_eq!(decoded.kwargs.as_ref().unwrap()["at"], iso);
+
+        // Decoding again is harmless
+        let once = decoded.clone();
+        decoded.decode_payloads(&decode).unwrap();
+        assert_eq!(decoded, once);
+
+        let wrap = DeserializeOptions {
+            unknown_types: UnknownTypePolicy::Wrap,
+            ..decode.clone()
+        };
+        let mut wrapped = deserialize_message_with_options(&bytes, &wrap).unwrap();
+        let once = wrapped.clone();
+        wrapped.decode_payloads(&wrap).unwrap();
+        assert_eq!(wrapped, once);
+
+        for options in [
+            DeserializeOptions {
+                use_type_registry: false,
+                ..decode.clone()
+            },
+            DeserializeOptions {
+                recursive: false,
+                ..decode.clone()
+            },
+        ] {
+            let raw = deserialize_message_with_options(&bytes, &options).unwrap();
+            assert_eq!(raw.data.unwrap()["when"], when);
+        }
+
+        let mut in_hand = msg.clone();
+        in_hand.decode_payloads(&decode).unwrap();
+        assert_eq!(in_hand.data.unwrap()["when"], iso);
+    }
+
+    #[test]
+    fn test_preserve_unknown_types() {
+        let zdata = ZData::new("custom.Unregistered")
+            .with_binary(vec![9, 8, 7])
+            .with_field("label", json!("scan"));
+        let message = Message::new("SCAN").with_data(json!({"scan": zdata}));
+        let batch = crate::serializer::serialize_batch(std::slice::from_ref(&message)).unwrap();
+        let json = crate::serializer::serialize_json(&message).unwrap();
+
+        let options = DeserializeOptions::default();
+        let decoded = deserialize_batch_with_options(&batch, &options).unwrap();
+        let preserved = ZData::deserialize(&decoded[0].data.as_ref().unwrap()["scan"]).unwrap();
+        assert_eq!(preserved, zdata);
+        let decoded: Message = deserialize_json_with_options(&json, &options).unwrap();
+        assert_eq!(decoded.data, message.data);
+
+        for options in [
+            DeserializeOptions {
+                unknown_types: UnknownTypePolicy::Error,
+                ..Default::default()
+            },
+            DeserializeOptions {
+                unknown_types: UnknownTypePolicy::Wrap,
+                preserve_unknown_types: false,
+                ..Default::default()
+            },
+        ] {
+            assert!(matches!(
+                deserialize_batch_with_options(&batch, &options),
+                Err(VmpError::Deserialization(m)) if m.contains("custom.Unregistered")
+            ));
+            assert!(matches!(
+                deserialize_json_with_options::<Message>(&json, &options),
+                Err(VmpError::TypeNotRegistered(ztype)) if ztype == "custom.Unregistered"
+            ));
+        }
+    }
+
+    #[test]
+    fn test_decode_validates_zdata() {
+        let malformed = json!({"ztype": "custom.Grid", "dtype": "int32", "shape": [4]});
+        let err = decode_value_recursive(&malformed, &DeserializeOptions::default()).unwrap_err();
+        assert!(matches!(err, VmpError::InvalidMessage(msg) if msg.contains("no binary data")));
+
+        let options = DeserializeOptions {
+            validate_zdata: false,
+            ..Default::default()
+        };
+        assert_eq!(decode_value_recursive(&malformed, &options).unwrap(), malformed);
+
+        let strided = json!({"ztype": "custom.Grid", "b": [0], "shape": [1], "strides": [1, 1]});
+        let err = decode_value_recursive(&strided, &DeserializeOptions::default()).unwrap_err();
+        assert!(matches!(err, VmpError::InvalidMessage(msg) if msg.contains("strides")));
+
+        let custom = json!({"ztype": "custom.Grid", "dtype": "<U5", "b": [1], "shape": [1]});
+        assert!(decode_value_recursive(&custom, &DeserializeOptions::default()).is_ok());
+        let options = DeserializeOptions {
+            allow_unknown_dtypes: false,
+            ..Default::default()
+        };
+        assert!(decode_value_recursive(&custom, &options).is_err());
+    }
+
+    #[test]
+    fn test_zdata_size_limits() {
+        let zdata = ZData::new("custom.blob")
+            .with_binary(vec![0u8; 64])
+            .with_shape(vec![8, 8]);
+        let value = serde_json::to_value(&zdata).unwrap();
+
+        let options = DeserializeOptions {
+            max_binary_bytes: Some(64),
+            max_shape_elements: Some(64),
+            ..Default::default()
+        };
+        assert_eq!(decode_value_recursive(&value, &options).unwrap(), value);
+
+        let options = DeserializeOptions {
+            max_binary_bytes: Some(63),
+            ..Default::default()
+        };
+        let err = decode_value_recursive(&value, &options).unwrap_err();
+        assert!(matches!(err, VmpError::InvalidMessage(msg) if msg.contains("max_binary_bytes")));
+
+        let options = DeserializeOptions {
+            max_shape_elements: Some(63),
+            ..Default::default()
+        };
+        let err = decode_value_recursive(&value, &options).unwrap_err();
+        assert!(matches!(err, VmpError::InvalidMessage(msg) if msg.contains("max_shape_elements")));
+
+        // Shapes whose element count overflows are rejected outright
+        let huge = ZData::new("custom.blob").with_shape(vec![usize::MAX, 2]);
+        assert!(validate_zdata(&huge, &DeserializeOptions::default()).is_err());
+
+        let unlimited = DeserializeOptions {
+            max_binary_bytes: None,
+            max_shape_elements: None,
+            ..Default::default()
+        };
+        assert!(validate_zdata(&huge, &unlimited).is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "compression")]
+    fn test_decompressed_size_limit() {
+        let registry = crate::type_registry::TypeRegistry::new();
+        registry.register(
+            "test.Blob",
+            |value| Ok(ZData::new("test.Blob").with_field("len", value.clone())),
+            |zdata| Ok(json!(zdata.decompressed()?.len())),
+            None,
+        );
+        let bomb = ZData::new("test.Blob")
+            .with_binary(vec![0u8; 1 << 20])
+            .with_compression("zstd")
+            .unwrap();
+        let value = serde_json::to_value(&bomb).unwrap();
+
+        let options = DeserializeOptions {
+            type_registry: Some(registry),
+            max_binary_bytes: Some(4096),
+            ..Default::default()
+        };
+        let err = decode_value_recursive(&value, &options).unwrap_err();
+        assert!(matches!(err, VmpError::InvalidMessage(msg) if msg.contains("4096")));
+
+        let options = DeserializeOptions {
+            max_binary_bytes: Some(1 << 20),
+            ..options
+        };
+        assert_eq!(decode_value_recursive(&value, &options).unwrap(), json!(1 << 20));
+    }
+
+    #[test]
+    fn test_decode_value_max_depth() {
+        let mut value = json!(1);
+        for _ in 0..200 {
+            value = json!([value]);
+        }
+
+        let err = decode_value_recursive(&value, &DeserializeOptions::default()).unwrap_err();
+        let expected = format!("max depth of {0} exceeded at depth {0}", DEFAULT_MAX_DEPTH);
+        assert!(matches!(err, VmpError::InvalidMessage(msg) if msg == expected));
+
+        let options = DeserializeOptions {
+            max_depth: None,
+            ..Default::default()
+        };
+        assert_eq!(decode_value_recursive(&value, &options).unwrap(), value);
+    }
+
+    #[test]
+    fn test_validate_message_reject_expired() {
+        let mut msg = Message::new("CAMERA");
+        msg.expires_at = Some(msg.ts - 1);
+
+        let options = DeserializeOptions {
+            reject_expired: true,
+            ..Default::default()
+        };
+        assert!(validate_message(&msg).is_ok());
+        assert!(matches!(
+            validate_message_with_options(&msg, &options),
+            Err(VmpError::InvalidMessage(_))
+        ));
+
+        let fresh = Message::new("CAMERA").with_ttl(std::time::Duration::from_secs(60));
+        assert!(validate_message_with_options(&fresh, &options).is_ok());
+
+        let no_expiry = Message::new("CAMERA");
+        assert!(validate_message_with_options(&no_expiry, &options).is_ok());
+    }
+
+    #[test]
+    fn test_decode_verify_checksum() {
+        let zdata = ZData::new("test.Checked").with_binary(vec![9; 64]).with_crc32_checksum();
+        let options = DeserializeOptions {
+            verify_checksum: true,
+            ..Default::default()
+        };
+
+        let value = json!({"payload": serde_json::to_value(&zdata).unwrap()});
+        assert_eq!(decode_value_recursive(&value, &options).unwrap(), value);
+
+        let mut corrupted = zdata.clone();
+        let mut bytes = corrupted.b.unwrap().to_vec();
+        bytes[10] ^= 1;
+        corrupted.b = Some(bytes.into());
+        let value = json!({"payload": serde_json::to_value(&corrupted).unwrap()});
+        assert!(matches!(
+            decode_value_recursive(&value, &options),
+            Err(VmpError::InvalidMessage(_))
+        ));
+        // Checksums are only enforced when requested
+        assert!(decode_value_recursive(&value, &DeserializeOptions::default()).is_ok());
+
+        let unchecked = ZData::new("test.Checked").with_binary(vec![9; 64]);
+        let value = json!({"payload": serde_json::to_value(&unchecked).unwrap()});
+        assert_eq!(decode_value_recursive(&value, &options).unwrap(), value);
+    }
+
+    #[test]
+    fn test_decode_nested_zdata() {
+        let registry = TypeRegistry::new();
+        registry.register(
+            "test.NestedVertices",
+            |value| {
+                Ok(ZData::new("test.NestedVertices").with_field("count", value["points"].clone()))
+            },
+            |zdata| Ok(json!({"points": zdata.get_field("count").unwrap().clone()})),
+            None,
+        );
+        registry.register(
+            "test.NestedMesh",
+            |value| Ok(ZData::new("test.NestedMesh").with_field("vertices", value["mesh"].clone())),
+            // The inner ZData has already been decoded by the time this runs
+            |zdata| Ok(json!({"mesh": zdata.get_field("vertices").unwrap().clone()})),
+            None,
+        );
+        let options = DeserializeOptions {
+            type_registry: Some(registry),
+            ..Default::default()
+        };
+
+        let vertices = ZData::new("test.NestedVertices").with_field("count", json!(3));
+        let mesh = ZData::new("test.NestedMesh")
+            .with_field("vertices", serde_json::to_value(&vertices).unwrap());
+        let value = json!({"scene": serde_json::to_value(&mesh).unwrap()});
+
+        let decoded = decode_value_recursive(&value, &options).unwrap();
+        assert_eq!(decoded, json!({"scene": {"mesh": {"points": 3}}}));
+
+        // Unregistered outer types are kept, but their extras are still decoded
+        let wrapper = ZData::new("test.UnknownWrapper")
+            .with_field("inner", serde_json::to_value(&vertices).unwrap());
+        let decoded =
+            decode_value_recursive(&serde_json::to_value(&wrapper).unwrap(), &options).unwrap();
+        assert_eq!(decoded, json!({"ztype": "test.UnknownWrapper", "inner": {"points": 3}}));
+
+        let options = DeserializeOptions {
+            recursive: false,
+            ..options
+        };
+        assert_eq!(decode_value_recursive(&value, &options).unwrap(), value);
+    }
+
+    #[test]
+    fn test_decode_value_recursive_owned() {
+        let frames: Vec<Value> = (0..200)
+            .map(|i| {
+                json!({
+                    "index": i,
+                    "pose": (0..16).map(|j| json!(j as f64 * 0.1)).collect::<Vec<_>>(),
+                    "image": {"ztype": "custom.Frame", "b": [i % 256, 0, 0], "tags": ["raw"]},
+                })
+            })
+            .collect();
+        let value = json!({"frames": frames});
+
+        for unknown_types in [UnknownTypePolicy::Passthrough, UnknownTypePolicy::Wrap] {
+            let options = DeserializeOptions {
+                unknown_types,
+                ..Default::default()
+            };
+            let borrowed = decode_value_recursive(&value, &options).unwrap();
+            let owned = decode_value_recursive_owned(value.clone(), &options).unwrap();
+            assert_eq!(owned, borrowed);
+        }
+
+        let options = DeserializeOptions {
+            unknown_types: UnknownTypePolicy::Error,
+            ..Default::default()
+        };
+        assert!(matches!(
+            decode_value_recursive_owned(value, &options),
+            Err(VmpError::TypeNotRegistered(_))
+        ));
+    }
+
+    #[test]
+    fn test_batch_roundtrip() {
+        use crate::serializer::serialize_batch;
+
+        let empty = serialize_batch(&[]).unwrap();
+        assert_eq!(empty, [0x90]);
+        assert!(deserialize_batch(&empty).unwrap().is_empty());
+
+        let single = vec![Message::new("TICK").with_data(json!({"frame": 1}))];
+        assert_eq!(deserialize_batch(&serialize_batch(&single).unwrap()).unwrap(), single);
+
+        let burst: Vec<Message> = (0..500)
+            .map(|i| Message::new("SET").with_data(json!({"id": i, "position": [i, 0, 1]})))
+            .collect();
+        let bytes = serialize_batch(&burst).unwrap();
+        assert_eq!(deserialize_batch(&bytes).unwrap(), burst);
+
+        // Elements are encoded exactly like single messages
+        assert_eq!(bytes[..3], [0xdc, 0x01, 0xf4]);
+        let first = serialize_message(&burst[0]).unwrap();
+        assert_eq!(&bytes[3..3 + first.len()], &first[..]);
+    }
+
+    #[test]
+    fn test_batch_corrupted_element() {
+        let good = serde_json::to_value(Message::new("OK")).unwrap();
+        let bytes = crate::serializer::serialize(&json!([good, good, 42, good])).unwrap();
+
+        let err = deserialize_batch(&bytes).unwrap_err();
+        assert!(
+            matches!(&err, VmpError::Deserialization(msg) if msg.contains("batch element 2")),
+            "{}",
+            err
+        );
+        assert!(deserialize_batch(&[0x93, 0xc0]).is_err());
+        let map = crate::serializer::serialize(&json!({"a": 1})).unwrap();
+        assert!(deserialize_batch(&map).is_err());
+    }
+
+    #[test]
+    fn test_batch_decodes_payloads() {
+        let registry = TypeRegistry::new();
+        registry.register(
+            "test.BatchPoint",
+            |value| Ok(ZData::new("test.BatchPoint").with_field("xyz", value["point"].clone())),
+            |zdata| Ok(json!({"point": zdata.get_field("xyz").unwrap().clone()})),
+            None,
+        );
+        let options = DeserializeOptions {
+            type_registry: Some(registry),
+            ..Default::default()
+        };
+
+        let zdata = ZData::new("test.BatchPoint").with_field("xyz", json!([1, 2, 3]));
+        let messages = vec![
+            Message::new("PLAIN").with_data(json!("text")),
+            Message::new("POINT").with_data(json!({"p": serde_json::to_value(&zdata).unwrap()})),
+        ];
+        let bytes = crate::serializer::serialize_batch(&messages).unwrap();
+
+        assert_eq!(deserialize_batch(&bytes).unwrap(), messages);
+        let decoded = deserialize_batch_with_options(&bytes, &options).unwrap();
+        assert_eq!(decoded[0], messages[0]);
+        assert_eq!(decoded[1].data, Some(json!({"p": {"point": [1, 2, 3]}})));
+    }
+
+    #[test]
+    fn test_json_roundtrip_matches_msgpack() {
+        let zdata = ZData::new("test.json_blob")
+            .with_binary(vec![0, 1, 254, 255])
+            .with_dtype("uint8")
+            .with_shape(vec![4]);
+        let msg = Message::new("UPDATE")
+            .with_data(json!({"blob": serde_json::to_value(&zdata).unwrap()}));
+
+        let s = crate::serializer::serialize_json(&msg).unwrap();
+        let parsed: Value = serde_json::from_str(&s).unwrap();
+        assert_eq!(parsed["data"]["blob"]["b"], json!("AAH+/w=="));
+
+        let from_json: Message = deserialize_json(&s).unwrap();
+        assert_eq!(from_json, msg);
+        let from_msgpack = deserialize_message(&serialize_message(&from_json).unwrap()).unwrap();
+        assert_eq!(from_msgpack, msg);
+        let blob = ZData::deserialize(&from_msgpack.data.unwrap()["blob"]).unwrap();
+        assert_eq!(blob, zdata);
+    }
+
+    #[test]
+    fn test_json_typed_zdata() {
+        let zdata = ZData::new("test.json_typed").with_binary(vec![7; 16]);
+        let s = crate::serializer::serialize_json(&zdata).unwrap();
+
+        let options = DeserializeOptions {
+            use_type_registry: false,
+            ..Default::default()
+        };
+        let restored: ZData = deserialize_json_with_options(&s, &options).unwrap();
+        assert_eq!(restored, zdata);
+
+        let bad = s.replace("BwcH", "!!!!");
+        assert!(matches!(
+            deserialize_json::<ZData>(&bad),
+            Err(VmpError::Deserialization(_))
+        ));
+    }
+
+    #[test]
+    fn test_read_framed_stream() {
+        let messages: Vec<Message> = (0..3)
+            .map(|i| Message::new("TICK").with_data(json!({"i": i})))
+            .collect();
+        let mut wire = Vec::new();
+        for msg in &messages {
+            wire.extend(crate::serializer::frame_message(msg).unwrap());
+        }
+        let len = u32::from_be_bytes(wire[..4].try_into().unwrap()) as usize;
+        assert_eq!(len, serialize_message(&messages[0]).unwrap().len());
+
+        let big = Message::new("BIG").with_data(json!("x".repeat(256)));
+        wire.extend(crate::serializer::frame_message(&big).unwrap());
+
+        let options = DeserializeOptions {
+            max_bytes: Some(128),
+            ..Default::default()
+        };
+        let mut cursor = std::io::Cursor::new(wire.clone());
+        for msg in &messages {
+            let read = read_framed_with_options(&mut cursor, &options).unwrap();
+            assert_eq!(read.as_ref(), Some(msg));
+        }
+        assert!(matches!(
+            read_framed_with_options(&mut cursor, &options),
+            Err(VmpError::InvalidMessage(_))
+        ));
+
+        // Without the limit the large frame is read and the stream ends cleanly
+        let mut cursor = std::io::Cursor::new(wire);
+        for _ in &messages {
+            read_framed(&mut cursor).unwrap().unwrap();
+        }
+        assert_eq!(read_framed(&mut cursor).unwrap(), Some(big));
+        assert_eq!(read_framed(&mut cursor).unwrap(), None);
+    }
+
+    #[test]
+    fn test_read_framed_truncated() {
+        let wire = crate::serializer::frame_message(&Message::new("CUT")).unwrap();
+        for cut in [2, wire.len() - 1] {
+            let mut cursor = std::io::Cursor::new(&wire[..cut]);
+            match read_framed(&mut cursor) {
+                Err(VmpError::Io(e)) => assert_eq!(e.kind(), std::io::ErrorKind::UnexpectedEof),
+                other => panic!("expected a truncation error, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_message_reader_stream() {
+        let messages: Vec<Message> = ["A", "B", "C"]
+            .iter()
+            .map(|etype| Message::new(*etype).with_data(json!({"n": etype})))
+            .collect();
+        let mut wire = Vec::new();
+        for msg in &messages {
+            wire.extend(serialize_message(msg).unwrap());
+        }
+        let total = wire.len() as u64;
+
+        let mut reader = deserialize_stream(wire.as_slice());
+        let read: Vec<Message> = reader.by_ref().collect::<Result<_>>().unwrap();
+        assert_eq!(read, messages);
+        assert_eq!(reader.offset(), total);
+
+        assert_eq!(deserialize_stream(std::io::empty()).count(), 0);
+    }
+
+    #[test]
+    fn test_message_reader_truncated_tail() {
+        let first = serialize_message(&Message::new("FIRST")).unwrap();
+        let second = serialize_message(&Message::new("SECOND").with_data(json!([1, 2]))).unwrap();
+        let mut wire = first.clone();
+        wire.extend(&second[..second.len() - 2]);
+
+        let mut reader = MessageReader::new(wire.as_slice());
+        assert_eq!(reader.next().unwrap().unwrap().etype, "FIRST");
+        let err = reader.next().unwrap().unwrap_err();
+        let offset = format!("byte offset {}", first.len());
+        assert!(matches!(&err, VmpError::Deserialization(m) if m.contains(&offset)), "{}", err);
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_message_reader_size_cap() {
+        let small = serialize_message(&Message::new("SMALL")).unwrap();
+        let large = serialize_message(&Message::new("LARGE").with_data(json!("x".repeat(200))))
+            .unwrap();
+        let mut wire = small.clone();
+        wire.extend(&large);
+
+        let mut reader = MessageReader::new(wire.as_slice()).with_max_message_size(small.len());
+        assert_eq!(reader.next().unwrap().unwrap().etype, "SMALL");
+        assert!(matches!(reader.next(), Some(Err(VmpError::InvalidMessage(_)))));
+        // Reading stopped at the cap rather than consuming the whole message
+        assert_eq!(reader.offset(), 2 * small.len() as u64);
+
+        // The cap is the options' max_bytes
+        let options = DeserializeOptions {
+            max_bytes: Some(small.len()),
+            ..Default::default()
+        };
+        let mut reader = MessageReader::new(wire.as_slice()).with_options(options);
+        assert_eq!(reader.next().unwrap().unwrap().etype, "SMALL");
+        let err = reader.next().unwrap().unwrap_err();
+        assert!(matches!(err, VmpError::InvalidMessage(m) if m.contains("max_bytes")));
+    }
+
+    #[test]
+    fn test_peek_fields_matches_full_decode() {
+        let zdata = ZData::new("numpy.ndarray").with_binary(vec![1u8; 1 << 18]);
+        let msg = Message::new("FRAME")
+            .with_rtype("rpc-9")
+            .with_data(json!({"frame": serde_json::to_value(&zdata).unwrap(), "n": [1, 2]}));
+        let bytes = serialize_message(&msg).unwrap();
+
+        let full = deserialize_message(&bytes).unwrap();
+        let peeked = peek_fields(&bytes).unwrap();
+        assert_eq!(peeked.etype, full.etype);
+        assert_eq!(peeked.rtype, full.rtype);
+        assert_eq!(peeked.ts, full.ts);
+        assert_eq!(peek_etype(&bytes).unwrap(), "FRAME");
+
+        let bare = serialize_message(&Message::new("PING")).unwrap();
+        assert_eq!(peek_fields(&bare).unwrap().rtype, None);
+    }
+
+    #[test]
+    fn test_peek_fields_positional() {
+        // Every field is set, since positional encoding refuses skipped ones
+        let mut msg = Message::new("MOVE")
+            .with_rtype("rpc-1")
+            .with_data(json!("x".repeat(1000)))
+            .with_value(json!(1))
+            .with_correlation("req-1")
+            .with_metadata("trace_id", json!("t"))
+            .with_vmp_version("1.0");
+        msg.expires_at = Some(msg.ts + 1000);
+        msg.args = Some(vec![]);
+        msg.kwargs = Some(std::collections::HashMap::new());
+        msg.ok = Some(true);
+        msg.error = Some("e".to_string());
+        msg.error_kind = Some(crate::error::ErrorKind::Internal);
+        msg.is_final = Some(true);
+        msg.attempt = Some(1);
+        let options = crate::serializer::SerializeOptions {
+            struct_map: false,
+            ..Default::default()
+        };
+        let bytes = crate::serializer::serialize_with_options(&msg, &options).unwrap();
+        assert_eq!(deserialize::<Message>(&bytes).unwrap(), msg);
+
+        let err = peek_fields(&bytes).unwrap_err();
+        assert!(matches!(err, VmpError::Deserialization(m) if m.contains("positional")));
+        let minimal = crate::serializer::serialize(&json!(["PING"])).unwrap();
+        assert!(matches!(peek_etype(&minimal), Err(VmpError::Deserialization(_))));
+    }
+
+    #[test]
+    fn test_peek_fields_minimal() {
+        // Only etype is present, as other implementations may send it
+        let bytes = crate::serializer::serialize(&json!({"etype": "PING"})).unwrap();
+        let full = deserialize_message(&bytes).unwrap();
+        let peeked = peek_fields(&bytes).unwrap();
+        assert_eq!(peeked, PeekedFields {
+            etype: "PING".to_string(),
+            rtype: None,
+            ts: full.ts,
+        });
+    }
+
+    #[test]
+    fn test_deserialize_envelope() {
+        use crate::serializer::serialize;
+        use crate::types::{ClientEvent, RpcRequest, RpcResponse, ServerEvent};
+
+        let client = ClientEvent::new("CLICK", json!({"x": 1})).with_rtype("CLICK_ACK");
+        let bytes = serialize(&client).unwrap();
+        assert_eq!(deserialize_envelope(&bytes).unwrap(), VmpEnvelope::ClientEvent(client));
+
+        let server = ServerEvent::new("SET", json!({"tag": "Scene"}));
+        let bytes = serialize(&server).unwrap();
+        assert_eq!(deserialize_envelope(&bytes).unwrap(), VmpEnvelope::ServerEvent(server));
+
+        let request = RpcRequest::new("render", "render_done").with_args(vec![json!(2)]);
+        let bytes = serialize(&request).unwrap();
+        assert_eq!(
+            deserialize_envelope(&bytes).unwrap(),
+            VmpEnvelope::RpcRequest(request.clone())
+        );
+
+        // Responses carry a payload too, but `ok` and `error` decide
+        for response in [
+            RpcResponse::success("render_done", json!("ok")),
+            RpcResponse::error("render_done", "no camera"),
+        ] {
+            let bytes = serialize(&response).unwrap();
+            assert_eq!(deserialize_envelope(&bytes).unwrap(), VmpEnvelope::RpcResponse(response));
+        }
+
+        // An rtype without arguments is not a request
+        let message = Message::new("PING").with_rtype("PONG");
+        let bytes = serialize_message(&message).unwrap();
+        assert_eq!(deserialize_envelope(&bytes).unwrap(), VmpEnvelope::Message(message));
+
+        let both = Message::new("ECHO").with_data(json!(1)).with_value(json!(2));
+        let bytes = serialize_message(&both).unwrap();
+        assert_eq!(deserialize_envelope(&bytes).unwrap(), VmpEnvelope::Message(both));
+
+        let mut conflicting = serde_json::to_value(&request).unwrap();
+        conflicting["ok"] = json!(true);
+        let bytes = rmp_serde::to_vec_named(&conflicting).unwrap();
+        let mut expected = Message::from(request);
+        expected.ok = Some(true);
+        assert_eq!(deserialize_envelope(&bytes).unwrap(), VmpEnvelope::Message(expected));
+
+        let nulls = json!({"etype": "TICK", "ts": 5, "data": null, "value": 3});
+        let bytes = rmp_serde::to_vec_named(&nulls).unwrap();
+        assert!(matches!(
+            deserialize_envelope(&bytes).unwrap(),
+            VmpEnvelope::ClientEvent(e) if e.value == json!(3)
+        ));
+
+        let future = Message::new("NEW").with_data(json!(1)).with_vmp_version("99.0");
+        assert!(deserialize_envelope(&serialize_message(&future).unwrap()).is_err());
+        assert!(deserialize_envelope(&serialize(&json!([1, 2])).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_peek_non_message() {
+        for bytes in [
+            crate::serializer::serialize(&42).unwrap(),
+            crate::serializer::serialize(&"CLICK").unwrap(),
+            vec![0x81],
+        ] {
+            assert!(matches!(peek_etype(&bytes), Err(VmpError::Deserialization(_))));
+        }
+    }
+
+    #[test]
+    fn test_hostile_nesting_fails_fast() {
+        const DEPTH: usize = 10_000;
+
+        // A message whose data is 10,000 nested one-element arrays, built
+        // by hand since no encoder will produce it
+        let mut wire = vec![0x82, 0xa5];
+        wire.extend(b"etype");
+        wire.extend([0xa4]);
+        wire.extend(b"DEEP");
+        wire.extend([0xa4]);
+        wire.extend(b"data");
+        wire.extend(std::iter::repeat_n(0x91, DEPTH));
+        wire.push(0x01);
+
+        let start = std::time::Instant::now();
+        assert!(deserialize_message(&wire).is_err());
+        assert!(deserialize_batch_with_options(&wire, &DeserializeOptions::default()).is_err());
+        assert!(start.elapsed() < std::time::Duration::from_secs(1));
+
+        // Values nested past the limit built in memory are caught while
+        // decoding. Kept shallower than the wire fixture, since dropping a
+        // `Value` recurses
+        let mut value = json!(1);
+        for _ in 0..1_000 {
+            value = json!([value]);
+        }
+        let err = decode_value_recursive_owned(value, &DeserializeOptions::default());
+        assert!(matches!(err, Err(VmpError::InvalidMessage(m)) if m.contains("at depth 128")));
+    }
+
+    #[test]
+    fn test_strict_rejects_unknown_fields() {
+        use crate::serializer::serialize;
+        use crate::types::{ClientEvent, RpcRequest, RpcResponse, ServerEvent};
+
+        fn with_foo<T: serde::Serialize>(value: &T) -> Vec<u8> {
+            let mut map = serde_json::to_value(value).unwrap();
+            map["foo"] = json!(1);
+            serialize(&map).unwrap()
+        }
+        fn check<T: DeserializeOwned + std::fmt::Debug>(bytes: &[u8], name: &str) {
+            let strict = DeserializeOptions {
+                strict: true,
+                ..Default::default()
+            };
+            let expected = format!("unknown field `foo` in {}", name);
+            assert!(matches!(
+                deserialize_with_options::<T>(bytes, &strict),
+                Err(VmpError::InvalidMessage(m)) if m == expected
+            ));
+            deserialize_with_options::<T>(bytes, &DeserializeOptions::default()).unwrap();
+        }
+
+        let message = Message::new("CLICK").with_value(json!({"x": 1}));
+        let bytes = with_foo(&message);
+        let strict = DeserializeOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let err = deserialize_message_with_options(&bytes, &strict).unwrap_err();
+        assert!(matches!(err, VmpError::InvalidMessage(m) if m.contains("`foo`")));
+        assert_eq!(deserialize_message(&bytes).unwrap(), message);
+        let clean = serialize_message(&message).unwrap();
+        assert_eq!(deserialize_message_with_options(&clean, &strict).unwrap(), message);
+
+        check::<Message>(&bytes, "Message");
+        check::<ClientEvent>(&with_foo(&ClientEvent::new("CLICK", json!(1))), "ClientEvent");
+        check::<ServerEvent>(&with_foo(&ServerEvent::new("SET", json!(1))), "ServerEvent");
+        check::<RpcRequest>(&with_foo(&RpcRequest::new("render", "done")), "RpcRequest");
+        check::<RpcResponse>(&with_foo(&RpcResponse::success("done", json!(1))), "RpcResponse");
+
+        // Components flatten their props, so any key is accepted
+        let component = VuerComponent::new("Box").with_prop("size", json!(2));
+        let bytes = crate::serializer::serialize_component(&component).unwrap();
+        assert_eq!(deserialize_with_options::<VuerComponent>(&bytes, &strict).unwrap(), component);
+    }
+
+    #[test]
+    fn test_detailed_errors() {
+        // Fields are only there to give the decoder a shape to follow
+        #[allow(dead_code)]
+        #[derive(Debug, Deserialize)]
+        struct Camera {
+            position: Vec<f64>,
+        }
+        #[allow(dead_code)]
+        #[derive(Debug, Deserialize)]
+        struct Kwargs {
+            camera: Camera,
+        }
+        #[allow(dead_code)]
+        #[derive(Debug, Deserialize)]
+        struct Call {
+            kwargs: Kwargs,
+        }
+
+        let bytes = crate::serializer::serialize(
+            &json!({"kwargs": {"camera": {"position": [1.0, 2.0, "three"]}}}),
+        )
+        .unwrap();
+        let err = deserialize::<Call>(&bytes).unwrap_err();
+        let VmpError::DeserializationDetailed { path, offset, message } = &err else {
+            panic!("{:?}", err);
+        };
+        assert_eq!(path, "kwargs.camera.position[2]");
+        // Decoding stopped inside the string, past the two floats before it
+        let string_at = bytes.windows(6).position(|w| w == b"\xa5three").unwrap() as u64;
+        assert!(*offset > string_at && *offset <= bytes.len() as u64, "{}", offset);
+        assert!(message.contains("FixStr"), "{}", message);
+        assert!(err.to_string().contains("kwargs.camera.position[2]"));
+
+        let bytes = crate::serializer::serialize(&json!({"etype": 5, "ts": 1})).unwrap();
+        assert!(matches!(
+            deserialize_message(&bytes),
+            Err(VmpError::DeserializationDetailed { path, .. }) if path == "etype"
+        ));
+
+        // Truncated input has no field to blame beyond the top level
+        let bytes = serialize_message(&Message::new("CUT")).unwrap();
+        assert!(matches!(
+            deserialize_message(&bytes[..bytes.len() - 1]),
+            Err(VmpError::DeserializationDetailed { .. })
+        ));
+    }
+
+    #[test]
+    fn test_max_bytes() {
+        let bytes = serialize_message(&Message::new("BIG").with_data(json!("x".repeat(100))))
+            .unwrap();
+        let options = DeserializeOptions {
+            max_bytes: Some(64),
+            ..Default::default()
+        };
+        for result in [
+            deserialize_message_with_options(&bytes, &options).map(|_| ()),
+            deserialize_with_options::<Message>(&bytes, &options).map(|_| ()),
+            deserialize_batch_with_options(&bytes, &options).map(|_| ()),
+        ] {
+            assert!(matches!(result, Err(VmpError::InvalidMessage(m)) if m.contains("max_bytes")));
+        }
+
+        let options = DeserializeOptions {
+            max_bytes: Some(bytes.len()),
+            ..Default::default()
+        };
+        assert!(deserialize_message_with_options(&bytes, &options).is_ok());
+    }
 }
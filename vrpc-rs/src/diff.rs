@@ -0,0 +1,636 @@
+//! Incremental updates for Vuer component trees
+//!
+//! Author: Ge Yang
+
+use crate::error::{Result, VmpError};
+use crate::types::VuerComponent;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+/// Wire name of [`VuerComponent::key`], used to match children across two
+/// versions of a tree
+pub const CHILD_KEY_PROP: &str = "key";
+
+/// Change to a single prop
+///
+/// A prop set to `null` stays distinct from a removed prop on the wire.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PropChange {
+    /// The prop is added or takes this value
+    Set(Value),
+
+    /// The prop is gone
+    Removed,
+}
+
+/// Difference between two versions of a component
+///
+/// Children are identified by their key, or by their position (as a
+/// decimal string) if they have none.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ComponentDiff {
+    /// The components are identical
+    NoChange,
+
+    /// Only props changed
+    PropsChanged(HashMap<String, PropChange>),
+
+    /// Children were added, removed or updated, possibly alongside props
+    ChildrenChanged {
+        /// Prop changes on this component, as in `PropsChanged`
+        props: HashMap<String, PropChange>,
+        /// New children, appended after the existing ones
+        added: Vec<VuerComponent>,
+        /// Identifiers of removed children
+        removed: Vec<String>,
+        /// Identifiers of changed children with their diffs
+        updated: Vec<(String, ComponentDiff)>,
+    },
+
+    /// The component must be replaced entirely
+    Replaced(VuerComponent),
+}
+
+impl ComponentDiff {
+    /// Whether applying this diff would change anything
+    pub fn is_empty(&self) -> bool {
+        matches!(self, ComponentDiff::NoChange)
+    }
+}
+
+/// Identifiers of a child list, or `None` if they are not unique
+fn child_ids(children: &[VuerComponent]) -> Option<Vec<String>> {
+    let ids: Vec<String> = children
+        .iter()
+        .enumerate()
+        .map(|(i, child)| child.key_text().map_or_else(|| i.to_string(), Into::into))
+        .collect();
+
+    let unique: HashSet<&String> = ids.iter().collect();
+    (unique.len() == ids.len()).then_some(ids)
+}
+
+fn diff_props(
+    old: &HashMap<String, Value>,
+    new: &HashMap<String, Value>,
+) -> HashMap<String, PropChange> {
+    let mut changes: HashMap<String, PropChange> = old
+        .keys()
+        .filter(|key| !new.contains_key(*key))
+        .map(|key| (key.clone(), PropChange::Removed))
+        .collect();
+
+    for (key, value) in new {
+        if old.get(key) != Some(value) {
+            changes.insert(key.clone(), PropChange::Set(value.clone()));
+        }
+    }
+
+    changes
+}
+
+fn apply_props(target: &mut HashMap<String, Value>, changes: &HashMap<String, PropChange>) {
+    for (key, change) in changes {
+        match change {
+            PropChange::Set(value) => target.insert(key.clone(), value.clone()),
+            PropChange::Removed => target.remove(key),
+        };
+    }
+}
+
+impl VuerComponent {
+    /// Compute the diff that turns `self` into `other`
+    ///
+    /// Added children are always appended, so a change in the order of
+    /// existing children, duplicate child keys, or dropping the children
+    /// list entirely is expressed by replacing the component, as is a
+    /// changed tag or key.
+    pub fn diff(&self, other: &VuerComponent) -> ComponentDiff {
+        if self.tag != other.tag
+            || self.key != other.key
+            || (self.children.is_some() && other.children.is_none())
+        {
+            return ComponentDiff::Replaced(other.clone());
+        }
+
+        let props = diff_props(&self.props, &other.props);
+
+        let old_children = self.children.as_deref().unwrap_or_default();
+        let new_children = other.children.as_deref().unwrap_or_default();
+        let (Some(old_ids), Some(new_ids)) = (child_ids(old_children), child_ids(new_children))
+        else {
+            return ComponentDiff::Replaced(other.clone());
+        };
+
+        let old_index: HashMap<&str, usize> =
+            old_ids.iter().enumerate().map(|(i, id)| (id.as_str(), i)).collect();
+        let new_set: HashSet<&str> = new_ids.iter().map(String::as_str).collect();
+
+        let mut added = Vec::new();
+        let mut updated = Vec::new();
+        let mut last_kept = None;
+        for (id, child) in new_ids.iter().zip(new_children) {
+            let Some(&i) = old_index.get(id.as_str()) else {
+                added.push(child.clone());
+                continue;
+            };
+
+            // Surviving children must keep their relative order and come
+            // before every added child
+            if !added.is_empty() || last_kept.is_some_and(|last| last > i) {
+                return ComponentDiff::Replaced(other.clone());
+            }
+            last_kept = Some(i);
+
+            let child_diff = old_children[i].diff(child);
+            if !child_diff.is_empty() {
+                updated.push((id.clone(), child_diff));
+            }
+        }
+
+        let removed: Vec<String> = old_ids
+            .iter()
+            .filter(|id| !new_set.contains(id.as_str()))
+            .cloned()
+            .collect();
+
+        let children_changed = !added.is_empty()
+            || !removed.is_empty()
+            || !updated.is_empty()
+            || self.children.is_none() != other.children.is_none();
+
+        if children_changed {
+            ComponentDiff::ChildrenChanged {
+                props,
+                added,
+                removed,
+                updated,
+            }
+        } else if !props.is_empty() {
+            ComponentDiff::PropsChanged(props)
+        } else {
+            ComponentDiff::NoChange
+        }
+    }
+
+    /// Apply a diff produced by [`VuerComponent::diff`] in place
+    ///
+    /// Fails with `VmpError::InvalidMessage` if the diff refers to a child
+    /// that does not exist, in which case `self` is left unchanged.
+    pub fn apply_diff(&mut self, diff: &ComponentDiff) -> Result<()> {
+        match diff {
+            ComponentDiff::NoChange => {}
+            ComponentDiff::Replaced(component) => *self = component.clone(),
+            ComponentDiff::PropsChanged(props) => apply_props(&mut self.props, props),
+            ComponentDiff::ChildrenChanged {
+                props,
+                added,
+                removed,
+                updated,
+            } => {
+                let mut children = self.children.clone().unwrap_or_default();
+                let ids = child_ids(&children).ok_or_else(|| {
+                    VmpError::InvalidMessage("Cannot apply diff: duplicate child keys".to_string())
+                })?;
+                let find = |id: &str| {
+                    ids.iter().position(|c| c == id).ok_or_else(|| {
+                        VmpError::InvalidMessage(format!("Cannot apply diff: no child '{}'", id))
+                    })
+                };
+
+                for (id, child_diff) in updated {
+                    children[find(id)?].apply_diff(child_diff)?;
+                }
+
+                let mut keep = vec![true; children.len()];
+                for id in removed {
+                    keep[find(id)?] = false;
+                }
+                let mut keep = keep.into_iter();
+                children.retain(|_| keep.next().unwrap_or(true));
+                children.extend(added.iter().cloned());
+
+                apply_props(&mut self.props, props);
+                self.children = Some(children);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Change to a single child, as reported by [`VuerComponent::reconcile`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ChildChange {
+    /// A child with a new key was inserted at `index`
+    Added {
+        key: String,
+        index: usize,
+        component: VuerComponent,
+    },
+
+    /// The child with this key is gone
+    Removed { key: String },
+
+    /// The child kept its place but its contents changed
+    Updated { key: String, update: ReconciledUpdate },
+
+    /// The child moved from index `from` in the old list to `to` in the new one
+    Moved {
+        key: String,
+        from: usize,
+        to: usize,
+        update: ReconciledUpdate,
+    },
+}
+
+/// Key-based reconciliation of two versions of a component
+///
+/// Unlike [`ComponentDiff`], children keep their identity when reordered:
+/// only the children that must move to restore the new order are reported
+/// as `Moved`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReconciledUpdate {
+    /// Prop changes
+    pub props: HashMap<String, PropChange>,
+    /// Removals in old order, followed by the other changes in new order
+    pub children: Vec<ChildChange>,
+    /// Set when the component cannot be reconciled and must be replaced
+    pub replaced: Option<VuerComponent>,
+}
+
+impl ReconciledUpdate {
+    /// Whether this update changes anything
+    pub fn is_empty(&self) -> bool {
+        self.props.is_empty() && self.children.is_empty() && self.replaced.is_none()
+    }
+
+    fn replace(component: &VuerComponent) -> Self {
+        ReconciledUpdate {
+            replaced: Some(component.clone()),
+            ..Default::default()
+        }
+    }
+}
+
+/// Positions in `seq` that form a longest increasing subsequence
+fn longest_increasing(seq: &[usize]) -> HashSet<usize> {
+    let mut tails: Vec<usize> = Vec::new();
+    let mut prev = vec![None; seq.len()];
+    for (pos, &value) in seq.iter().enumerate() {
+        let idx = tails.partition_point(|&t| seq[t] < value);
+        prev[pos] = idx.checked_sub(1).map(|i| tails[i]);
+        if idx == tails.len() {
+            tails.push(pos);
+        } else {
+            tails[idx] = pos;
+        }
+    }
+
+    let mut result = HashSet::new();
+    let mut cursor = tails.last().copied();
+    while let Some(pos) = cursor {
+        result.insert(pos);
+        cursor = prev[pos];
+    }
+    result
+}
+
+impl VuerComponent {
+    /// Reconcile `self` against `new_tree`, pairing children by their `key`
+    ///
+    /// Children without a key are matched by position. A changed tag or
+    /// key, duplicate child keys, or dropping the children list entirely
+    /// marks the component as replaced.
+    pub fn reconcile(&self, new_tree: &VuerComponent) -> ReconciledUpdate {
+        if self.tag != new_tree.tag
+            || self.key != new_tree.key
+            || (self.children.is_some() && new_tree.children.is_none())
+        {
+            return ReconciledUpdate::replace(new_tree);
+        }
+
+        let old_children = self.children.as_deref().unwrap_or_default();
+        let new_children = new_tree.children.as_deref().unwrap_or_default();
+        let (Some(old_ids), Some(new_ids)) = (child_ids(old_children), child_ids(new_children))
+        else {
+            return ReconciledUpdate::replace(new_tree);
+        };
+
+        let old_index: HashMap<&str, usize> =
+            old_ids.iter().enumerate().map(|(i, id)| (id.as_str(), i)).collect();
+        let new_set: HashSet<&str> = new_ids.iter().map(String::as_str).collect();
+
+        let mut children: Vec<ChildChange> = old_ids
+            .iter()
+            .filter(|id| !new_set.contains(id.as_str()))
+            .map(|id| ChildChange::Removed { key: id.clone() })
+            .collect();
+
+        // Kept children outside the longest run of preserved order must move
+        let kept: Vec<usize> =
+            new_ids.iter().filter_map(|id| old_index.get(id.as_str()).copied()).collect();
+        let stable = longest_increasing(&kept);
+
+        let mut kept_pos = 0;
+        for (to, (id, child)) in new_ids.iter().zip(new_children).enumerate() {
+            let Some(&from) = old_index.get(id.as_str()) else {
+                children.push(ChildChange::Added {
+                    key: id.clone(),
+                    index: to,
+                    component: child.clone(),
+                });
+                continue;
+            };
+
+            let update = old_children[from].reconcile(child);
+            let key = id.clone();
+            if !stable.contains(&kept_pos) {
+                children.push(ChildChange::Moved { key, from, to, update });
+            } else if !update.is_empty() {
+                children.push(ChildChange::Updated { key, update });
+            }
+            kept_pos += 1;
+        }
+
+        ReconciledUpdate {
+            props: diff_props(&self.props, &new_tree.props),
+            children,
+            replaced: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn keyed(tag: &str, key: &str) -> VuerComponent {
+        VuerComponent::new(tag).with_key(key)
+    }
+
+    fn scene() -> VuerComponent {
+        VuerComponent::new("scene")
+            .with_prop("background", json!("#000"))
+            .with_child(keyed("box", "a").with_prop("position", json!([0, 0, 0])))
+            .with_child(keyed("sphere", "b"))
+            .with_child(keyed("light", "c"))
+    }
+
+    fn assert_patches(old: &VuerComponent, new: &VuerComponent) -> ComponentDiff {
+        let diff = old.diff(new);
+        let mut patched = old.clone();
+        patched.apply_diff(&diff).unwrap();
+        assert_eq!(&patched, new);
+        diff
+    }
+
+    #[test]
+    fn test_no_change() {
+        assert_eq!(scene().diff(&scene()), ComponentDiff::NoChange);
+    }
+
+    #[test]
+    fn test_props_changed() {
+        let mut new = scene();
+        new.props.insert("background".to_string(), json!("#fff"));
+        new.props.insert("fog".to_string(), json!(true));
+
+        let diff = assert_patches(&scene(), &new);
+        assert_eq!(
+            diff,
+            ComponentDiff::PropsChanged(HashMap::from([
+                ("background".to_string(), PropChange::Set(json!("#fff"))),
+                ("fog".to_string(), PropChange::Set(json!(true))),
+            ]))
+        );
+
+        let diff = assert_patches(&new, &scene());
+        assert_eq!(
+            diff,
+            ComponentDiff::PropsChanged(HashMap::from([
+                ("background".to_string(), PropChange::Set(json!("#000"))),
+                ("fog".to_string(), PropChange::Removed),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_keyed_children_changed() {
+        let mut new = scene();
+        let children = new.children.as_mut().unwrap();
+        children[0].props.insert("position".to_string(), json!([1, 0, 0]));
+        children.remove(1);
+        children.push(keyed("text", "d"));
+
+        let diff = assert_patches(&scene(), &new);
+        assert_eq!(
+            diff,
+            ComponentDiff::ChildrenChanged {
+                props: HashMap::new(),
+                added: vec![keyed("text", "d")],
+                removed: vec!["b".to_string()],
+                updated: vec![(
+                    "a".to_string(),
+                    ComponentDiff::PropsChanged(HashMap::from([(
+                        "position".to_string(),
+                        PropChange::Set(json!([1, 0, 0]))
+                    )]))
+                )],
+            }
+        );
+    }
+
+    #[test]
+    fn test_positional_children_and_nested_diff() {
+        let old = VuerComponent::new("group")
+            .with_child(VuerComponent::new("mesh").with_child(VuerComponent::new("material")))
+            .with_child(VuerComponent::new("mesh"));
+        let new = VuerComponent::new("group")
+            .with_prop("visible", json!(false))
+            .with_child(VuerComponent::new("mesh").with_child(VuerComponent::new("texture")));
+
+        let diff = assert_patches(&old, &new);
+        let ComponentDiff::ChildrenChanged {
+            props,
+            added,
+            removed,
+            updated,
+        } = diff
+        else {
+            panic!("expected ChildrenChanged");
+        };
+        assert_eq!(props.len(), 1);
+        assert!(added.is_empty());
+        assert_eq!(removed, vec!["1".to_string()]);
+        assert_eq!(updated.len(), 1);
+        assert!(matches!(
+            &updated[0].1,
+            ComponentDiff::ChildrenChanged { updated, .. }
+                if matches!(updated[0].1, ComponentDiff::Replaced(_))
+        ));
+    }
+
+    #[test]
+    fn test_replaced() {
+        let diff = assert_patches(&scene(), &VuerComponent::new("canvas"));
+        assert!(matches!(diff, ComponentDiff::Replaced(_)));
+
+        // Reordering existing children cannot be expressed incrementally
+        let mut reordered = scene();
+        reordered.children.as_mut().unwrap().swap(0, 2);
+        assert!(matches!(assert_patches(&scene(), &reordered), ComponentDiff::Replaced(_)));
+
+        let mut inserted = scene();
+        inserted.children.as_mut().unwrap().insert(0, keyed("text", "d"));
+        assert!(matches!(assert_patches(&scene(), &inserted), ComponentDiff::Replaced(_)));
+    }
+
+    #[test]
+    fn test_children_list_presence() {
+        let bare = VuerComponent::new("group");
+        let mut empty = VuerComponent::new("group");
+        empty.children = Some(Vec::new());
+
+        assert_patches(&bare, &empty);
+        assert_patches(&empty, &bare);
+        assert_patches(&scene(), &VuerComponent::new("scene"));
+    }
+
+    #[test]
+    fn test_apply_diff_unknown_child() {
+        let diff = ComponentDiff::ChildrenChanged {
+            props: HashMap::new(),
+            added: Vec::new(),
+            removed: vec!["missing".to_string()],
+            updated: Vec::new(),
+        };
+
+        let mut component = scene();
+        assert!(matches!(component.apply_diff(&diff), Err(VmpError::InvalidMessage(_))));
+        assert_eq!(component, scene());
+    }
+
+    fn keys(update: &ReconciledUpdate) -> Vec<String> {
+        update
+            .children
+            .iter()
+            .map(|change| match change {
+                ChildChange::Added { key, index, .. } => format!("+{}@{}", key, index),
+                ChildChange::Removed { key } => format!("-{}", key),
+                ChildChange::Updated { key, .. } => format!("~{}", key),
+                ChildChange::Moved { key, from, to, .. } => format!("{}:{}->{}", key, from, to),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reconcile_reordered() {
+        assert!(scene().reconcile(&scene()).is_empty());
+
+        // Moving one child to the front reports a single move
+        let mut new = scene();
+        let children = new.children.as_mut().unwrap();
+        let light = children.pop().unwrap();
+        children.insert(0, light);
+
+        let update = scene().reconcile(&new);
+        assert_eq!(keys(&update), vec!["c:2->0"]);
+        assert!(update.props.is_empty());
+        assert!(update.replaced.is_none());
+
+        // A moved child still carries its own changes
+        new.children.as_mut().unwrap()[0] = keyed("light", "c").with_prop("power", json!(2));
+        let update = scene().reconcile(&new);
+        let ChildChange::Moved { update, .. } = &update.children[0] else {
+            panic!("expected Moved");
+        };
+        assert_eq!(update.props.get("power"), Some(&PropChange::Set(json!(2))));
+    }
+
+    #[test]
+    fn test_reconcile_added_and_removed() {
+        let new = VuerComponent::new("scene")
+            .with_prop("background", json!("#000"))
+            .with_child(keyed("text", "d"))
+            .with_child(keyed("light", "c"))
+            .with_child(keyed("box", "a").with_prop("position", json!([1, 0, 0])));
+
+        let update = scene().reconcile(&new);
+        assert_eq!(keys(&update), vec!["-b", "+d@0", "c:2->1", "~a"]);
+    }
+
+    #[test]
+    fn test_reconcile_nested_and_replaced() {
+        let old = VuerComponent::new("scene").with_child(
+            VuerComponent::new("group")
+                .with_key("g")
+                .with_child(keyed("box", "a"))
+                .with_child(keyed("box", "b")),
+        );
+        let new = VuerComponent::new("scene").with_child(
+            VuerComponent::new("group")
+                .with_key("g")
+                .with_child(keyed("box", "b"))
+                .with_child(keyed("mesh", "a")),
+        );
+
+        let update = old.reconcile(&new);
+        let [ChildChange::Updated { key, update }] = update.children.as_slice() else {
+            panic!("expected a single Updated");
+        };
+        assert_eq!(key, "g");
+        assert_eq!(keys(update), vec!["b:1->0", "~a"]);
+
+        // The tag of "a" changed, so it is replaced in place
+        let ChildChange::Updated { update, .. } = &update.children[1] else {
+            panic!("expected Updated");
+        };
+        assert_eq!(update.replaced, Some(keyed("mesh", "a")));
+    }
+
+    #[test]
+    fn test_diff_serialization() {
+        let mut new = scene();
+        new.children.as_mut().unwrap().pop();
+        let diff = scene().diff(&new);
+
+        let bytes = crate::serializer::serialize(&diff).unwrap();
+        let restored: ComponentDiff = crate::deserializer::deserialize(&bytes).unwrap();
+        assert_eq!(restored, diff);
+    }
+
+    #[test]
+    fn test_null_prop_survives_serialization() {
+        let old = VuerComponent::new("mesh").with_prop("color", json!("red"));
+        let cleared = VuerComponent::new("mesh").with_prop("color", Value::Null);
+        let bare = VuerComponent::new("mesh");
+
+        let cases = [(&cleared, PropChange::Set(Value::Null)), (&bare, PropChange::Removed)];
+        for (new, change) in cases {
+            let diff = assert_patches(&old, new);
+            let bytes = crate::serializer::serialize(&diff).unwrap();
+            let restored: ComponentDiff = crate::deserializer::deserialize(&bytes).unwrap();
+            assert_eq!(
+                restored,
+                ComponentDiff::PropsChanged(HashMap::from([("color".to_string(), change)]))
+            );
+
+            let mut patched = old.clone();
+            patched.apply_diff(&restored).unwrap();
+            assert_eq!(&patched, new);
+        }
+    }
+
+    #[test]
+    fn test_reconciled_update_serialization() {
+        let mut new = scene();
+        new.children.as_mut().unwrap().swap(0, 2);
+        let update = scene().reconcile(&new);
+
+        let bytes = crate::serializer::serialize_reconciled_update(&update).unwrap();
+        let restored: ReconciledUpdate = crate::deserializer::deserialize(&bytes).unwrap();
+        assert_eq!(restored, update);
+    }
+}
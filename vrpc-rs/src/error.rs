@@ -2,6 +2,8 @@
 //!
 //! Author: Ge Yang
 
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -12,6 +14,18 @@ pub enum VmpError {
     #[error("Deserialization error: {0}")]
     Deserialization(String),
 
+    /// A decode failure located in the input
+    #[error("Deserialization error at {path} (byte {offset}): {message}")]
+    DeserializationDetailed {
+        /// Dotted path of the field being decoded, such as
+        /// `kwargs.camera.position[2]` (`.` for the top level)
+        path: String,
+        /// Number of bytes consumed when decoding stopped
+        offset: u64,
+        /// What went wrong
+        message: String,
+    },
+
     #[error("Type conversion error: {0}")]
     TypeConversion(String),
 
@@ -30,6 +44,18 @@ pub enum VmpError {
     #[error("Missing required field: {0}")]
     MissingField(String),
 
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+
+    #[error("Unavailable: {0}")]
+    Unavailable(String),
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
@@ -47,4 +73,246 @@ pub enum VmpError {
     Image(#[from] image::ImageError),
 }
 
+impl VmpError {
+    /// Whether retrying the operation may succeed
+    ///
+    /// Timeouts, unavailable services and I/O failures are transient, as are
+    /// MessagePack decode errors, which truncation on the network can cause.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            VmpError::RpcTimeout(_)
+                | VmpError::Unavailable(_)
+                | VmpError::Io(_)
+                | VmpError::MsgPackDecode(_)
+        )
+    }
+
+    /// Whether retrying the operation will fail the same way
+    pub fn is_permanent(&self) -> bool {
+        !self.is_transient()
+    }
+
+    /// How long to wait before retrying, or `None` if retrying is pointless
+    pub fn suggested_retry_delay(&self) -> Option<Duration> {
+        self.is_transient().then(|| Duration::from_millis(500))
+    }
+
+    /// The category reported to the caller when this error ends an RPC
+    ///
+    /// Malformed input of any kind is an invalid argument, and timeouts
+    /// mean the handler was unavailable. Everything else is internal.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            VmpError::NotFound(_) => ErrorKind::NotFound,
+            VmpError::PermissionDenied(_) => ErrorKind::PermissionDenied,
+            VmpError::InvalidArgument(_)
+            | VmpError::TypeConversion(_)
+            | VmpError::InvalidMessage(_)
+            | VmpError::MissingField(_) => ErrorKind::InvalidArgument,
+            VmpError::Unavailable(_) | VmpError::RpcTimeout(_) => ErrorKind::Unavailable,
+            _ => ErrorKind::Internal,
+        }
+    }
+
+    /// The text to put in `RpcResponse::error` for this error
+    ///
+    /// Variants that `From<RpcResponse>` rebuilds from the response keep
+    /// only their message, so the prefix is not added a second time.
+    pub fn response_message(&self) -> String {
+        match self {
+            VmpError::NotFound(message)
+            | VmpError::PermissionDenied(message)
+            | VmpError::InvalidArgument(message)
+            | VmpError::Unavailable(message)
+            | VmpError::RpcError(message) => message.clone(),
+            other => other.to_string(),
+        }
+    }
+}
+
+/// Category of a failed RPC, carried in `RpcResponse::error_kind`
+///
+/// Serialized as a snake_case string such as `"not_found"`. Names this
+/// version does not know decode as `Unknown`, so peers can add categories.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum ErrorKind {
+    /// The requested object does not exist
+    NotFound,
+
+    /// The caller may not perform the request
+    PermissionDenied,
+
+    /// The request's arguments are malformed or out of range
+    InvalidArgument,
+
+    /// The handler failed
+    Internal,
+
+    /// The handler cannot serve requests right now; retrying may succeed
+    Unavailable,
+
+    /// A category this version does not know, by its wire name
+    Unknown(String),
+}
+
+impl ErrorKind {
+    /// The name used on the wire
+    pub fn as_str(&self) -> &str {
+        match self {
+            ErrorKind::NotFound => "not_found",
+            ErrorKind::PermissionDenied => "permission_denied",
+            ErrorKind::InvalidArgument => "invalid_argument",
+            ErrorKind::Internal => "internal",
+            ErrorKind::Unavailable => "unavailable",
+            ErrorKind::Unknown(name) => name,
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<String> for ErrorKind {
+    fn from(name: String) -> Self {
+        match name.as_str() {
+            "not_found" => ErrorKind::NotFound,
+            "permission_denied" => ErrorKind::PermissionDenied,
+            "invalid_argument" => ErrorKind::InvalidArgument,
+            "internal" => ErrorKind::Internal,
+            "unavailable" => ErrorKind::Unavailable,
+            _ => ErrorKind::Unknown(name),
+        }
+    }
+}
+
+impl From<ErrorKind> for String {
+    fn from(kind: ErrorKind) -> Self {
+        match kind {
+            ErrorKind::Unknown(name) => name,
+            known => known.as_str().to_string(),
+        }
+    }
+}
+
+/// Turn a failed response into the error matching its `error_kind`
+///
+/// Internal, unknown and missing kinds become `VmpError::RpcError`.
+impl From<crate::types::RpcResponse> for VmpError {
+    fn from(response: crate::types::RpcResponse) -> Self {
+        let message = response.error.unwrap_or_else(|| "Request failed".to_string());
+        match response.error_kind {
+            Some(ErrorKind::NotFound) => VmpError::NotFound(message),
+            Some(ErrorKind::PermissionDenied) => VmpError::PermissionDenied(message),
+            Some(ErrorKind::InvalidArgument) => VmpError::InvalidArgument(message),
+            Some(ErrorKind::Unavailable) => VmpError::Unavailable(message),
+            _ => VmpError::RpcError(message),
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, VmpError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transient_classification() {
+        let transient = [
+            VmpError::RpcTimeout("render".to_string()),
+            VmpError::Unavailable("render".to_string()),
+            VmpError::Io(std::io::ErrorKind::ConnectionReset.into()),
+            VmpError::MsgPackDecode(rmp_serde::decode::Error::Syntax("eof".to_string())),
+        ];
+        for err in &transient {
+            assert!(err.is_transient(), "{}", err);
+            assert!(!err.is_permanent(), "{}", err);
+            assert_eq!(err.suggested_retry_delay(), Some(Duration::from_millis(500)));
+        }
+
+        let permanent = [
+            VmpError::Serialization(String::new()),
+            VmpError::Deserialization(String::new()),
+            VmpError::DeserializationDetailed {
+                path: ".".to_string(),
+                offset: 0,
+                message: String::new(),
+            },
+            VmpError::TypeConversion(String::new()),
+            VmpError::TypeNotRegistered(String::new()),
+            VmpError::RpcError(String::new()),
+            VmpError::InvalidMessage(String::new()),
+            VmpError::MissingField(String::new()),
+            VmpError::NotFound(String::new()),
+            VmpError::PermissionDenied(String::new()),
+            VmpError::InvalidArgument(String::new()),
+            VmpError::MsgPackEncode(rmp_serde::encode::Error::UnknownLength),
+            VmpError::Json(serde_json::from_str::<()>("{").unwrap_err()),
+        ];
+        for err in &permanent {
+            assert!(err.is_permanent(), "{}", err);
+            assert_eq!(err.suggested_retry_delay(), None);
+        }
+
+        #[cfg(feature = "image")]
+        {
+            let err = VmpError::Image(image::ImageError::Limits(
+                image::error::LimitError::from_kind(image::error::LimitErrorKind::DimensionError),
+            ));
+            assert!(err.is_permanent());
+        }
+    }
+
+    #[test]
+    fn test_error_kind_names() {
+        let kinds = [
+            ErrorKind::NotFound,
+            ErrorKind::PermissionDenied,
+            ErrorKind::InvalidArgument,
+            ErrorKind::Internal,
+            ErrorKind::Unavailable,
+            ErrorKind::Unknown("quota_exceeded".to_string()),
+        ];
+        for kind in kinds {
+            let json = serde_json::to_value(&kind).unwrap();
+            assert_eq!(json, serde_json::json!(kind.as_str()));
+            assert_eq!(serde_json::from_value::<ErrorKind>(json).unwrap(), kind);
+        }
+        assert_eq!(ErrorKind::PermissionDenied.to_string(), "permission_denied");
+    }
+
+    #[test]
+    fn test_error_from_response() {
+        use crate::types::RpcResponse;
+
+        let cases = [
+            (ErrorKind::NotFound, "Not found: no mesh"),
+            (ErrorKind::PermissionDenied, "Permission denied: no mesh"),
+            (ErrorKind::InvalidArgument, "Invalid argument: no mesh"),
+            (ErrorKind::Unavailable, "Unavailable: no mesh"),
+            (ErrorKind::Internal, "RPC error: no mesh"),
+            (ErrorKind::Unknown("teapot".to_string()), "RPC error: no mesh"),
+        ];
+        for (kind, expected) in cases {
+            let response = RpcResponse::error_with_kind("rpc-1", "no mesh", kind.clone());
+            assert_eq!(response.error_kind(), Some(kind));
+            assert_eq!(VmpError::from(response).to_string(), expected);
+        }
+
+        let plain = VmpError::from(RpcResponse::error("rpc-1", "no mesh"));
+        assert!(matches!(plain, VmpError::RpcError(m) if m == "no mesh"));
+    }
+
+    #[test]
+    fn test_error_kind_of_errors() {
+        assert_eq!(VmpError::NotFound(String::new()).kind(), ErrorKind::NotFound);
+        assert_eq!(VmpError::MissingField(String::new()).kind(), ErrorKind::InvalidArgument);
+        assert_eq!(VmpError::RpcTimeout(String::new()).kind(), ErrorKind::Unavailable);
+        assert_eq!(VmpError::Serialization(String::new()).kind(), ErrorKind::Internal);
+    }
+}
@@ -0,0 +1,336 @@
+//! Length-prefixed framing for VMP messages on byte streams
+//!
+//! Author: Ge Yang
+//!
+//! Each frame is a 4-byte big-endian length followed by that many bytes of
+//! payload, typically a serialized message.
+
+use crate::error::{Result, VmpError};
+use std::io::{Read, Write};
+
+/// Size of the length prefix in bytes
+pub const FRAME_HEADER_SIZE: usize = 4;
+
+pub(crate) fn frame_len(bytes: &[u8]) -> Result<u32> {
+    u32::try_from(bytes.len()).map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Frame of {} bytes exceeds the 4-byte length prefix", bytes.len()),
+        )
+        .into()
+    })
+}
+
+fn short_header(received: usize) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::UnexpectedEof,
+        format!("Frame header truncated after {} bytes", received),
+    )
+}
+
+/// Payload length announced by `header`, if within `max_frame_size`
+fn checked_frame_len(
+    header: [u8; FRAME_HEADER_SIZE],
+    max_frame_size: Option<usize>,
+) -> Result<u32> {
+    let len = u32::from_be_bytes(header);
+    if max_frame_size.is_some_and(|max| len as usize > max) {
+        return Err(VmpError::InvalidMessage(format!(
+            "Frame of {} bytes exceeds the limit of {} bytes",
+            len,
+            max_frame_size.unwrap_or_default()
+        )));
+    }
+    Ok(len)
+}
+
+fn short_frame(expected: u32, received: usize) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::UnexpectedEof,
+        format!("Frame truncated: expected {} bytes, got {}", expected, received),
+    )
+}
+
+/// Write `bytes` as a single length-prefixed frame
+pub fn write_framed<W: Write>(writer: &mut W, bytes: &[u8]) -> Result<()> {
+    let len = frame_len(bytes)?;
+    writer.write_all(&len.to_be_bytes())?;
+    writer.write_all(bytes)?;
+    Ok(())
+}
+
+/// Read one length-prefixed frame
+///
+/// Returns `VmpError::Io` if the stream ends before the full frame arrives.
+pub fn read_framed<R: Read>(reader: &mut R) -> Result<Vec<u8>> {
+    read_framed_with_limit(reader, None)?.ok_or_else(|| short_header(0).into())
+}
+
+/// Read one length-prefixed frame, or `None` if the stream ends cleanly
+/// before it
+///
+/// Frames announcing more than `max_frame_size` bytes are rejected with
+/// `VmpError::InvalidMessage` before their payload is read. A stream that
+/// ends partway through a frame is a `VmpError::Io` of kind
+/// `UnexpectedEof`.
+pub fn read_framed_with_limit<R: Read>(
+    reader: &mut R,
+    max_frame_size: Option<usize>,
+) -> Result<Option<Vec<u8>>> {
+    let mut header = [0u8; FRAME_HEADER_SIZE];
+    let mut filled = 0;
+    while filled < header.len() {
+        match reader.read(&mut header[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => return Err(short_header(filled).into()),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    let len = checked_frame_len(header, max_frame_size)?;
+
+    // Grow with the data actually received rather than trusting the prefix
+    let mut buf = Vec::new();
+    reader.take(u64::from(len)).read_to_end(&mut buf)?;
+    if buf.len() != len as usize {
+        return Err(short_frame(len, buf.len()).into());
+    }
+    Ok(Some(buf))
+}
+
+/// Write `bytes` as a single length-prefixed frame to an async writer
+#[cfg(feature = "tokio")]
+pub async fn write_framed_async<W>(writer: &mut W, bytes: &[u8]) -> Result<()>
+where
+    W: tokio::io::AsyncWrite + Unpin,
+{
+    use tokio::io::AsyncWriteExt;
+
+    let len = frame_len(bytes)?;
+    writer.write_all(&len.to_be_bytes()).await?;
+    writer.write_all(bytes).await?;
+    Ok(())
+}
+
+/// Read one length-prefixed frame from an async reader
+///
+/// Returns `VmpError::Io` if the stream ends before the full frame arrives.
+#[cfg(feature = "tokio")]
+pub async fn read_framed_async<R>(reader: &mut R) -> Result<Vec<u8>>
+where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    let mut frames = FrameReader::new(None);
+    std::future::poll_fn(|cx| frames.poll_read_frame(reader, cx))
+        .await?
+        .ok_or_else(|| short_header(0).into())
+}
+
+/// Payload bytes read per call once a frame header has arrived
+#[cfg(feature = "tokio")]
+const READ_CHUNK_SIZE: usize = 8 * 1024;
+
+/// Reads length-prefixed frames from an async reader one poll at a time
+///
+/// For `poll`-based code such as [`crate::stream::MessageStream`], which
+/// cannot keep a [`read_framed_async`] future between calls. A frame may
+/// arrive over any number of polls.
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub struct FrameReader {
+    max_frame_size: Option<usize>,
+    header: [u8; FRAME_HEADER_SIZE],
+    filled: usize,
+    /// Payload received so far and its announced length, once the header
+    /// is complete
+    payload: Option<(Vec<u8>, u32)>,
+}
+
+#[cfg(feature = "tokio")]
+impl FrameReader {
+    /// Read frames, rejecting those larger than `max_frame_size`
+    pub fn new(max_frame_size: Option<usize>) -> Self {
+        Self {
+            max_frame_size,
+            header: [0; FRAME_HEADER_SIZE],
+            filled: 0,
+            payload: None,
+        }
+    }
+
+    /// Poll for the next frame, or `None` if the reader ends cleanly before it
+    ///
+    /// Fails as [`read_framed_with_limit`] does.
+    pub fn poll_read_frame<R>(
+        &mut self,
+        reader: &mut R,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<Option<Vec<u8>>>>
+    where
+        R: tokio::io::AsyncRead + Unpin,
+    {
+        use std::pin::Pin;
+        use std::task::{ready, Poll};
+        use tokio::io::ReadBuf;
+
+        loop {
+            let Some((buf, len)) = &mut self.payload else {
+                let mut read_buf = ReadBuf::new(&mut self.header[self.filled..]);
+                ready!(Pin::new(&mut *reader).poll_read(cx, &mut read_buf))?;
+                let n = read_buf.filled().len();
+                if n == 0 {
+                    return Poll::Ready(match self.filled {
+                        0 => Ok(None),
+                        filled => Err(short_header(filled).into()),
+                    });
+                }
+                self.filled += n;
+                if self.filled == FRAME_HEADER_SIZE {
+                    self.filled = 0;
+                    let len = checked_frame_len(self.header, self.max_frame_size)?;
+                    self.payload = Some((Vec::new(), len));
+                }
+                continue;
+            };
+            if buf.len() == *len as usize {
+                return Poll::Ready(Ok(self.payload.take().map(|(buf, _)| buf)));
+            }
+
+            // Grow with the data actually received rather than trusting the
+            // prefix
+            let mut chunk = [0u8; READ_CHUNK_SIZE];
+            let want = (*len as usize - buf.len()).min(READ_CHUNK_SIZE);
+            let mut read_buf = ReadBuf::new(&mut chunk[..want]);
+            ready!(Pin::new(&mut *reader).poll_read(cx, &mut read_buf))?;
+            if read_buf.filled().is_empty() {
+                return Poll::Ready(Err(short_frame(*len, buf.len()).into()));
+            }
+            buf.extend_from_slice(read_buf.filled());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::VmpError;
+
+    /// Reader that hands out at most a few bytes per call and is
+    /// occasionally interrupted
+    struct TrickleReader {
+        data: Vec<u8>,
+        pos: usize,
+        calls: usize,
+    }
+
+    impl Read for TrickleReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.calls += 1;
+            if self.calls.is_multiple_of(4) {
+                return Err(std::io::ErrorKind::Interrupted.into());
+            }
+            let n = (self.calls % 3 + 1).min(buf.len()).min(self.data.len() - self.pos);
+            buf[..n].copy_from_slice(&self.data[self.pos..self.pos + n]);
+            self.pos += n;
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_framed_roundtrip_with_short_reads() {
+        let frames: Vec<Vec<u8>> = vec![b"hello".to_vec(), Vec::new(), (0..=255).collect()];
+
+        let mut wire = Vec::new();
+        for frame in &frames {
+            write_framed(&mut wire, frame).unwrap();
+        }
+        assert_eq!(&wire[..FRAME_HEADER_SIZE], &[0, 0, 0, 5]);
+
+        let mut reader = TrickleReader {
+            data: wire,
+            pos: 0,
+            calls: 0,
+        };
+        for frame in &frames {
+            assert_eq!(&read_framed(&mut reader).unwrap(), frame);
+        }
+        assert!(matches!(read_framed(&mut reader), Err(VmpError::Io(_))));
+    }
+
+    #[test]
+    fn test_read_framed_truncated() {
+        let mut wire = Vec::new();
+        write_framed(&mut wire, b"truncated payload").unwrap();
+        wire.truncate(wire.len() - 3);
+
+        let err = read_framed(&mut wire.as_slice()).unwrap_err();
+        assert!(matches!(err, VmpError::Io(e) if e.kind() == std::io::ErrorKind::UnexpectedEof));
+
+        // A short header is reported the same way
+        let err = read_framed(&mut [0u8, 0].as_slice()).unwrap_err();
+        assert!(matches!(err, VmpError::Io(e) if e.kind() == std::io::ErrorKind::UnexpectedEof));
+    }
+
+    #[test]
+    fn test_read_framed_with_limit() {
+        let mut wire = Vec::new();
+        write_framed(&mut wire, b"small").unwrap();
+        write_framed(&mut wire, &[0; 64]).unwrap();
+
+        let mut reader = wire.as_slice();
+        let frame = read_framed_with_limit(&mut reader, Some(16)).unwrap();
+        assert_eq!(frame.as_deref(), Some(&b"small"[..]));
+        let err = read_framed_with_limit(&mut reader, Some(16)).unwrap_err();
+        assert!(matches!(err, VmpError::InvalidMessage(_)));
+
+        // Only a stream ending between frames ends cleanly
+        let mut reader = &wire[..FRAME_HEADER_SIZE + 5];
+        assert!(read_framed_with_limit(&mut reader, None).unwrap().is_some());
+        assert!(read_framed_with_limit(&mut reader, None).unwrap().is_none());
+        assert!(matches!(read_framed(&mut [].as_slice()), Err(VmpError::Io(_))));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn test_framed_async_roundtrip() {
+        use crate::serializer::serialize_message;
+        use crate::types::Message;
+
+        // A tiny duplex buffer forces both sides through many partial reads and writes
+        let (mut client, mut server) = tokio::io::duplex(3);
+
+        let messages: Vec<Vec<u8>> = (0..5)
+            .map(|i| serialize_message(&Message::new(format!("EVENT_{}", i))).unwrap())
+            .collect();
+
+        let writer = async {
+            for bytes in &messages {
+                write_framed_async(&mut client, bytes).await.unwrap();
+            }
+            drop(client);
+        };
+        let reader = async {
+            let mut received = Vec::new();
+            for _ in 0..messages.len() {
+                received.push(read_framed_async(&mut server).await.unwrap());
+            }
+            assert!(matches!(read_framed_async(&mut server).await, Err(VmpError::Io(_))));
+            received
+        };
+
+        let ((), received) = tokio::join!(writer, reader);
+        assert_eq!(received, messages);
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn test_read_framed_async_truncated() {
+        let mut wire = Vec::new();
+        write_framed(&mut wire, b"truncated payload").unwrap();
+        wire.truncate(wire.len() - 1);
+
+        let err = read_framed_async(&mut wire.as_slice()).await.unwrap_err();
+        assert!(matches!(err, VmpError::Io(e) if e.kind() == std::io::ErrorKind::UnexpectedEof));
+    }
+}
@@ -0,0 +1,134 @@
+//! JSON serialization backend for VMP
+//!
+//! Author: Ge Yang
+
+use crate::error::{Result, VmpError};
+use crate::types::Message;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Wire format used by [`serialize_with_backend`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SerializeBackend {
+    /// MessagePack, the default VMP encoding
+    #[default]
+    MessagePack,
+    /// JSON, with ZData binary fields base64-encoded
+    Json,
+}
+
+/// Serialize a message to a JSON string
+pub fn serialize_message_json(message: &Message) -> Result<String> {
+    to_json_string(message)
+}
+
+/// Deserialize a message from a JSON string
+pub fn deserialize_message_json(s: &str) -> Result<Message> {
+    from_json_str(s)
+}
+
+/// Serialize a value to bytes using the chosen backend
+pub fn serialize_with_backend<T: Serialize>(
+    value: &T,
+    backend: SerializeBackend,
+) -> Result<Vec<u8>> {
+    match backend {
+        SerializeBackend::MessagePack => crate::serializer::serialize(value),
+        SerializeBackend::Json => to_json_string(value).map(String::into_bytes),
+    }
+}
+
+/// Deserialize bytes produced by [`serialize_with_backend`]
+pub fn deserialize_with_backend<T: DeserializeOwned>(
+    bytes: &[u8],
+    backend: SerializeBackend,
+) -> Result<T> {
+    match backend {
+        SerializeBackend::MessagePack => crate::deserializer::deserialize(bytes),
+        SerializeBackend::Json => {
+            let s = std::str::from_utf8(bytes)
+                .map_err(|e| VmpError::Deserialization(format!("Invalid UTF-8: {}", e)))?;
+            from_json_str(s)
+        }
+    }
+}
+
+fn to_json_string<T: Serialize>(value: &T) -> Result<String> {
+    let mut value = serde_json::to_value(value)?;
+    crate::serializer::encode_binary_fields(&mut value);
+    Ok(serde_json::to_string(&value)?)
+}
+
+fn from_json_str<T: DeserializeOwned>(s: &str) -> Result<T> {
+    let mut value: Value = serde_json::from_str(s)?;
+    crate::deserializer::decode_binary_fields(&mut value)?;
+    Ok(serde_json::from_value(value)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{ClientEvent, RpcRequest, RpcResponse, ServerEvent, VuerComponent};
+    use crate::zdata::ZData;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    fn roundtrip<T>(value: &T)
+    where
+        T: Serialize + DeserializeOwned + PartialEq + std::fmt::Debug,
+    {
+        for backend in [SerializeBackend::MessagePack, SerializeBackend::Json] {
+            let bytes = serialize_with_backend(value, backend).unwrap();
+            let restored: T = deserialize_with_backend(&bytes, backend).unwrap();
+            assert_eq!(&restored, value, "{:?} roundtrip", backend);
+        }
+    }
+
+    #[test]
+    fn test_message_json_roundtrip() {
+        let zdata = ZData::new("numpy.ndarray")
+            .with_binary(vec![0, 1, 254, 255])
+            .with_dtype("uint8")
+            .with_shape(vec![4]);
+        let msg = Message::new("UPDATE")
+            .with_data(json!({"array": serde_json::to_value(&zdata).unwrap()}));
+
+        let s = serialize_message_json(&msg).unwrap();
+        let parsed: Value = serde_json::from_str(&s).unwrap();
+        assert_eq!(parsed["data"]["array"]["b"], json!("AAH+/w=="));
+
+        assert_eq!(deserialize_message_json(&s).unwrap(), msg);
+    }
+
+    #[test]
+    fn test_core_types_roundtrip() {
+        roundtrip(&Message::new("TEST").with_data(json!([1, "two", null])));
+        roundtrip(&ClientEvent::new("CLICK", json!({"x": 1})));
+        roundtrip(&ServerEvent::new("SET", json!({"key": "scene"})));
+        roundtrip(
+            &RpcRequest::new("CAMERA", "rpc-1")
+                .with_args(vec![json!(1)])
+                .with_kwargs(HashMap::from([("fov".to_string(), json!(60))])),
+        );
+        roundtrip(&RpcResponse::success("rpc-1", json!({"ok": true})));
+        roundtrip(&RpcResponse::error("rpc-1", "failed"));
+        roundtrip(
+            &VuerComponent::new("scene")
+                .with_prop("background", json!("#000"))
+                .with_child(VuerComponent::new("box").with_key("a")),
+        );
+        roundtrip(
+            &ZData::new("custom.Blob")
+                .with_binary(vec![9, 8, 7])
+                .with_field("meta", json!({"nested": true})),
+        );
+    }
+
+    #[test]
+    fn test_invalid_base64_rejected() {
+        let s = r#"{"ztype": "custom.Blob", "b": "not base64!"}"#;
+        let err = deserialize_with_backend::<ZData>(s.as_bytes(), SerializeBackend::Json);
+        assert!(matches!(err, Err(VmpError::Deserialization(_))));
+    }
+}
@@ -74,35 +74,105 @@
 //! );
 //! ```
 
+pub mod borrowed;
 pub mod builtin_types;
+#[cfg(feature = "cbor")]
+pub mod cbor_backend;
+#[cfg(feature = "crypto")]
+pub mod dedup;
 pub mod deserializer;
+pub mod diff;
 pub mod error;
+pub mod framing;
+#[cfg(feature = "json-backend")]
+pub mod json_backend;
+pub mod pool;
+#[cfg(feature = "tokio")]
+pub mod pubsub;
+pub mod rate_limit;
+pub mod router;
 pub mod rpc;
+pub mod sequencer;
 pub mod serializer;
+#[cfg(feature = "tokio")]
+pub mod stream;
 pub mod type_registry;
 pub mod types;
+pub mod validator;
 pub mod zdata;
 
 // Re-export commonly used types
-pub use error::{Result, VmpError};
+pub use error::{ErrorKind, Result, VmpError};
 pub use types::{
-    ClientEvent, Message, RpcRequest, RpcResponse, ServerEvent, Timestamp, VuerComponent,
+    ClientEvent, FlatComponent, Message, MessageKind, NoEtype, NoRtype, RpcRequest,
+    RpcRequestBuilder, RpcResponse, ServerEvent, Timestamp, VmpEnvelope, VuerComponent, YesEtype,
+    YesRtype, PROTOCOL_VERSION, SCHEMA_HINT_PROP, SECONDS_TIMESTAMP_LIMIT, TRACE_ID_KEY,
+    is_expired,
+};
+pub use borrowed::{MessageRef, ValueRef, ZDataRef};
+pub use zdata::{
+    DecodedZData, HandleAccess, ZData, ZDataChecksum, ZDataConversion, ZDataHandle,
 };
-pub use zdata::{ZData, ZDataConversion};
+pub use diff::{ChildChange, ComponentDiff, PropChange, ReconciledUpdate};
 
 // Re-export serialization functions
 pub use deserializer::{
-    deserialize, deserialize_component, deserialize_from_base64, deserialize_message,
+    deserialize, deserialize_batch, deserialize_batch_with_options, deserialize_borrowed,
+    deserialize_component, deserialize_component_with_options, deserialize_envelope,
+    deserialize_from_base64, deserialize_from_base64_url, deserialize_from_base64_with,
+    deserialize_json, deserialize_json_with_options, deserialize_message, deserialize_message_named,
+    deserialize_message_with_options, deserialize_stream, peek_etype, peek_fields, MessageReader,
+    PeekedFields,
 };
-pub use serializer::{serialize, serialize_component, serialize_message, serialize_to_base64};
+pub use serializer::{
+    serialize, serialize_batch, serialize_component, serialize_component_with_options,
+    serialize_into, serialize_json, serialize_json_with_options, serialize_message,
+    serialize_message_bounded, serialize_message_into, serialize_message_named,
+    serialize_message_with_options, serialize_reconciled_update, serialize_to_base64,
+    serialize_to_base64_url, serialize_to_base64_with, Base64Variant,
+};
+
+#[cfg(feature = "json-backend")]
+pub use json_backend::{
+    deserialize_message_json, serialize_message_json, serialize_with_backend, SerializeBackend,
+};
+
+// Re-export deduplication and signing
+#[cfg(feature = "crypto")]
+pub use dedup::MessageDeduplicator;
+#[cfg(feature = "crypto")]
+pub use types::SignedMessage;
+
+// Re-export message pooling
+pub use pool::{MessagePool, PooledMessage};
+
+// Re-export rate limiting
+pub use rate_limit::MessageRateLimiter;
+
+// Re-export ordered delivery
+pub use sequencer::{MessageReorderer, MessageSequencer, SequencedMessage};
+
+// Re-export message routing
+#[cfg(feature = "tokio")]
+pub use pubsub::{PubSubBus, Subscription};
+#[cfg(feature = "tokio")]
+pub use router::AsyncMessageRouter;
+pub use router::MessageRouter;
+#[cfg(feature = "tokio")]
+pub use stream::{MessageSink, MessageStream};
 
 // Re-export RPC utilities
 #[cfg(feature = "tokio")]
-pub use rpc::RpcManager;
+pub use rpc::{CircuitState, PendingRequest, ProgressFuture, RpcManager, ScopedRpcManager};
 pub use rpc::{create_rpc_request, create_rpc_response, generate_request_id};
 
 // Re-export type registry
-pub use type_registry::{TypeRegistration, TypeRegistry, GLOBAL_TYPE_REGISTRY};
+pub use type_registry::{
+    TypeInfo, TypeRegistration, TypeRegistry, UnknownOrKnown, GLOBAL_TYPE_REGISTRY,
+};
+
+// Re-export message validation
+pub use validator::MessageValidator;
 
 /// Prelude module for convenient imports
 pub mod prelude {
@@ -120,14 +190,17 @@ pub mod prelude {
     pub use crate::zdata::{ZData, ZDataConversion};
 
     #[cfg(feature = "tokio")]
-    pub use crate::rpc::RpcManager;
+    pub use crate::rpc::{CircuitState, PendingRequest, RpcManager};
     pub use crate::rpc::{create_rpc_request, create_rpc_response, generate_request_id};
 
     #[cfg(feature = "ndarray")]
     pub use crate::builtin_types::NumpyArray;
 
     #[cfg(feature = "image")]
-    pub use crate::builtin_types::ImageData;
+    pub use crate::builtin_types::{ImageData, ImageEncodeOptions};
+
+    #[cfg(feature = "video")]
+    pub use crate::builtin_types::VideoFrame;
 }
 
 #[cfg(test)]
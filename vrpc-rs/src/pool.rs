@@ -0,0 +1,180 @@
+//! Recycling of message allocations
+//!
+//! Author: Ge Yang
+
+use crate::types::Message;
+use crossbeam_queue::ArrayQueue;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+/// A pool of reusable messages for hot receive loops
+///
+/// [`MessagePool::acquire`] hands out a recycled message, or a new one if the
+/// pool is empty. Dropping the [`PooledMessage`] resets it and returns it to
+/// the pool, unless the pool already holds `capacity` messages. The pool is
+/// cheap to clone and can be shared between threads.
+///
+/// Only the `etype` buffer survives a reset. Every other field is optional
+/// and must read as unset once recycled, so payloads, `args`, `kwargs` and
+/// `metadata` are freed rather than kept for the next message.
+///
+/// # Example
+///
+/// ```rust
+/// use vuer_rpc::pool::MessagePool;
+///
+/// let pool = MessagePool::new(64);
+/// {
+///     let mut msg = pool.acquire();
+///     msg.etype.push_str("CLICK");
+/// }
+/// assert_eq!(pool.available(), 1);
+/// assert!(pool.acquire().etype.is_empty());
+/// ```
+#[derive(Clone)]
+pub struct MessagePool {
+    queue: Arc<ArrayQueue<Message>>,
+}
+
+impl MessagePool {
+    /// Create a pool keeping up to `capacity` idle messages (at least one)
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            queue: Arc::new(ArrayQueue::new(capacity.max(1))),
+        }
+    }
+
+    /// Take a message from the pool, allocating one if none is idle
+    ///
+    /// The message is empty, as by `Message::default`.
+    pub fn acquire(&self) -> PooledMessage<'_> {
+        PooledMessage {
+            message: Some(self.queue.pop().unwrap_or_default()),
+            queue: &self.queue,
+        }
+    }
+
+    /// Return a message to its pool; the same as dropping it
+    pub fn release(msg: PooledMessage<'_>) {
+        drop(msg);
+    }
+
+    /// Number of idle messages
+    pub fn available(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Maximum number of idle messages
+    pub fn capacity(&self) -> usize {
+        self.queue.capacity()
+    }
+}
+
+/// A message borrowed from a [`MessagePool`], returned to it on drop
+pub struct PooledMessage<'a> {
+    message: Option<Message>,
+    queue: &'a ArrayQueue<Message>,
+}
+
+impl PooledMessage<'_> {
+    /// Take the message out of the pool for good
+    pub fn into_inner(mut self) -> Message {
+        self.message.take().unwrap_or_default()
+    }
+}
+
+impl Deref for PooledMessage<'_> {
+    type Target = Message;
+
+    fn deref(&self) -> &Message {
+        self.message.as_ref().expect("pooled message is present until dropped")
+    }
+}
+
+impl DerefMut for PooledMessage<'_> {
+    fn deref_mut(&mut self) -> &mut Message {
+        self.message.as_mut().expect("pooled message is present until dropped")
+    }
+}
+
+impl Drop for PooledMessage<'_> {
+    fn drop(&mut self) {
+        if let Some(mut message) = self.message.take() {
+            reset(&mut message);
+            // A full pool lets the message be freed
+            let _ = self.queue.push(message);
+        }
+    }
+}
+
+/// Reset every field to its default, keeping the `etype` buffer for the
+/// next use
+fn reset(message: &mut Message) {
+    let mut etype = std::mem::take(&mut message.etype);
+    etype.clear();
+    *message = Message {
+        etype,
+        ..Default::default()
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_reuses_and_resets() {
+        let pool = MessagePool::new(2);
+        assert_eq!(pool.capacity(), 2);
+        assert_eq!(pool.available(), 0);
+
+        let mut msg = pool.acquire();
+        msg.etype.push_str("UPDATE_SCENE");
+        msg.ts = 42;
+        msg.data = Some(json!({"x": 1}));
+        msg.correlation_id = Some("req-1".to_string());
+        let buffer = msg.etype.as_ptr();
+        MessagePool::release(msg);
+        assert_eq!(pool.available(), 1);
+
+        let reused = pool.acquire();
+        assert_eq!(pool.available(), 0);
+        assert_eq!(*reused, Message::default());
+        assert_eq!(reused.etype.as_ptr(), buffer);
+    }
+
+    #[test]
+    fn test_capacity_bound() {
+        let pool = MessagePool::new(1);
+        let a = pool.acquire();
+        let b = pool.acquire();
+        drop(a);
+        drop(b);
+        assert_eq!(pool.available(), 1);
+
+        let kept = pool.acquire().into_inner();
+        assert!(kept.etype.is_empty());
+        assert_eq!(pool.available(), 0);
+    }
+
+    #[test]
+    fn test_shared_between_threads() {
+        let pool = MessagePool::new(8);
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let pool = pool.clone();
+                std::thread::spawn(move || {
+                    for i in 0..100 {
+                        let mut msg = pool.acquire();
+                        msg.ts = i;
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert!(pool.available() <= 4);
+    }
+}
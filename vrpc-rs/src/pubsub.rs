@@ -0,0 +1,231 @@
+//! Publish-subscribe broadcasting of VMP messages
+//!
+//! Author: Ge Yang
+//!
+//! Subscribers pick messages by etype pattern, with the same `*` globs as
+//! [`crate::router::MessageRouter`]. Each subscriber buffers a bounded
+//! number of messages; see [`PubSubBus`] for what happens to a slow one.
+
+use crate::router::etype_matches;
+use crate::types::Message;
+use futures::Stream;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::sync::mpsc;
+
+/// Default for [`PubSubBus::with_capacity`]
+pub const DEFAULT_SUBSCRIBER_CAPACITY: usize = 1024;
+
+struct Subscriber {
+    id: u64,
+    pattern: String,
+    sender: mpsc::Sender<Message>,
+    dropped: Arc<AtomicU64>,
+}
+
+struct BusInner {
+    subscribers: Mutex<Vec<Subscriber>>,
+    next_id: AtomicU64,
+    capacity: usize,
+}
+
+/// In-process bus delivering published messages to every subscriber whose
+/// pattern matches
+///
+/// Clones share the same subscribers, so a bus can be handed to several
+/// tasks.
+///
+/// Publishing never blocks: each subscriber buffers at most `capacity`
+/// unread messages, and a message arriving while its buffer is full is
+/// dropped for that subscriber only. [`Subscription::dropped`] counts the
+/// messages a subscriber lost this way.
+#[derive(Clone)]
+pub struct PubSubBus {
+    inner: Arc<BusInner>,
+}
+
+impl Default for PubSubBus {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_SUBSCRIBER_CAPACITY)
+    }
+}
+
+impl PubSubBus {
+    /// Create a bus with no subscribers
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a bus buffering at most `capacity` unread messages per
+    /// subscriber
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn with_capacity(capacity: usize) -> Self {
+        assert!(capacity > 0, "PubSubBus capacity must be positive");
+        Self {
+            inner: Arc::new(BusInner {
+                subscribers: Mutex::new(Vec::new()),
+                next_id: AtomicU64::new(0),
+                capacity,
+            }),
+        }
+    }
+
+    /// Receive every message published from now on whose etype matches
+    /// `pattern`
+    ///
+    /// The subscription is removed when the returned stream is dropped.
+    pub fn subscribe(&self, pattern: &str) -> Subscription {
+        let (sender, receiver) = mpsc::channel(self.inner.capacity);
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        let dropped = Arc::new(AtomicU64::new(0));
+        self.inner.subscribers.lock().unwrap().push(Subscriber {
+            id,
+            pattern: pattern.to_string(),
+            sender,
+            dropped: dropped.clone(),
+        });
+        Subscription {
+            id,
+            receiver,
+            dropped,
+            bus: Arc::downgrade(&self.inner),
+        }
+    }
+
+    /// Send `msg` to every matching subscriber, returning how many received
+    /// it
+    ///
+    /// Subscribers whose buffer is full miss the message and are not
+    /// counted.
+    pub fn publish(&self, msg: Message) -> usize {
+        let subscribers = self.inner.subscribers.lock().unwrap();
+        subscribers
+            .iter()
+            .filter(|sub| etype_matches(&sub.pattern, &msg.etype))
+            .filter(|sub| match sub.sender.try_send(msg.clone()) {
+                Ok(()) => true,
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    sub.dropped.fetch_add(1, Ordering::Relaxed);
+                    false
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => false,
+            })
+            .count()
+    }
+
+    /// Number of live subscriptions
+    pub fn subscriber_count(&self) -> usize {
+        self.inner.subscribers.lock().unwrap().len()
+    }
+}
+
+/// Stream of the messages published to a [`PubSubBus`] that match one
+/// pattern
+///
+/// Ends once every clone of the bus has been dropped and the buffered
+/// messages have been read.
+pub struct Subscription {
+    id: u64,
+    receiver: mpsc::Receiver<Message>,
+    dropped: Arc<AtomicU64>,
+    bus: std::sync::Weak<BusInner>,
+}
+
+impl Subscription {
+    /// Number of matching messages dropped because this subscription's
+    /// buffer was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Stream for Subscription {
+    type Item = Message;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Message>> {
+        self.get_mut().receiver.poll_recv(cx)
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if let Some(bus) = self.bus.upgrade() {
+            bus.subscribers.lock().unwrap().retain(|sub| sub.id != self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn test_subscribers_receive_matching_messages() {
+        let bus = PubSubBus::new();
+        let camera = bus.subscribe("CAMERA:*");
+        let clicks = bus.subscribe("CLICK");
+        let everything = bus.subscribe("*");
+
+        let publisher = bus.clone();
+        let counts = tokio::spawn(async move {
+            ["CAMERA:MOVE", "CLICK", "CAMERA:ZOOM", "KEY", "CLICK"]
+                .into_iter()
+                .map(|etype| publisher.publish(Message::new(etype)))
+                .collect::<Vec<_>>()
+        })
+        .await
+        .unwrap();
+        assert_eq!(counts, [2, 2, 2, 1, 2]);
+
+        // Dropping the last bus handle ends the streams after the backlog
+        drop(bus);
+        let etypes = |sub: Subscription| sub.map(|msg| msg.etype).collect::<Vec<_>>();
+        assert_eq!(etypes(camera).await, ["CAMERA:MOVE", "CAMERA:ZOOM"]);
+        assert_eq!(etypes(clicks).await, ["CLICK", "CLICK"]);
+        assert_eq!(etypes(everything).await.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_subscription_is_removed() {
+        let bus = PubSubBus::new();
+        let kept = bus.subscribe("*");
+        let dropped = bus.subscribe("*");
+        assert_eq!(bus.subscriber_count(), 2);
+
+        drop(dropped);
+        assert_eq!(bus.subscriber_count(), 1);
+        assert_eq!(bus.publish(Message::new("PING")), 1);
+        drop(kept);
+        assert_eq!(bus.publish(Message::new("PING")), 0);
+    }
+
+    #[tokio::test]
+    async fn test_slow_subscriber_drops_newest() {
+        let bus = PubSubBus::with_capacity(2);
+        let mut slow = bus.subscribe("*");
+        let mut fast = bus.subscribe("*");
+
+        let mut counts = Vec::new();
+        for i in 0..5 {
+            counts.push(bus.publish(Message::new(format!("E{}", i))));
+            if i < 4 {
+                assert_eq!(fast.next().await.unwrap().etype, format!("E{}", i));
+            }
+        }
+        assert_eq!(counts, [2, 2, 1, 1, 1]);
+        assert_eq!(slow.dropped(), 3);
+        assert_eq!(fast.dropped(), 0);
+
+        // The oldest messages are kept, and delivery resumes once read
+        assert_eq!(slow.next().await.unwrap().etype, "E0");
+        assert_eq!(slow.next().await.unwrap().etype, "E1");
+        assert_eq!(bus.publish(Message::new("E5")), 2);
+        assert_eq!(slow.next().await.unwrap().etype, "E5");
+    }
+}
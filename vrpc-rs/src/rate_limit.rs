@@ -0,0 +1,272 @@
+//! Per-etype rate limiting of incoming messages
+//!
+//! Author: Ge Yang
+
+use crate::error::{Result, VmpError};
+use crate::types::Message;
+use dashmap::DashMap;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Default for [`MessageRateLimiter::with_max_buckets`]
+pub const DEFAULT_MAX_BUCKETS: usize = 1024;
+
+/// Error message returned by [`MessageRateLimiter::check`]
+const RATE_LIMIT_EXCEEDED: &str = "rate limit exceeded";
+
+/// A token bucket holding up to `burst` tokens, refilled at `rate` per second
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn full(burst: usize, now: Instant) -> Self {
+        Self {
+            tokens: burst as f64,
+            last_refill: now,
+        }
+    }
+
+    /// Refill for the time since the last call, then take a token if one
+    /// is left
+    fn take(&mut self, rate: f64, burst: usize, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(burst as f64);
+        self.last_refill = now;
+
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+/// Panic unless `rate` is a finite, non-negative number of messages per
+/// second
+fn check_rate(rate: f64) {
+    assert!(
+        rate.is_finite() && rate >= 0.0,
+        "rate must be finite and non-negative, got {}",
+        rate
+    );
+}
+
+/// Limits how many messages of each etype are accepted, with a token bucket
+/// per etype
+///
+/// Each etype may send up to `burst` messages at once, then `rate` messages
+/// per second. Buckets start full and are created on first use, so
+/// [`MessageRateLimiter::check`] can be called from many tasks at once.
+///
+/// Etypes come from the peer, so the number of buckets is capped. Once
+/// `max_buckets` etypes have their own bucket, every further etype without
+/// a [`MessageRateLimiter::set_rate`] override shares one overflow bucket
+/// at the default rate. A client cycling through made-up etypes is then
+/// limited as one sender.
+///
+/// # Example
+///
+/// ```rust
+/// use vuer_rpc::{Message, MessageRateLimiter};
+///
+/// let mut limiter = MessageRateLimiter::new(10.0, 20);
+/// limiter.set_rate("CAMERA_MOVE", 60.0, 5);
+///
+/// let msg = Message::new("CAMERA_MOVE");
+/// let accepted = (0..10).filter(|_| limiter.check(&msg).is_ok()).count();
+/// assert_eq!(accepted, 5);
+/// ```
+pub struct MessageRateLimiter {
+    default_rate: f64,
+    default_burst: usize,
+    overrides: HashMap<String, (f64, usize)>,
+    buckets: DashMap<String, TokenBucket>,
+    max_buckets: usize,
+    overflow: Mutex<TokenBucket>,
+}
+
+impl MessageRateLimiter {
+    /// Create a limiter allowing `default_rate` messages per second per
+    /// etype, with bursts of up to `burst`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `default_rate` is negative, infinite or NaN.
+    pub fn new(default_rate: f64, burst: usize) -> Self {
+        check_rate(default_rate);
+        Self {
+            default_rate,
+            default_burst: burst,
+            overrides: HashMap::new(),
+            buckets: DashMap::new(),
+            max_buckets: DEFAULT_MAX_BUCKETS,
+            overflow: Mutex::new(TokenBucket::full(burst, Instant::now())),
+        }
+    }
+
+    /// Give at most `max_buckets` etypes without an override a bucket of
+    /// their own (default [`DEFAULT_MAX_BUCKETS`])
+    pub fn with_max_buckets(mut self, max_buckets: usize) -> Self {
+        self.max_buckets = max_buckets;
+        self
+    }
+
+    /// Use a different rate and burst for one etype
+    ///
+    /// The etype's bucket starts over, full. Etypes with an override always
+    /// get their own bucket.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is negative, infinite or NaN.
+    pub fn set_rate(&mut self, etype: &str, rate: f64, burst: usize) -> &mut Self {
+        check_rate(rate);
+        self.overrides.insert(etype.to_string(), (rate, burst));
+        self.buckets.remove(etype);
+        self
+    }
+
+    /// Take a token for the message's etype
+    ///
+    /// Fails with `VmpError::RpcError("rate limit exceeded")` if the etype's
+    /// bucket is empty, in which case the message should be dropped.
+    pub fn check(&self, msg: &Message) -> Result<()> {
+        let (rate, burst) = self.limits(&msg.etype);
+        let now = Instant::now();
+
+        let taken = if let Some(mut bucket) = self.buckets.get_mut(&msg.etype) {
+            bucket.take(rate, burst, now)
+        } else if self.buckets.len() < self.max_buckets
+            || self.overrides.contains_key(&msg.etype)
+        {
+            let mut bucket = self
+                .buckets
+                .entry(msg.etype.clone())
+                .or_insert_with(|| TokenBucket::full(burst, now));
+            bucket.take(rate, burst, now)
+        } else {
+            let mut overflow = self.overflow.lock().unwrap();
+            overflow.take(self.default_rate, self.default_burst, now)
+        };
+
+        if !taken {
+            return Err(VmpError::RpcError(RATE_LIMIT_EXCEEDED.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Number of etypes with a bucket of their own
+    pub fn bucket_count(&self) -> usize {
+        self.buckets.len()
+    }
+
+    /// The rate and burst that apply to `etype`
+    fn limits(&self, etype: &str) -> (f64, usize) {
+        self.overrides
+            .get(etype)
+            .copied()
+            .unwrap_or((self.default_rate, self.default_burst))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_burst_then_rejected() {
+        let limiter = MessageRateLimiter::new(1.0, 10);
+        let msg = Message::new("CLICK");
+
+        let results: Vec<_> = (0..200).map(|_| limiter.check(&msg)).collect();
+        assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 10);
+        assert!(results[..10].iter().all(Result::is_ok));
+        assert!(matches!(&results[10], Err(VmpError::RpcError(m)) if m == "rate limit exceeded"));
+
+        // Other etypes have their own bucket
+        assert!(limiter.check(&Message::new("KEY_DOWN")).is_ok());
+    }
+
+    #[test]
+    fn test_refill() {
+        let limiter = MessageRateLimiter::new(100.0, 1);
+        let msg = Message::new("CAMERA_MOVE");
+        assert!(limiter.check(&msg).is_ok());
+        assert!(limiter.check(&msg).is_err());
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(limiter.check(&msg).is_ok());
+    }
+
+    #[test]
+    fn test_per_etype_override() {
+        let mut limiter = MessageRateLimiter::new(1.0, 2);
+        limiter.set_rate("RENDER", 1.0, 5).set_rate("PING", 0.0, 0);
+
+        let count = |etype: &str| {
+            let msg = Message::new(etype);
+            (0..20).filter(|_| limiter.check(&msg).is_ok()).count()
+        };
+        assert_eq!(count("RENDER"), 5);
+        assert_eq!(count("PING"), 0);
+        assert_eq!(count("CLICK"), 2);
+    }
+
+    #[test]
+    fn test_random_etype_flood() {
+        let limiter = MessageRateLimiter::new(0.0, 3).with_max_buckets(16);
+        let accepted = (0..10_000)
+            .map(|_| Message::new(uuid::Uuid::new_v4().to_string()))
+            .filter(|msg| limiter.check(msg).is_ok())
+            .count();
+
+        // One message for each of the 16 own buckets, then the burst of the
+        // bucket shared by the rest
+        assert_eq!(limiter.bucket_count(), 16);
+        assert_eq!(accepted, 16 + 3);
+
+        // Known etypes keep their buckets, and overrides still get one
+        let mut limiter = MessageRateLimiter::new(0.0, 3).with_max_buckets(0);
+        limiter.set_rate("RENDER", 0.0, 5);
+        let render = Message::new("RENDER");
+        assert_eq!((0..10).filter(|_| limiter.check(&render).is_ok()).count(), 5);
+        assert!(limiter.check(&Message::new("CLICK")).is_ok());
+        assert_eq!(limiter.bucket_count(), 1);
+    }
+
+    #[test]
+    fn test_invalid_rates_rejected() {
+        for rate in [-1.0, f64::NAN, f64::INFINITY] {
+            assert!(std::panic::catch_unwind(|| MessageRateLimiter::new(rate, 1)).is_err());
+            let result = std::panic::catch_unwind(|| {
+                MessageRateLimiter::new(1.0, 1).set_rate("CLICK", rate, 1);
+            });
+            assert!(result.is_err());
+        }
+        MessageRateLimiter::new(0.0, 1).set_rate("CLICK", 0.0, 0);
+    }
+
+    #[test]
+    fn test_concurrent_checks() {
+        let limiter = MessageRateLimiter::new(0.0, 50);
+        let msg = Message::new("CLICK");
+        let accepted = std::sync::atomic::AtomicUsize::new(0);
+
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..100 {
+                        if limiter.check(&msg).is_ok() {
+                            accepted.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(accepted.into_inner(), 50);
+    }
+}
@@ -0,0 +1,224 @@
+//! Dispatch of incoming messages to handlers by etype
+//!
+//! Author: Ge Yang
+//!
+//! Patterns are matched against the whole etype, with `*` standing for any
+//! run of characters: `"CAMERA:*"` matches every etype starting with
+//! `"CAMERA:"`, and `"*"` matches everything.
+
+use crate::error::Result;
+use crate::types::Message;
+
+type Handler = Box<dyn Fn(&Message) -> Result<()> + Send + Sync>;
+
+/// Whether `etype` matches the glob `pattern`
+pub fn etype_matches(pattern: &str, etype: &str) -> bool {
+    let mut parts = pattern.split('*');
+    // `split` always yields at least one part
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = etype.strip_prefix(first) else {
+        return false;
+    };
+
+    let mut parts: Vec<&str> = parts.collect();
+    let Some(last) = parts.pop() else {
+        // No `*` in the pattern
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// Calls the handlers registered for a message's etype
+///
+/// ```
+/// use vuer_rpc::router::MessageRouter;
+/// use vuer_rpc::Message;
+///
+/// let mut router = MessageRouter::new();
+/// router
+///     .on("CAMERA:*", |msg| Ok(println!("camera event {}", msg.etype)))
+///     .on("*", |_| Ok(()));
+/// assert!(router.route(&Message::new("CAMERA:MOVE")).unwrap());
+/// ```
+#[derive(Default)]
+pub struct MessageRouter {
+    routes: Vec<(String, Handler)>,
+}
+
+impl MessageRouter {
+    /// Create a router with no handlers
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `handler` for etypes matching `pattern`
+    pub fn on(
+        &mut self,
+        pattern: &str,
+        handler: impl Fn(&Message) -> Result<()> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.routes.push((pattern.to_string(), Box::new(handler)));
+        self
+    }
+
+    /// Call every handler whose pattern matches, in registration order
+    ///
+    /// Returns whether any handler matched. Stops at the first handler that
+    /// fails and returns its error.
+    pub fn route(&self, msg: &Message) -> Result<bool> {
+        let mut matched = false;
+        for (pattern, handler) in &self.routes {
+            if etype_matches(pattern, &msg.etype) {
+                handler(msg)?;
+                matched = true;
+            }
+        }
+        Ok(matched)
+    }
+}
+
+#[cfg(feature = "tokio")]
+type AsyncHandler =
+    Box<dyn Fn(Message) -> futures::future::BoxFuture<'static, Result<()>> + Send + Sync>;
+
+/// [`MessageRouter`] for async handlers
+///
+/// Each matching handler receives its own copy of the message.
+#[cfg(feature = "tokio")]
+#[derive(Default)]
+pub struct AsyncMessageRouter {
+    routes: Vec<(String, AsyncHandler)>,
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncMessageRouter {
+    /// Create a router with no handlers
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `handler` for etypes matching `pattern`
+    pub fn on<F, Fut>(&mut self, pattern: &str, handler: F) -> &mut Self
+    where
+        F: Fn(Message) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = Result<()>> + Send + 'static,
+    {
+        use futures::FutureExt;
+
+        let handler: AsyncHandler = Box::new(move |msg| handler(msg).boxed());
+        self.routes.push((pattern.to_string(), handler));
+        self
+    }
+
+    /// Await every handler whose pattern matches, one after another in
+    /// registration order
+    ///
+    /// Returns whether any handler matched. Stops at the first handler that
+    /// fails and returns its error.
+    pub async fn route(&self, msg: &Message) -> Result<bool> {
+        let mut matched = false;
+        for (pattern, handler) in &self.routes {
+            if etype_matches(pattern, &msg.etype) {
+                handler(msg.clone()).await?;
+                matched = true;
+            }
+        }
+        Ok(matched)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::VmpError;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_etype_matches() {
+        assert!(etype_matches("CLICK", "CLICK"));
+        assert!(!etype_matches("CLICK", "CLICKED"));
+        assert!(!etype_matches("CLICK", "DOUBLE_CLICK"));
+
+        assert!(etype_matches("CAMERA:*", "CAMERA:MOVE"));
+        assert!(etype_matches("CAMERA:*", "CAMERA:"));
+        assert!(!etype_matches("CAMERA:*", "CAMERA"));
+
+        assert!(etype_matches("*", ""));
+        assert!(etype_matches("*", "ANYTHING"));
+        assert!(etype_matches("*_END", "DRAG_END"));
+        assert!(etype_matches("A*B*C", "A-B-B-C"));
+        assert!(!etype_matches("A*B*C", "A-C-B"));
+        assert!(!etype_matches("AB*BA", "ABA"));
+    }
+
+    #[test]
+    fn test_route_calls_all_matching_handlers() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let record = |name: &'static str| {
+            let calls = calls.clone();
+            move |msg: &Message| {
+                calls.lock().unwrap().push(format!("{}:{}", name, msg.etype));
+                Ok(())
+            }
+        };
+
+        let mut router = MessageRouter::new();
+        router
+            .on("CAMERA:MOVE", record("exact"))
+            .on("CAMERA:*", record("prefix"))
+            .on("*", record("any"));
+
+        assert!(router.route(&Message::new("CAMERA:MOVE")).unwrap());
+        assert!(router.route(&Message::new("CLICK")).unwrap());
+        assert_eq!(
+            *calls.lock().unwrap(),
+            ["exact:CAMERA:MOVE", "prefix:CAMERA:MOVE", "any:CAMERA:MOVE", "any:CLICK"]
+        );
+
+        let mut router = MessageRouter::new();
+        router.on("CAMERA:*", record("prefix"));
+        assert!(!router.route(&Message::new("CLICK")).unwrap());
+    }
+
+    #[test]
+    fn test_route_stops_at_error() {
+        let later = Arc::new(Mutex::new(false));
+        let mut router = MessageRouter::new();
+        let flag = later.clone();
+        router
+            .on("*", |_| Err(VmpError::RpcError("handler failed".to_string())))
+            .on("*", move |_| {
+                *flag.lock().unwrap() = true;
+                Ok(())
+            });
+
+        assert!(matches!(router.route(&Message::new("CLICK")), Err(VmpError::RpcError(_))));
+        assert!(!*later.lock().unwrap());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_router() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut router = AsyncMessageRouter::new();
+        let record = seen.clone();
+        router.on("CAMERA:*", move |msg| {
+            let record = record.clone();
+            async move {
+                tokio::task::yield_now().await;
+                record.lock().unwrap().push(msg.etype);
+                Ok(())
+            }
+        });
+
+        assert!(router.route(&Message::new("CAMERA:ZOOM")).await.unwrap());
+        assert!(!router.route(&Message::new("CLICK")).await.unwrap());
+        assert_eq!(*seen.lock().unwrap(), ["CAMERA:ZOOM"]);
+    }
+}
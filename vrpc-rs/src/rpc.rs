@@ -10,7 +10,9 @@ use std::time::Duration;
 use uuid::Uuid;
 
 #[cfg(feature = "tokio")]
-use tokio::sync::oneshot;
+use futures::Stream;
+#[cfg(feature = "tokio")]
+use tokio::sync::{mpsc, oneshot};
 #[cfg(feature = "tokio")]
 use tokio::time::timeout;
 
@@ -36,19 +38,397 @@ pub fn create_rpc_request(
     req
 }
 
+/// Create a request sent by an `RpcManager`, carrying the current trace
+/// context when the `opentelemetry` feature is enabled
+#[cfg(feature = "tokio")]
+fn outgoing_request(
+    etype: impl Into<String>,
+    args: Option<Vec<Value>>,
+    kwargs: Option<HashMap<String, Value>>,
+) -> RpcRequest {
+    #[allow(unused_mut)]
+    let mut req = create_rpc_request(etype, args, kwargs);
+    #[cfg(feature = "opentelemetry")]
+    req.inject_trace_context();
+    req
+}
+
 /// Create an RPC response
+///
+/// A failed result is categorized with [`VmpError::kind`] and described
+/// with [`VmpError::response_message`], so the caller gets the same error
+/// back from `VmpError::from`.
 pub fn create_rpc_response(
     etype: impl Into<String>,
     result: Result<Value>,
 ) -> RpcResponse {
     match result {
         Ok(data) => RpcResponse::success(etype, data),
-        Err(e) => RpcResponse::error(etype, e.to_string()),
+        Err(e) => RpcResponse::error_with_kind(etype, e.response_message(), e.kind()),
+    }
+}
+
+#[cfg(feature = "tokio")]
+type ResponseSender = oneshot::Sender<Result<RpcResponse>>;
+
+#[cfg(feature = "tokio")]
+type StreamSender = mpsc::UnboundedSender<Result<RpcResponse>>;
+
+#[cfg(feature = "tokio")]
+type ProgressSender = mpsc::UnboundedSender<f32>;
+
+/// Error message used when a pending request is cancelled
+#[cfg(feature = "tokio")]
+const CANCELLED: &str = "Cancelled";
+
+/// Channel through which the response(s) to a pending request are delivered
+#[cfg(feature = "tokio")]
+enum PendingSender {
+    /// A plain request expecting a single response
+    Response(ResponseSender),
+    /// A streaming request expecting any number of responses
+    Stream(StreamSender),
+    /// A request expecting progress updates before a single response
+    Progress(ResponseSender, ProgressSender),
+}
+
+/// An in-flight request registered with an `RpcManager`
+///
+/// The original request is kept alongside the response channel so pending
+/// requests can be inspected and selectively cancelled.
+#[cfg(feature = "tokio")]
+pub struct PendingRequest {
+    /// The request as it was sent
+    pub request: RpcRequest,
+    sender: PendingSender,
+    /// Set for requests issued through a [`ScopedRpcManager`]
+    _scope: Option<ScopeEntry>,
+}
+
+#[cfg(feature = "tokio")]
+impl PendingRequest {
+    fn new(request: RpcRequest, sender: PendingSender, scope: Option<ScopeEntry>) -> Self {
+        Self {
+            request,
+            sender,
+            _scope: scope,
+        }
+    }
+
+    /// Whether this is a streaming request
+    pub fn is_stream(&self) -> bool {
+        matches!(self.sender, PendingSender::Stream(_))
+    }
+
+    /// Wake the waiting future or stream with a cancellation error
+    fn cancel(self) {
+        let err = VmpError::RpcError(CANCELLED.to_string());
+        // The caller may already have dropped its future
+        match self.sender {
+            PendingSender::Response(tx) | PendingSender::Progress(tx, _) => {
+                let _ = tx.send(Err(err));
+            }
+            PendingSender::Stream(tx) => {
+                let _ = tx.send(Err(err));
+            }
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+type PendingMap = std::sync::Arc<tokio::sync::Mutex<HashMap<String, PendingRequest>>>;
+
+/// A single call in a batch: (etype, args, kwargs)
+pub type BatchCall = (
+    String,
+    Option<Vec<Value>>,
+    Option<HashMap<String, Value>>,
+);
+
+/// Upper bound for the delay between two attempts of `request_with_retry`
+#[cfg(feature = "tokio")]
+pub const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// State of the circuit breaker of an `RpcManager`
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests flow normally
+    Closed,
+    /// Too many recent failures; requests are rejected immediately
+    Open,
+    /// The cooldown has elapsed; a single probe request is allowed through
+    HalfOpen,
+}
+
+/// Circuit breaker tracking the outcome of the last `window` requests
+///
+/// Only timeouts count as failures: an error response means the server is
+/// reachable, and cancellations say nothing about the link.
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+struct CircuitBreaker {
+    window: usize,
+    threshold: f64,
+    cooldown: Duration,
+    /// Recent outcomes, `true` for a failure
+    outcomes: std::collections::VecDeque<bool>,
+    state: CircuitState,
+    opened_at: Option<std::time::Instant>,
+    probe_in_flight: bool,
+}
+
+#[cfg(feature = "tokio")]
+type SharedBreaker = std::sync::Arc<std::sync::Mutex<CircuitBreaker>>;
+
+#[cfg(feature = "tokio")]
+impl CircuitBreaker {
+    fn new(window: usize, threshold: f64, cooldown: Duration) -> Self {
+        Self {
+            window: window.max(1),
+            threshold,
+            cooldown,
+            outcomes: std::collections::VecDeque::new(),
+            state: CircuitState::Closed,
+            opened_at: None,
+            probe_in_flight: false,
+        }
+    }
+
+    /// Current state, moving from Open to HalfOpen once the cooldown has elapsed
+    fn state(&mut self) -> CircuitState {
+        if self.state == CircuitState::Open
+            && self.opened_at.is_some_and(|t| t.elapsed() >= self.cooldown)
+        {
+            self.state = CircuitState::HalfOpen;
+            self.probe_in_flight = false;
+        }
+        self.state
+    }
+
+    /// Check whether a new request may be issued
+    fn try_acquire(&mut self) -> bool {
+        match self.state() {
+            CircuitState::Closed => true,
+            CircuitState::Open => false,
+            CircuitState::HalfOpen if self.probe_in_flight => false,
+            CircuitState::HalfOpen => {
+                self.probe_in_flight = true;
+                true
+            }
+        }
+    }
+
+    /// Record the outcome of a request
+    fn record(&mut self, result: &Result<RpcResponse>) {
+        let failed = match result {
+            Ok(_) => false,
+            Err(VmpError::RpcTimeout(_)) => true,
+            Err(_) => {
+                // Neither a success nor a failure; just free the probe slot
+                self.probe_in_flight = false;
+                return;
+            }
+        };
+
+        match self.state {
+            CircuitState::HalfOpen => {
+                self.probe_in_flight = false;
+                if failed {
+                    self.open();
+                } else {
+                    self.state = CircuitState::Closed;
+                    self.outcomes.clear();
+                }
+            }
+            CircuitState::Closed => {
+                self.outcomes.push_back(failed);
+                while self.outcomes.len() > self.window {
+                    self.outcomes.pop_front();
+                }
+
+                let failures = self.outcomes.iter().filter(|f| **f).count();
+                let rate = failures as f64 / self.outcomes.len() as f64;
+                if self.outcomes.len() == self.window && rate > self.threshold {
+                    self.open();
+                }
+            }
+            // Late outcomes from requests issued before the circuit opened
+            CircuitState::Open => {}
+        }
+    }
+
+    fn open(&mut self) {
+        self.state = CircuitState::Open;
+        self.opened_at = Some(std::time::Instant::now());
+        self.outcomes.clear();
+    }
+}
+
+/// Records the outcome of a request in the circuit breaker, if any
+///
+/// A request dropped before it has an outcome, such as a response future
+/// that is cancelled or never polled, frees the probe slot on drop, so a
+/// half-open circuit cannot get stuck waiting for a probe that is gone.
+#[cfg(feature = "tokio")]
+struct BreakerGuard(Option<SharedBreaker>);
+
+#[cfg(feature = "tokio")]
+impl BreakerGuard {
+    fn record(mut self, result: &Result<RpcResponse>) {
+        if let Some(breaker) = self.0.take() {
+            breaker.lock().unwrap().record(result);
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Drop for BreakerGuard {
+    fn drop(&mut self) {
+        if let Some(breaker) = self.0.take() {
+            breaker.lock().unwrap().probe_in_flight = false;
+        }
+    }
+}
+
+/// How long a request may wait for a response: its own timeout, cut short
+/// by the deadline of the [`ScopedRpcManager`] it was issued through
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone, Copy)]
+struct Wait {
+    timeout: Duration,
+    deadline: Option<std::time::Instant>,
+}
+
+#[cfg(feature = "tokio")]
+impl Wait {
+    /// Time left to wait, starting now
+    fn remaining(&self) -> Duration {
+        match self.deadline {
+            Some(deadline) => {
+                self.timeout.min(deadline.saturating_duration_since(std::time::Instant::now()))
+            }
+            None => self.timeout,
+        }
+    }
+
+    /// The error for running out of time: a cancellation once the scope
+    /// deadline has passed, a timeout otherwise
+    fn expired(&self, what: &str) -> VmpError {
+        if self.deadline.is_some_and(|deadline| std::time::Instant::now() >= deadline) {
+            VmpError::RpcError(CANCELLED.to_string())
+        } else {
+            VmpError::RpcTimeout(format!("{} timed out after {:?}", what, self.timeout))
+        }
+    }
+}
+
+/// Wait for the response to a registered request
+///
+/// The pending entry is removed if the request times out or the channel
+/// is closed without a response. The outcome is recorded in the circuit
+/// breaker, if any.
+#[cfg(feature = "tokio")]
+async fn await_response(
+    pending: PendingMap,
+    breaker: BreakerGuard,
+    rtype: String,
+    rx: oneshot::Receiver<Result<RpcResponse>>,
+    wait: Wait,
+) -> Result<RpcResponse> {
+    let result = wait_for(pending, rtype, rx, wait).await;
+    breaker.record(&result);
+    result
+}
+
+/// Wait for the response to a registered request, ignoring the circuit breaker
+#[cfg(feature = "tokio")]
+async fn wait_for(
+    pending: PendingMap,
+    rtype: String,
+    rx: oneshot::Receiver<Result<RpcResponse>>,
+    wait: Wait,
+) -> Result<RpcResponse> {
+    match timeout(wait.remaining(), rx).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(_)) => {
+            // Channel closed without response
+            let mut pending = pending.lock().await;
+            pending.remove(&rtype);
+            Err(VmpError::RpcError("Response channel closed".to_string()))
+        }
+        Err(_) => {
+            // Timeout
+            let mut pending = pending.lock().await;
+            pending.remove(&rtype);
+            Err(wait.expired("Request"))
+        }
+    }
+}
+
+/// The response to a request made with [`RpcManager::request_with_progress`],
+/// preceded by any number of progress updates
+///
+/// Both the updates and the final response must arrive within the timeout
+/// given to the request. Dropping the future before the response arrives
+/// gives up on the request and removes it from the manager.
+#[cfg(feature = "tokio")]
+pub struct ProgressFuture {
+    progress: mpsc::UnboundedReceiver<f32>,
+    response: oneshot::Receiver<Result<RpcResponse>>,
+    pending: PendingGuard,
+    breaker: BreakerGuard,
+    deadline: tokio::time::Instant,
+    /// Deadline of the scope the request was issued through
+    scope_deadline: Option<std::time::Instant>,
+}
+
+#[cfg(feature = "tokio")]
+impl ProgressFuture {
+    /// Wait for the next progress update, between 0.0 and 1.0
+    ///
+    /// Returns `None` once the final response has arrived and all earlier
+    /// updates have been read, or when the request is cancelled or times out.
+    pub async fn next_progress(&mut self) -> Option<f32> {
+        tokio::time::timeout_at(self.deadline, self.progress.recv())
+            .await
+            .ok()
+            .flatten()
+    }
+
+    /// Wait for the final response, discarding unread progress updates
+    pub async fn final_response(self) -> Result<RpcResponse> {
+        let wait = Wait {
+            timeout: self.deadline.saturating_duration_since(tokio::time::Instant::now()),
+            deadline: self.scope_deadline,
+        };
+        let (pending, rtype) = (self.pending.pending.clone(), self.pending.rtype.clone());
+        await_response(pending, self.breaker, rtype, self.response, wait).await
     }
 }
 
+/// Removes a request from the pending map when its caller stops waiting
+#[cfg(feature = "tokio")]
+struct PendingGuard {
+    pending: PendingMap,
+    rtype: String,
+}
+
 #[cfg(feature = "tokio")]
-type ResponseSender = oneshot::Sender<RpcResponse>;
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        let rtype = std::mem::take(&mut self.rtype);
+        if let Ok(mut pending) = self.pending.try_lock() {
+            pending.remove(&rtype);
+        } else if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            // The map is busy; remove the entry once it is free
+            let pending = self.pending.clone();
+            runtime.spawn(async move {
+                pending.lock().await.remove(&rtype);
+            });
+        }
+    }
+}
 
 /// RPC Manager for handling request-response correlation
 ///
@@ -57,7 +437,32 @@ type ResponseSender = oneshot::Sender<RpcResponse>;
 #[cfg(feature = "tokio")]
 #[derive(Clone)]
 pub struct RpcManager {
-    pending: std::sync::Arc<tokio::sync::Mutex<HashMap<String, ResponseSender>>>,
+    pending: PendingMap,
+    breaker: Option<SharedBreaker>,
+    /// Set on the manager inside a [`ScopedRpcManager`]
+    scope: Option<std::sync::Arc<DeadlineScope>>,
+}
+
+/// Deadline of a [`ScopedRpcManager`] and the requests issued through it
+/// that are still pending
+#[cfg(feature = "tokio")]
+struct DeadlineScope {
+    deadline: std::time::Instant,
+    issued: std::sync::Mutex<std::collections::HashSet<String>>,
+}
+
+/// Removes a request from its deadline scope once it leaves the pending map
+#[cfg(feature = "tokio")]
+struct ScopeEntry {
+    scope: std::sync::Arc<DeadlineScope>,
+    rtype: String,
+}
+
+#[cfg(feature = "tokio")]
+impl Drop for ScopeEntry {
+    fn drop(&mut self) {
+        self.scope.issued.lock().unwrap().remove(&self.rtype);
+    }
 }
 
 #[cfg(feature = "tokio")]
@@ -73,6 +478,46 @@ impl RpcManager {
     pub fn new() -> Self {
         Self {
             pending: std::sync::Arc::new(tokio::sync::Mutex::new(HashMap::new())),
+            breaker: None,
+            scope: None,
+        }
+    }
+
+    /// Create a new RPC manager with a circuit breaker
+    ///
+    /// Once more than `threshold` (a fraction between 0 and 1) of the last
+    /// `window` requests have timed out, the circuit opens and new requests
+    /// fail immediately with `VmpError::RpcError("Circuit open")`. After
+    /// `cooldown` a single probe request is let through: if it succeeds
+    /// the circuit closes again, otherwise it reopens.
+    ///
+    /// Streaming requests bypass the circuit breaker.
+    pub fn with_circuit_breaker(window: usize, threshold: f64, cooldown: Duration) -> Self {
+        Self {
+            breaker: Some(std::sync::Arc::new(std::sync::Mutex::new(CircuitBreaker::new(
+                window, threshold, cooldown,
+            )))),
+            ..Self::new()
+        }
+    }
+
+    /// Get the current state of the circuit breaker
+    ///
+    /// A manager without a circuit breaker is always `CircuitState::Closed`.
+    pub fn circuit_state(&self) -> CircuitState {
+        match &self.breaker {
+            Some(breaker) => breaker.lock().unwrap().state(),
+            None => CircuitState::Closed,
+        }
+    }
+
+    /// Fail fast if the circuit breaker does not allow a new request
+    fn check_circuit(&self) -> Result<()> {
+        match &self.breaker {
+            Some(breaker) if !breaker.lock().unwrap().try_acquire() => {
+                Err(VmpError::RpcError("Circuit open".to_string()))
+            }
+            _ => Ok(()),
         }
     }
 
@@ -99,39 +544,17 @@ impl RpcManager {
         kwargs: Option<HashMap<String, Value>>,
         timeout_duration: Duration,
     ) -> Result<(RpcRequest, impl std::future::Future<Output = Result<RpcResponse>>)> {
-        let req = create_rpc_request(etype, args, kwargs);
-        let rtype = req.rtype.clone();
+        self.check_circuit()?;
 
-        let (tx, rx) = oneshot::channel();
-
-        // Register the pending request
-        {
-            let mut pending = self.pending.lock().await;
-            pending.insert(rtype.clone(), tx);
-        }
-
-        // Create a future that will resolve when the response is received
-        let pending = self.pending.clone();
-        let response_future = async move {
-            match timeout(timeout_duration, rx).await {
-                Ok(Ok(response)) => Ok(response),
-                Ok(Err(_)) => {
-                    // Channel closed without response
-                    let mut pending = pending.lock().await;
-                    pending.remove(&rtype);
-                    Err(VmpError::RpcError("Response channel closed".to_string()))
-                }
-                Err(_) => {
-                    // Timeout
-                    let mut pending = pending.lock().await;
-                    pending.remove(&rtype);
-                    Err(VmpError::RpcTimeout(format!(
-                        "Request timed out after {:?}",
-                        timeout_duration
-                    )))
-                }
-            }
-        };
+        let req = outgoing_request(etype, args, kwargs);
+        let rx = self.register(&req).await;
+        let response_future = await_response(
+            self.pending.clone(),
+            BreakerGuard(self.breaker.clone()),
+            req.rtype.clone(),
+            rx,
+            self.wait(timeout_duration),
+        );
 
         Ok((req, response_future))
     }
@@ -143,66 +566,583 @@ impl RpcManager {
     pub async fn handle_response(&self, response: RpcResponse) -> Result<()> {
         let mut pending = self.pending.lock().await;
 
-        if let Some(sender) = pending.remove(&response.etype) {
-            sender
-                .send(response)
-                .map_err(|_| VmpError::RpcError("Failed to send response".to_string()))?;
-            Ok(())
-        } else {
-            Err(VmpError::RpcError(format!(
+        match pending.remove(&response.etype) {
+            Some(PendingRequest {
+                sender: PendingSender::Response(sender) | PendingSender::Progress(sender, _),
+                ..
+            }) => {
+                sender
+                    .send(Ok(response))
+                    .map_err(|_| VmpError::RpcError("Failed to send response".to_string()))?;
+                Ok(())
+            }
+            Some(stream) => {
+                let rtype = response.etype.clone();
+                pending.insert(rtype.clone(), stream);
+                Err(VmpError::RpcError(format!(
+                    "Pending request {} is a stream; use handle_streaming_response",
+                    rtype
+                )))
+            }
+            None => Err(VmpError::RpcError(format!(
                 "No pending request for response type: {}",
                 response.etype
-            )))
+            ))),
         }
     }
 
-    /// Cancel a pending request
-    pub async fn cancel(&self, rtype: &str) -> bool {
-        let mut pending = self.pending.lock().await;
-        pending.remove(rtype).is_some()
+    /// Send an RPC request whose handler reports progress before responding
+    ///
+    /// Progress updates pushed with [`RpcManager::handle_progress`] are read
+    /// with [`ProgressFuture::next_progress`]; the response, delivered by
+    /// [`RpcManager::handle_response`] as usual, with
+    /// [`ProgressFuture::final_response`]. `timeout_duration` bounds the
+    /// whole exchange.
+    pub async fn request_with_progress(
+        &self,
+        etype: impl Into<String>,
+        args: Option<Vec<Value>>,
+        kwargs: Option<HashMap<String, Value>>,
+        timeout_duration: Duration,
+    ) -> Result<(RpcRequest, ProgressFuture)> {
+        self.check_circuit()?;
+
+        let req = outgoing_request(etype, args, kwargs);
+        let (response_tx, response_rx) = oneshot::channel();
+        let (progress_tx, progress_rx) = mpsc::unbounded_channel();
+        {
+            let mut pending = self.pending.lock().await;
+            let sender = PendingSender::Progress(response_tx, progress_tx);
+            self.add_pending(&mut pending, &req, sender);
+        }
+
+        let wait = self.wait(timeout_duration);
+        let future = ProgressFuture {
+            progress: progress_rx,
+            response: response_rx,
+            pending: PendingGuard {
+                pending: self.pending.clone(),
+                rtype: req.rtype.clone(),
+            },
+            breaker: BreakerGuard(self.breaker.clone()),
+            deadline: tokio::time::Instant::now() + wait.remaining(),
+            scope_deadline: wait.deadline,
+        };
+        Ok((req, future))
     }
 
-    /// Get the number of pending requests
-    pub async fn pending_count(&self) -> usize {
+    /// Report the progress of a request made with
+    /// [`RpcManager::request_with_progress`]
+    ///
+    /// `progress` is clamped to `[0.0, 1.0]`; NaN is rejected.
+    pub async fn handle_progress(&self, rtype: &str, progress: f32) -> Result<()> {
+        if progress.is_nan() {
+            return Err(VmpError::RpcError("Progress must be a number".to_string()));
+        }
+
         let pending = self.pending.lock().await;
-        pending.len()
+        match pending.get(rtype) {
+            Some(PendingRequest {
+                sender: PendingSender::Progress(_, sender),
+                ..
+            }) => sender
+                .send(progress.clamp(0.0, 1.0))
+                // The caller dropped its ProgressFuture
+                .map_err(|_| VmpError::RpcError("Failed to send progress".to_string())),
+            _ => Err(VmpError::RpcError(format!(
+                "No pending progress request for response type: {}",
+                rtype
+            ))),
+        }
     }
 
-    /// Clear all pending requests
-    pub async fn clear(&self) {
-        let mut pending = self.pending.lock().await;
-        pending.clear();
-    }
-}
+    /// Send several RPC requests at once and collect all responses
+    ///
+    /// All requests are created and registered before this returns, so
+    /// responses can arrive in any order. The returned future resolves
+    /// once every response has arrived or `timeout_duration` has expired.
+    ///
+    /// # Arguments
+    ///
+    /// * `requests` - The calls to make, as (etype, args, kwargs) tuples
+    /// * `timeout_duration` - Maximum time to wait for the whole batch
+    ///
+    /// # Returns
+    ///
+    /// A tuple of (Vec<RpcRequest>, Future<Vec<Result<RpcResponse>>>). The
+    /// results are in the same order as the input requests; a request that
+    /// times out or receives an error response yields an `Err` without
+    /// affecting the others.
+    pub async fn batch_request(
+        &self,
+        requests: Vec<BatchCall>,
+        timeout_duration: Duration,
+    ) -> Result<(
+        Vec<RpcRequest>,
+        impl std::future::Future<Output = Vec<Result<RpcResponse>>>,
+    )> {
+        self.check_circuit()?;
 
-#[cfg(test)]
-#[cfg(feature = "tokio")]
-mod tests {
-    use super::*;
-    use serde_json::json;
+        let reqs: Vec<RpcRequest> = requests
+            .into_iter()
+            .map(|(etype, args, kwargs)| outgoing_request(etype, args, kwargs))
+            .collect();
 
-    #[tokio::test]
-    async fn test_generate_request_id() {
-        let id1 = generate_request_id();
-        let id2 = generate_request_id();
+        // Register all pending requests before any of them can be answered
+        let mut receivers = Vec::with_capacity(reqs.len());
+        {
+            let mut pending = self.pending.lock().await;
+            for req in &reqs {
+                let (tx, rx) = oneshot::channel();
+                self.add_pending(&mut pending, req, PendingSender::Response(tx));
+                receivers.push((req.rtype.clone(), rx));
+            }
+        }
 
-        assert!(id1.starts_with("rpc-"));
-        assert_ne!(id1, id2);
+        let pending = self.pending.clone();
+        let breaker = self.breaker.clone();
+        let wait = self.wait(timeout_duration);
+        let responses = futures::future::join_all(receivers.into_iter().map(|(rtype, rx)| {
+            let pending = pending.clone();
+            let breaker = BreakerGuard(breaker.clone());
+            async move {
+                let response = await_response(pending, breaker, rtype, rx, wait).await?;
+                if response.ok == Some(false) {
+                    return Err(response.into());
+                }
+                Ok(response)
+            }
+        }));
+
+        Ok((reqs, responses))
     }
 
-    #[tokio::test]
-    async fn test_create_rpc_request() {
-        let mut kwargs = HashMap::new();
-        kwargs.insert("seed".to_string(), json!(100));
+    /// Send an RPC request, retrying with exponential backoff on timeout
+    ///
+    /// The same request (and `rtype`) is handed to `send` on every attempt,
+    /// so a late response to an earlier attempt still completes the call.
+    /// Only `VmpError::RpcTimeout` triggers a retry; error responses from
+    /// the server and transport errors from `send` are returned as-is.
+    ///
+    /// # Arguments
+    ///
+    /// * `etype` - The event type (method name)
+    /// * `args` - Optional positional arguments
+    /// * `kwargs` - Optional keyword arguments
+    /// * `timeout_duration` - Maximum time to wait for each attempt
+    /// * `max_attempts` - Total number of attempts (at least one is made)
+    /// * `base_delay` - Delay before the first retry, doubled after each one
+    ///   and capped at `MAX_RETRY_DELAY`
+    /// * `send` - Transmits the request over the network
+    ///
+    /// # Returns
+    ///
+    /// The response, with `attempt` set to the 1-based attempt that succeeded
+    #[allow(clippy::too_many_arguments)]
+    pub async fn request_with_retry<F>(
+        &self,
+        etype: impl Into<String>,
+        args: Option<Vec<Value>>,
+        kwargs: Option<HashMap<String, Value>>,
+        timeout_duration: Duration,
+        max_attempts: usize,
+        base_delay: Duration,
+        mut send: F,
+    ) -> Result<RpcResponse>
+    where
+        F: FnMut(&RpcRequest) -> Result<()>,
+    {
+        let req = outgoing_request(etype, args, kwargs);
+        let max_attempts = max_attempts.max(1);
+        let mut delay = base_delay;
+        let mut attempt = 1;
 
-        let req = create_rpc_request("render", None, Some(kwargs));
+        loop {
+            self.check_circuit()?;
+            let rx = self.register(&req).await;
 
-        assert_eq!(req.etype, "render");
-        assert!(req.rtype.starts_with("rpc-"));
-        assert!(req.kwargs.is_some());
-    }
+            if let Err(e) = send(&req) {
+                self.pending.lock().await.remove(&req.rtype);
+                return Err(e);
+            }
 
-    #[tokio::test]
+            let result = await_response(
+                self.pending.clone(),
+                BreakerGuard(self.breaker.clone()),
+                req.rtype.clone(),
+                rx,
+                self.wait(timeout_duration),
+            )
+            .await;
+
+            match result {
+                Ok(mut response) => {
+                    response.attempt = Some(attempt as u32);
+                    return Ok(response);
+                }
+                Err(VmpError::RpcTimeout(_)) if attempt < max_attempts => {
+                    tokio::time::sleep(delay).await;
+                    delay = (delay * 2).min(MAX_RETRY_DELAY);
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Send a streaming RPC request
+    ///
+    /// Like [`RpcManager::request`], but the server may answer with any
+    /// number of partial responses before a final one. The returned stream
+    /// yields each response as it arrives and ends after the response
+    /// marked `is_final`. Dropping the stream before then gives up on the
+    /// request and removes it from the manager.
+    ///
+    /// # Arguments
+    ///
+    /// * `etype` - The event type (method name)
+    /// * `args` - Optional positional arguments
+    /// * `kwargs` - Optional keyword arguments
+    /// * `timeout_duration` - Maximum time to wait between two responses
+    ///
+    /// # Returns
+    ///
+    /// A tuple of (RpcRequest, Stream<Item = Result<RpcResponse>>)
+    pub async fn streaming_request(
+        &self,
+        etype: impl Into<String>,
+        args: Option<Vec<Value>>,
+        kwargs: Option<HashMap<String, Value>>,
+        timeout_duration: Duration,
+    ) -> Result<(RpcRequest, impl Stream<Item = Result<RpcResponse>>)> {
+        let req = outgoing_request(etype, args, kwargs);
+
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        // Register the pending stream
+        {
+            let mut pending = self.pending.lock().await;
+            self.add_pending(&mut pending, &req, PendingSender::Stream(tx));
+        }
+
+        // The state is dropped once the stream has finished or is dropped,
+        // taking the pending entry with it
+        let guard = PendingGuard {
+            pending: self.pending.clone(),
+            rtype: req.rtype.clone(),
+        };
+        let wait = self.wait(timeout_duration);
+        let response_stream = futures::stream::unfold(Some((rx, guard)), move |state| {
+            async move {
+                let (mut rx, guard) = state?;
+                match timeout(wait.remaining(), rx.recv()).await {
+                    Ok(Some(Ok(response))) => {
+                        let next = if response.is_final == Some(true) {
+                            None
+                        } else {
+                            Some((rx, guard))
+                        };
+                        Some((Ok(response), next))
+                    }
+                    Ok(Some(Err(e))) => Some((Err(e), None)),
+                    Ok(None) => {
+                        // Channel closed before the final response
+                        Some((
+                            Err(VmpError::RpcError("Response channel closed".to_string())),
+                            None,
+                        ))
+                    }
+                    Err(_) => {
+                        // Timeout
+                        Some((Err(wait.expired("Stream")), None))
+                    }
+                }
+            }
+        });
+
+        Ok((req, response_stream))
+    }
+
+    /// Handle an incoming streaming RPC response
+    ///
+    /// The response is forwarded to the stream returned by
+    /// [`RpcManager::streaming_request`]. When `is_final` is true the
+    /// stream is completed and its channel is dropped.
+    pub async fn handle_streaming_response(
+        &self,
+        mut response: RpcResponse,
+        is_final: bool,
+    ) -> Result<()> {
+        response.is_final = Some(is_final);
+
+        let mut pending = self.pending.lock().await;
+
+        let sender = match pending.get(&response.etype) {
+            Some(PendingRequest {
+                sender: PendingSender::Stream(sender),
+                ..
+            }) => sender.clone(),
+            _ => {
+                return Err(VmpError::RpcError(format!(
+                    "No pending stream for response type: {}",
+                    response.etype
+                )));
+            }
+        };
+
+        let etype = response.etype.clone();
+        let sent = sender.send(Ok(response));
+        if is_final || sent.is_err() {
+            pending.remove(&etype);
+        }
+        sent.map_err(|_| {
+            // The receiving stream was dropped
+            VmpError::RpcError("Failed to send response".to_string())
+        })
+    }
+
+    /// Register a pending request and return the receiving end of its channel
+    async fn register(&self, req: &RpcRequest) -> oneshot::Receiver<Result<RpcResponse>> {
+        let (tx, rx) = oneshot::channel();
+        let mut pending = self.pending.lock().await;
+        self.add_pending(&mut pending, req, PendingSender::Response(tx));
+        rx
+    }
+
+    /// Add a pending request, recording it in the deadline scope, if any
+    fn add_pending(
+        &self,
+        pending: &mut HashMap<String, PendingRequest>,
+        req: &RpcRequest,
+        sender: PendingSender,
+    ) {
+        let scope = self.scope.as_ref().map(|scope| {
+            scope.issued.lock().unwrap().insert(req.rtype.clone());
+            ScopeEntry {
+                scope: scope.clone(),
+                rtype: req.rtype.clone(),
+            }
+        });
+        pending.insert(req.rtype.clone(), PendingRequest::new(req.clone(), sender, scope));
+    }
+
+    /// How long a request with `timeout_duration` may wait, given the
+    /// deadline scope, if any
+    fn wait(&self, timeout_duration: Duration) -> Wait {
+        Wait {
+            timeout: timeout_duration,
+            deadline: self.scope.as_ref().map(|scope| scope.deadline),
+        }
+    }
+
+    /// Cancel a pending request
+    pub async fn cancel(&self, rtype: &str) -> bool {
+        let mut pending = self.pending.lock().await;
+        pending.remove(rtype).is_some()
+    }
+
+    /// Get the number of pending requests, including open streams
+    pub async fn pending_count(&self) -> usize {
+        let pending = self.pending.lock().await;
+        pending.len()
+    }
+
+    /// Get the `rtype` of every pending request, including open streams
+    pub async fn pending_ids(&self) -> Vec<String> {
+        let pending = self.pending.lock().await;
+        pending.keys().cloned().collect()
+    }
+
+    /// Cancel the pending requests whose original request matches `predicate`
+    ///
+    /// Like [`RpcManager::cancel_all`], the affected futures and streams are
+    /// woken with `VmpError::RpcError("Cancelled")`.
+    ///
+    /// # Returns
+    ///
+    /// The number of requests cancelled
+    pub async fn cancel_if<F>(&self, predicate: F) -> usize
+    where
+        F: Fn(&RpcRequest) -> bool,
+    {
+        let mut pending = self.pending.lock().await;
+        let matching: Vec<String> = pending
+            .iter()
+            .filter(|(_, entry)| predicate(&entry.request))
+            .map(|(rtype, _)| rtype.clone())
+            .collect();
+
+        for rtype in &matching {
+            if let Some(entry) = pending.remove(rtype) {
+                entry.cancel();
+            }
+        }
+
+        matching.len()
+    }
+
+    /// Cancel all pending requests and open streams
+    ///
+    /// Every waiting future or stream is woken immediately with
+    /// `VmpError::RpcError("Cancelled")`.
+    ///
+    /// # Returns
+    ///
+    /// The number of requests cancelled
+    pub async fn cancel_all(&self) -> usize {
+        let mut pending = self.pending.lock().await;
+        let count = pending.len();
+
+        for (_, entry) in pending.drain() {
+            entry.cancel();
+        }
+
+        count
+    }
+
+    /// Cancel all pending requests
+    ///
+    /// Requests issued by other tasks while this runs are not cancelled.
+    pub async fn shutdown(&self) {
+        self.cancel_all().await;
+    }
+
+    /// Clear all pending requests
+    ///
+    /// Waiting futures resolve with a "Response channel closed" error. Use
+    /// [`RpcManager::cancel_all`] to have them report a cancellation instead.
+    pub async fn clear(&self) {
+        let mut pending = self.pending.lock().await;
+        pending.clear();
+    }
+}
+
+/// An [`RpcManager`] handle that cancels the requests issued through it
+/// once a deadline passes, created by [`RpcManager::with_deadline`]
+///
+/// All methods of the underlying manager are available through `Deref`.
+/// Dropping the handle before the deadline stops the timer without
+/// cancelling anything.
+#[cfg(feature = "tokio")]
+pub struct ScopedRpcManager {
+    manager: RpcManager,
+    deadline: std::time::Instant,
+    timer: tokio::task::JoinHandle<()>,
+}
+
+#[cfg(feature = "tokio")]
+impl RpcManager {
+    /// Cancel the requests issued through the returned handle when
+    /// `deadline` passes
+    ///
+    /// At the deadline, requests issued through the handle that are still
+    /// pending are cancelled with `VmpError::RpcError("Cancelled")`;
+    /// requests issued through this manager or its other clones are left
+    /// alone. Each request issued through the handle waits at most until
+    /// the deadline, whatever its own timeout, so one issued after the
+    /// deadline is cancelled at once. Must be called within a tokio runtime.
+    pub fn with_deadline(&self, deadline: std::time::Instant) -> ScopedRpcManager {
+        let scope = std::sync::Arc::new(DeadlineScope {
+            deadline,
+            issued: std::sync::Mutex::new(std::collections::HashSet::new()),
+        });
+        let manager = RpcManager {
+            scope: Some(scope.clone()),
+            ..self.clone()
+        };
+        let timer_manager = manager.clone();
+        let timer = tokio::spawn(async move {
+            tokio::time::sleep_until(tokio::time::Instant::from_std(deadline)).await;
+            let issued = std::mem::take(&mut *scope.issued.lock().unwrap());
+            timer_manager.cancel_if(|req| issued.contains(&req.rtype)).await;
+        });
+        ScopedRpcManager {
+            manager,
+            deadline,
+            timer,
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl ScopedRpcManager {
+    /// When pending requests are cancelled
+    pub fn deadline(&self) -> std::time::Instant {
+        self.deadline
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl std::ops::Deref for ScopedRpcManager {
+    type Target = RpcManager;
+
+    fn deref(&self) -> &RpcManager {
+        &self.manager
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Drop for ScopedRpcManager {
+    fn drop(&mut self) {
+        self.timer.abort();
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "tokio")]
+mod tests {
+    use super::*;
+    use crate::error::ErrorKind;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_generate_request_id() {
+        let id1 = generate_request_id();
+        let id2 = generate_request_id();
+
+        assert!(id1.starts_with("rpc-"));
+        assert_ne!(id1, id2);
+    }
+
+    #[test]
+    fn test_create_rpc_response() {
+        let ok = create_rpc_response("rpc-1", Ok(json!(1)));
+        assert_eq!((ok.ok, ok.error_kind()), (Some(true), None));
+
+        let missing = create_rpc_response("rpc-1", Err(VmpError::NotFound("mesh".to_string())));
+        assert_eq!(missing.error.as_deref(), Some("mesh"));
+        assert_eq!(missing.error_kind(), Some(ErrorKind::NotFound));
+        assert_eq!(VmpError::from(missing).to_string(), "Not found: mesh");
+
+        let bad = create_rpc_response("rpc-1", Err(VmpError::MissingField("seed".to_string())));
+        assert_eq!(bad.error.as_deref(), Some("Missing required field: seed"));
+        assert_eq!(bad.error_kind(), Some(ErrorKind::InvalidArgument));
+
+        // The kind survives the wire and picks the error variant
+        let bytes = crate::serializer::serialize(&bad).unwrap();
+        let decoded: RpcResponse = crate::deserializer::deserialize(&bytes).unwrap();
+        assert_eq!(
+            VmpError::from(decoded).to_string(),
+            "Invalid argument: Missing required field: seed"
+        );
+
+        let failed = create_rpc_response("rpc-1", Err(VmpError::RpcError("boom".to_string())));
+        assert_eq!(VmpError::from(failed).to_string(), "RPC error: boom");
+    }
+
+    #[tokio::test]
+    async fn test_create_rpc_request() {
+        let mut kwargs = HashMap::new();
+        kwargs.insert("seed".to_string(), json!(100));
+
+        let req = create_rpc_request("render", None, Some(kwargs));
+
+        assert_eq!(req.etype, "render");
+        assert!(req.rtype.starts_with("rpc-"));
+        assert!(req.kwargs.is_some());
+    }
+
+    #[tokio::test]
     async fn test_rpc_manager() {
         let manager = RpcManager::new();
 
@@ -255,4 +1195,782 @@ mod tests {
         assert!(cancelled);
         assert_eq!(manager.pending_count().await, 0);
     }
+
+    #[tokio::test]
+    async fn test_with_deadline() {
+        let manager = RpcManager::new();
+        let start = std::time::Instant::now();
+        let scoped = manager.with_deadline(start + Duration::from_millis(100));
+        assert_eq!(scoped.deadline(), start + Duration::from_millis(100));
+
+        let (_req, response_fut) = scoped
+            .request("render", None, None, Duration::from_secs(10))
+            .await
+            .unwrap();
+        let result = response_fut.await;
+        assert!(start.elapsed() < Duration::from_millis(200));
+        assert!(matches!(result, Err(VmpError::RpcError(msg)) if msg == CANCELLED));
+        assert_eq!(manager.pending_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_with_deadline_scope() {
+        use futures::StreamExt;
+
+        let manager = RpcManager::new();
+        let start = std::time::Instant::now();
+        let scoped = manager.with_deadline(start + Duration::from_millis(50));
+
+        // Requests issued outside the scope outlive its deadline
+        let (outside, _outside_fut) = manager
+            .request("render", None, None, Duration::from_secs(10))
+            .await
+            .unwrap();
+        let (_req, inside_fut) = scoped
+            .request("render", None, None, Duration::from_secs(10))
+            .await
+            .unwrap();
+        let (_req, stream) = scoped
+            .streaming_request("frames", None, None, Duration::from_secs(10))
+            .await
+            .unwrap();
+        let mut stream = Box::pin(stream);
+        assert!(matches!(inside_fut.await, Err(VmpError::RpcError(msg)) if msg == CANCELLED));
+        let item = stream.next().await.unwrap();
+        assert!(matches!(item, Err(VmpError::RpcError(msg)) if msg == CANCELLED));
+        assert_eq!(manager.pending_ids().await, [outside.rtype]);
+
+        // A shorter timeout of its own still applies
+        let scoped = manager.with_deadline(std::time::Instant::now() + Duration::from_secs(10));
+        let (_req, fut) = scoped
+            .request("render", None, None, Duration::from_millis(10))
+            .await
+            .unwrap();
+        assert!(matches!(fut.await, Err(VmpError::RpcTimeout(_))));
+        assert!(scoped.scope.as_ref().unwrap().issued.lock().unwrap().is_empty());
+
+        // Answered requests leave the scope
+        let (req, fut) = scoped
+            .request("render", None, None, Duration::from_secs(10))
+            .await
+            .unwrap();
+        let issued = || scoped.scope.as_ref().unwrap().issued.lock().unwrap().len();
+        assert_eq!(issued(), 1);
+        scoped.handle_response(RpcResponse::success(&req.rtype, json!(1))).await.unwrap();
+        assert!(fut.await.is_ok());
+        assert_eq!(issued(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_with_deadline_passed() {
+        let manager = RpcManager::new();
+        let scoped = manager.with_deadline(std::time::Instant::now());
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        // Issued after the timer fired, yet cancelled without waiting out
+        // the timeout
+        let start = std::time::Instant::now();
+        let (_req, fut) = scoped
+            .request("render", None, None, Duration::from_secs(10))
+            .await
+            .unwrap();
+        assert!(matches!(fut.await, Err(VmpError::RpcError(msg)) if msg == CANCELLED));
+        let (_req, progress) = scoped
+            .request_with_progress("render", None, None, Duration::from_secs(10))
+            .await
+            .unwrap();
+        let result = progress.final_response().await;
+        assert!(matches!(result, Err(VmpError::RpcError(msg)) if msg == CANCELLED));
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(manager.pending_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_with_deadline_dropped() {
+        let manager = RpcManager::new();
+        let scoped =
+            manager.with_deadline(std::time::Instant::now() + Duration::from_millis(20));
+        let (_req, _response_fut) = manager
+            .request("render", None, None, Duration::from_secs(10))
+            .await
+            .unwrap();
+        drop(scoped);
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(manager.pending_count().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_batch_request() {
+        let manager = RpcManager::new();
+
+        let calls: Vec<BatchCall> = (0..10)
+            .map(|i| (format!("method_{}", i), Some(vec![json!(i)]), None))
+            .collect();
+
+        let (reqs, responses_fut) = manager
+            .batch_request(calls, Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        assert_eq!(reqs.len(), 10);
+        assert_eq!(manager.pending_count().await, 10);
+
+        // Answer in reverse order, rejecting every third request
+        let server = manager.clone();
+        let pairs: Vec<(usize, String)> = reqs
+            .iter()
+            .enumerate()
+            .map(|(i, r)| (i, r.rtype.clone()))
+            .rev()
+            .collect();
+        tokio::spawn(async move {
+            for (i, rtype) in pairs {
+                let response = if i % 3 == 0 {
+                    RpcResponse::error(&rtype, format!("rejected {}", i))
+                } else {
+                    RpcResponse::success(&rtype, json!(i * 10))
+                };
+                server.handle_response(response).await.unwrap();
+            }
+        });
+
+        let responses = responses_fut.await;
+        assert_eq!(responses.len(), 10);
+        for (i, result) in responses.iter().enumerate() {
+            if i % 3 == 0 {
+                assert!(matches!(result, Err(VmpError::RpcError(msg)) if msg == &format!("rejected {}", i)));
+            } else {
+                let response = result.as_ref().unwrap();
+                assert_eq!(response.etype, reqs[i].rtype);
+                assert_eq!(response.data, Some(json!(i * 10)));
+            }
+        }
+        assert_eq!(manager.pending_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_batch_request_partial_timeout() {
+        let manager = RpcManager::new();
+
+        let calls: Vec<BatchCall> = vec![
+            ("a".to_string(), None, None),
+            ("b".to_string(), None, None),
+        ];
+        let (reqs, responses_fut) = manager
+            .batch_request(calls, Duration::from_millis(100))
+            .await
+            .unwrap();
+
+        let response = RpcResponse::success(&reqs[1].rtype, json!("done"));
+        manager.handle_response(response).await.unwrap();
+
+        let responses = responses_fut.await;
+        assert!(matches!(responses[0], Err(VmpError::RpcTimeout(_))));
+        assert_eq!(responses[1].as_ref().unwrap().data, Some(json!("done")));
+        assert_eq!(manager.pending_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_request_with_retry() {
+        let manager = RpcManager::new();
+        let (tx, mut rx) = mpsc::unbounded_channel::<RpcRequest>();
+
+        // Mock server that drops the first two transmissions
+        let server = manager.clone();
+        tokio::spawn(async move {
+            let mut seen = 0;
+            while let Some(req) = rx.recv().await {
+                seen += 1;
+                if seen == 3 {
+                    let response = RpcResponse::success(&req.rtype, json!("ok"));
+                    server.handle_response(response).await.unwrap();
+                }
+            }
+        });
+
+        let response = manager
+            .request_with_retry(
+                "render",
+                None,
+                None,
+                Duration::from_millis(50),
+                5,
+                Duration::from_millis(10),
+                |req| {
+                    tx.send(req.clone())
+                        .map_err(|e| VmpError::RpcError(e.to_string()))
+                },
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.ok, Some(true));
+        assert_eq!(response.attempt, Some(3));
+        assert_eq!(manager.pending_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_request_with_retry_exhausted() {
+        let manager = RpcManager::new();
+        let mut sent = 0;
+
+        let result = manager
+            .request_with_retry(
+                "render",
+                None,
+                None,
+                Duration::from_millis(20),
+                3,
+                Duration::from_millis(5),
+                |_| {
+                    sent += 1;
+                    Ok(())
+                },
+            )
+            .await;
+
+        assert!(matches!(result, Err(VmpError::RpcTimeout(_))));
+        assert_eq!(sent, 3);
+        assert_eq!(manager.pending_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_request_with_retry_no_retry_on_rejection() {
+        let manager = RpcManager::new();
+        let mut sent = 0;
+
+        let server = manager.clone();
+        let result = manager
+            .request_with_retry(
+                "render",
+                None,
+                None,
+                Duration::from_millis(50),
+                3,
+                Duration::from_millis(5),
+                |req| {
+                    sent += 1;
+                    let server = server.clone();
+                    let response = RpcResponse::error(&req.rtype, "bad arguments");
+                    tokio::spawn(async move { server.handle_response(response).await });
+                    Ok(())
+                },
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.ok, Some(false));
+        assert_eq!(result.attempt, Some(1));
+        assert_eq!(sent, 1);
+
+        // Transport errors are not retried either
+        let result = manager
+            .request_with_retry(
+                "render",
+                None,
+                None,
+                Duration::from_millis(50),
+                3,
+                Duration::from_millis(5),
+                |_| Err(VmpError::RpcError("connection refused".to_string())),
+            )
+            .await;
+
+        assert!(matches!(result, Err(VmpError::RpcError(_))));
+        assert_eq!(manager.pending_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_streaming_request() {
+        use futures::StreamExt;
+
+        let manager = RpcManager::new();
+
+        let (req, stream) = manager
+            .streaming_request("render_frames", None, None, Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        assert_eq!(manager.pending_count().await, 1);
+
+        let manager_clone = manager.clone();
+        let rtype = req.rtype.clone();
+        tokio::spawn(async move {
+            for i in 0..5 {
+                let chunk = RpcResponse::success(&rtype, json!({"frame": i}));
+                manager_clone
+                    .handle_streaming_response(chunk, false)
+                    .await
+                    .unwrap();
+            }
+            let last = RpcResponse::success(&rtype, json!({"frame": 5}));
+            manager_clone
+                .handle_streaming_response(last, true)
+                .await
+                .unwrap();
+        });
+
+        let responses: Vec<RpcResponse> = stream
+            .map(|r| r.unwrap())
+            .collect()
+            .await;
+
+        assert_eq!(responses.len(), 6);
+        for (i, response) in responses.iter().enumerate() {
+            assert_eq!(response.data, Some(json!({"frame": i})));
+            assert_eq!(response.is_final, Some(i == 5));
+        }
+        assert_eq!(manager.pending_count().await, 0);
+
+        // The stream is gone once the final response was delivered
+        let late = RpcResponse::success(&req.rtype, json!(null));
+        assert!(manager.handle_streaming_response(late, false).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_request_with_progress() {
+        let manager = RpcManager::new();
+        let (req, mut future) = manager
+            .request_with_progress("train", None, None, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(manager.pending_count().await, 1);
+
+        let server = manager.clone();
+        let rtype = req.rtype.clone();
+        tokio::spawn(async move {
+            for i in 0..10 {
+                server.handle_progress(&rtype, i as f32 / 9.0).await.unwrap();
+            }
+            let response = RpcResponse::success(&rtype, json!({"loss": 0.1}));
+            server.handle_response(response).await.unwrap();
+        });
+
+        let mut ticks = Vec::new();
+        while let Some(progress) = future.next_progress().await {
+            ticks.push(progress);
+        }
+        assert_eq!(ticks.len(), 10);
+        assert_eq!((ticks[0], ticks[9]), (0.0, 1.0));
+        assert!(ticks.windows(2).all(|w| w[0] < w[1]));
+
+        let response = future.final_response().await.unwrap();
+        assert_eq!(response.data, Some(json!({"loss": 0.1})));
+        assert_eq!(manager.pending_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_progress_clamped_and_rejected() {
+        let manager = RpcManager::new();
+        let (req, mut future) = manager
+            .request_with_progress("train", None, None, Duration::from_millis(100))
+            .await
+            .unwrap();
+
+        manager.handle_progress(&req.rtype, -0.5).await.unwrap();
+        manager.handle_progress(&req.rtype, 3.0).await.unwrap();
+        assert!(manager.handle_progress(&req.rtype, f32::NAN).await.is_err());
+        assert!(manager.handle_progress("rpc-unknown", 0.5).await.is_err());
+        assert_eq!(future.next_progress().await, Some(0.0));
+        assert_eq!(future.next_progress().await, Some(1.0));
+
+        // Plain requests take no progress
+        let (plain, _response) = manager
+            .request("render", None, None, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert!(manager.handle_progress(&plain.rtype, 0.5).await.is_err());
+
+        // No response within the timeout
+        assert_eq!(future.next_progress().await, None);
+        assert!(matches!(future.final_response().await, Err(VmpError::RpcTimeout(_))));
+        assert_eq!(manager.pending_count().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_progress_future_removes_request() {
+        let manager = RpcManager::new();
+        let (req, future) = manager
+            .request_with_progress("train", None, None, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(manager.pending_count().await, 1);
+        drop(future);
+        assert_eq!(manager.pending_count().await, 0);
+        assert!(manager.handle_progress(&req.rtype, 0.5).await.is_err());
+
+        // Also when the map is locked at the time of the drop
+        let (_, future) = manager
+            .request_with_progress("train", None, None, Duration::from_secs(5))
+            .await
+            .unwrap();
+        let locked = manager.pending.lock().await;
+        drop(future);
+        drop(locked);
+        tokio::task::yield_now().await;
+        assert_eq!(manager.pending_count().await, 0);
+
+        // Abandoning the wait for the final response does the same
+        let (_, future) = manager
+            .request_with_progress("train", None, None, Duration::from_secs(5))
+            .await
+            .unwrap();
+        let waiting = tokio::time::timeout(Duration::from_millis(10), future.final_response());
+        assert!(waiting.await.is_err());
+        assert_eq!(manager.pending_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_streaming_request_timeout() {
+        use futures::StreamExt;
+
+        let manager = RpcManager::new();
+
+        let (req, stream) = manager
+            .streaming_request("render_frames", None, None, Duration::from_millis(100))
+            .await
+            .unwrap();
+
+        let chunk = RpcResponse::success(&req.rtype, json!({"frame": 0}));
+        manager.handle_streaming_response(chunk, false).await.unwrap();
+
+        let results: Vec<Result<RpcResponse>> = stream.collect().await;
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        assert!(matches!(results[1], Err(VmpError::RpcTimeout(_))));
+        assert_eq!(manager.pending_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_streaming_request_dropped() {
+        use futures::StreamExt;
+
+        let manager = RpcManager::new();
+
+        let (req, stream) = manager
+            .streaming_request("render_frames", None, None, Duration::from_secs(10))
+            .await
+            .unwrap();
+        drop(stream);
+        assert_eq!(manager.pending_count().await, 0);
+
+        // Also after some responses have been read
+        let (req2, stream) = manager
+            .streaming_request("render_frames", None, None, Duration::from_secs(10))
+            .await
+            .unwrap();
+        let mut stream = Box::pin(stream);
+        let chunk = RpcResponse::success(&req2.rtype, json!({"frame": 0}));
+        manager.handle_streaming_response(chunk, false).await.unwrap();
+        assert!(stream.next().await.unwrap().is_ok());
+        drop(stream);
+        assert_eq!(manager.pending_count().await, 0);
+
+        let late = RpcResponse::success(&req.rtype, json!({"frame": 1}));
+        assert!(manager.handle_streaming_response(late, false).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_all() {
+        let manager = RpcManager::new();
+
+        let (_req1, fut1) = manager
+            .request("a", None, None, Duration::from_secs(10))
+            .await
+            .unwrap();
+        let (_req2, fut2) = manager
+            .request("b", None, None, Duration::from_secs(10))
+            .await
+            .unwrap();
+        let (_req3, stream) = manager
+            .streaming_request("c", None, None, Duration::from_secs(10))
+            .await
+            .unwrap();
+
+        let waiters = async {
+            use futures::StreamExt;
+            let stream_results: Vec<Result<RpcResponse>> = stream.collect().await;
+            (fut1.await, fut2.await, stream_results)
+        };
+        let canceller = async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            manager.cancel_all().await
+        };
+
+        // All waiters wake up long before their 10 second timeouts
+        let ((r1, r2, stream_results), cancelled) =
+            tokio::time::timeout(Duration::from_secs(1), async { tokio::join!(waiters, canceller) })
+                .await
+                .unwrap();
+
+        assert_eq!(cancelled, 3);
+        assert_eq!(manager.pending_count().await, 0);
+        assert!(matches!(r1, Err(VmpError::RpcError(msg)) if msg == "Cancelled"));
+        assert!(matches!(r2, Err(VmpError::RpcError(msg)) if msg == "Cancelled"));
+        assert_eq!(stream_results.len(), 1);
+        assert!(matches!(&stream_results[0], Err(VmpError::RpcError(msg)) if msg == "Cancelled"));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown() {
+        let manager = RpcManager::new();
+
+        let (_req, fut) = manager
+            .request("a", None, None, Duration::from_secs(10))
+            .await
+            .unwrap();
+
+        manager.shutdown().await;
+        assert_eq!(manager.pending_count().await, 0);
+        assert!(matches!(fut.await, Err(VmpError::RpcError(_))));
+
+        // Nothing left to cancel
+        assert_eq!(manager.cancel_all().await, 0);
+    }
+
+    async fn time_out_request(manager: &RpcManager) -> Result<RpcResponse> {
+        let (_req, fut) = manager
+            .request("probe", None, None, Duration::from_millis(10))
+            .await?;
+        fut.await
+    }
+
+    async fn answer_request(manager: &RpcManager) -> Result<RpcResponse> {
+        let (req, fut) = manager
+            .request("probe", None, None, Duration::from_secs(1))
+            .await?;
+        let response = RpcResponse::success(&req.rtype, json!("ok"));
+        manager.handle_response(response).await?;
+        fut.await
+    }
+
+    #[test]
+    fn test_circuit_breaker_window() {
+        let mut breaker = CircuitBreaker::new(4, 0.5, Duration::from_secs(60));
+        let timeout = || Err(VmpError::RpcTimeout("timeout".to_string()));
+        let success = || Ok(RpcResponse::success("rpc", json!(null)));
+
+        // The window must fill before the circuit can open
+        breaker.record(&timeout());
+        breaker.record(&timeout());
+        breaker.record(&timeout());
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        // 3/4 failures is above the threshold
+        breaker.record(&success());
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(!breaker.try_acquire());
+
+        // Exactly at the threshold stays closed; error responses count as success
+        let mut breaker = CircuitBreaker::new(4, 0.5, Duration::from_secs(60));
+        breaker.record(&timeout());
+        breaker.record(&timeout());
+        breaker.record(&Ok(RpcResponse::error("rpc", "rejected")));
+        breaker.record(&success());
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_opens() {
+        let manager = RpcManager::with_circuit_breaker(3, 0.5, Duration::from_secs(60));
+        assert_eq!(manager.circuit_state(), CircuitState::Closed);
+
+        for _ in 0..3 {
+            assert!(matches!(
+                time_out_request(&manager).await,
+                Err(VmpError::RpcTimeout(_))
+            ));
+        }
+        assert_eq!(manager.circuit_state(), CircuitState::Open);
+
+        // Requests are rejected without registering anything
+        let result = manager.request("test", None, None, Duration::from_secs(1)).await;
+        assert!(matches!(result, Err(VmpError::RpcError(msg)) if msg == "Circuit open"));
+        assert_eq!(manager.pending_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_half_open_recovers() {
+        let manager = RpcManager::with_circuit_breaker(2, 0.5, Duration::from_millis(50));
+
+        for _ in 0..2 {
+            let _ = time_out_request(&manager).await;
+        }
+        assert_eq!(manager.circuit_state(), CircuitState::Open);
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(manager.circuit_state(), CircuitState::HalfOpen);
+
+        // Only one probe is allowed while half-open
+        let (probe, probe_fut) = manager
+            .request("probe", None, None, Duration::from_secs(1))
+            .await
+            .unwrap();
+        let second = manager.request("other", None, None, Duration::from_secs(1)).await;
+        assert!(matches!(second, Err(VmpError::RpcError(msg)) if msg == "Circuit open"));
+
+        let response = RpcResponse::success(&probe.rtype, json!("ok"));
+        manager.handle_response(response).await.unwrap();
+        assert!(probe_fut.await.is_ok());
+        assert_eq!(manager.circuit_state(), CircuitState::Closed);
+
+        assert!(answer_request(&manager).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_half_open_fails() {
+        let manager = RpcManager::with_circuit_breaker(2, 0.5, Duration::from_millis(50));
+
+        for _ in 0..2 {
+            let _ = time_out_request(&manager).await;
+        }
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(manager.circuit_state(), CircuitState::HalfOpen);
+
+        // A failed probe reopens the circuit for another cooldown
+        assert!(matches!(
+            time_out_request(&manager).await,
+            Err(VmpError::RpcTimeout(_))
+        ));
+        assert_eq!(manager.circuit_state(), CircuitState::Open);
+        assert!(answer_request(&manager).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_dropped_probe() {
+        let manager = RpcManager::with_circuit_breaker(2, 0.5, Duration::from_millis(50));
+
+        for _ in 0..2 {
+            let _ = time_out_request(&manager).await;
+        }
+        tokio::time::sleep(Duration::from_millis(60)).await;
+
+        // A probe whose future is dropped unpolled frees the slot
+        let (_, probe_fut) =
+            manager.request("probe", None, None, Duration::from_secs(1)).await.unwrap();
+        drop(probe_fut);
+        assert_eq!(manager.circuit_state(), CircuitState::HalfOpen);
+
+        // So does one cancelled while waiting
+        let (_, probe_fut) =
+            manager.request("probe", None, None, Duration::from_secs(1)).await.unwrap();
+        let waiting = tokio::time::timeout(Duration::from_millis(10), probe_fut).await;
+        assert!(waiting.is_err());
+
+        assert!(answer_request(&manager).await.is_ok());
+        assert_eq!(manager.circuit_state(), CircuitState::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_no_circuit_breaker() {
+        let manager = RpcManager::new();
+
+        for _ in 0..5 {
+            let _ = time_out_request(&manager).await;
+        }
+        assert_eq!(manager.circuit_state(), CircuitState::Closed);
+        assert!(answer_request(&manager).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_pending_ids_and_cancel_if() {
+        let manager = RpcManager::new();
+
+        let mut futures = Vec::new();
+        let mut render_ids = Vec::new();
+        for i in 0..10 {
+            let etype = if i % 2 == 0 { "render" } else { "query" };
+            let (req, fut) = manager
+                .request(etype, None, None, Duration::from_secs(10))
+                .await
+                .unwrap();
+            if etype == "render" {
+                render_ids.push(req.rtype.clone());
+            }
+            futures.push((etype, fut));
+        }
+
+        let mut ids = manager.pending_ids().await;
+        ids.sort();
+        assert_eq!(ids.len(), 10);
+        assert!(render_ids.iter().all(|id| ids.contains(id)));
+
+        let cancelled = manager.cancel_if(|req| req.etype == "render").await;
+        assert_eq!(cancelled, 5);
+        assert_eq!(manager.pending_count().await, 5);
+
+        let remaining = manager.pending_ids().await;
+        assert!(render_ids.iter().all(|id| !remaining.contains(id)));
+
+        // Cancelled futures resolve immediately; the others are still pending
+        for (etype, fut) in futures {
+            let result = tokio::time::timeout(Duration::from_millis(50), fut).await;
+            if etype == "render" {
+                assert!(matches!(result, Ok(Err(VmpError::RpcError(msg))) if msg == "Cancelled"));
+            } else {
+                assert!(result.is_err());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancel_if_streams() {
+        let manager = RpcManager::new();
+
+        let (stream_req, _stream) = manager
+            .streaming_request("frames", None, None, Duration::from_secs(10))
+            .await
+            .unwrap();
+        let (req, _fut) = manager
+            .request("frames", None, None, Duration::from_secs(10))
+            .await
+            .unwrap();
+
+        let cancelled = manager
+            .cancel_if(|req| req.rtype == stream_req.rtype)
+            .await;
+        assert_eq!(cancelled, 1);
+        assert_eq!(manager.pending_count().await, 1);
+
+        // Responses are routed according to the kind of pending request
+        let response = RpcResponse::success(&req.rtype, json!(null));
+        assert!(manager.handle_streaming_response(response, false).await.is_err());
+        assert_eq!(manager.pending_count().await, 1);
+    }
+
+    #[cfg(feature = "opentelemetry")]
+    #[tokio::test]
+    async fn test_request_injects_trace_context() {
+        use opentelemetry::trace::{
+            SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState,
+        };
+        use opentelemetry_sdk::propagation::TraceContextPropagator;
+
+        opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+        let manager = RpcManager::new();
+
+        let (req, _response_fut) = manager
+            .request("render", None, None, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(req.metadata, None);
+
+        let span_context = SpanContext::new(
+            TraceId::from_hex("0af7651916cd43dd8448eb211c80319c").unwrap(),
+            SpanId::from_hex("b7ad6b7169203331").unwrap(),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::NONE,
+        );
+        let _guard = opentelemetry::Context::new()
+            .with_remote_span_context(span_context.clone())
+            .attach();
+        let (req, _response_fut) = manager
+            .request("render", None, None, Duration::from_secs(5))
+            .await
+            .unwrap();
+        let context = req.extract_trace_context().unwrap();
+        assert_eq!(context.span().span_context(), &span_context);
+    }
 }
@@ -0,0 +1,311 @@
+//! Sequence numbers for ordered delivery
+//!
+//! Author: Ge Yang
+
+use crate::error::{Result, VmpError};
+use crate::types::Message;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Metadata key holding a message's sequence number
+pub const SEQ_KEY: &str = "seq";
+
+/// Stamps messages with sequence numbers 0, 1, 2, ...
+///
+/// Numbers are taken atomically, so one sequencer can be shared by several
+/// senders.
+#[derive(Debug, Default)]
+pub struct MessageSequencer {
+    counter: AtomicU64,
+}
+
+impl MessageSequencer {
+    /// Create a sequencer whose first number is 0
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take the next number and store it in `metadata["seq"]`
+    pub fn next(&self, mut msg: Message) -> SequencedMessage {
+        let seq = self.counter.fetch_add(1, Ordering::Relaxed);
+        msg.metadata
+            .get_or_insert_with(Default::default)
+            .insert(SEQ_KEY.to_string(), Value::from(seq));
+        SequencedMessage { seq, message: msg }
+    }
+}
+
+/// A message with its sequence number
+#[derive(Debug, Clone, PartialEq)]
+pub struct SequencedMessage {
+    /// Position in the sender's sequence
+    pub seq: u64,
+    /// The message, with `seq` also in its metadata
+    pub message: Message,
+}
+
+impl SequencedMessage {
+    /// Read the sequence number from a received message's metadata
+    pub fn from_message(message: Message) -> Result<Self> {
+        let seq = match message.get_meta(SEQ_KEY) {
+            Some(seq) => seq.as_u64().ok_or_else(|| {
+                VmpError::InvalidMessage(format!("Invalid sequence number: {}", seq))
+            })?,
+            None => return Err(VmpError::MissingField(SEQ_KEY.to_string())),
+        };
+        Ok(Self { seq, message })
+    }
+
+    /// Take the message, dropping the wrapper
+    pub fn into_inner(self) -> Message {
+        self.message
+    }
+}
+
+impl std::ops::Deref for SequencedMessage {
+    type Target = Message;
+
+    fn deref(&self) -> &Message {
+        &self.message
+    }
+}
+
+/// Puts sequenced messages back in order
+///
+/// Only sequence numbers less than `window` past the next expected one are
+/// accepted, so a message claiming a number far ahead cannot make the
+/// reorderer skip the ones in between. Messages that arrive early are held
+/// until the gap before them is filled. Once every number in the window but
+/// the expected one is held, the missing message is given up on: delivery
+/// skips ahead to the earliest held message. Messages older than the next
+/// expected number, such as duplicates, and messages beyond the window are
+/// dropped.
+///
+/// # Example
+///
+/// ```rust
+/// use vuer_rpc::sequencer::{MessageReorderer, MessageSequencer};
+/// use vuer_rpc::Message;
+///
+/// let sequencer = MessageSequencer::new();
+/// let first = sequencer.next(Message::new("A"));
+/// let second = sequencer.next(Message::new("B"));
+///
+/// let mut reorderer = MessageReorderer::new(16);
+/// assert_eq!(reorderer.push(second).count(), 0);
+/// let delivered: Vec<u64> = reorderer.push(first).map(|m| m.seq).collect();
+/// assert_eq!(delivered, [0, 1]);
+/// ```
+#[derive(Debug)]
+pub struct MessageReorderer {
+    next_seq: u64,
+    window: usize,
+    held: BTreeMap<u64, SequencedMessage>,
+    /// Set once `u64::MAX` has been delivered
+    exhausted: bool,
+}
+
+impl MessageReorderer {
+    /// Create a reorderer expecting sequence number 0 first, accepting
+    /// numbers less than `window` (at least 2) past the next expected one
+    pub fn new(window: usize) -> Self {
+        Self {
+            next_seq: 0,
+            window: window.max(2),
+            held: BTreeMap::new(),
+            exhausted: false,
+        }
+    }
+
+    /// Accept a message, returning every message that is now in order
+    pub fn push(&mut self, msg: SequencedMessage) -> impl Iterator<Item = SequencedMessage> {
+        let mut ready = Vec::new();
+        if self.exhausted || !self.in_window(msg.seq) {
+            return ready.into_iter();
+        }
+        self.held.entry(msg.seq).or_insert(msg);
+        if self.held.len() >= self.window - 1
+            && let Some(&earliest) = self.held.keys().next()
+        {
+            self.next_seq = earliest;
+        }
+
+        while let Some(next) = self.held.remove(&self.next_seq) {
+            ready.push(next);
+            match self.next_seq.checked_add(1) {
+                Some(seq) => self.next_seq = seq,
+                None => {
+                    self.exhausted = true;
+                    break;
+                }
+            }
+        }
+        ready.into_iter()
+    }
+
+    /// Whether `seq` is the next expected number or less than `window`
+    /// past it
+    fn in_window(&self, seq: u64) -> bool {
+        seq >= self.next_seq
+            && (self.next_seq.checked_add(self.window as u64)).is_none_or(|end| seq < end)
+    }
+
+    /// The sequence number expected next
+    pub fn next_seq(&self) -> u64 {
+        self.next_seq
+    }
+
+    /// Number of early messages being held
+    pub fn pending(&self) -> usize {
+        self.held.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sequenced(count: usize) -> Vec<SequencedMessage> {
+        let sequencer = MessageSequencer::new();
+        (0..count).map(|i| sequencer.next(Message::new(format!("E{}", i)))).collect()
+    }
+
+    #[test]
+    fn test_sequencer_stamps_metadata() {
+        let messages = sequenced(3);
+        for (i, msg) in messages.iter().enumerate() {
+            assert_eq!(msg.seq, i as u64);
+            assert_eq!(msg.get_meta(SEQ_KEY), Some(&Value::from(i)));
+        }
+
+        // The number survives the wire
+        let bytes = crate::serializer::serialize_message(&messages[2]).unwrap();
+        let received = crate::deserializer::deserialize_message(&bytes).unwrap();
+        assert_eq!(SequencedMessage::from_message(received).unwrap(), messages[2]);
+
+        assert!(matches!(
+            SequencedMessage::from_message(Message::new("A")),
+            Err(VmpError::MissingField(_))
+        ));
+        let bad = Message::new("A").with_metadata(SEQ_KEY, Value::from(-1));
+        assert!(matches!(
+            SequencedMessage::from_message(bad),
+            Err(VmpError::InvalidMessage(_))
+        ));
+    }
+
+    #[test]
+    fn test_sequencer_shared() {
+        let sequencer = MessageSequencer::new();
+        let mut seqs: Vec<u64> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..4)
+                .map(|_| {
+                    scope.spawn(|| {
+                        (0..100).map(|_| sequencer.next(Message::new("A")).seq).collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            handles.into_iter().flat_map(|h| h.join().unwrap()).collect()
+        });
+        seqs.sort_unstable();
+        assert_eq!(seqs, (0..400).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_reorderer_shuffled() {
+        let mut messages = sequenced(50);
+        // Deterministic shuffle: reverse blocks of 7, then swap neighbours
+        for block in messages.chunks_mut(7) {
+            block.reverse();
+        }
+        for pair in messages.chunks_mut(2) {
+            pair.swap(0, pair.len() - 1);
+        }
+
+        let mut reorderer = MessageReorderer::new(16);
+        let delivered: Vec<SequencedMessage> =
+            messages.into_iter().flat_map(|msg| reorderer.push(msg).collect::<Vec<_>>()).collect();
+
+        let seqs: Vec<u64> = delivered.iter().map(|m| m.seq).collect();
+        assert_eq!(seqs, (0..50).collect::<Vec<_>>());
+        assert_eq!(delivered[7].etype, "E7");
+        assert_eq!((reorderer.next_seq(), reorderer.pending()), (50, 0));
+    }
+
+    #[test]
+    fn test_reorderer_drops_duplicates() {
+        let messages = sequenced(3);
+        let mut reorderer = MessageReorderer::new(4);
+        assert_eq!(reorderer.push(messages[0].clone()).count(), 1);
+        assert_eq!(reorderer.push(messages[0].clone()).count(), 0);
+        assert_eq!(reorderer.push(messages[2].clone()).count(), 0);
+        assert_eq!(reorderer.push(messages[2].clone()).count(), 0);
+        assert_eq!(reorderer.pending(), 1);
+        assert_eq!(reorderer.push(messages[1].clone()).count(), 2);
+    }
+
+    #[test]
+    fn test_reorderer_window_full() {
+        let messages = sequenced(6);
+        let mut reorderer = MessageReorderer::new(3);
+
+        // 0 is lost; 1 fits in the window, 3 does not
+        assert_eq!(reorderer.push(messages[1].clone()).count(), 0);
+        assert_eq!(reorderer.push(messages[3].clone()).count(), 0);
+        assert_eq!(reorderer.pending(), 1);
+
+        // Holding 2 fills the window, so delivery skips to 1
+        let seqs: Vec<u64> = reorderer.push(messages[2].clone()).map(|m| m.seq).collect();
+        assert_eq!(seqs, [1, 2]);
+
+        // The lost message is now too old, and 3 is accepted
+        assert_eq!(reorderer.push(messages[0].clone()).count(), 0);
+        assert_eq!(reorderer.push(messages[3].clone()).count(), 1);
+        assert_eq!(reorderer.next_seq(), 4);
+    }
+
+    #[test]
+    fn test_reorderer_rejects_far_ahead() {
+        let messages = sequenced(4);
+        let mut reorderer = MessageReorderer::new(16);
+        let forged = |seq| SequencedMessage {
+            seq,
+            message: Message::new("FORGED"),
+        };
+
+        assert_eq!(reorderer.push(messages[0].clone()).count(), 1);
+        for seq in [17, 1_000, u64::MAX] {
+            assert_eq!(reorderer.push(forged(seq)).count(), 0);
+        }
+        assert_eq!((reorderer.next_seq(), reorderer.pending()), (1, 0));
+
+        // Legitimate messages are still delivered in order
+        assert_eq!(reorderer.push(messages[2].clone()).count(), 0);
+        let seqs: Vec<u64> = messages[1..]
+            .iter()
+            .flat_map(|m| reorderer.push(m.clone()).map(|m| m.seq).collect::<Vec<_>>())
+            .collect();
+        assert_eq!(seqs, [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_reorderer_end_of_sequence() {
+        let mut reorderer = MessageReorderer::new(4);
+        reorderer.next_seq = u64::MAX - 1;
+        let at = |seq| SequencedMessage {
+            seq,
+            message: Message::new("A"),
+        };
+
+        // The window is cut short at the end of the sequence
+        assert_eq!(reorderer.push(at(u64::MAX)).count(), 0);
+        let seqs: Vec<u64> = reorderer.push(at(u64::MAX - 1)).map(|m| m.seq).collect();
+        assert_eq!(seqs, [u64::MAX - 1, u64::MAX]);
+
+        // Nothing comes after the last number, not even it again
+        assert_eq!(reorderer.push(at(u64::MAX)).count(), 0);
+        assert_eq!(reorderer.push(at(0)).count(), 0);
+        assert_eq!(reorderer.pending(), 0);
+    }
+}
@@ -2,13 +2,16 @@
 //!
 //! Author: Ge Yang
 
-use crate::error::{Result, VmpError};
 use base64::Engine;
-use crate::type_registry::GLOBAL_TYPE_REGISTRY;
-use crate::types::{Message, VuerComponent};
-use crate::zdata::ZData;
+use crate::diff::ReconciledUpdate;
+use crate::error::{Result, VmpError};
+use crate::framing::{frame_len, FRAME_HEADER_SIZE};
+use crate::type_registry::{TypeRegistry, GLOBAL_TYPE_REGISTRY};
+use crate::types::{Message, VuerComponent, PROTOCOL_VERSION};
+use crate::zdata::{unwrap_unknown, ZData, ZDATA_RESERVED_KEYS};
 use serde::Serialize;
 use serde_json::Value;
+use std::borrow::Cow;
 
 /// Serialization options
 #[derive(Debug, Clone)]
@@ -17,18 +20,62 @@ pub struct SerializeOptions {
     pub recursive: bool,
 
     /// Encode undefined/null values
+    ///
+    /// When unset, null entries of objects are dropped, as the Python
+    /// implementation does with `None`. Nulls inside arrays and a top-level
+    /// null are kept, since they cannot be removed without changing meaning.
     pub encode_undefined: bool,
 
-    /// Use the global type registry for custom types
+    /// Use the type registry for custom types
     pub use_type_registry: bool,
+
+    /// Registry consulted instead of [`GLOBAL_TYPE_REGISTRY`] when
+    /// `use_type_registry` is set
+    pub type_registry: Option<TypeRegistry>,
+
+    /// Reject output larger than this many bytes (`None` for no limit)
+    pub max_message_size: Option<usize>,
+
+    /// Maximum nesting depth for recursively encoded values (`None` for no
+    /// limit)
+    pub max_depth: Option<usize>,
+
+    /// Set `vmp_version` to [`PROTOCOL_VERSION`] in
+    /// [`serialize_message_with_options`]
+    pub stamp_version: bool,
+
+    /// Write structs as maps keyed by field name (as other VMP
+    /// implementations expect) rather than as positional arrays, which
+    /// cannot leave out unset optional fields
+    pub struct_map: bool,
+
+    /// Sort the keys of every map, so equal values always encode to the
+    /// same bytes regardless of insertion or hash order
+    ///
+    /// Useful for content hashing. Fields may come out in a different order
+    /// than the Python implementation sends them, which decoders ignore.
+    pub canonical: bool,
+}
+
+impl SerializeOptions {
+    /// The registry selected by `type_registry`
+    pub(crate) fn registry(&self) -> &TypeRegistry {
+        self.type_registry.as_ref().unwrap_or(&GLOBAL_TYPE_REGISTRY)
+    }
 }
 
 impl Default for SerializeOptions {
     fn default() -> Self {
         Self {
             recursive: true,
-            encode_undefined: false,
+            encode_undefined: true,
             use_type_registry: true,
+            type_registry: None,
+            max_message_size: None,
+            max_depth: Some(crate::deserializer::DEFAULT_MAX_DEPTH),
+            stamp_version: false,
+            struct_map: true,
+            canonical: false,
         }
     }
 }
@@ -44,75 +91,756 @@ pub fn serialize<T: Serialize>(value: &T) -> Result<Vec<u8>> {
 }
 
 /// Serialize with custom options
+///
+/// By default structs are written as maps keyed by field name, matching the
+/// other VMP implementations and keeping optional fields that are skipped
+/// when unset from shifting their neighbours. With `struct_map` unset they
+/// are written as arrays instead, which is more compact; since a skipped
+/// field would shift the positions of the ones after it, this fails with
+/// `VmpError::Serialization` unless every optional field is set. Earlier
+/// releases always wrote arrays; [`crate::deserializer::deserialize`] reads
+/// both forms.
+///
+/// When `max_message_size` is set, encoding stops as soon as the output
+/// would grow past the limit, so oversized payloads are never fully buffered.
 pub fn serialize_with_options<T: Serialize>(
     value: &T,
-    _options: &SerializeOptions,
+    options: &SerializeOptions,
 ) -> Result<Vec<u8>> {
-    let bytes = rmp_serde::to_vec(value)
+    if !options.struct_map {
+        check_positional(value)?;
+    }
+    let bytes = match options.max_message_size {
+        None => {
+            let mut bytes = Vec::new();
+            write_msgpack(&mut bytes, value, options.struct_map)
+                .map_err(|e| VmpError::Serialization(e.to_string()))?;
+            bytes
+        }
+        Some(limit) => {
+            let mut writer = BoundedWriter {
+                buf: Vec::new(),
+                limit,
+                exceeded: false,
+            };
+            match write_msgpack(&mut writer, value, options.struct_map) {
+                Ok(()) => writer.buf,
+                Err(_) if writer.exceeded => {
+                    return Err(VmpError::Serialization(
+                        "message exceeds max_message_size".to_string(),
+                    ));
+                }
+                Err(e) => return Err(VmpError::Serialization(e.to_string())),
+            }
+        }
+    };
+
+    if options.canonical {
+        canonicalize(&bytes)
+    } else {
+        Ok(bytes)
+    }
+}
+
+/// Re-encode MessagePack bytes with the keys of every map sorted
+///
+/// String keys sort by their UTF-8 bytes and come before any other keys.
+/// Integers are already written in their shortest form, so the output has
+/// the same length as the input.
+fn canonicalize(bytes: &[u8]) -> Result<Vec<u8>> {
+    fn sort_maps(value: &mut rmpv::Value) {
+        match value {
+            rmpv::Value::Map(entries) => {
+                for (_, v) in entries.iter_mut() {
+                    sort_maps(v);
+                }
+                entries.sort_by_cached_key(|(key, _)| match key.as_str() {
+                    Some(s) => (0, s.as_bytes().to_vec()),
+                    None => {
+                        let mut encoded = Vec::new();
+                        rmpv::encode::write_value(&mut encoded, key)
+                            .expect("writing to a Vec cannot fail");
+                        (1, encoded)
+                    }
+                });
+            }
+            rmpv::Value::Array(items) => items.iter_mut().for_each(sort_maps),
+            _ => {}
+        }
+    }
+
+    let mut value = rmpv::decode::read_value(&mut &bytes[..])
+        .map_err(|e| VmpError::Serialization(e.to_string()))?;
+    sort_maps(&mut value);
+
+    let mut canonical = Vec::with_capacity(bytes.len());
+    rmpv::encode::write_value(&mut canonical, &value)
         .map_err(|e| VmpError::Serialization(e.to_string()))?;
-    Ok(bytes)
+    Ok(canonical)
+}
+
+/// Serialize a value to MessagePack, writing directly into `writer`
+pub fn serialize_into<T: Serialize, W: std::io::Write>(value: &T, mut writer: W) -> Result<()> {
+    write_msgpack(&mut writer, value, true).map_err(|e| VmpError::Serialization(e.to_string()))
+}
+
+fn write_msgpack<T: Serialize, W: std::io::Write>(
+    writer: &mut W,
+    value: &T,
+    struct_map: bool,
+) -> std::result::Result<(), rmp_serde::encode::Error> {
+    if struct_map {
+        rmp_serde::encode::write_named(writer, value)
+    } else {
+        rmp_serde::encode::write(writer, value)
+    }
+}
+
+/// Fail if `value` contains a struct that skips a field, since positional
+/// arrays would then shift every later field into the wrong place
+fn check_positional<T: Serialize>(value: &T) -> Result<()> {
+    match value.serialize(SkipDetector) {
+        Err(SkipError::Skipped(field)) => Err(VmpError::Serialization(format!(
+            "positional encoding cannot skip field `{}`; set struct_map or fill in every \
+             optional field",
+            field
+        ))),
+        // Other errors are reported by the real encoding pass
+        _ => Ok(()),
+    }
+}
+
+/// Error of [`SkipDetector`]: the first skipped field, as `Struct.field`
+#[derive(Debug)]
+enum SkipError {
+    Skipped(String),
+    Custom(String),
+}
+
+impl std::fmt::Display for SkipError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SkipError::Skipped(field) => write!(f, "skipped field {}", field),
+            SkipError::Custom(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl std::error::Error for SkipError {}
+
+impl serde::ser::Error for SkipError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        SkipError::Custom(msg.to_string())
+    }
+}
+
+/// Serializer that writes nothing and only looks for skipped struct fields
+struct SkipDetector;
+
+/// [`SkipDetector`] inside a struct, naming it in the error
+struct SkipDetectorStruct(&'static str);
+
+macro_rules! ignore_values {
+    ($($method:ident($ty:ty)),* $(,)?) => {
+        $(fn $method(self, _: $ty) -> std::result::Result<(), SkipError> {
+            Ok(())
+        })*
+    };
+}
+
+impl serde::Serializer for SkipDetector {
+    type Ok = ();
+    type Error = SkipError;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = SkipDetectorStruct;
+    type SerializeStructVariant = SkipDetectorStruct;
+
+    ignore_values!(
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_i128(i128),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_u128(u128),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+        serialize_str(&str),
+        serialize_bytes(&[u8]),
+        serialize_unit_struct(&'static str),
+    );
+
+    fn serialize_none(self) -> std::result::Result<(), SkipError> {
+        Ok(())
+    }
+
+    fn serialize_unit(self) -> std::result::Result<(), SkipError> {
+        Ok(())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(
+        self,
+        value: &T,
+    ) -> std::result::Result<(), SkipError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+    ) -> std::result::Result<(), SkipError> {
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> std::result::Result<(), SkipError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> std::result::Result<(), SkipError> {
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> std::result::Result<Self, SkipError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> std::result::Result<Self, SkipError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> std::result::Result<Self, SkipError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> std::result::Result<Self, SkipError> {
+        Ok(self)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> std::result::Result<Self, SkipError> {
+        Ok(self)
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        _len: usize,
+    ) -> std::result::Result<SkipDetectorStruct, SkipError> {
+        Ok(SkipDetectorStruct(name))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> std::result::Result<SkipDetectorStruct, SkipError> {
+        Ok(SkipDetectorStruct(variant))
+    }
+}
+
+impl serde::ser::SerializeSeq for SkipDetector {
+    type Ok = ();
+    type Error = SkipError;
+
+    fn serialize_element<T: ?Sized + Serialize>(
+        &mut self,
+        value: &T,
+    ) -> std::result::Result<(), SkipError> {
+        value.serialize(SkipDetector)
+    }
+
+    fn end(self) -> std::result::Result<(), SkipError> {
+        Ok(())
+    }
+}
+
+impl serde::ser::SerializeTuple for SkipDetector {
+    type Ok = ();
+    type Error = SkipError;
+
+    fn serialize_element<T: ?Sized + Serialize>(
+        &mut self,
+        value: &T,
+    ) -> std::result::Result<(), SkipError> {
+        value.serialize(SkipDetector)
+    }
+
+    fn end(self) -> std::result::Result<(), SkipError> {
+        Ok(())
+    }
+}
+
+impl serde::ser::SerializeTupleStruct for SkipDetector {
+    type Ok = ();
+    type Error = SkipError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        value: &T,
+    ) -> std::result::Result<(), SkipError> {
+        value.serialize(SkipDetector)
+    }
+
+    fn end(self) -> std::result::Result<(), SkipError> {
+        Ok(())
+    }
+}
+
+impl serde::ser::SerializeTupleVariant for SkipDetector {
+    type Ok = ();
+    type Error = SkipError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        value: &T,
+    ) -> std::result::Result<(), SkipError> {
+        value.serialize(SkipDetector)
+    }
+
+    fn end(self) -> std::result::Result<(), SkipError> {
+        Ok(())
+    }
+}
+
+impl serde::ser::SerializeMap for SkipDetector {
+    type Ok = ();
+    type Error = SkipError;
+
+    fn serialize_key<T: ?Sized + Serialize>(
+        &mut self,
+        key: &T,
+    ) -> std::result::Result<(), SkipError> {
+        key.serialize(SkipDetector)
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(
+        &mut self,
+        value: &T,
+    ) -> std::result::Result<(), SkipError> {
+        value.serialize(SkipDetector)
+    }
+
+    fn end(self) -> std::result::Result<(), SkipError> {
+        Ok(())
+    }
+}
+
+impl serde::ser::SerializeStruct for SkipDetectorStruct {
+    type Ok = ();
+    type Error = SkipError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> std::result::Result<(), SkipError> {
+        value.serialize(SkipDetector)
+    }
+
+    fn skip_field(&mut self, key: &'static str) -> std::result::Result<(), SkipError> {
+        Err(SkipError::Skipped(format!("{}.{}", self.0, key)))
+    }
+
+    fn end(self) -> std::result::Result<(), SkipError> {
+        Ok(())
+    }
+}
+
+impl serde::ser::SerializeStructVariant for SkipDetectorStruct {
+    type Ok = ();
+    type Error = SkipError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> std::result::Result<(), SkipError> {
+        value.serialize(SkipDetector)
+    }
+
+    fn skip_field(&mut self, key: &'static str) -> std::result::Result<(), SkipError> {
+        Err(SkipError::Skipped(format!("{}.{}", self.0, key)))
+    }
+
+    fn end(self) -> std::result::Result<(), SkipError> {
+        Ok(())
+    }
+}
+
+/// Output buffer that refuses writes past a size limit
+struct BoundedWriter {
+    buf: Vec<u8>,
+    limit: usize,
+    exceeded: bool,
+}
+
+impl std::io::Write for BoundedWriter {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        if self.buf.len() + data.len() > self.limit {
+            self.exceeded = true;
+            return Err(std::io::Error::other("max_message_size exceeded"));
+        }
+        self.buf.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
 }
 
 /// Serialize a message to MessagePack
+///
+/// Custom types registered in the global type registry are converted to
+/// ZData in the `data`, `value`, `args` and `kwargs` payloads.
 pub fn serialize_message(message: &Message) -> Result<Vec<u8>> {
-    serialize(message)
+    serialize_message_with_options(message, &SerializeOptions::default())
+}
+
+/// Serialize a message as a map keyed by field name, whatever the default
+/// [`SerializeOptions::struct_map`]
+///
+/// For MessagePack clients that only understand string keys, such as those
+/// for Lua or Ruby. Read back with [`crate::deserializer::deserialize_message`]
+/// or [`crate::deserializer::deserialize_message_named`].
+pub fn serialize_message_named(message: &Message) -> Result<Vec<u8>> {
+    let options = SerializeOptions {
+        struct_map: true,
+        ..Default::default()
+    };
+    serialize_message_with_options(message, &options)
+}
+
+/// Serialize a message, appending it to `buf`
+///
+/// Produces the same bytes as [`serialize_message`] and returns how many
+/// were appended. On error `buf` is left as it was.
+pub fn serialize_message_into(message: &Message, buf: &mut Vec<u8>) -> Result<usize> {
+    let message = prepare_message(message, &SerializeOptions::default())?;
+    let start = buf.len();
+    serialize_into(&*message, &mut *buf).inspect_err(|_| buf.truncate(start))?;
+    Ok(buf.len() - start)
+}
+
+/// Serialize a message as one length-prefixed frame for stream transports
+///
+/// The frame is a big-endian `u32` length followed by the bytes of
+/// [`serialize_message`]. Read it back with
+/// [`crate::deserializer::read_framed`].
+pub fn frame_message(message: &Message) -> Result<Vec<u8>> {
+    let mut buf = vec![0; FRAME_HEADER_SIZE];
+    serialize_message_into(message, &mut buf)?;
+    let len = frame_len(&buf[FRAME_HEADER_SIZE..])?;
+    buf[..FRAME_HEADER_SIZE].copy_from_slice(&len.to_be_bytes());
+    Ok(buf)
+}
+
+/// Serialize a message with custom options
+///
+/// When `recursive` and `use_type_registry` are both set, the payload
+/// fields are run through [`encode_value_recursive`] first. Values that are
+/// already ZData are left as they are.
+pub fn serialize_message_with_options(
+    message: &Message,
+    options: &SerializeOptions,
+) -> Result<Vec<u8>> {
+    serialize_with_options(&*prepare_message(message, options)?, options)
+}
+
+/// Apply the payload encoding and version stamping selected by `options`
+pub(crate) fn prepare_message<'a>(
+    message: &'a Message,
+    options: &SerializeOptions,
+) -> Result<Cow<'a, Message>> {
+    let encode_payloads = options.recursive && options.use_type_registry;
+    if !encode_payloads && !options.stamp_version {
+        return Ok(Cow::Borrowed(message));
+    }
+
+    let mut message = message.clone();
+    if encode_payloads {
+        let encode = |value: Value| encode_value_recursive_owned(value, options);
+        message.data = message.data.take().map(encode).transpose()?;
+        message.value = message.value.take().map(encode).transpose()?;
+        message.args = message
+            .args
+            .take()
+            .map(|args| args.into_iter().map(encode).collect::<Result<_>>())
+            .transpose()?;
+        message.kwargs = message
+            .kwargs
+            .take()
+            .map(|kwargs| {
+                kwargs
+                    .into_iter()
+                    .map(|(key, value)| Ok((key, encode(value)?)))
+                    .collect::<Result<_>>()
+            })
+            .transpose()?;
+    }
+    if options.stamp_version {
+        message.vmp_version = Some(PROTOCOL_VERSION.to_string());
+    }
+    Ok(Cow::Owned(message))
+}
+
+/// Serialize a message, failing if the output exceeds `max_bytes`
+pub fn serialize_message_bounded(message: &Message, max_bytes: usize) -> Result<Vec<u8>> {
+    let options = SerializeOptions {
+        max_message_size: Some(max_bytes),
+        ..Default::default()
+    };
+    serialize_message_with_options(message, &options)
+}
+
+/// Serialize several messages into one MessagePack array
+///
+/// Each element is encoded as by [`serialize_message`], so a batch costs
+/// one frame instead of one per message. Read it back with
+/// [`crate::deserializer::deserialize_batch`].
+pub fn serialize_batch(messages: &[Message]) -> Result<Vec<u8>> {
+    let options = SerializeOptions::default();
+    let prepared = messages
+        .iter()
+        .map(|message| prepare_message(message, &options))
+        .collect::<Result<Vec<_>>>()?;
+    serialize_with_options(&prepared, &options)
 }
 
 /// Serialize a Vuer component tree to MessagePack
 ///
-/// This recursively encodes the component and all its children,
-/// including any ZData types in the component properties.
+/// Custom types registered in the global type registry are converted to
+/// ZData in the props of the component and all its children.
 pub fn serialize_component(component: &VuerComponent) -> Result<Vec<u8>> {
-    serialize(component)
+    serialize_component_with_options(component, &SerializeOptions::default())
+}
+
+/// Serialize a Vuer component tree with custom options
+///
+/// When `recursive` and `use_type_registry` are both set, the props of
+/// every component in the tree are run through [`encode_value_recursive`]
+/// first. Children keep their order and props that are already ZData are
+/// left as they are.
+pub fn serialize_component_with_options(
+    component: &VuerComponent,
+    options: &SerializeOptions,
+) -> Result<Vec<u8>> {
+    serialize_with_options(&*prepare_component(component, options)?, options)
+}
+
+/// Apply the prop encoding selected by `options` to a whole tree
+pub(crate) fn prepare_component<'a>(
+    component: &'a VuerComponent,
+    options: &SerializeOptions,
+) -> Result<Cow<'a, VuerComponent>> {
+    if !(options.recursive && options.use_type_registry) {
+        return Ok(Cow::Borrowed(component));
+    }
+
+    let mut component = component.clone();
+    let mut stack = vec![&mut component];
+    while let Some(node) = stack.pop() {
+        for value in node.props.values_mut() {
+            *value = encode_value_recursive_owned(value.take(), options)?;
+        }
+        stack.extend(node.children.iter_mut().flatten());
+    }
+    Ok(Cow::Owned(component))
+}
+
+/// Serialize the result of [`VuerComponent::reconcile`] to MessagePack
+pub fn serialize_reconciled_update(update: &ReconciledUpdate) -> Result<Vec<u8>> {
+    serialize(update)
 }
 
 /// Recursively encode a JSON value, converting custom types to ZData
 pub fn encode_value_recursive(value: &Value, options: &SerializeOptions) -> Result<Value> {
-    if !options.recursive {
-        return Ok(value.clone());
+    encode_value_recursive_owned(value.clone(), options)
+}
+
+/// Like [`encode_value_recursive`], but consumes `value` and rewrites it in
+/// place, so parts that need no encoding are never copied
+pub fn encode_value_recursive_owned(mut value: Value, options: &SerializeOptions) -> Result<Value> {
+    if options.recursive {
+        encode_value_at_depth(&mut value, options, 0)?;
+    }
+    Ok(value)
+}
+
+fn encode_value_at_depth(
+    value: &mut Value,
+    options: &SerializeOptions,
+    depth: usize,
+) -> Result<()> {
+    if matches!(value, Value::Object(_) | Value::Array(_))
+        && options.max_depth.is_some_and(|max| depth >= max)
+    {
+        return Err(VmpError::Serialization("max depth exceeded".to_string()));
+    }
+
+    // Unknown types kept by the deserializer go out exactly as received
+    if unwrap_unknown(value).is_some() {
+        if let Value::Object(wrapper) = value.take() {
+            *value = wrapper.into_iter().next().map(|(_, original)| original).unwrap_or_default();
+        }
+        return Ok(());
+    }
+
+    // Try to encode using type registry
+    if options.use_type_registry
+        && value.as_object().is_some_and(|map| !map.contains_key("ztype"))
+        && let Some(zdata) = options.registry().try_encode(value)
+    {
+        *value = serde_json::to_value(&zdata)?;
     }
 
     match value {
         Value::Object(map) => {
-            // Check if this is already a ZData object
-            if map.contains_key("ztype") {
-                return Ok(value.clone());
+            // Only the extra fields of a ZData can hold further custom types
+            let is_zdata = map.contains_key("ztype");
+            if !options.encode_undefined {
+                map.retain(|key, val| {
+                    !val.is_null() || (is_zdata && ZDATA_RESERVED_KEYS.contains(&key.as_str()))
+                });
             }
-
-            // Try to encode using type registry
-            if options.use_type_registry {
-                if let Some(zdata) = GLOBAL_TYPE_REGISTRY.try_encode(value) {
-                    return Ok(serde_json::to_value(&zdata)?);
+            for (key, val) in map.iter_mut() {
+                if !is_zdata || !ZDATA_RESERVED_KEYS.contains(&key.as_str()) {
+                    encode_value_at_depth(val, options, depth + 1)?;
                 }
             }
-
-            // Recursively process object fields
-            let mut result = serde_json::Map::new();
-            for (key, val) in map {
-                let encoded = encode_value_recursive(val, options)?;
-                result.insert(key.clone(), encoded);
-            }
-            Ok(Value::Object(result))
+            Ok(())
         }
         Value::Array(arr) => {
             // Recursively process array elements
-            let encoded: Result<Vec<Value>> = arr
-                .iter()
-                .map(|v| encode_value_recursive(v, options))
-                .collect();
-            Ok(Value::Array(encoded?))
+            for val in arr.iter_mut() {
+                encode_value_at_depth(val, options, depth + 1)?;
+            }
+            Ok(())
         }
-        Value::Null if !options.encode_undefined => {
-            Err(VmpError::Serialization("Null value not allowed".to_string()))
+        _ => Ok(()),
+    }
+}
+
+/// Serialize a value to JSON text, for clients that cannot decode MessagePack
+///
+/// The value goes through the same recursive ZData encoding as messages, and
+/// the bytes of ZData binary buffers are written to `b` as standard base64.
+pub fn serialize_json<T: Serialize>(value: &T) -> Result<String> {
+    serialize_json_with_options(value, &SerializeOptions::default())
+}
+
+/// Serialize to JSON text with custom options
+///
+/// `struct_map` has no effect and keys always come out sorted, so the output
+/// is canonical whether or not `canonical` is set.
+pub fn serialize_json_with_options<T: Serialize>(
+    value: &T,
+    options: &SerializeOptions,
+) -> Result<String> {
+    let mut value = encode_value_recursive_owned(serde_json::to_value(value)?, options)?;
+    encode_binary_fields(&mut value);
+    let s = serde_json::to_string(&value)?;
+    if options.max_message_size.is_some_and(|limit| s.len() > limit) {
+        return Err(VmpError::Serialization("message exceeds max_message_size".to_string()));
+    }
+    Ok(s)
+}
+
+/// Replace the byte arrays of ZData `b` fields with base64 strings
+pub(crate) fn encode_binary_fields(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            if map.contains_key("ztype")
+                && let Some(b) = map.get_mut("b")
+                && let Value::Array(items) = b
+            {
+                let bytes: Option<Vec<u8>> = items
+                    .iter()
+                    .map(|item| item.as_u64().and_then(|n| u8::try_from(n).ok()))
+                    .collect();
+                if let Some(bytes) = bytes {
+                    *b = Value::String(base64::engine::general_purpose::STANDARD.encode(bytes));
+                }
+            }
+            map.values_mut().for_each(encode_binary_fields);
+        }
+        Value::Array(items) => items.iter_mut().for_each(encode_binary_fields),
+        _ => {}
+    }
+}
+
+/// Base64 alphabet and padding used for text encodings of MessagePack
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Base64Variant {
+    /// Standard alphabet without padding
+    Standard,
+    /// Standard alphabet with `=` padding
+    StandardPad,
+    /// URL and filename safe alphabet without padding
+    UrlSafe,
+    /// URL and filename safe alphabet with `=` padding
+    UrlSafePad,
+}
+
+impl Base64Variant {
+    pub(crate) fn engine(self) -> &'static base64::engine::GeneralPurpose {
+        use base64::engine::general_purpose;
+
+        match self {
+            Base64Variant::Standard => &general_purpose::STANDARD_NO_PAD,
+            Base64Variant::StandardPad => &general_purpose::STANDARD,
+            Base64Variant::UrlSafe => &general_purpose::URL_SAFE_NO_PAD,
+            Base64Variant::UrlSafePad => &general_purpose::URL_SAFE,
         }
-        _ => Ok(value.clone()),
     }
 }
 
 /// Serialize to base64-encoded MessagePack
 pub fn serialize_to_base64<T: Serialize>(value: &T) -> Result<String> {
+    serialize_to_base64_with(value, Base64Variant::StandardPad)
+}
+
+/// Serialize to unpadded URL-safe base64, for use in URLs and cookies
+pub fn serialize_to_base64_url<T: Serialize>(value: &T) -> Result<String> {
+    serialize_to_base64_with(value, Base64Variant::UrlSafe)
+}
+
+/// Serialize to MessagePack encoded with the given base64 variant
+pub fn serialize_to_base64_with<T: Serialize>(value: &T, variant: Base64Variant) -> Result<String> {
     let bytes = serialize(value)?;
-    Ok(base64::engine::general_purpose::STANDARD.encode(&bytes))
+    Ok(variant.engine().encode(&bytes))
 }
 
 /// Helper to convert ZData to MessagePack bytes
@@ -124,6 +852,7 @@ pub fn zdata_to_bytes(zdata: &ZData) -> Result<Vec<u8>> {
 mod tests {
     use super::*;
     use serde_json::json;
+    use std::collections::HashMap;
 
     #[test]
     fn test_serialize_message() {
@@ -134,6 +863,17 @@ mod tests {
         assert!(!bytes.is_empty());
     }
 
+    #[test]
+    fn test_structs_written_as_maps() {
+        let msg = Message::new("PING").with_data(json!(1));
+
+        // Only ts, etype and data are written, each under its name
+        let bytes = serialize_message(&msg).unwrap();
+        assert_eq!(bytes[0], 0x83);
+        assert!(bytes.windows(6).any(|w| w == b"\xa5etype"));
+        assert_eq!(crate::deserializer::deserialize_message(&bytes).unwrap(), msg);
+    }
+
     #[test]
     fn test_serialize_component() {
         let component = VuerComponent::new("scene")
@@ -165,4 +905,472 @@ mod tests {
         let bytes = zdata_to_bytes(&zdata).unwrap();
         assert!(!bytes.is_empty());
     }
+
+    #[test]
+    fn test_max_message_size() {
+        let zdata = ZData::new("test.Blob")
+            .with_binary(vec![0u8; 10 * 1024 * 1024]);
+
+        let options = SerializeOptions {
+            max_message_size: Some(1024 * 1024),
+            ..Default::default()
+        };
+        let err = serialize_with_options(&zdata, &options).unwrap_err();
+        assert!(
+            matches!(err, VmpError::Serialization(msg) if msg == "message exceeds max_message_size")
+        );
+
+        let options = SerializeOptions {
+            max_message_size: Some(11 * 1024 * 1024),
+            ..Default::default()
+        };
+        let bytes = serialize_with_options(&zdata, &options).unwrap();
+        assert_eq!(bytes, zdata_to_bytes(&zdata).unwrap());
+    }
+
+    #[test]
+    fn test_serialize_message_bounded() {
+        let msg = Message::new("TEST_EVENT").with_data(json!("x".repeat(2048)));
+
+        assert!(serialize_message_bounded(&msg, 1024).is_err());
+
+        let bytes = serialize_message_bounded(&msg, 4096).unwrap();
+        assert_eq!(bytes, serialize_message(&msg).unwrap());
+    }
+
+    #[test]
+    fn test_struct_map_encoding() {
+        let msg = Message::new("CLICK").with_data(json!({"x": 1}));
+
+        // Named maps carry the field names other implementations look up
+        let named = serialize_message(&msg).unwrap();
+        assert_eq!(named[0], 0x83);
+        for key in ["ts", "etype", "data"] {
+            let mut encoded = vec![0xa0 | key.len() as u8];
+            encoded.extend_from_slice(key.as_bytes());
+            assert!(named.windows(encoded.len()).any(|w| w == encoded), "missing {}", key);
+        }
+        assert_eq!(crate::deserializer::deserialize_message(&named).unwrap(), msg);
+
+        // Positional arrays only roundtrip when every optional field is set
+        let mut full = msg.clone().with_correlation("req-1").with_vmp_version("1.0");
+        full.expires_at = Some(full.ts + 1000);
+        full.rtype = Some("CLICK_RESPONSE".to_string());
+        full.args = Some(vec![json!(1)]);
+        full.kwargs = Some(HashMap::from([("k".to_string(), json!("v"))]));
+        full.value = Some(json!(true));
+        full.metadata = Some(HashMap::from([("trace".to_string(), json!("t"))]));
+        full.ok = Some(true);
+        full.error = Some("e".to_string());
+        full.error_kind = Some(crate::error::ErrorKind::Internal);
+        full.is_final = Some(true);
+        full.attempt = Some(1);
+        let options = SerializeOptions {
+            struct_map: false,
+            ..Default::default()
+        };
+        let compact = serialize_message_with_options(&full, &options).unwrap();
+        assert_eq!(compact[..3], [0xdc, 0x00, 0x10]);
+        assert!(!compact.windows(6).any(|w| w == b"\xa5etype"));
+        assert!(compact.len() < serialize_message(&full).unwrap().len());
+        assert_eq!(crate::deserializer::deserialize_message(&compact).unwrap(), full);
+
+        // A skipped field would shift the rest, so it is refused
+        let err = serialize_message_with_options(&msg, &options).unwrap_err();
+        assert!(matches!(err, VmpError::Serialization(m) if m.contains("`Message.expires_at`")));
+        let nested = vec![full.clone(), msg.clone()];
+        assert!(serialize_with_options(&nested, &options).is_err());
+        assert!(serialize_with_options(&vec![full], &options).is_ok());
+    }
+
+    #[test]
+    fn test_named_message() {
+        use crate::deserializer::{deserialize_message, deserialize_message_named};
+
+        let msg = Message::new("CLICK").with_value(json!({"x": 1}));
+        let named = serialize_message_named(&msg).unwrap();
+        assert_eq!(named, serialize_message(&msg).unwrap());
+        assert!(named.windows(6).any(|w| w == b"\xa5etype"));
+        assert_eq!(deserialize_message(&named).unwrap(), msg);
+        assert_eq!(deserialize_message_named(&named).unwrap(), msg);
+
+        // Positional input is still understood
+        let mut full = Message::new("CLICK").with_value(json!(1)).with_data(json!(2));
+        full.expires_at = Some(full.ts + 1);
+        full.rtype = Some("r".to_string());
+        full.args = Some(vec![]);
+        full.kwargs = Some(HashMap::new());
+        full.correlation_id = Some("c".to_string());
+        full.metadata = Some(HashMap::new());
+        full.vmp_version = Some("1.0".to_string());
+        full.ok = Some(true);
+        full.error = Some("e".to_string());
+        full.error_kind = Some(crate::error::ErrorKind::Internal);
+        full.is_final = Some(false);
+        full.attempt = Some(1);
+        let options = SerializeOptions {
+            struct_map: false,
+            ..Default::default()
+        };
+        let positional = serialize_message_with_options(&full, &options).unwrap();
+        assert_eq!(deserialize_message_named(&positional).unwrap(), full);
+    }
+
+    #[test]
+    fn test_serialize_into_buffer() {
+        let msg = Message::new("UPDATE")
+            .with_data(json!({"position": [1.0, 2.0, 3.0]}))
+            .with_correlation("req-7");
+        let expected = serialize_message(&msg).unwrap();
+
+        let mut buf = Vec::new();
+        assert_eq!(serialize_message_into(&msg, &mut buf).unwrap(), expected.len());
+        assert_eq!(buf, expected);
+
+        // Appending keeps the existing contents
+        let written = serialize_message_into(&msg, &mut buf).unwrap();
+        assert_eq!(written, expected.len());
+        assert_eq!(&buf[..expected.len()], &expected[..]);
+        assert_eq!(&buf[expected.len()..], &expected[..]);
+
+        let component = VuerComponent::new("scene").with_prop("background", json!("#000"));
+        let mut out = std::io::Cursor::new(Vec::new());
+        serialize_into(&component, &mut out).unwrap();
+        assert_eq!(out.into_inner(), serialize_component(&component).unwrap());
+    }
+
+    #[test]
+    fn test_serialize_message_encodes_payloads() {
+        let registry = TypeRegistry::new();
+        registry.register(
+            "test.WireVec3",
+            |value| Ok(ZData::new("test.WireVec3").with_field("xyz", value["wire_vec3"].clone())),
+            |zdata| Ok(json!({"wire_vec3": zdata.get_field("xyz").cloned()})),
+            Some(std::sync::Arc::new(|value: &Value| value.get("wire_vec3").is_some())),
+        );
+        let options = SerializeOptions {
+            type_registry: Some(registry),
+            ..Default::default()
+        };
+
+        let vec3 = json!({"wire_vec3": [1, 2, 3]});
+        let zdata = json!({"ztype": "test.WireVec3", "xyz": [1, 2, 3]});
+        let mut msg = Message::new("UPDATE")
+            .with_data(json!({"position": vec3, "plain": {"a": null}}))
+            .with_value(vec3.clone())
+            .with_rtype("rpc-1");
+        msg.args = Some(vec![vec3.clone(), zdata.clone()]);
+        msg.kwargs = Some(HashMap::from([("target".to_string(), vec3.clone())]));
+
+        let bytes = serialize_message_with_options(&msg, &options).unwrap();
+        let wire: Message = rmp_serde::from_slice(&bytes).unwrap();
+        assert_eq!(wire.data, Some(json!({"position": zdata, "plain": {"a": null}})));
+        assert_eq!(wire.value, Some(zdata.clone()));
+        // Already-encoded ZData is not encoded a second time
+        assert_eq!(wire.args, Some(vec![zdata.clone(), zdata.clone()]));
+        assert_eq!(wire.kwargs.unwrap()["target"], zdata);
+
+        let options = SerializeOptions {
+            use_type_registry: false,
+            ..options
+        };
+        let raw = serialize_message_with_options(&msg, &options).unwrap();
+        let wire: Message = rmp_serde::from_slice(&raw).unwrap();
+        assert_eq!(wire.value, Some(vec3));
+    }
+
+    #[test]
+    fn test_encode_nested_zdata() {
+        let registry = TypeRegistry::new();
+        registry.register(
+            "test.EncodedPoints",
+            |value| Ok(ZData::new("test.EncodedPoints").with_field("n", value["points"].clone())),
+            |zdata| Ok(json!({"points": zdata.get_field("n").cloned()})),
+            Some(std::sync::Arc::new(|value: &Value| value.get("points").is_some())),
+        );
+        let options = SerializeOptions {
+            type_registry: Some(registry),
+            ..Default::default()
+        };
+
+        let mesh = json!({
+            "ztype": "test.EncodedMesh",
+            "vertices": {"points": 3},
+        });
+        let encoded = encode_value_recursive(&mesh, &options).unwrap();
+        assert_eq!(
+            encoded,
+            json!({
+                "ztype": "test.EncodedMesh",
+                "vertices": {"ztype": "test.EncodedPoints", "n": 3},
+            })
+        );
+
+        let mut deep = json!(1);
+        for _ in 0..200 {
+            deep = json!({"ztype": "test.Wrapper", "inner": deep});
+        }
+        assert!(encode_value_recursive(&deep, &options).is_err());
+    }
+
+    #[test]
+    fn test_base64_variants() {
+        // Bytes chosen so that the standard alphabet needs '+' and '/'
+        let value = serde_bytes::ByteBuf::from(vec![0xfb, 0xff, 0xbf, 0xfe, 0xff]);
+
+        let url = serialize_to_base64_url(&value).unwrap();
+        assert!(!url.contains(['+', '/', '=']));
+        let standard = serialize_to_base64(&value).unwrap();
+        assert!(standard.contains(['+', '/']) && standard.ends_with('='));
+
+        for variant in [
+            Base64Variant::Standard,
+            Base64Variant::StandardPad,
+            Base64Variant::UrlSafe,
+            Base64Variant::UrlSafePad,
+        ] {
+            let encoded = serialize_to_base64_with(&value, variant).unwrap();
+            let decoded: serde_bytes::ByteBuf =
+                crate::deserializer::deserialize_from_base64_with(&encoded, variant).unwrap();
+            assert_eq!(decoded, value);
+        }
+
+        let decoded: serde_bytes::ByteBuf =
+            crate::deserializer::deserialize_from_base64_url(&url).unwrap();
+        assert_eq!(decoded, value);
+        assert!(crate::deserializer::deserialize_from_base64_url::<serde_bytes::ByteBuf>(
+            &standard
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_encode_value_recursive_owned() {
+        let layers: Vec<Value> = (0..200)
+            .map(|i| {
+                json!({
+                    "name": format!("layer-{}", i),
+                    "path": (0..50).map(|j| json!([i, j, 0.5])).collect::<Vec<_>>(),
+                    "tensor": {"ztype": "test.Tensor", "b": [1, 2, 3], "meta": {"frame": i}},
+                })
+            })
+            .collect();
+        let value = json!({"scene": {"layers": layers, "background": null}});
+
+        let options = SerializeOptions::default();
+        let borrowed = encode_value_recursive(&value, &options).unwrap();
+        let owned = encode_value_recursive_owned(value.clone(), &options).unwrap();
+        assert_eq!(owned, borrowed);
+        assert_eq!(owned, value);
+
+        let strict = SerializeOptions {
+            encode_undefined: false,
+            ..Default::default()
+        };
+        let stripped = encode_value_recursive_owned(value, &strict).unwrap();
+        assert!(stripped["scene"].get("background").is_none());
+        assert_eq!(stripped["scene"]["layers"], borrowed["scene"]["layers"]);
+    }
+
+    #[test]
+    fn test_encode_undefined() {
+        let value = json!({
+            "parent": null,
+            "name": "cube",
+            "path": [null, 1, null],
+            "nested": {"color": null, "children": [{"key": null, "id": 3}, null]},
+            "mesh": {"ztype": "test.Mesh", "dtype": null, "label": null},
+        });
+
+        let keep = encode_value_recursive(&value, &SerializeOptions::default()).unwrap();
+        assert_eq!(keep, value);
+
+        let options = SerializeOptions {
+            encode_undefined: false,
+            ..Default::default()
+        };
+        let dropped = encode_value_recursive(&value, &options).unwrap();
+        assert_eq!(
+            dropped,
+            json!({
+                "name": "cube",
+                "path": [null, 1, null],
+                "nested": {"children": [{"id": 3}, null]},
+                "mesh": {"ztype": "test.Mesh", "dtype": null},
+            })
+        );
+        assert_eq!(encode_value_recursive(&Value::Null, &options).unwrap(), Value::Null);
+
+        let msg = Message::new("SET").with_data(json!({"parent": null, "id": 1}));
+        let bytes = serialize_message_with_options(&msg, &options).unwrap();
+        let restored = crate::deserializer::deserialize_message(&bytes).unwrap();
+        assert_eq!(restored.data, Some(json!({"id": 1})));
+    }
+
+    #[test]
+    fn test_canonical_encoding() {
+        let keys = ["color", "position", "scale", "visible", "opacity", "rotation", "name"];
+        let build = |order: &[&str]| {
+            let mut component = VuerComponent::new("mesh");
+            for key in order {
+                component = component.with_prop(*key, json!({"value": key, "n": key.len()}));
+            }
+            component
+        };
+        let forward = build(&keys);
+        let reversed: Vec<&str> = keys.iter().rev().copied().collect();
+        let backward = build(&reversed);
+
+        let canonical = SerializeOptions {
+            canonical: true,
+            ..Default::default()
+        };
+        let a = serialize_with_options(&forward, &canonical).unwrap();
+        let b = serialize_with_options(&backward, &canonical).unwrap();
+        assert_eq!(a, b);
+        assert_eq!(a.len(), serialize(&forward).unwrap().len());
+        assert_eq!(crate::deserializer::deserialize_component(&a).unwrap(), forward);
+
+        // ZData extra fields keep insertion order unless canonical
+        let blob = |first: &str, second: &str| {
+            ZData::new("test.Blob")
+                .with_binary(vec![1, 2, 3])
+                .with_field(first, json!(first))
+                .with_field(second, json!(second))
+        };
+        let (x, y) = (blob("alpha", "beta"), blob("beta", "alpha"));
+        assert_ne!(serialize(&x).unwrap(), serialize(&y).unwrap());
+        let x_bytes = serialize_with_options(&x, &canonical).unwrap();
+        assert_eq!(x_bytes, serialize_with_options(&y, &canonical).unwrap());
+
+        // Binary data stays a MessagePack bin field
+        assert!(x_bytes.windows(5).any(|w| w == [0xc4, 3, 1, 2, 3]));
+        let restored: ZData = crate::deserializer::deserialize(&x_bytes).unwrap();
+        assert_eq!(restored, x);
+    }
+
+    #[test]
+    fn test_serialize_component_encodes_props() {
+        let registry = TypeRegistry::new();
+        registry.register(
+            "test.ComponentVertices",
+            |value| {
+                let flat: Vec<u8> = value["component_vertices"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|v| v.as_u64().unwrap() as u8)
+                    .collect();
+                Ok(ZData::new("test.ComponentVertices").with_binary(flat))
+            },
+            |zdata| Ok(json!({"component_vertices": zdata.b.as_deref().unwrap()})),
+            Some(std::sync::Arc::new(|value: &Value| {
+                value.get("component_vertices").is_some()
+            })),
+        );
+        let options = SerializeOptions {
+            type_registry: Some(registry.clone()),
+            ..Default::default()
+        };
+        let decode = crate::deserializer::DeserializeOptions {
+            type_registry: Some(registry),
+            ..Default::default()
+        };
+
+        let existing = ZData::new("test.Opaque").with_binary(vec![9, 9]);
+        let existing = serde_json::to_value(&existing).unwrap();
+        let scene = VuerComponent::new("scene")
+            .with_child(
+                VuerComponent::new("mesh")
+                    .with_key("a")
+                    .with_prop("geometry", json!({"component_vertices": [1, 2, 3]})),
+            )
+            .with_child(VuerComponent::new("texture").with_key("b").with_prop("data", existing))
+            .with_child(VuerComponent::new("light").with_key("c"));
+
+        let bytes = serialize_component_with_options(&scene, &options).unwrap();
+        let name = b"test.ComponentVertices";
+        assert!(bytes.windows(name.len()).any(|w| w == name));
+
+        let restored =
+            crate::deserializer::deserialize_component_with_options(&bytes, &decode).unwrap();
+        let children = restored.children.as_ref().unwrap();
+        let keys: Vec<_> = children.iter().map(|c| c.key.clone().unwrap()).collect();
+        assert_eq!(keys, [json!("a"), json!("b"), json!("c")]);
+        assert_eq!(children[0].props["geometry"], json!({"component_vertices": [1, 2, 3]}));
+        assert_eq!(children[1].props["data"], scene.children.as_ref().unwrap()[1].props["data"]);
+
+        // Without the registry the raw JSON goes out as is
+        let options = SerializeOptions {
+            use_type_registry: false,
+            ..options
+        };
+        let raw = serialize_component_with_options(&scene, &options).unwrap();
+        assert!(!raw.windows(name.len()).any(|w| w == name));
+        assert_eq!(
+            crate::deserializer::deserialize_component_with_options(&raw, &decode).unwrap(),
+            scene
+        );
+    }
+
+    #[test]
+    fn test_serialize_json_options() {
+        let msg = Message::new("UPDATE").with_data(json!({"missing": null, "n": 1}));
+        let options = SerializeOptions {
+            encode_undefined: false,
+            ..Default::default()
+        };
+        let s = serialize_json_with_options(&msg, &options).unwrap();
+        let parsed: Value = serde_json::from_str(&s).unwrap();
+        assert_eq!(parsed["data"], json!({"n": 1}));
+
+        let options = SerializeOptions {
+            max_message_size: Some(16),
+            ..Default::default()
+        };
+        assert!(serialize_json_with_options(&msg, &options).is_err());
+    }
+
+    #[test]
+    fn test_raw_data_forwarding() {
+        let items: Vec<String> = (0..4000)
+            .map(|i| format!(r#"{{"id":{i},"pos":[{i}.5,-1.25,3e2]}}"#))
+            .collect();
+        let text = format!(r#"{{"items":[{}],"source":"upstream"}}"#, items.join(","));
+        assert!(text.len() > 100_000);
+
+        let raw = serde_json::value::RawValue::from_string(text.clone()).unwrap();
+        let msg = Message::new("FORWARD").with_raw_data(raw);
+        assert!(msg.data.is_none());
+        let bytes = serialize_message(&msg).unwrap();
+
+        // The raw text encodes to the same bytes as the parsed payload would
+        let parsed: Value = serde_json::from_str(&text).unwrap();
+        let mut expected = msg.clone();
+        expected.raw_data = None;
+        expected.data = Some(parsed.clone());
+        assert_eq!(bytes, serialize_message(&expected).unwrap());
+
+        let decoded = crate::deserializer::deserialize_message(&bytes).unwrap();
+        assert!(decoded.raw_data.is_none());
+        assert_eq!(decoded.data, Some(parsed));
+
+        // Setting one payload form replaces the other
+        let msg = msg.with_data(json!(1));
+        assert!(msg.raw_data.is_none());
+    }
+
+    #[test]
+    fn test_raw_data_conflicts_with_data() {
+        let raw = serde_json::value::RawValue::from_string(r#"{"x": 1}"#.into()).unwrap();
+        let msg = Message::new("FORWARD").with_raw_data(raw);
+        assert_eq!(msg.to_string(), format!(r#"Message[FORWARD @ {}ms, data={{"x": 1}}]"#, msg.ts));
+
+        // Both fields set by hand would write two `data` keys
+        let mut both = msg.clone();
+        both.data = Some(json!(2));
+        let err = serialize_message(&both).unwrap_err();
+        assert!(err.to_string().contains("both data and raw_data"), "{err}");
+        assert_ne!(both, msg);
+    }
 }
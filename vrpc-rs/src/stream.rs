@@ -0,0 +1,271 @@
+//! Async streams of framed VMP messages
+//!
+//! Author: Ge Yang
+//!
+//! [`MessageStream`] and [`MessageSink`] carry messages over any tokio
+//! reader and writer, one length-prefixed frame per message as written by
+//! [`crate::serializer::frame_message`].
+
+use crate::deserializer::{deserialize_message_with_options, DeserializeOptions};
+use crate::error::{Result, VmpError};
+use crate::framing::FrameReader;
+use crate::router::AsyncMessageRouter;
+use crate::serializer::frame_message;
+use crate::types::Message;
+use futures::future::BoxFuture;
+use futures::{Sink, Stream};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+enum ReadState {
+    Reading,
+    /// Routing a decoded message, which the future hands back when done
+    Routing(BoxFuture<'static, Result<Message>>),
+    Done,
+}
+
+/// Stream of messages read from length-prefixed frames
+///
+/// Ends cleanly when the reader ends between two frames. A frame cut short,
+/// a frame over the options' `max_bytes` or an I/O error is yielded as an error,
+/// after which the stream ends.
+pub struct MessageStream<R> {
+    reader: R,
+    frames: FrameReader,
+    options: DeserializeOptions,
+    router: Option<Arc<AsyncMessageRouter>>,
+    state: ReadState,
+}
+
+impl<R: AsyncRead + Unpin> MessageStream<R> {
+    /// Read messages from `reader` with the default options
+    pub fn new(reader: R) -> Self {
+        let options = DeserializeOptions::default();
+        Self {
+            reader,
+            frames: FrameReader::new(options.max_bytes),
+            options,
+            router: None,
+            state: ReadState::Reading,
+        }
+    }
+
+    /// Decode and validate frames as [`deserialize_message_with_options`]
+    /// does, with `max_bytes` limiting each frame
+    pub fn with_options(mut self, options: DeserializeOptions) -> Self {
+        self.frames = FrameReader::new(options.max_bytes);
+        self.options = options;
+        self
+    }
+
+    /// Route each message through `router` before yielding it
+    ///
+    /// A message is yielded whether or not a handler matched it. If a
+    /// handler fails, its error is yielded in place of the message and the
+    /// stream ends.
+    pub fn with_router(mut self, router: AsyncMessageRouter) -> Self {
+        self.router = Some(Arc::new(router));
+        self
+    }
+
+    /// Give back the underlying reader
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn poll_frame(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<Message>>> {
+        loop {
+            match &mut self.state {
+                ReadState::Reading => {
+                    let Some(buf) = ready!(self.frames.poll_read_frame(&mut self.reader, cx))?
+                    else {
+                        return Poll::Ready(None);
+                    };
+                    let message = deserialize_message_with_options(&buf, &self.options)?;
+                    let Some(router) = self.router.clone() else {
+                        return Poll::Ready(Some(Ok(message)));
+                    };
+                    self.state = ReadState::Routing(Box::pin(async move {
+                        router.route(&message).await.map(|_| message)
+                    }));
+                }
+                ReadState::Routing(route) => {
+                    let routed = ready!(route.as_mut().poll(cx));
+                    self.state = ReadState::Reading;
+                    return Poll::Ready(Some(routed));
+                }
+                ReadState::Done => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for MessageStream<R> {
+    type Item = Result<Message>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let item = ready!(this.poll_frame(cx));
+        if !matches!(item, Some(Ok(_))) {
+            this.state = ReadState::Done;
+        }
+        Poll::Ready(item)
+    }
+}
+
+/// Sink writing each message as one length-prefixed frame
+pub struct MessageSink<W> {
+    writer: W,
+    buf: Vec<u8>,
+    written: usize,
+}
+
+impl<W: AsyncWrite + Unpin> MessageSink<W> {
+    /// Write messages to `writer`
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            buf: Vec::new(),
+            written: 0,
+        }
+    }
+
+    /// Give back the underlying writer, dropping any frame not yet flushed
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Write out the buffered frame
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        while self.written < self.buf.len() {
+            let n = ready!(Pin::new(&mut self.writer).poll_write(cx, &self.buf[self.written..]))?;
+            if n == 0 {
+                return Poll::Ready(Err(std::io::Error::from(std::io::ErrorKind::WriteZero).into()));
+            }
+            self.written += n;
+        }
+        self.buf.clear();
+        self.written = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> Sink<Message> for MessageSink<W> {
+    type Error = VmpError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().poll_drain(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Message) -> Result<()> {
+        let this = self.get_mut();
+        this.buf.extend(frame_message(&item)?);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        Poll::Ready(ready!(Pin::new(&mut this.writer).poll_flush(cx)).map_err(Into::into))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        Poll::Ready(ready!(Pin::new(&mut this.writer).poll_shutdown(cx)).map_err(Into::into))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{SinkExt, StreamExt};
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    fn messages() -> Vec<Message> {
+        (0..5)
+            .map(|i| Message::new("TICK").with_data(json!({"i": i, "pad": "x".repeat(100)})))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_stream_over_duplex() {
+        // A buffer smaller than one frame forces partial reads and writes
+        let (client, server) = tokio::io::duplex(64);
+        let sent = messages();
+
+        let writer = tokio::spawn({
+            let sent = sent.clone();
+            async move {
+                let mut sink = MessageSink::new(client);
+                for msg in sent {
+                    sink.send(msg).await.unwrap();
+                }
+                sink.close().await.unwrap();
+            }
+        });
+
+        let received: Vec<Message> = MessageStream::new(server)
+            .map(|msg| msg.unwrap())
+            .collect()
+            .await;
+        writer.await.unwrap();
+        assert_eq!(received, sent);
+    }
+
+    #[tokio::test]
+    async fn test_stream_with_router() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut router = AsyncMessageRouter::new();
+        let record = seen.clone();
+        router.on("CAMERA:*", move |msg| {
+            let record = record.clone();
+            async move {
+                record.lock().unwrap().push(msg.etype);
+                Ok(())
+            }
+        });
+
+        let (client, server) = tokio::io::duplex(1024);
+        let mut sink = MessageSink::new(client);
+        for etype in ["CAMERA:MOVE", "CLICK", "CAMERA:ZOOM"] {
+            sink.feed(Message::new(etype)).await.unwrap();
+        }
+        sink.close().await.unwrap();
+
+        let etypes: Vec<String> = MessageStream::new(server)
+            .with_router(router)
+            .map(|msg| msg.unwrap().etype)
+            .collect()
+            .await;
+        assert_eq!(etypes, ["CAMERA:MOVE", "CLICK", "CAMERA:ZOOM"]);
+        assert_eq!(*seen.lock().unwrap(), ["CAMERA:MOVE", "CAMERA:ZOOM"]);
+    }
+
+    #[tokio::test]
+    async fn test_stream_errors_end_stream() {
+        let mut wire = frame_message(&Message::new("OK")).unwrap();
+        let big = frame_message(&Message::new("BIG").with_data(json!("x".repeat(200)))).unwrap();
+        wire.extend(&big);
+
+        let options = DeserializeOptions {
+            max_bytes: Some(64),
+            ..Default::default()
+        };
+        let mut stream = MessageStream::new(wire.as_slice()).with_options(options);
+        assert_eq!(stream.next().await.unwrap().unwrap().etype, "OK");
+        assert!(matches!(stream.next().await, Some(Err(VmpError::InvalidMessage(_)))));
+        assert!(stream.next().await.is_none());
+
+        let truncated = &big[..big.len() - 1];
+        let mut stream = MessageStream::new(truncated);
+        assert!(matches!(
+            stream.next().await,
+            Some(Err(VmpError::Io(e))) if e.kind() == std::io::ErrorKind::UnexpectedEof
+        ));
+        assert!(stream.next().await.is_none());
+    }
+}
@@ -3,9 +3,11 @@
 //! Author: Ge Yang
 
 use crate::error::{Result, VmpError};
+use crate::types::Timestamp;
 use crate::zdata::ZData;
 use serde_json::Value;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
 use std::sync::{Arc, RwLock};
 
 /// Encoder function type: converts a JSON value to ZData
@@ -33,13 +35,102 @@ pub struct TypeRegistration {
     pub type_checker: Option<TypeCheckerFn>,
 }
 
+/// A registration held by a [`TypeRegistry`], with its usage counters
+struct RegisteredType {
+    registration: TypeRegistration,
+    stats: TypeStats,
+}
+
+/// Usage counters of a registered type, updated without the registry's
+/// write lock
+#[derive(Debug)]
+struct TypeStats {
+    registered_at: Timestamp,
+    encode_count: AtomicU64,
+    decode_count: AtomicU64,
+    /// Last use in milliseconds, or `NEVER_USED`
+    last_used: AtomicI64,
+}
+
+const NEVER_USED: Timestamp = Timestamp::MIN;
+
+impl TypeStats {
+    fn new() -> Self {
+        Self {
+            registered_at: chrono::Utc::now().timestamp_millis(),
+            encode_count: AtomicU64::new(0),
+            decode_count: AtomicU64::new(0),
+            last_used: AtomicI64::new(NEVER_USED),
+        }
+    }
+
+    fn record(&self, counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+        self.last_used.fetch_max(chrono::Utc::now().timestamp_millis(), Ordering::Relaxed);
+    }
+}
+
+/// Usage information about a registered type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeInfo {
+    /// Type identifier
+    pub ztype: String,
+
+    /// When the type was last registered, in milliseconds
+    pub registered_at: Timestamp,
+
+    /// Number of values successfully encoded as this type
+    pub encode_count: u64,
+
+    /// Number of ZData of this type successfully decoded
+    pub decode_count: u64,
+
+    /// When the type was last encoded or decoded (`None` if never)
+    pub last_used: Option<Timestamp>,
+}
+
+/// Result of [`TypeRegistry::try_decode_unknown`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum UnknownOrKnown {
+    /// The type is registered and this is its decoded value
+    Known(Value),
+
+    /// The type is not registered; the ZData is kept as received
+    Unknown(Box<ZData>),
+}
+
+impl RegisteredType {
+    fn info(&self) -> TypeInfo {
+        let last_used = self.stats.last_used.load(Ordering::Relaxed);
+        TypeInfo {
+            ztype: self.registration.ztype.clone(),
+            registered_at: self.stats.registered_at,
+            encode_count: self.stats.encode_count.load(Ordering::Relaxed),
+            decode_count: self.stats.decode_count.load(Ordering::Relaxed),
+            last_used: (last_used != NEVER_USED).then_some(last_used),
+        }
+    }
+
+    fn encode(&self, value: &Value) -> Result<ZData> {
+        let zdata = (self.registration.encoder)(value)?;
+        self.stats.record(&self.stats.encode_count);
+        Ok(zdata)
+    }
+
+    fn decode(&self, zdata: &ZData) -> Result<Value> {
+        let value = (self.registration.decoder)(zdata)?;
+        self.stats.record(&self.stats.decode_count);
+        Ok(value)
+    }
+}
+
 /// Global type registry for custom ZData types
 ///
 /// This allows users to register custom encoders/decoders for types
 /// that may not have native Rust equivalents.
 #[derive(Clone)]
 pub struct TypeRegistry {
-    types: Arc<RwLock<HashMap<String, TypeRegistration>>>,
+    types: Arc<RwLock<HashMap<String, RegisteredType>>>,
 }
 
 impl Default for TypeRegistry {
@@ -48,6 +139,14 @@ impl Default for TypeRegistry {
     }
 }
 
+impl std::fmt::Debug for TypeRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut types = self.registered_types();
+        types.sort();
+        f.debug_struct("TypeRegistry").field("types", &types).finish()
+    }
+}
+
 impl TypeRegistry {
     /// Create a new empty type registry
     pub fn new() -> Self {
@@ -96,7 +195,13 @@ impl TypeRegistry {
         };
 
         let mut types = self.types.write().unwrap();
-        types.insert(ztype, registration);
+        types.insert(
+            ztype,
+            RegisteredType {
+                registration,
+                stats: TypeStats::new(),
+            },
+        );
     }
 
     /// Encode a value using a registered type
@@ -106,7 +211,7 @@ impl TypeRegistry {
             .get(ztype)
             .ok_or_else(|| VmpError::TypeNotRegistered(ztype.to_string()))?;
 
-        (registration.encoder)(value)
+        registration.encode(value)
     }
 
     /// Decode ZData using a registered type
@@ -116,7 +221,19 @@ impl TypeRegistry {
             .get(&zdata.ztype)
             .ok_or_else(|| VmpError::TypeNotRegistered(zdata.ztype.clone()))?;
 
-        (registration.decoder)(zdata)
+        registration.decode(zdata)
+    }
+
+    /// Decode ZData of a registered type, or hand it back unchanged if its
+    /// type is not registered
+    ///
+    /// Errors from a registered decoder are still returned.
+    pub fn try_decode_unknown(&self, zdata: &ZData) -> Result<UnknownOrKnown> {
+        let types = self.types.read().unwrap();
+        match types.get(&zdata.ztype) {
+            Some(registration) => registration.decode(zdata).map(UnknownOrKnown::Known),
+            None => Ok(UnknownOrKnown::Unknown(Box::new(zdata.clone()))),
+        }
     }
 
     /// Check if a type is registered
@@ -129,13 +246,12 @@ impl TypeRegistry {
     pub fn try_encode(&self, value: &Value) -> Option<ZData> {
         let types = self.types.read().unwrap();
 
-        for registration in types.values() {
-            if let Some(checker) = &registration.type_checker {
-                if checker(value) {
-                    if let Ok(zdata) = (registration.encoder)(value) {
-                        return Some(zdata);
-                    }
-                }
+        for registered in types.values() {
+            if let Some(checker) = &registered.registration.type_checker
+                && checker(value)
+                && let Ok(zdata) = registered.encode(value)
+            {
+                return Some(zdata);
             }
         }
 
@@ -147,6 +263,22 @@ impl TypeRegistry {
         let types = self.types.read().unwrap();
         types.keys().cloned().collect()
     }
+
+    /// Usage information for all registered types, sorted by name
+    ///
+    /// Counts start over when a type is registered again.
+    pub fn registered_types_with_metadata(&self) -> Vec<TypeInfo> {
+        let types = self.types.read().unwrap();
+        let mut infos: Vec<TypeInfo> = types.values().map(RegisteredType::info).collect();
+        infos.sort_by(|a, b| a.ztype.cmp(&b.ztype));
+        infos
+    }
+
+    /// Usage information for one registered type
+    pub fn stats_for(&self, ztype: &str) -> Option<TypeInfo> {
+        let types = self.types.read().unwrap();
+        types.get(ztype).map(RegisteredType::info)
+    }
 }
 
 lazy_static::lazy_static! {
@@ -224,4 +356,77 @@ mod tests {
         assert!(registry.try_encode(&number).is_some());
         assert!(registry.try_encode(&string).is_none());
     }
+
+    #[test]
+    fn test_usage_stats() {
+        let registry = TypeRegistry::new();
+        registry.register(
+            "counted",
+            |value| Ok(ZData::new("counted").with_field("v", value.clone())),
+            |zdata| Ok(zdata.get_field("v").unwrap().clone()),
+            Some(Arc::new(|v| v.is_string())),
+        );
+        registry.register(
+            "failing",
+            |_| Err(VmpError::TypeConversion("no".to_string())),
+            |_| Err(VmpError::TypeConversion("no".to_string())),
+            None,
+        );
+
+        let fresh = registry.stats_for("counted").unwrap();
+        assert_eq!((fresh.encode_count, fresh.decode_count, fresh.last_used), (0, 0, None));
+
+        for i in 0..3 {
+            let zdata = registry.encode("counted", &json!(i)).unwrap();
+            registry.decode(&zdata).unwrap();
+        }
+        registry.try_encode(&json!("via checker")).unwrap();
+        let zdata = registry.encode("counted", &json!(0)).unwrap();
+        registry.decode(&zdata).unwrap();
+
+        let info = registry.stats_for("counted").unwrap();
+        assert_eq!((info.encode_count, info.decode_count), (5, 4));
+        assert!(info.last_used.unwrap() >= info.registered_at);
+
+        // Failed conversions are not counted
+        assert!(registry.encode("failing", &json!(1)).is_err());
+        assert!(registry.decode(&ZData::new("failing")).is_err());
+        let infos = registry.registered_types_with_metadata();
+        let names: Vec<&str> = infos.iter().map(|info| info.ztype.as_str()).collect();
+        assert_eq!(names, ["counted", "failing"]);
+        assert_eq!((infos[1].encode_count, infos[1].last_used), (0, None));
+        assert!(registry.stats_for("missing").is_none());
+    }
+
+    #[test]
+    fn test_try_decode_unknown() {
+        let registry = TypeRegistry::new();
+        registry.register(
+            "known",
+            |value| Ok(ZData::new("known").with_field("v", value.clone())),
+            |zdata| Ok(zdata.get_field("v").unwrap().clone()),
+            None,
+        );
+        registry.register(
+            "broken",
+            |_| Err(VmpError::TypeConversion("no".to_string())),
+            |_| Err(VmpError::TypeConversion("no".to_string())),
+            None,
+        );
+
+        let known = ZData::new("known").with_field("v", json!(7));
+        assert_eq!(
+            registry.try_decode_unknown(&known).unwrap(),
+            UnknownOrKnown::Known(json!(7))
+        );
+
+        let unknown = ZData::new("other").with_field("v", json!(7));
+        assert_eq!(
+            registry.try_decode_unknown(&unknown).unwrap(),
+            UnknownOrKnown::Unknown(Box::new(unknown.clone()))
+        );
+        assert!(matches!(registry.decode(&unknown), Err(VmpError::TypeNotRegistered(_))));
+
+        assert!(registry.try_decode_unknown(&ZData::new("broken")).is_err());
+    }
 }
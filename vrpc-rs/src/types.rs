@@ -4,39 +4,324 @@
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::time::Duration;
 
 /// Timestamp in milliseconds since Unix epoch
 pub type Timestamp = i64;
 
+/// Magnitude below which a decoded `ts` is taken to be in seconds
+///
+/// 10^11 seconds is in the year 5138, while 10^11 milliseconds is March 1973,
+/// so current times in either unit fall clearly on one side.
+pub const SECONDS_TIMESTAMP_LIMIT: i64 = 100_000_000_000;
+
+/// Metadata key read by `trace_id()`
+pub const TRACE_ID_KEY: &str = "trace_id";
+
+/// Version of the VMP encoding rules implemented by this crate
+pub const PROTOCOL_VERSION: &str = "1.0";
+
+/// Prop holding the hints set by [`VuerComponent::with_schema_hint`]
+pub const SCHEMA_HINT_PROP: &str = "__schema__";
+
+/// Deserialize a `ts` sent as integer milliseconds, float seconds, or a
+/// string holding either
+///
+/// Python senders use `time.time()` as often as milliseconds, so numbers
+/// whose magnitude is below [`SECONDS_TIMESTAMP_LIMIT`] are read as seconds
+/// and scaled to milliseconds; larger ones are already milliseconds.
+/// Fractional milliseconds are rounded.
+pub(crate) fn deserialize_timestamp<'de, D>(deserializer: D) -> Result<Timestamp, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::de::{Error, Unexpected, Visitor};
+
+    struct TimestampVisitor;
+
+    impl Visitor<'_> for TimestampVisitor {
+        type Value = Timestamp;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a timestamp in milliseconds or seconds")
+        }
+
+        fn visit_i64<E: Error>(self, v: i64) -> Result<Timestamp, E> {
+            if v.unsigned_abs() < SECONDS_TIMESTAMP_LIMIT as u64 {
+                Ok(v * 1000)
+            } else {
+                Ok(v)
+            }
+        }
+
+        fn visit_u64<E: Error>(self, v: u64) -> Result<Timestamp, E> {
+            let v = i64::try_from(v)
+                .map_err(|_| E::invalid_value(Unexpected::Unsigned(v), &self))?;
+            self.visit_i64(v)
+        }
+
+        fn visit_f64<E: Error>(self, v: f64) -> Result<Timestamp, E> {
+            let millis = if v.abs() < SECONDS_TIMESTAMP_LIMIT as f64 {
+                v * 1000.0
+            } else {
+                v
+            }
+            .round();
+            // The upper bound of i64 is not representable as f64
+            if !(i64::MIN as f64..i64::MAX as f64).contains(&millis) {
+                return Err(E::invalid_value(Unexpected::Float(v), &self));
+            }
+            Ok(millis as Timestamp)
+        }
+
+        fn visit_str<E: Error>(self, v: &str) -> Result<Timestamp, E> {
+            let v = v.trim();
+            if let Ok(int) = v.parse::<i64>() {
+                return self.visit_i64(int);
+            }
+            match v.parse::<f64>() {
+                Ok(float) => self.visit_f64(float),
+                Err(_) => Err(E::invalid_value(Unexpected::Str(v), &self)),
+            }
+        }
+    }
+
+    deserializer.deserialize_any(TimestampVisitor)
+}
+
+/// Reject versions whose major component is newer than [`PROTOCOL_VERSION`]
+pub(crate) fn check_protocol_version(version: &str) -> crate::error::Result<()> {
+    let major = |v: &str| v.split('.').next().and_then(|m| m.parse::<u64>().ok());
+    let ours = major(PROTOCOL_VERSION).unwrap_or_default();
+
+    match major(version) {
+        Some(theirs) if theirs <= ours => Ok(()),
+        Some(_) => Err(crate::error::VmpError::InvalidMessage(format!(
+            "VMP version {} is newer than the supported version {}",
+            version, PROTOCOL_VERSION
+        ))),
+        None => Err(crate::error::VmpError::InvalidMessage(format!(
+            "Invalid VMP version: {}",
+            version
+        ))),
+    }
+}
+
 /// Generic message envelope with all possible fields
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Default, Deserialize)]
 #[serde(default)]
 pub struct Message {
     /// Timestamp in milliseconds
+    #[serde(deserialize_with = "deserialize_timestamp")]
     pub ts: Timestamp,
 
+    /// Time in milliseconds after which the message is stale
+    pub expires_at: Option<Timestamp>,
+
     /// Event type or queue name
     pub etype: String,
 
     /// Response type (RPC only)
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub rtype: Option<String>,
 
     /// Positional arguments (RPC)
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub args: Option<Vec<serde_json::Value>>,
 
     /// Keyword arguments (RPC)
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub kwargs: Option<HashMap<String, serde_json::Value>>,
 
     /// Server payload
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub data: Option<serde_json::Value>,
 
     /// Client payload
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub value: Option<serde_json::Value>,
+
+    /// Identifier linking this message to the one that caused it
+    pub correlation_id: Option<String>,
+
+    /// Cross-cutting metadata such as trace IDs or auth tokens
+    pub metadata: Option<HashMap<String, serde_json::Value>>,
+
+    /// Protocol version the sender encoded this message with
+    pub vmp_version: Option<String>,
+
+    /// Success flag (RPC response only)
+    pub ok: Option<bool>,
+
+    /// Error message (RPC response only)
+    pub error: Option<String>,
+
+    /// Category of the error (RPC response only)
+    pub error_kind: Option<crate::error::ErrorKind>,
+
+    /// Final chunk flag (streaming RPC only)
+    pub is_final: Option<bool>,
+
+    /// Attempt number that produced this response (retried RPC only)
+    pub attempt: Option<u32>,
+
+    /// Server payload kept as JSON text, written on the wire as `data`
+    /// without first being parsed into a `Value`
+    ///
+    /// Set it with [`Message::with_raw_data`]; received messages always
+    /// carry the payload in `data`. Registered types inside it are not
+    /// encoded. Serializing a message with both `data` and `raw_data` set
+    /// fails, since the wire has room for only one.
+    #[serde(skip_deserializing)]
+    pub raw_data: Option<Box<serde_json::value::RawValue>>,
+}
+
+/// Wire form of [`Message`], borrowing its fields, with `data` and
+/// `raw_data` written as the one `data` field
+#[derive(Serialize)]
+#[serde(rename = "Message")]
+struct MessageWire<'a> {
+    ts: Timestamp,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires_at: Option<Timestamp>,
+    etype: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rtype: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    args: Option<&'a [serde_json::Value]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kwargs: Option<&'a HashMap<String, serde_json::Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<DataRef<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<&'a serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    correlation_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<&'a HashMap<String, serde_json::Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vmp_version: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ok: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_kind: Option<&'a crate::error::ErrorKind>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    is_final: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attempt: Option<u32>,
+}
+
+/// The `data` payload of a message, parsed or as JSON text
+enum DataRef<'a> {
+    Value(&'a serde_json::Value),
+    Raw(&'a serde_json::value::RawValue),
+}
+
+impl Serialize for DataRef<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            DataRef::Value(value) => value.serialize(serializer),
+            // Transcoded token by token into the serializer's own format
+            DataRef::Raw(raw) => {
+                let mut deserializer = serde_json::Deserializer::from_str(raw.get());
+                serde_transcode::transcode(&mut deserializer, serializer)
+            }
+        }
+    }
+}
+
+impl Serialize for Message {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Message {
+            ts,
+            expires_at,
+            etype,
+            rtype,
+            args,
+            kwargs,
+            data,
+            value,
+            correlation_id,
+            metadata,
+            vmp_version,
+            ok,
+            error,
+            error_kind,
+            is_final,
+            attempt,
+            raw_data,
+        } = self;
+        let data = match (data, raw_data) {
+            (Some(_), Some(_)) => {
+                return Err(serde::ser::Error::custom(
+                    "message has both data and raw_data set",
+                ));
+            }
+            (Some(data), None) => Some(DataRef::Value(data)),
+            (None, Some(raw)) => Some(DataRef::Raw(raw)),
+            (None, None) => None,
+        };
+        MessageWire {
+            ts: *ts,
+            expires_at: *expires_at,
+            etype,
+            rtype: rtype.as_deref(),
+            args: args.as_deref(),
+            kwargs: kwargs.as_ref(),
+            data,
+            value: value.as_ref(),
+            correlation_id: correlation_id.as_deref(),
+            metadata: metadata.as_ref(),
+            vmp_version: vmp_version.as_deref(),
+            ok: *ok,
+            error: error.as_deref(),
+            error_kind: error_kind.as_ref(),
+            is_final: *is_final,
+            attempt: *attempt,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Raw payloads compare by their JSON text
+impl PartialEq for Message {
+    fn eq(&self, other: &Self) -> bool {
+        // Destructured so that a new field cannot be left out by accident
+        let Message {
+            ts,
+            expires_at,
+            etype,
+            rtype,
+            args,
+            kwargs,
+            data,
+            value,
+            correlation_id,
+            metadata,
+            vmp_version,
+            ok,
+            error,
+            error_kind,
+            is_final,
+            attempt,
+            raw_data,
+        } = self;
+        *ts == other.ts
+            && *expires_at == other.expires_at
+            && *etype == other.etype
+            && *rtype == other.rtype
+            && *args == other.args
+            && *kwargs == other.kwargs
+            && *data == other.data
+            && *value == other.value
+            && *correlation_id == other.correlation_id
+            && *metadata == other.metadata
+            && *vmp_version == other.vmp_version
+            && *ok == other.ok
+            && *error == other.error
+            && *error_kind == other.error_kind
+            && *is_final == other.is_final
+            && *attempt == other.attempt
+            && raw_data.as_deref().map(|raw| raw.get())
+                == other.raw_data.as_deref().map(|raw| raw.get())
+    }
 }
 
 /// Client-to-server event (uses value for payload)
@@ -44,6 +329,7 @@ pub struct Message {
 #[serde(default)]
 pub struct ClientEvent {
     /// Timestamp in milliseconds
+    #[serde(deserialize_with = "deserialize_timestamp")]
     pub ts: Timestamp,
 
     /// Event type
@@ -55,6 +341,14 @@ pub struct ClientEvent {
 
     /// Client payload
     pub value: serde_json::Value,
+
+    /// Identifier linking this message to the one that caused it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+
+    /// Cross-cutting metadata such as trace IDs or auth tokens
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<HashMap<String, serde_json::Value>>,
 }
 
 /// Server-to-client event (uses data for payload)
@@ -62,6 +356,7 @@ pub struct ClientEvent {
 #[serde(default)]
 pub struct ServerEvent {
     /// Timestamp in milliseconds
+    #[serde(deserialize_with = "deserialize_timestamp")]
     pub ts: Timestamp,
 
     /// Event type
@@ -69,13 +364,22 @@ pub struct ServerEvent {
 
     /// Server payload
     pub data: serde_json::Value,
+
+    /// Identifier linking this message to the one that caused it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+
+    /// Cross-cutting metadata such as trace IDs or auth tokens
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<HashMap<String, serde_json::Value>>,
 }
 
 /// RPC Request (includes rtype for response routing)
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 #[serde(default)]
 pub struct RpcRequest {
     /// Timestamp in milliseconds
+    #[serde(deserialize_with = "deserialize_timestamp")]
     pub ts: Timestamp,
 
     /// Event type (method name)
@@ -91,13 +395,18 @@ pub struct RpcRequest {
     /// Keyword arguments
     #[serde(skip_serializing_if = "Option::is_none")]
     pub kwargs: Option<HashMap<String, serde_json::Value>>,
+
+    /// Cross-cutting metadata such as trace IDs or auth tokens
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<HashMap<String, serde_json::Value>>,
 }
 
 /// RPC Response
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 #[serde(default)]
 pub struct RpcResponse {
     /// Timestamp in milliseconds
+    #[serde(deserialize_with = "deserialize_timestamp")]
     pub ts: Timestamp,
 
     /// Event type (matches request's rtype)
@@ -118,61 +427,256 @@ pub struct RpcResponse {
     /// Error message
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
+
+    /// Category of the error, for callers that handle errors by kind
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_kind: Option<crate::error::ErrorKind>,
+
+    /// Final chunk flag (streaming RPC only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_final: Option<bool>,
+
+    /// Attempt number that produced this response (retried RPC only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attempt: Option<u32>,
+}
+
+/// A decoded message classified by the fields it carries, as returned by
+/// [`crate::deserializer::deserialize_envelope`]
+#[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::large_enum_variant)]
+pub enum VmpEnvelope {
+    /// Has `value`
+    ClientEvent(ClientEvent),
+
+    /// Has `data`
+    ServerEvent(ServerEvent),
+
+    /// Has `rtype` and `args` or `kwargs`
+    RpcRequest(RpcRequest),
+
+    /// Has `ok` or `error`
+    RpcResponse(RpcResponse),
+
+    /// Matches none of the above, or more than one
+    Message(Message),
+}
+
+/// The specialized type a [`Message`] converts to, as returned by
+/// [`Message::kind`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MessageKind {
+    /// Has `value`; converts to [`ClientEvent`]
+    ClientEvent,
+
+    /// Has `data`; converts to [`ServerEvent`]
+    ServerEvent,
+
+    /// Has `rtype` and `args` or `kwargs`; converts to [`RpcRequest`]
+    RpcRequest,
+
+    /// Has `ok` or `error`; converts to [`RpcResponse`]
+    RpcResponse,
+
+    /// Matches none of the above, or more than one
+    Message,
 }
 
 /// Vuer component schema (nested structure)
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-#[serde(default)]
+///
+/// On the wire `tag`, `children`, `key` and the props share one flat map.
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct VuerComponent {
     /// Component type
     pub tag: String,
 
     /// Nested components
-    #[serde(skip_serializing_if = "Option::is_none")]
     pub children: Option<Vec<VuerComponent>>,
 
+    /// Identity used to match children across updates
+    ///
+    /// Serialized as a `key` entry next to the props, as Vuer sends it, so
+    /// `props` never holds one. Vuer may send a string, number or boolean,
+    /// and the key is written back with the type it was read with; see
+    /// [`VuerComponent::key_text`] for the form used to compare keys.
+    pub key: Option<serde_json::Value>,
+
     /// Additional properties stored as dynamic values
-    #[serde(flatten)]
     pub props: HashMap<String, serde_json::Value>,
 }
 
-impl Default for Message {
-    fn default() -> Self {
-        Self {
-            ts: 0,
-            etype: String::new(),
-            rtype: None,
-            args: None,
-            kwargs: None,
-            data: None,
-            value: None,
+/// Writes the flat map read by `Deserialize`, with a `key` prop left over
+/// from direct edits to `props` giving way to the `key` field
+impl Serialize for VuerComponent {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+
+        let key = self.key.as_ref().or_else(|| self.props.get(crate::diff::CHILD_KEY_PROP));
+        let props = self.props.iter().filter(|(name, _)| *name != crate::diff::CHILD_KEY_PROP);
+        let len = 1 + self.children.is_some() as usize + key.is_some() as usize;
+        let mut map = serializer.serialize_map(Some(len + props.clone().count()))?;
+        map.serialize_entry("tag", &self.tag)?;
+        if let Some(children) = &self.children {
+            map.serialize_entry("children", children)?;
+        }
+        if let Some(key) = key {
+            map.serialize_entry(crate::diff::CHILD_KEY_PROP, key)?;
+        }
+        for (name, value) in props {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+/// Reads the flat map field by field instead of deriving with
+/// `#[serde(flatten)]`, which buffers every subtree before decoding it and
+/// takes far more stack per level of nesting
+impl<'de> Deserialize<'de> for VuerComponent {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::{Error, MapAccess, Visitor};
+
+        struct ComponentVisitor;
+
+        impl<'de> Visitor<'de> for ComponentVisitor {
+            type Value = VuerComponent;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str("a component map")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<VuerComponent, A::Error> {
+                let mut component = VuerComponent::default();
+                while let Some(name) = map.next_key::<String>()? {
+                    match name.as_str() {
+                        "tag" => component.tag = map.next_value()?,
+                        "children" => component.children = map.next_value()?,
+                        crate::diff::CHILD_KEY_PROP => {
+                            component.key = match map.next_value()? {
+                                serde_json::Value::Null => None,
+                                key @ (serde_json::Value::String(_)
+                                | serde_json::Value::Number(_)
+                                | serde_json::Value::Bool(_)) => Some(key),
+                                other => {
+                                    return Err(A::Error::custom(format!(
+                                        "invalid component key: {}",
+                                        other
+                                    )));
+                                }
+                            }
+                        }
+                        _ => {
+                            component.props.insert(name, map.next_value()?);
+                        }
+                    }
+                }
+                Ok(component)
+            }
         }
+
+        deserializer.deserialize_map(ComponentVisitor)
     }
 }
 
+/// A component in a flattened tree, linked to its parent by key
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FlatComponent {
+    /// Component type
+    pub tag: String,
+
+    /// Component properties
+    pub props: HashMap<String, serde_json::Value>,
+
+    /// The component's key, or a generated UUID if it has none
+    ///
+    /// A key the component had is also kept in `props` under
+    /// [`crate::diff::CHILD_KEY_PROP`], so generated ones can be told apart.
+    pub key: String,
+
+    /// Key of the parent component (`None` for the root)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_key: Option<String>,
+}
+
 impl Message {
     /// Create a new message with the current timestamp
     pub fn new(etype: impl Into<String>) -> Self {
         Self {
             ts: chrono::Utc::now().timestamp_millis(),
+            expires_at: None,
             etype: etype.into(),
             rtype: None,
             args: None,
             kwargs: None,
             data: None,
             value: None,
+            correlation_id: None,
+            metadata: None,
+            vmp_version: None,
+            ok: None,
+            error: None,
+            error_kind: None,
+            is_final: None,
+            attempt: None,
+            raw_data: None,
         }
     }
 
+    /// Build a message from a client event, keeping its shared fields
+    pub fn from_client_event(event: ClientEvent) -> Self {
+        event.into()
+    }
+
+    /// Classify the message by the fields it carries
+    ///
+    /// Follows [`crate::deserializer::deserialize_envelope`]: `ok` or
+    /// `error` make a response and an `rtype` with `args` or `kwargs` a
+    /// request, with a
+    /// message that is both counting as neither. Otherwise exactly one of
+    /// `value` and `data` makes an event.
+    pub fn kind(&self) -> MessageKind {
+        let has_data = self.data.is_some() || self.raw_data.is_some();
+        let is_response = self.ok.is_some() || self.error.is_some();
+        let is_request = self.rtype.is_some() && (self.args.is_some() || self.kwargs.is_some());
+        match (is_response, is_request) {
+            (true, true) => return MessageKind::Message,
+            (true, false) => return MessageKind::RpcResponse,
+            (false, true) => return MessageKind::RpcRequest,
+            (false, false) => {}
+        }
+        match (self.value.is_some(), has_data) {
+            (true, false) => MessageKind::ClientEvent,
+            (false, true) => MessageKind::ServerEvent,
+            _ => MessageKind::Message,
+        }
+    }
+
+    /// Set the protocol version, usually [`PROTOCOL_VERSION`]
+    pub fn with_vmp_version(mut self, version: impl Into<String>) -> Self {
+        self.vmp_version = Some(version.into());
+        self
+    }
+
     /// Set the response type for RPC
     pub fn with_rtype(mut self, rtype: impl Into<String>) -> Self {
         self.rtype = Some(rtype.into());
         self
     }
 
-    /// Set the data payload
+    /// Set the data payload, replacing any raw payload
     pub fn with_data(mut self, data: serde_json::Value) -> Self {
         self.data = Some(data);
+        self.raw_data = None;
+        self
+    }
+
+    /// Set the data payload from JSON text, serialized without parsing it
+    /// into a `Value`
+    ///
+    /// Replaces any `data` payload. The receiver sees the payload in `data`.
+    pub fn with_raw_data(mut self, raw: Box<serde_json::value::RawValue>) -> Self {
+        self.raw_data = Some(raw);
+        self.data = None;
         self
     }
 
@@ -181,6 +685,270 @@ impl Message {
         self.value = Some(value);
         self
     }
+
+    /// Set the data payload to any serializable value, replacing any raw
+    /// payload
+    pub fn with_data_of<T: Serialize>(self, data: T) -> crate::error::Result<Self> {
+        Ok(self.with_data(to_json_value(data)?))
+    }
+
+    /// Set the value payload to any serializable value
+    pub fn with_value_of<T: Serialize>(self, value: T) -> crate::error::Result<Self> {
+        Ok(self.with_value(to_json_value(value)?))
+    }
+
+    /// Add a keyword argument of any serializable type
+    pub fn with_kwarg<K: Into<String>, T: Serialize>(
+        mut self,
+        key: K,
+        value: T,
+    ) -> crate::error::Result<Self> {
+        let value = to_json_value(value)?;
+        self.kwargs.get_or_insert_with(HashMap::new).insert(key.into(), value);
+        Ok(self)
+    }
+
+    /// Create a new message with the same etype that is correlated with this one
+    ///
+    /// The child inherits this message's correlation ID, or uses this
+    /// message's `ts` as the ID if none is set.
+    pub fn spawn_correlated(&self) -> Message {
+        let id = match &self.correlation_id {
+            Some(id) => id.clone(),
+            None => self.ts.to_string(),
+        };
+        let mut child = Message::new(self.etype.clone());
+        child.correlation_id = Some(id);
+        child
+    }
+
+    /// Expire the message `ttl` from now
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        let ttl_ms = Timestamp::try_from(ttl.as_millis()).unwrap_or(Timestamp::MAX);
+        self.expires_at = Some(chrono::Utc::now().timestamp_millis().saturating_add(ttl_ms));
+        self
+    }
+
+    /// Set the correlation ID
+    pub fn with_correlation(mut self, id: &str) -> Self {
+        self.correlation_id = Some(id.to_string());
+        self
+    }
+
+    /// Get the correlation ID
+    pub fn correlation_id(&self) -> Option<&str> {
+        self.correlation_id.as_deref()
+    }
+
+    /// Attach a metadata entry
+    pub fn with_metadata(mut self, key: impl Into<String>, value: serde_json::Value) -> Self {
+        self.metadata.get_or_insert_with(HashMap::new).insert(key.into(), value);
+        self
+    }
+
+    /// Get a metadata entry
+    pub fn get_meta(&self, key: &str) -> Option<&serde_json::Value> {
+        self.metadata.as_ref()?.get(key)
+    }
+
+    /// Get the trace ID stored under the "trace_id" metadata key
+    pub fn trace_id(&self) -> Option<&str> {
+        self.get_meta(TRACE_ID_KEY)?.as_str()
+    }
+
+    /// Decode ZData in the `data`, `value`, `args` and `kwargs` payloads with
+    /// [`crate::deserializer::decode_value_recursive`]
+    ///
+    /// Decoding an already decoded message again leaves it unchanged.
+    pub fn decode_payloads(
+        &mut self,
+        options: &crate::deserializer::DeserializeOptions,
+    ) -> crate::error::Result<()> {
+        crate::deserializer::decode_message_payloads(self, options)
+    }
+}
+
+/// Writes W3C trace context headers into a metadata map
+#[cfg(feature = "opentelemetry")]
+struct MetadataInjector<'a>(&'a mut HashMap<String, serde_json::Value>);
+
+#[cfg(feature = "opentelemetry")]
+impl opentelemetry::propagation::Injector for MetadataInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        self.0.insert(key.to_string(), serde_json::Value::String(value));
+    }
+}
+
+/// Reads W3C trace context headers from a metadata map
+#[cfg(feature = "opentelemetry")]
+struct MetadataExtractor<'a>(Option<&'a HashMap<String, serde_json::Value>>);
+
+#[cfg(feature = "opentelemetry")]
+impl opentelemetry::propagation::Extractor for MetadataExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0?.get(key)?.as_str()
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.into_iter().flat_map(|m| m.keys().map(String::as_str)).collect()
+    }
+}
+
+/// Inject the current span context with the global propagator, leaving
+/// `metadata` untouched if there is nothing to propagate
+#[cfg(feature = "opentelemetry")]
+fn inject_trace_context(metadata: &mut Option<HashMap<String, serde_json::Value>>) {
+    let mut headers = HashMap::new();
+    opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(
+            &opentelemetry::Context::current(),
+            &mut MetadataInjector(&mut headers),
+        )
+    });
+    if !headers.is_empty() {
+        metadata.get_or_insert_with(HashMap::new).extend(headers);
+    }
+}
+
+#[cfg(feature = "opentelemetry")]
+fn extract_trace_context(
+    metadata: Option<&HashMap<String, serde_json::Value>>,
+) -> Option<opentelemetry::Context> {
+    use opentelemetry::trace::TraceContextExt;
+
+    let context = opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.extract(&MetadataExtractor(metadata))
+    });
+    context.span().span_context().is_valid().then_some(context)
+}
+
+#[cfg(feature = "opentelemetry")]
+impl Message {
+    /// Store the current OpenTelemetry span context in `metadata`, as the
+    /// `traceparent` and `tracestate` entries written by the global text
+    /// map propagator
+    pub fn inject_trace_context(&mut self) {
+        inject_trace_context(&mut self.metadata);
+    }
+
+    /// Rebuild the sender's span context from `metadata` with the global
+    /// text map propagator (`None` if it carries no valid span context)
+    pub fn extract_trace_context(&self) -> Option<opentelemetry::Context> {
+        extract_trace_context(self.metadata.as_ref())
+    }
+}
+
+#[cfg(feature = "opentelemetry")]
+impl RpcRequest {
+    /// Store the current OpenTelemetry span context in `metadata`, as
+    /// [`Message::inject_trace_context`] does
+    pub fn inject_trace_context(&mut self) {
+        inject_trace_context(&mut self.metadata);
+    }
+
+    /// Rebuild the caller's span context from `metadata`, as
+    /// [`Message::extract_trace_context`] does
+    pub fn extract_trace_context(&self) -> Option<opentelemetry::Context> {
+        extract_trace_context(self.metadata.as_ref())
+    }
+}
+
+#[cfg(feature = "crypto")]
+impl Message {
+    /// SHA-256 of the message content, ignoring `ts`
+    ///
+    /// The message is hashed as MessagePack with all object keys sorted, so
+    /// retransmissions of the same content hash identically regardless of
+    /// when they were sent or the iteration order of their maps. Fails if
+    /// the message cannot be serialized, as when both `data` and `raw_data`
+    /// are set.
+    pub fn content_hash(&self) -> crate::error::Result<[u8; 32]> {
+        use sha2::{Digest, Sha256};
+
+        Ok(Sha256::digest(self.canonical_bytes(false)?).into())
+    }
+
+    /// Sign the message with HMAC-SHA256
+    ///
+    /// The MAC covers every field, including `ts`, so a signed message
+    /// cannot be replayed with a fresh timestamp. Fails if the message
+    /// cannot be serialized.
+    pub fn sign(&self, key: &[u8]) -> crate::error::Result<SignedMessage> {
+        use hmac::Mac;
+
+        Ok(SignedMessage {
+            signature: self.hmac(key)?.finalize().into_bytes().into(),
+            message: self.clone(),
+        })
+    }
+
+    fn hmac(&self, key: &[u8]) -> crate::error::Result<hmac::Hmac<sha2::Sha256>> {
+        use hmac::{KeyInit, Mac};
+
+        let mut mac = hmac::Hmac::<sha2::Sha256>::new_from_slice(key)
+            .expect("HMAC accepts keys of any length");
+        mac.update(&self.canonical_bytes(true)?);
+        Ok(mac)
+    }
+
+    /// MessagePack encoding with all object keys sorted
+    fn canonical_bytes(&self, include_ts: bool) -> crate::error::Result<Vec<u8>> {
+        let mut value = serde_json::to_value(self)?;
+        if !include_ts && let Some(map) = value.as_object_mut() {
+            map.remove("ts");
+        }
+        value.sort_all_objects();
+
+        Ok(rmp_serde::to_vec_named(&value)?)
+    }
+}
+
+/// A message with an HMAC-SHA256 signature
+///
+/// Serializes as the message's own fields plus a binary `signature` field,
+/// so receivers that do not check signatures can still read it as a
+/// `Message`.
+#[cfg(feature = "crypto")]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SignedMessage {
+    #[serde(flatten)]
+    message: Message,
+
+    #[serde(with = "serde_bytes")]
+    signature: [u8; 32],
+}
+
+#[cfg(feature = "crypto")]
+impl SignedMessage {
+    /// Check the signature and return the message if it is authentic
+    pub fn verify(&self, key: &[u8]) -> crate::error::Result<&Message> {
+        use hmac::Mac;
+
+        self.message
+            .hmac(key)?
+            .verify_slice(&self.signature)
+            .map_err(|_| {
+                crate::error::VmpError::InvalidMessage("Invalid message signature".to_string())
+            })?;
+        Ok(&self.message)
+    }
+
+    /// The HMAC-SHA256 signature
+    pub fn signature(&self) -> &[u8; 32] {
+        &self.signature
+    }
+}
+
+/// Check whether a message has passed its `expires_at` time
+pub fn is_expired(msg: &Message) -> bool {
+    is_expired_at(msg, chrono::Utc::now().timestamp_millis())
+}
+
+/// Check whether a message is expired at `now` (milliseconds)
+///
+/// A message is still valid during its `expires_at` millisecond.
+pub fn is_expired_at(msg: &Message, now: Timestamp) -> bool {
+    msg.expires_at.is_some_and(|expires_at| now > expires_at)
 }
 
 impl Default for ClientEvent {
@@ -190,6 +958,8 @@ impl Default for ClientEvent {
             etype: String::new(),
             rtype: None,
             value: serde_json::Value::Null,
+            correlation_id: None,
+            metadata: None,
         }
     }
 }
@@ -202,6 +972,8 @@ impl ClientEvent {
             etype: etype.into(),
             rtype: None,
             value,
+            correlation_id: None,
+            metadata: None,
         }
     }
 
@@ -210,6 +982,33 @@ impl ClientEvent {
         self.rtype = Some(rtype.into());
         self
     }
+
+    /// Set the correlation ID
+    pub fn with_correlation(mut self, id: &str) -> Self {
+        self.correlation_id = Some(id.to_string());
+        self
+    }
+
+    /// Get the correlation ID
+    pub fn correlation_id(&self) -> Option<&str> {
+        self.correlation_id.as_deref()
+    }
+
+    /// Attach a metadata entry
+    pub fn with_metadata(mut self, key: impl Into<String>, value: serde_json::Value) -> Self {
+        self.metadata.get_or_insert_with(HashMap::new).insert(key.into(), value);
+        self
+    }
+
+    /// Get a metadata entry
+    pub fn get_meta(&self, key: &str) -> Option<&serde_json::Value> {
+        self.metadata.as_ref()?.get(key)
+    }
+
+    /// Get the trace ID stored under the "trace_id" metadata key
+    pub fn trace_id(&self) -> Option<&str> {
+        self.get_meta(TRACE_ID_KEY)?.as_str()
+    }
 }
 
 impl Default for ServerEvent {
@@ -218,6 +1017,8 @@ impl Default for ServerEvent {
             ts: 0,
             etype: String::new(),
             data: serde_json::Value::Null,
+            correlation_id: None,
+            metadata: None,
         }
     }
 }
@@ -229,19 +1030,36 @@ impl ServerEvent {
             ts: chrono::Utc::now().timestamp_millis(),
             etype: etype.into(),
             data,
+            correlation_id: None,
+            metadata: None,
         }
     }
-}
 
-impl Default for RpcRequest {
-    fn default() -> Self {
-        Self {
-            ts: 0,
-            etype: String::new(),
-            rtype: String::new(),
-            args: None,
-            kwargs: None,
-        }
+    /// Set the correlation ID
+    pub fn with_correlation(mut self, id: &str) -> Self {
+        self.correlation_id = Some(id.to_string());
+        self
+    }
+
+    /// Get the correlation ID
+    pub fn correlation_id(&self) -> Option<&str> {
+        self.correlation_id.as_deref()
+    }
+
+    /// Attach a metadata entry
+    pub fn with_metadata(mut self, key: impl Into<String>, value: serde_json::Value) -> Self {
+        self.metadata.get_or_insert_with(HashMap::new).insert(key.into(), value);
+        self
+    }
+
+    /// Get a metadata entry
+    pub fn get_meta(&self, key: &str) -> Option<&serde_json::Value> {
+        self.metadata.as_ref()?.get(key)
+    }
+
+    /// Get the trace ID stored under the "trace_id" metadata key
+    pub fn trace_id(&self) -> Option<&str> {
+        self.get_meta(TRACE_ID_KEY)?.as_str()
     }
 }
 
@@ -254,6 +1072,7 @@ impl RpcRequest {
             rtype: rtype.into(),
             args: None,
             kwargs: None,
+            metadata: None,
         }
     }
 
@@ -268,17 +1087,231 @@ impl RpcRequest {
         self.kwargs = Some(kwargs);
         self
     }
+
+    /// Append a positional argument of any serializable type
+    pub fn with_arg<T: Serialize>(mut self, value: T) -> crate::error::Result<Self> {
+        let value = to_json_value(value)?;
+        self.args.get_or_insert_with(Vec::new).push(value);
+        Ok(self)
+    }
+
+    /// Add a keyword argument of any serializable type
+    pub fn with_kwarg<K: Into<String>, T: Serialize>(
+        mut self,
+        key: K,
+        value: T,
+    ) -> crate::error::Result<Self> {
+        let value = to_json_value(value)?;
+        self.kwargs.get_or_insert_with(HashMap::new).insert(key.into(), value);
+        Ok(self)
+    }
+
+    /// Attach a metadata entry
+    pub fn with_metadata(mut self, key: impl Into<String>, value: serde_json::Value) -> Self {
+        self.metadata.get_or_insert_with(HashMap::new).insert(key.into(), value);
+        self
+    }
+
+    /// Get a metadata entry
+    pub fn get_meta(&self, key: &str) -> Option<&serde_json::Value> {
+        self.metadata.as_ref()?.get(key)
+    }
+
+    /// Start an [`RpcRequestBuilder`] with no fields set
+    pub fn builder() -> RpcRequestBuilder {
+        RpcRequestBuilder::new()
+    }
+
+    /// Deserialize a keyword argument
+    ///
+    /// Fails with `VmpError::MissingField` if there is no such argument, or
+    /// `VmpError::TypeConversion` if it does not deserialize as `T`.
+    pub fn kwarg<T: serde::de::DeserializeOwned>(&self, key: &str) -> crate::error::Result<T> {
+        let value = self.kwargs.as_ref().and_then(|kwargs| kwargs.get(key)).ok_or_else(|| {
+            crate::error::VmpError::MissingField(format!("kwarg `{}`", key))
+        })?;
+        convert_argument(value, || format!("kwarg `{}`", key))
+    }
+
+    /// Deserialize a keyword argument, or return `default` if it is missing
+    ///
+    /// An argument of the wrong type is still an error.
+    pub fn kwarg_or<T: serde::de::DeserializeOwned>(
+        &self,
+        key: &str,
+        default: T,
+    ) -> crate::error::Result<T> {
+        match self.kwargs.as_ref().and_then(|kwargs| kwargs.get(key)) {
+            Some(value) => convert_argument(value, || format!("kwarg `{}`", key)),
+            None => Ok(default),
+        }
+    }
+
+    /// Deserialize a positional argument
+    ///
+    /// Fails like [`RpcRequest::kwarg`].
+    pub fn arg<T: serde::de::DeserializeOwned>(&self, index: usize) -> crate::error::Result<T> {
+        let value = self.args.as_ref().and_then(|args| args.get(index)).ok_or_else(|| {
+            crate::error::VmpError::MissingField(format!("arg {}", index))
+        })?;
+        convert_argument(value, || format!("arg {}", index))
+    }
+
+    /// Deserialize all keyword arguments into a struct, one field per key
+    ///
+    /// A request without `kwargs` is treated as having none, so structs
+    /// whose fields all have defaults can still be built.
+    pub fn kwargs_as<T: serde::de::DeserializeOwned>(&self) -> crate::error::Result<T> {
+        use serde::de::value::MapDeserializer;
+
+        let entries = self.kwargs.iter().flatten().map(|(key, value)| (key.as_str(), value));
+        T::deserialize(MapDeserializer::<_, serde_json::Error>::new(entries)).map_err(|e| {
+            crate::error::VmpError::TypeConversion(format!(
+                "kwargs do not deserialize as {}: {}",
+                std::any::type_name::<T>(),
+                e
+            ))
+        })
+    }
 }
 
-impl Default for RpcResponse {
-    fn default() -> Self {
+/// Convert a payload or argument to JSON for the `with_*_of` builders
+fn to_json_value<T: Serialize>(value: T) -> crate::error::Result<serde_json::Value> {
+    serde_json::to_value(value).map_err(|e| crate::error::VmpError::Serialization(e.to_string()))
+}
+
+/// Deserialize an RPC argument, naming it and the expected type on failure
+fn convert_argument<T: serde::de::DeserializeOwned>(
+    value: &serde_json::Value,
+    name: impl FnOnce() -> String,
+) -> crate::error::Result<T> {
+    T::deserialize(value).map_err(|e| {
+        crate::error::VmpError::TypeConversion(format!(
+            "{} does not deserialize as {}: {}",
+            name(),
+            std::any::type_name::<T>(),
+            e
+        ))
+    })
+}
+
+/// [`RpcRequestBuilder`] state: `etype` not set yet
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoEtype;
+
+/// [`RpcRequestBuilder`] state: `etype` set
+#[derive(Debug, Clone, Copy, Default)]
+pub struct YesEtype;
+
+/// [`RpcRequestBuilder`] state: `rtype` not set yet
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoRtype;
+
+/// [`RpcRequestBuilder`] state: `rtype` set
+#[derive(Debug, Clone, Copy, Default)]
+pub struct YesRtype;
+
+/// Builder for [`RpcRequest`] that only offers `build` once both `etype`
+/// and `rtype` are set
+///
+/// ```
+/// use vuer_rpc::RpcRequest;
+///
+/// let request = RpcRequest::builder().etype("render").rtype("rpc-123").build();
+/// assert_eq!(request.rtype, "rpc-123");
+/// ```
+///
+/// Leaving either one out is a compile error:
+///
+/// ```compile_fail
+/// use vuer_rpc::RpcRequest;
+///
+/// let request = RpcRequest::builder().etype("render").build();
+/// ```
+///
+/// ```compile_fail
+/// use vuer_rpc::RpcRequest;
+///
+/// let request = RpcRequest::builder().rtype("rpc-123").build();
+/// ```
+#[derive(Debug, Clone)]
+pub struct RpcRequestBuilder<E = NoEtype, R = NoRtype> {
+    etype: String,
+    rtype: String,
+    args: Option<Vec<serde_json::Value>>,
+    kwargs: Option<HashMap<String, serde_json::Value>>,
+    state: std::marker::PhantomData<(E, R)>,
+}
+
+impl RpcRequestBuilder {
+    /// Create a builder with no fields set
+    pub fn new() -> Self {
         Self {
-            ts: 0,
             etype: String::new(),
-            data: None,
-            value: None,
-            ok: None,
-            error: None,
+            rtype: String::new(),
+            args: None,
+            kwargs: None,
+            state: std::marker::PhantomData,
+        }
+    }
+}
+
+impl Default for RpcRequestBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R> RpcRequestBuilder<NoEtype, R> {
+    /// Set the event type
+    pub fn etype(self, etype: impl Into<String>) -> RpcRequestBuilder<YesEtype, R> {
+        RpcRequestBuilder {
+            etype: etype.into(),
+            rtype: self.rtype,
+            args: self.args,
+            kwargs: self.kwargs,
+            state: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<E> RpcRequestBuilder<E, NoRtype> {
+    /// Set the response type (request ID)
+    pub fn rtype(self, rtype: impl Into<String>) -> RpcRequestBuilder<E, YesRtype> {
+        RpcRequestBuilder {
+            etype: self.etype,
+            rtype: rtype.into(),
+            args: self.args,
+            kwargs: self.kwargs,
+            state: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<E, R> RpcRequestBuilder<E, R> {
+    /// Set positional arguments
+    pub fn args(mut self, args: Vec<serde_json::Value>) -> Self {
+        self.args = Some(args);
+        self
+    }
+
+    /// Set keyword arguments
+    pub fn kwargs(mut self, kwargs: HashMap<String, serde_json::Value>) -> Self {
+        self.kwargs = Some(kwargs);
+        self
+    }
+}
+
+impl RpcRequestBuilder<YesEtype, YesRtype> {
+    /// Build the request with the current timestamp
+    pub fn build(self) -> RpcRequest {
+        RpcRequest {
+            ts: chrono::Utc::now().timestamp_millis(),
+            etype: self.etype,
+            rtype: self.rtype,
+            args: self.args,
+            kwargs: self.kwargs,
+            metadata: None,
         }
     }
 }
@@ -293,6 +1326,9 @@ impl RpcResponse {
             value: None,
             ok: Some(true),
             error: None,
+            error_kind: None,
+            is_final: None,
+            attempt: None,
         }
     }
 
@@ -305,17 +1341,311 @@ impl RpcResponse {
             value: None,
             ok: Some(false),
             error: Some(error.into()),
+            error_kind: None,
+            is_final: None,
+            attempt: None,
         }
     }
+
+    /// Create a failed RPC response with an error category
+    pub fn error_with_kind(
+        etype: impl Into<String>,
+        error: impl Into<String>,
+        kind: crate::error::ErrorKind,
+    ) -> Self {
+        Self {
+            error_kind: Some(kind),
+            ..Self::error(etype, error)
+        }
+    }
+
+    /// Get the error category, if the responder gave one
+    pub fn error_kind(&self) -> Option<crate::error::ErrorKind> {
+        self.error_kind.clone()
+    }
 }
 
-impl Default for VuerComponent {
-    fn default() -> Self {
+impl From<ClientEvent> for Message {
+    fn from(event: ClientEvent) -> Self {
         Self {
-            tag: String::new(),
-            children: None,
-            props: HashMap::new(),
+            ts: event.ts,
+            etype: event.etype,
+            rtype: event.rtype,
+            value: Some(event.value),
+            correlation_id: event.correlation_id,
+            metadata: event.metadata,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<ServerEvent> for Message {
+    fn from(event: ServerEvent) -> Self {
+        Self {
+            ts: event.ts,
+            etype: event.etype,
+            data: Some(event.data),
+            correlation_id: event.correlation_id,
+            metadata: event.metadata,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<RpcRequest> for Message {
+    fn from(request: RpcRequest) -> Self {
+        Self {
+            ts: request.ts,
+            etype: request.etype,
+            rtype: Some(request.rtype),
+            args: request.args,
+            kwargs: request.kwargs,
+            metadata: request.metadata,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<RpcResponse> for Message {
+    fn from(response: RpcResponse) -> Self {
+        Self {
+            ts: response.ts,
+            etype: response.etype,
+            data: response.data,
+            value: response.value,
+            ok: response.ok,
+            error: response.error,
+            error_kind: response.error_kind,
+            is_final: response.is_final,
+            attempt: response.attempt,
+            ..Default::default()
+        }
+    }
+}
+
+/// Fails if the message has no `value`
+impl TryFrom<Message> for ClientEvent {
+    type Error = crate::error::VmpError;
+
+    fn try_from(message: Message) -> crate::error::Result<Self> {
+        let value = message.value.ok_or_else(|| {
+            crate::error::VmpError::MissingField("ClientEvent requires value".to_string())
+        })?;
+        Ok(Self {
+            ts: message.ts,
+            etype: message.etype,
+            rtype: message.rtype,
+            value,
+            correlation_id: message.correlation_id,
+            metadata: message.metadata,
+        })
+    }
+}
+
+/// Fails if the message has no `data`; a raw payload is parsed into `data`
+impl TryFrom<Message> for ServerEvent {
+    type Error = crate::error::VmpError;
+
+    fn try_from(message: Message) -> crate::error::Result<Self> {
+        let data = match (message.data, message.raw_data) {
+            (Some(data), _) => data,
+            (None, Some(raw)) => serde_json::from_str(raw.get())?,
+            (None, None) => {
+                return Err(crate::error::VmpError::MissingField(
+                    "ServerEvent requires data".to_string(),
+                ));
+            }
+        };
+        Ok(Self {
+            ts: message.ts,
+            etype: message.etype,
+            data,
+            correlation_id: message.correlation_id,
+            metadata: message.metadata,
+        })
+    }
+}
+
+/// Fails if the message has no `rtype`
+impl TryFrom<Message> for RpcRequest {
+    type Error = crate::error::VmpError;
+
+    fn try_from(message: Message) -> crate::error::Result<Self> {
+        let rtype = message.rtype.ok_or_else(|| {
+            crate::error::VmpError::MissingField("RpcRequest requires rtype".to_string())
+        })?;
+        Ok(Self {
+            ts: message.ts,
+            etype: message.etype,
+            rtype,
+            args: message.args,
+            kwargs: message.kwargs,
+            metadata: message.metadata,
+        })
+    }
+}
+
+/// Fails if the message has none of `data`, `value`, `ok` and `error`
+impl TryFrom<Message> for RpcResponse {
+    type Error = crate::error::VmpError;
+
+    fn try_from(message: Message) -> crate::error::Result<Self> {
+        let data = match (message.data, message.raw_data) {
+            (None, Some(raw)) => Some(serde_json::from_str(raw.get())?),
+            (data, _) => data,
+        };
+        if data.is_none()
+            && message.value.is_none()
+            && message.ok.is_none()
+            && message.error.is_none()
+        {
+            return Err(crate::error::VmpError::MissingField(
+                "RpcResponse requires data, value, ok or error".to_string(),
+            ));
+        }
+        Ok(Self {
+            ts: message.ts,
+            etype: message.etype,
+            data,
+            value: message.value,
+            ok: message.ok,
+            error: message.error,
+            error_kind: message.error_kind,
+            is_final: message.is_final,
+            attempt: message.attempt,
+        })
+    }
+}
+
+/// Writes `, name=value` for each payload field that is set
+struct DisplayFields<'a> {
+    rtype: Option<&'a str>,
+    data: Option<&'a dyn std::fmt::Display>,
+    value: Option<&'a serde_json::Value>,
+    args: Option<&'a [serde_json::Value]>,
+    kwargs: Option<&'a HashMap<String, serde_json::Value>>,
+    correlation_id: Option<&'a str>,
+}
+
+impl std::fmt::Display for DisplayFields<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(rtype) = self.rtype {
+            write!(f, ", rtype={}", rtype)?;
+        }
+        if let Some(data) = self.data {
+            write!(f, ", data={}", data)?;
+        }
+        if let Some(value) = self.value {
+            write!(f, ", value={}", value)?;
+        }
+        if let Some(args) = self.args {
+            write!(f, ", args={}", serde_json::Value::from(args))?;
+        }
+        if let Some(kwargs) = self.kwargs {
+            // Sorted, so the output does not depend on hash order
+            let kwargs: std::collections::BTreeMap<_, _> = kwargs.iter().collect();
+            write!(f, ", kwargs={}", serde_json::json!(kwargs))?;
+        }
+        if let Some(correlation_id) = self.correlation_id {
+            write!(f, ", correlation_id={}", correlation_id)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Message {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let raw_data = self.raw_data.as_ref().map(|raw| raw as &dyn std::fmt::Display);
+        let fields = DisplayFields {
+            rtype: self.rtype.as_deref(),
+            data: self.data.as_ref().map(|data| data as &dyn std::fmt::Display).or(raw_data),
+            value: self.value.as_ref(),
+            args: self.args.as_deref(),
+            kwargs: self.kwargs.as_ref(),
+            correlation_id: self.correlation_id.as_deref(),
+        };
+        write!(f, "Message[{} @ {}ms", self.etype, self.ts)?;
+        if let Some(ok) = self.ok {
+            write!(f, ", ok={}", ok)?;
+        }
+        if let Some(error) = &self.error {
+            write!(f, ", error={}", error)?;
+        }
+        write!(f, "{}]", fields)
+    }
+}
+
+impl std::fmt::Display for ClientEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fields = DisplayFields {
+            rtype: self.rtype.as_deref(),
+            data: None,
+            value: Some(&self.value),
+            args: None,
+            kwargs: None,
+            correlation_id: self.correlation_id.as_deref(),
+        };
+        write!(f, "ClientEvent[{} @ {}ms{}]", self.etype, self.ts, fields)
+    }
+}
+
+impl std::fmt::Display for ServerEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fields = DisplayFields {
+            rtype: None,
+            data: Some(&self.data as &dyn std::fmt::Display),
+            value: None,
+            args: None,
+            kwargs: None,
+            correlation_id: self.correlation_id.as_deref(),
+        };
+        write!(f, "ServerEvent[{} @ {}ms{}]", self.etype, self.ts, fields)
+    }
+}
+
+impl std::fmt::Display for RpcRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fields = DisplayFields {
+            rtype: Some(&self.rtype),
+            data: None,
+            value: None,
+            args: self.args.as_deref(),
+            kwargs: self.kwargs.as_ref(),
+            correlation_id: None,
+        };
+        write!(f, "RpcRequest[{} @ {}ms{}]", self.etype, self.ts, fields)
+    }
+}
+
+impl std::fmt::Display for RpcResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "RpcResponse[{} @ {}ms", self.etype, self.ts)?;
+        if let Some(ok) = self.ok {
+            write!(f, ", ok={}", ok)?;
+        }
+        if let Some(error) = &self.error {
+            write!(f, ", error={}", error)?;
         }
+        let fields = DisplayFields {
+            rtype: None,
+            data: self.data.as_ref().map(|data| data as &dyn std::fmt::Display),
+            value: self.value.as_ref(),
+            args: None,
+            kwargs: None,
+            correlation_id: None,
+        };
+        write!(f, "{}]", fields)
+    }
+}
+
+/// Shows the tag and counts only; see [`VuerComponent::display_tree`] for
+/// the whole tree
+impl std::fmt::Display for VuerComponent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let children = self.children.as_ref().map_or(0, Vec::len);
+        // The key is counted as the prop it is on the wire
+        let props = self.props.len() + self.key.is_some() as usize;
+        write!(f, "<{} props={} children={}>", self.tag, props, children)
     }
 }
 
@@ -325,6 +1655,7 @@ impl VuerComponent {
         Self {
             tag: tag.into(),
             children: None,
+            key: None,
             props: HashMap::new(),
         }
     }
@@ -336,10 +1667,397 @@ impl VuerComponent {
     }
 
     /// Set a property
+    ///
+    /// A `key` prop sets [`VuerComponent::key`] instead, as it would when
+    /// read off the wire.
     pub fn with_prop(mut self, key: impl Into<String>, value: serde_json::Value) -> Self {
-        self.props.insert(key.into(), value);
+        let key = key.into();
+        if key == crate::diff::CHILD_KEY_PROP {
+            self.key = (!value.is_null()).then_some(value);
+        } else {
+            self.props.insert(key, value);
+        }
+        self
+    }
+
+    /// Set the key used to match children across updates
+    pub fn with_key(mut self, key: impl Into<String>) -> Self {
+        self.key = Some(serde_json::Value::String(key.into()));
+        self
+    }
+
+    /// The key as text: a string as it is, a number or boolean in its JSON
+    /// form
+    pub fn key_text(&self) -> Option<std::borrow::Cow<'_, str>> {
+        self.key.as_ref().map(|key| match key {
+            serde_json::Value::String(key) => std::borrow::Cow::Borrowed(key.as_str()),
+            other => std::borrow::Cow::Owned(other.to_string()),
+        })
+    }
+
+    /// Remove a property
+    pub fn without_prop(mut self, key: &str) -> Self {
+        self.props.remove(key);
         self
     }
+
+    /// Overlay `overrides` on the props, replacing existing values
+    pub fn merge_props(mut self, overrides: &HashMap<String, serde_json::Value>) -> Self {
+        self.props.extend(overrides.iter().map(|(k, v)| (k.clone(), v.clone())));
+        self
+    }
+
+    /// Overlay `overrides` on the props, merging nested objects key by key
+    ///
+    /// Any other value, including arrays, replaces the existing one.
+    pub fn deep_merge_props(mut self, overrides: &HashMap<String, serde_json::Value>) -> Self {
+        for (key, value) in overrides {
+            match self.props.get_mut(key) {
+                Some(existing) => deep_merge(existing, value),
+                None => {
+                    self.props.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        self
+    }
+
+    /// Give an explicit JSON Schema for the prop `key`, which
+    /// [`VuerComponent::to_json_schema`] uses instead of inferring one
+    ///
+    /// Hints are stored in the [`SCHEMA_HINT_PROP`] prop.
+    pub fn with_schema_hint(mut self, key: &str, schema: serde_json::Value) -> Self {
+        let hints = self
+            .props
+            .entry(SCHEMA_HINT_PROP.to_string())
+            .or_insert_with(|| serde_json::Value::Object(Default::default()));
+        if !hints.is_object() {
+            *hints = serde_json::Value::Object(Default::default());
+        }
+        if let Some(hints) = hints.as_object_mut() {
+            hints.insert(key.to_string(), schema);
+        }
+        self
+    }
+
+    /// Multi-line outline of the tree, one component per line, with each
+    /// level of nesting indented by `indent` more spaces
+    pub fn display_tree(&self, indent: usize) -> String {
+        let mut lines = Vec::new();
+        let mut stack = vec![(self, 0)];
+        while let Some((node, depth)) = stack.pop() {
+            lines.push(format!("{:width$}{}", "", node, width = depth * indent));
+            // Pushed in reverse so children come out in order
+            for child in node.children.iter().flatten().rev() {
+                stack.push((child, depth + 1));
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// JSON Schema (draft-07) describing this component tree
+    ///
+    /// Prop types are inferred from their current values unless a schema
+    /// hint was given. Children are described positionally by an `items`
+    /// array under `children`.
+    pub fn to_json_schema(&self) -> serde_json::Value {
+        let mut schema = self.component_schema();
+        schema["$schema"] = "http://json-schema.org/draft-07/schema#".into();
+        schema
+    }
+
+    fn component_schema(&self) -> serde_json::Value {
+        let hints = self.props.get(SCHEMA_HINT_PROP).and_then(|h| h.as_object());
+        let mut properties = serde_json::Map::new();
+        properties.insert("tag".to_string(), serde_json::json!({"const": self.tag}));
+        if let Some(key) = &self.key {
+            properties.insert(crate::diff::CHILD_KEY_PROP.to_string(), infer_schema(key));
+        }
+        for (key, value) in &self.props {
+            if key != SCHEMA_HINT_PROP {
+                let hint = hints.and_then(|h| h.get(key)).cloned();
+                properties.insert(key.clone(), hint.unwrap_or_else(|| infer_schema(value)));
+            }
+        }
+        if let Some(children) = &self.children {
+            let items: Vec<_> = children.iter().map(Self::component_schema).collect();
+            properties.insert(
+                "children".to_string(),
+                serde_json::json!({"type": "array", "items": items}),
+            );
+        }
+
+        serde_json::json!({
+            "title": self.tag,
+            "type": "object",
+            "properties": properties,
+            "required": ["tag"],
+        })
+    }
+
+    /// Iterate over the direct children
+    fn child_iter(&self) -> impl Iterator<Item = &VuerComponent> {
+        self.children.iter().flatten()
+    }
+
+    /// Find the first component, in depth-first document order, that
+    /// matches `predicate`. The search includes `self`.
+    pub fn find(&self, predicate: impl Fn(&VuerComponent) -> bool) -> Option<&VuerComponent> {
+        fn search<'a>(
+            node: &'a VuerComponent,
+            predicate: &dyn Fn(&VuerComponent) -> bool,
+        ) -> Option<&'a VuerComponent> {
+            if predicate(node) {
+                return Some(node);
+            }
+            node.child_iter().find_map(|child| search(child, predicate))
+        }
+        search(self, &predicate)
+    }
+
+    /// Mutable variant of [`VuerComponent::find`]
+    pub fn find_mut(
+        &mut self,
+        predicate: impl Fn(&VuerComponent) -> bool,
+    ) -> Option<&mut VuerComponent> {
+        fn search<'a>(
+            node: &'a mut VuerComponent,
+            predicate: &dyn Fn(&VuerComponent) -> bool,
+        ) -> Option<&'a mut VuerComponent> {
+            if predicate(node) {
+                return Some(node);
+            }
+            node.children
+                .iter_mut()
+                .flatten()
+                .find_map(|child| search(child, predicate))
+        }
+        search(self, &predicate)
+    }
+
+    /// Collect every component that matches `predicate`, in depth-first
+    /// document order. The search includes `self`.
+    pub fn find_all(&self, predicate: impl Fn(&VuerComponent) -> bool) -> Vec<&VuerComponent> {
+        let mut matches = Vec::new();
+        self.walk(&mut |node| {
+            if predicate(node) {
+                matches.push(node);
+            }
+        });
+        matches
+    }
+
+    /// Call `f` on every component that matches `predicate`, in depth-first
+    /// document order
+    ///
+    /// Matches can be nested inside each other, so they cannot all be
+    /// borrowed mutably at once; `f` is called on each in turn instead, and
+    /// a node's children are searched after `f` has run on it.
+    ///
+    /// # Returns
+    ///
+    /// The number of matching components
+    pub fn find_all_mut(
+        &mut self,
+        predicate: impl Fn(&VuerComponent) -> bool,
+        mut f: impl FnMut(&mut VuerComponent),
+    ) -> usize {
+        fn search(
+            node: &mut VuerComponent,
+            predicate: &dyn Fn(&VuerComponent) -> bool,
+            f: &mut dyn FnMut(&mut VuerComponent),
+        ) -> usize {
+            let mut count = 0;
+            if predicate(node) {
+                f(node);
+                count += 1;
+            }
+            for child in node.children.iter_mut().flatten() {
+                count += search(child, predicate, f);
+            }
+            count
+        }
+        search(self, &predicate, &mut f)
+    }
+
+    /// Collect every component with the given tag
+    pub fn find_by_tag(&self, tag: &str) -> Vec<&VuerComponent> {
+        self.find_all(|node| node.tag == tag)
+    }
+
+    /// Find the first component, in depth-first document order, whose
+    /// [`VuerComponent::key_text`] is `key`. The search includes `self`.
+    pub fn find_by_key(&self, key: &str) -> Option<&VuerComponent> {
+        self.find(|node| node.key_text().as_deref() == Some(key))
+    }
+
+    /// Mutable variant of [`VuerComponent::find_by_key`]
+    pub fn find_by_key_mut(&mut self, key: &str) -> Option<&mut VuerComponent> {
+        self.find_mut(|node| node.key_text().as_deref() == Some(key))
+    }
+
+    /// Collect every component whose prop `key` equals `value`
+    pub fn find_by_prop(&self, key: &str, value: &serde_json::Value) -> Vec<&VuerComponent> {
+        self.find_all(|node| node.props.get(key) == Some(value))
+    }
+
+    /// Flatten the tree into a list in depth-first document order
+    ///
+    /// Generated keys are not added to `props`, so
+    /// [`VuerComponent::from_flat`] restores the original keys. Empty
+    /// child lists are not represented and come back as `None`.
+    pub fn flatten(&self) -> Vec<FlatComponent> {
+        fn visit(node: &VuerComponent, parent_key: Option<&str>, out: &mut Vec<FlatComponent>) {
+            let mut props = node.props.clone();
+            let key = match (&node.key, node.key_text()) {
+                (Some(key), Some(text)) => {
+                    props.insert(crate::diff::CHILD_KEY_PROP.to_string(), key.clone());
+                    text.into_owned()
+                }
+                _ => uuid::Uuid::new_v4().to_string(),
+            };
+            out.push(FlatComponent {
+                tag: node.tag.clone(),
+                props,
+                key: key.clone(),
+                parent_key: parent_key.map(str::to_string),
+            });
+            for child in node.child_iter() {
+                visit(child, Some(&key), out);
+            }
+        }
+
+        let mut out = Vec::new();
+        visit(self, None, &mut out);
+        out
+    }
+
+    /// Rebuild a tree from the output of [`VuerComponent::flatten`]
+    ///
+    /// Children keep the order in which they appear in `nodes`. Fails with
+    /// `VmpError::InvalidMessage` unless there is exactly one root, keys
+    /// are unique and every node is reachable from the root.
+    pub fn from_flat(nodes: &[FlatComponent]) -> crate::error::Result<VuerComponent> {
+        use crate::error::VmpError;
+
+        let mut index: HashMap<&str, usize> = HashMap::new();
+        for (i, node) in nodes.iter().enumerate() {
+            if index.insert(node.key.as_str(), i).is_some() {
+                return Err(VmpError::InvalidMessage(format!(
+                    "Duplicate component key: {}",
+                    node.key
+                )));
+            }
+        }
+
+        let mut roots = Vec::new();
+        let mut child_lists: Vec<Vec<usize>> = vec![Vec::new(); nodes.len()];
+        for (i, node) in nodes.iter().enumerate() {
+            match &node.parent_key {
+                None => roots.push(i),
+                Some(parent) => {
+                    let &p = index.get(parent.as_str()).ok_or_else(|| {
+                        VmpError::InvalidMessage(format!(
+                            "Component {} refers to unknown parent {}",
+                            node.key, parent
+                        ))
+                    })?;
+                    child_lists[p].push(i);
+                }
+            }
+        }
+
+        let [root] = roots[..] else {
+            return Err(VmpError::InvalidMessage(format!(
+                "Expected exactly one root component, found {}",
+                roots.len()
+            )));
+        };
+
+        // Order the nodes parent-first without recursing, so deep input
+        // cannot overflow the stack
+        let mut order = Vec::with_capacity(nodes.len());
+        let mut stack = vec![root];
+        while let Some(i) = stack.pop() {
+            order.push(i);
+            stack.extend(child_lists[i].iter().rev());
+        }
+        if order.len() != nodes.len() {
+            return Err(VmpError::InvalidMessage(
+                "Some components are not reachable from the root".to_string(),
+            ));
+        }
+
+        // Build children before their parents
+        let mut built: Vec<Option<VuerComponent>> = vec![None; nodes.len()];
+        for &i in order.iter().rev() {
+            let children: Vec<VuerComponent> =
+                child_lists[i].iter().filter_map(|&c| built[c].take()).collect();
+            let mut props = nodes[i].props.clone();
+            let key = props.remove(crate::diff::CHILD_KEY_PROP).filter(|key| !key.is_null());
+            built[i] = Some(VuerComponent {
+                tag: nodes[i].tag.clone(),
+                children: (!children.is_empty()).then_some(children),
+                key,
+                props,
+            });
+        }
+
+        Ok(built[root].take().unwrap_or_default())
+    }
+
+    /// Visit `self` and every descendant in depth-first document order
+    pub fn walk<'a>(&'a self, visitor: &mut dyn FnMut(&'a VuerComponent)) {
+        visitor(self);
+        for child in self.child_iter() {
+            child.walk(visitor);
+        }
+    }
+}
+
+/// JSON Schema matching the type of `value`
+///
+/// Arrays get an `items` schema only when all their elements share one.
+fn infer_schema(value: &serde_json::Value) -> serde_json::Value {
+    use serde_json::{json, Value};
+
+    match value {
+        Value::Null => json!({"type": "null"}),
+        Value::Bool(_) => json!({"type": "boolean"}),
+        Value::Number(_) => json!({"type": "number"}),
+        Value::String(_) => json!({"type": "string"}),
+        Value::Array(items) => {
+            let mut schema = json!({"type": "array"});
+            let mut item_schemas = items.iter().map(infer_schema);
+            if let Some(first) = item_schemas.next()
+                && item_schemas.all(|s| s == first)
+            {
+                schema["items"] = first;
+            }
+            schema
+        }
+        Value::Object(map) => {
+            let properties: serde_json::Map<_, _> =
+                map.iter().map(|(k, v)| (k.clone(), infer_schema(v))).collect();
+            json!({"type": "object", "properties": properties})
+        }
+    }
+}
+
+fn deep_merge(target: &mut serde_json::Value, overlay: &serde_json::Value) {
+    match (target, overlay) {
+        (serde_json::Value::Object(target), serde_json::Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match target.get_mut(key) {
+                    Some(existing) => deep_merge(existing, value),
+                    None => {
+                        target.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (target, overlay) => *target = overlay.clone(),
+    }
 }
 
 #[cfg(test)]
@@ -364,6 +2082,237 @@ mod tests {
         assert_eq!(event.value["x"], 100);
     }
 
+    #[test]
+    fn test_event_conversions() {
+        let event = ClientEvent::new("CLICK", json!({"x": 1}))
+            .with_rtype("CLICK_RESPONSE")
+            .with_correlation("req-1")
+            .with_metadata("trace_id", json!("t-1"));
+        let msg = Message::from_client_event(event.clone());
+        assert_eq!(msg.value, Some(json!({"x": 1})));
+        assert_eq!(msg.rtype.as_deref(), Some("CLICK_RESPONSE"));
+        assert_eq!(msg.trace_id(), Some("t-1"));
+        assert!(msg.data.is_none());
+        assert_eq!(ClientEvent::try_from(msg).unwrap(), event);
+
+        let event = ServerEvent::new("SET", json!({"tag": "scene"})).with_correlation("req-2");
+        let msg = Message::from(event.clone());
+        assert_eq!(msg.data, Some(json!({"tag": "scene"})));
+        assert_eq!(msg.correlation_id.as_deref(), Some("req-2"));
+        assert_eq!(ServerEvent::try_from(msg).unwrap(), event);
+
+        let request = RpcRequest::new("render", "rpc-1").with_args(vec![json!(1)]);
+        let msg = Message::from(request.clone());
+        assert_eq!(msg.rtype.as_deref(), Some("rpc-1"));
+        assert_eq!(RpcRequest::try_from(msg).unwrap(), request);
+
+        let response = RpcResponse::success("rpc-1", json!("done"));
+        let msg = Message::from(response.clone());
+        assert_eq!((msg.ts, msg.etype.as_str()), (response.ts, "rpc-1"));
+        assert_eq!(msg.data, Some(json!("done")));
+        assert_eq!(msg.kind(), MessageKind::RpcResponse);
+        assert_eq!(RpcResponse::try_from(msg).unwrap(), response);
+
+        let response = RpcResponse {
+            is_final: Some(true),
+            attempt: Some(2),
+            ..RpcResponse::success("rpc-1", json!("chunk"))
+        };
+        assert_eq!(RpcResponse::try_from(Message::from(response.clone())).unwrap(), response);
+
+        let response = RpcResponse::error_with_kind(
+            "rpc-2",
+            "mesh not found",
+            crate::error::ErrorKind::NotFound,
+        );
+        let msg = Message::from(response.clone());
+        assert!(msg.data.is_none() && msg.value.is_none() && msg.metadata.is_none());
+        assert_eq!(msg.error.as_deref(), Some("mesh not found"));
+        assert_eq!(msg.kind(), MessageKind::RpcResponse);
+        let bytes = crate::serializer::serialize_message(&msg).unwrap();
+        let decoded = crate::deserializer::deserialize_message(&bytes).unwrap();
+        assert_eq!(RpcResponse::try_from(decoded).unwrap(), response);
+    }
+
+    #[test]
+    fn test_message_kind() {
+        let event = ClientEvent::new("CLICK", json!(1)).with_rtype("CLICK_ACK");
+        assert_eq!(Message::from(event).kind(), MessageKind::ClientEvent);
+        let event = ServerEvent::new("SET", json!(1));
+        assert_eq!(Message::from(event).kind(), MessageKind::ServerEvent);
+        let raw = serde_json::value::RawValue::from_string("[1]".to_string()).unwrap();
+        assert_eq!(Message::new("SET").with_raw_data(raw).kind(), MessageKind::ServerEvent);
+
+        let request = RpcRequest::new("render", "rpc-1").with_args(vec![json!(1)]);
+        assert_eq!(Message::from(request).kind(), MessageKind::RpcRequest);
+        let request = RpcRequest::new("render", "rpc-1").with_args(vec![json!(1)]);
+        let mut answered = Message::from(request);
+        answered.ok = Some(true);
+        assert_eq!(answered.kind(), MessageKind::Message);
+
+        // Response fields in user metadata do not make a response
+        let event = ClientEvent::new("FORM", json!(1)).with_metadata("error", json!("required"));
+        assert_eq!(Message::from_client_event(event).kind(), MessageKind::ClientEvent);
+
+        assert_eq!(Message::new("PING").kind(), MessageKind::Message);
+        assert_eq!(Message::new("PING").with_rtype("PONG").kind(), MessageKind::Message);
+        let both = Message::new("ECHO").with_data(json!(1)).with_value(json!(2));
+        assert_eq!(both.kind(), MessageKind::Message);
+    }
+
+    #[test]
+    fn test_event_conversion_errors() {
+        let bare = Message::new("PING");
+        assert!(matches!(
+            ClientEvent::try_from(bare.clone()),
+            Err(crate::error::VmpError::MissingField(_))
+        ));
+        assert!(ServerEvent::try_from(bare.clone()).is_err());
+        assert!(RpcRequest::try_from(bare.clone()).is_err());
+        assert!(matches!(
+            RpcResponse::try_from(bare.clone()),
+            Err(crate::error::VmpError::MissingField(m)) if m.contains("data, value, ok or error")
+        ));
+
+        // A payload in the other field does not count
+        assert!(ClientEvent::try_from(bare.clone().with_data(json!(1))).is_err());
+        assert!(ServerEvent::try_from(bare.with_value(json!(1))).is_err());
+    }
+
+    #[test]
+    fn test_rpc_request_builder() {
+        // Fields may be set in any order
+        let request = RpcRequest::builder()
+            .args(vec![json!(1)])
+            .rtype("rpc-123")
+            .etype("render")
+            .kwargs(HashMap::from([("fast".to_string(), json!(true))]))
+            .build();
+        let expected = RpcRequest::new("render", "rpc-123")
+            .with_args(vec![json!(1)])
+            .with_kwargs(HashMap::from([("fast".to_string(), json!(true))]));
+        assert_eq!(request, RpcRequest { ts: request.ts, ..expected });
+    }
+
+    #[test]
+    fn test_rpc_request_arguments() {
+        use crate::error::VmpError;
+
+        let request = RpcRequest::new("render", "rpc-1")
+            .with_args(vec![json!("scene.glb"), json!([1.0, 2.0])])
+            .with_kwargs(HashMap::from([
+                ("seed".to_string(), json!(100)),
+                ("fast".to_string(), json!(true)),
+            ]));
+
+        assert_eq!(request.kwarg::<i64>("seed").unwrap(), 100);
+        assert_eq!(request.arg::<String>(0).unwrap(), "scene.glb");
+        assert_eq!(request.arg::<[f32; 2]>(1).unwrap(), [1.0, 2.0]);
+        assert_eq!(request.kwarg_or("steps", 50u32).unwrap(), 50);
+        assert_eq!(request.kwarg_or("seed", 0u8).unwrap(), 100);
+
+        assert!(matches!(
+            request.kwarg::<i64>("steps"),
+            Err(VmpError::MissingField(m)) if m == "kwarg `steps`"
+        ));
+        assert!(matches!(request.arg::<String>(2), Err(VmpError::MissingField(m)) if m == "arg 2"));
+        assert!(matches!(
+            request.kwarg::<String>("fast"),
+            Err(VmpError::TypeConversion(m))
+                if m.starts_with("kwarg `fast` does not deserialize as alloc::string::String")
+        ));
+        assert!(matches!(
+            request.arg::<i64>(0),
+            Err(VmpError::TypeConversion(m)) if m.starts_with("arg 0 does not deserialize as i64")
+        ));
+        assert!(request.kwarg_or("fast", 1i64).is_err());
+        assert!(RpcRequest::new("render", "rpc-2").arg::<i64>(0).is_err());
+    }
+
+    #[test]
+    fn test_serializable_builders() {
+        #[derive(Serialize)]
+        struct Camera {
+            fov: f32,
+            position: [f32; 3],
+        }
+        let camera = || Camera {
+            fov: 60.0,
+            position: [0.0, 1.0, 2.0],
+        };
+
+        let request = RpcRequest::new("render", "rpc-1")
+            .with_arg("scene.glb")
+            .and_then(|r| r.with_arg(3u8))
+            .and_then(|r| r.with_arg(camera()))
+            .and_then(|r| r.with_kwarg("seed", 100))
+            .and_then(|r| r.with_kwarg(String::from("tags"), vec!["a", "b"]))
+            .unwrap();
+        let expected = RpcRequest::new("render", "rpc-1")
+            .with_args(vec![
+                json!("scene.glb"),
+                json!(3),
+                json!({"fov": 60.0, "position": [0.0, 1.0, 2.0]}),
+            ])
+            .with_kwargs(HashMap::from([
+                ("seed".to_string(), json!(100)),
+                ("tags".to_string(), json!(["a", "b"])),
+            ]));
+        assert_eq!(request, RpcRequest { ts: request.ts, ..expected.clone() });
+
+        let bytes = crate::serializer::serialize(&request).unwrap();
+        let decoded: RpcRequest = crate::deserializer::deserialize(&bytes).unwrap();
+        assert_eq!(decoded, request);
+        assert_eq!(decoded.kwarg::<i64>("seed").unwrap(), 100);
+
+        let msg = Message::new("CAMERA")
+            .with_data_of(camera())
+            .and_then(|m| m.with_value_of(Some(1.5)))
+            .and_then(|m| m.with_kwarg("fast", true))
+            .unwrap();
+        assert_eq!(msg.data, Some(json!({"fov": 60.0, "position": [0.0, 1.0, 2.0]})));
+        assert_eq!(msg.value, Some(json!(1.5)));
+        assert_eq!(msg.kwargs, Some(HashMap::from([("fast".to_string(), json!(true))])));
+
+        // Maps with non-string keys have no JSON form
+        let bad = HashMap::from([((1, 2), "x")]);
+        assert!(matches!(
+            RpcRequest::new("render", "rpc-2").with_arg(bad),
+            Err(crate::error::VmpError::Serialization(_))
+        ));
+    }
+
+    #[test]
+    fn test_rpc_request_kwargs_as() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct RenderOptions {
+            seed: u64,
+            #[serde(default)]
+            steps: Option<u32>,
+        }
+
+        let request = RpcRequest::new("render", "rpc-1")
+            .with_kwargs(HashMap::from([("seed".to_string(), json!(7))]));
+        assert_eq!(
+            request.kwargs_as::<RenderOptions>().unwrap(),
+            RenderOptions { seed: 7, steps: None }
+        );
+
+        let wrong = RpcRequest::new("render", "rpc-2")
+            .with_kwargs(HashMap::from([("seed".to_string(), json!("seven"))]));
+        assert!(matches!(
+            wrong.kwargs_as::<RenderOptions>(),
+            Err(crate::error::VmpError::TypeConversion(_))
+        ));
+
+        // Missing kwargs behave like an empty map
+        let bare = RpcRequest::new("render", "rpc-3");
+        let err = bare.kwargs_as::<RenderOptions>().unwrap_err();
+        assert!(err.to_string().contains("missing field `seed`"));
+        let all_default: HashMap<String, i64> = bare.kwargs_as().unwrap();
+        assert!(all_default.is_empty());
+    }
+
     #[test]
     fn test_rpc_request() {
         let mut kwargs = HashMap::new();
@@ -388,4 +2337,628 @@ mod tests {
         assert_eq!(component.tag, "scene");
         assert_eq!(component.children.as_ref().unwrap().len(), 1);
     }
+
+    #[test]
+    fn test_metadata_roundtrip() {
+        use crate::deserializer::deserialize;
+        use crate::serializer::serialize;
+
+        let msg = Message::new("TEST_EVENT")
+            .with_data(json!({"foo": "bar"}))
+            .with_metadata(TRACE_ID_KEY, json!("trace-123"))
+            .with_metadata("auth", json!({"token": "secret"}));
+        let restored: Message = deserialize(&serialize(&msg).unwrap()).unwrap();
+        assert_eq!(restored, msg);
+        assert_eq!(restored.trace_id(), Some("trace-123"));
+        assert_eq!(restored.get_meta("auth").unwrap()["token"], "secret");
+
+        let event = ClientEvent::new("CLICK", json!({"x": 1}))
+            .with_metadata(TRACE_ID_KEY, json!("trace-456"));
+        let restored: ClientEvent = deserialize(&serialize(&event).unwrap()).unwrap();
+        assert_eq!(restored, event);
+        assert_eq!(restored.trace_id(), Some("trace-456"));
+
+        let event = ServerEvent::new("UPDATE", json!([1, 2, 3]))
+            .with_metadata("correlation_id", json!(42));
+        let restored: ServerEvent = deserialize(&serialize(&event).unwrap()).unwrap();
+        assert_eq!(restored, event);
+        assert_eq!(restored.get_meta("correlation_id"), Some(&json!(42)));
+        assert_eq!(restored.trace_id(), None);
+    }
+
+    #[test]
+    fn test_metadata_omitted_when_unset() {
+        let msg = Message::new("TEST_EVENT");
+        let value = serde_json::to_value(&msg).unwrap();
+        assert!(value.get("metadata").is_none());
+        assert_eq!(msg.get_meta(TRACE_ID_KEY), None);
+    }
+
+    #[test]
+    #[cfg(feature = "crypto")]
+    fn test_content_hash() {
+        let mut a = Message::new("UPDATE").with_data(json!({"x": 1, "y": 2}));
+        let mut b = a.clone();
+        a.ts = 1;
+        b.ts = 2;
+        assert_eq!(a.content_hash().unwrap(), b.content_hash().unwrap());
+
+        let c = b.clone().with_metadata("k1", json!(1)).with_metadata("k2", json!(2));
+        let d = b.clone().with_metadata("k2", json!(2)).with_metadata("k1", json!(1));
+        assert_eq!(c.content_hash().unwrap(), d.content_hash().unwrap());
+
+        let e = b.with_data(json!({"x": 1, "y": 3}));
+        assert_ne!(a.content_hash().unwrap(), e.content_hash().unwrap());
+
+        // A message that cannot be serialized has no hash or signature
+        let raw = serde_json::value::RawValue::from_string("[1]".to_string()).unwrap();
+        let mut both = Message::new("UPDATE").with_data(json!(1));
+        both.raw_data = Some(raw);
+        assert!(both.content_hash().is_err());
+        assert!(both.sign(b"key").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "crypto")]
+    fn test_signed_message_roundtrip() {
+        use crate::deserializer::deserialize;
+        use crate::serializer::serialize;
+
+        let key = b"session-secret";
+        let msg = Message::new("CLICK")
+            .with_value(json!({"x": 1}))
+            .with_metadata("k", json!("v"));
+        let signed = msg.sign(key).unwrap();
+
+        let restored: SignedMessage = deserialize(&serialize(&signed).unwrap()).unwrap();
+        assert_eq!(restored, signed);
+        assert_eq!(restored.verify(key).unwrap(), &msg);
+
+        // Receivers that ignore signatures still see a plain message
+        let plain: Message = deserialize(&serialize(&signed).unwrap()).unwrap();
+        assert_eq!(plain, msg);
+    }
+
+    #[test]
+    #[cfg(feature = "crypto")]
+    fn test_signed_message_rejects_tampering() {
+        let key = b"session-secret";
+        let signed = Message::new("CLICK").with_value(json!({"x": 1})).sign(key).unwrap();
+
+        assert!(matches!(
+            signed.verify(b"wrong-key"),
+            Err(crate::error::VmpError::InvalidMessage(_))
+        ));
+
+        let mut tampered = signed.clone();
+        tampered.message.value = Some(json!({"x": 2}));
+        assert!(tampered.verify(key).is_err());
+
+        let mut replayed = signed.clone();
+        replayed.message.ts += 1;
+        assert!(replayed.verify(key).is_err());
+
+        let mut forged = signed;
+        forged.signature[0] ^= 1;
+        assert!(forged.verify(key).is_err());
+    }
+
+    #[test]
+    fn test_message_expiry() {
+        let msg = Message::new("CAMERA");
+        assert!(!is_expired(&msg));
+        assert!(!is_expired_at(&msg, Timestamp::MAX));
+
+        let mut msg = Message::new("CAMERA");
+        msg.expires_at = Some(1_000);
+        assert!(!is_expired_at(&msg, 999));
+        assert!(!is_expired_at(&msg, 1_000));
+        assert!(is_expired_at(&msg, 1_001));
+        assert!(is_expired(&msg));
+    }
+
+    #[test]
+    fn test_message_with_ttl() {
+        let msg = Message::new("CAMERA").with_ttl(Duration::from_secs(60));
+        let expires_at = msg.expires_at.unwrap();
+        assert!(expires_at >= msg.ts + 60_000);
+        assert!(!is_expired(&msg));
+
+        let msg = Message::new("CAMERA").with_ttl(Duration::MAX);
+        assert_eq!(msg.expires_at, Some(Timestamp::MAX));
+        assert!(!is_expired(&msg));
+    }
+
+    #[test]
+    fn test_correlation_roundtrip() {
+        use crate::deserializer::deserialize;
+        use crate::serializer::serialize;
+
+        let msg = Message::new("UPDATE").with_correlation("chain-1");
+        let restored: Message = deserialize(&serialize(&msg).unwrap()).unwrap();
+        assert_eq!(restored.correlation_id(), Some("chain-1"));
+
+        let event = ClientEvent::new("CLICK", json!(1)).with_correlation("chain-2");
+        let restored: ClientEvent = deserialize(&serialize(&event).unwrap()).unwrap();
+        assert_eq!(restored.correlation_id(), Some("chain-2"));
+
+        let event = ServerEvent::new("SET", json!(2)).with_correlation("chain-3");
+        let restored: ServerEvent = deserialize(&serialize(&event).unwrap()).unwrap();
+        assert_eq!(restored.correlation_id(), Some("chain-3"));
+
+        assert_eq!(Message::new("UPDATE").correlation_id(), None);
+    }
+
+    #[test]
+    fn test_spawn_correlated() {
+        let parent = Message::new("CLICK");
+        let child = parent.spawn_correlated();
+        assert_eq!(child.etype, "CLICK");
+        assert_eq!(child.correlation_id(), Some(parent.ts.to_string().as_str()));
+
+        // Explicit IDs are carried down the chain
+        let parent = Message::new("CLICK").with_correlation("chain-1");
+        let grandchild = parent.spawn_correlated().spawn_correlated();
+        assert_eq!(grandchild.correlation_id(), Some("chain-1"));
+    }
+
+    fn scene_graph() -> VuerComponent {
+        VuerComponent::new("scene")
+            .with_child(
+                VuerComponent::new("group")
+                    .with_key("left")
+                    .with_child(VuerComponent::new("box").with_prop("color", json!("red")))
+                    .with_child(VuerComponent::new("sphere").with_prop("color", json!("blue"))),
+            )
+            .with_child(
+                VuerComponent::new("group")
+                    .with_key("right")
+                    .with_child(VuerComponent::new("box").with_prop("color", json!("blue"))),
+            )
+            .with_child(VuerComponent::new("light"))
+    }
+
+    #[test]
+    fn test_component_walk_order() {
+        let scene = scene_graph();
+        let mut tags = Vec::new();
+        scene.walk(&mut |node| tags.push(node.tag.clone()));
+        assert_eq!(tags, ["scene", "group", "box", "sphere", "group", "box", "light"]);
+    }
+
+    #[test]
+    fn test_component_find() {
+        let scene = scene_graph();
+
+        let found = scene.find(|node| node.tag == "box").unwrap();
+        assert_eq!(found.props["color"], "red");
+        assert_eq!(scene.find(|node| node.tag == "scene").unwrap().tag, "scene");
+        assert!(scene.find(|node| node.tag == "camera").is_none());
+
+        let boxes = scene.find_by_tag("box");
+        assert_eq!(boxes.len(), 2);
+        assert_eq!(boxes[1].props["color"], "blue");
+
+        let blue = scene.find_by_prop("color", &json!("blue"));
+        let blue_tags: Vec<&str> = blue.iter().map(|node| node.tag.as_str()).collect();
+        assert_eq!(blue_tags, ["sphere", "box"]);
+
+        let groups = scene.find_all(|node| node.children.is_some());
+        assert_eq!(groups.len(), 3);
+    }
+
+    #[test]
+    fn test_component_find_mut() {
+        let mut scene = scene_graph();
+
+        let right = scene.find_mut(|node| node.key == Some(json!("right")));
+        right.unwrap().props.insert("visible".to_string(), json!(false));
+        assert_eq!(scene.find_by_prop("visible", &json!(false)).len(), 1);
+
+        let count = scene.find_all_mut(
+            |node| node.tag == "box" || node.tag == "group",
+            |node| {
+                node.props.insert("selected".to_string(), json!(true));
+            },
+        );
+        assert_eq!(count, 4);
+        assert_eq!(scene.find_by_prop("selected", &json!(true)).len(), 4);
+    }
+
+    #[test]
+    fn test_component_find_by_key() {
+        let mut scene = scene_graph();
+        assert_eq!(scene.find_by_key("right").unwrap().children.as_ref().unwrap().len(), 1);
+        assert!(scene.find_by_key("missing").is_none());
+
+        let nested = VuerComponent::new("mesh").with_key("handle");
+        scene.find_by_key_mut("left").unwrap().children.as_mut().unwrap().push(nested);
+        assert_eq!(scene.find_by_key("handle").unwrap().tag, "mesh");
+
+        scene.find_by_key_mut("handle").unwrap().props.insert("visible".to_string(), json!(false));
+        assert_eq!(scene.find_by_key("handle").unwrap().props["visible"], false);
+        assert!(scene.find_by_key_mut("missing").is_none());
+
+        // Keys are found on `self` too, and the first match wins
+        let twins = VuerComponent::new("group")
+            .with_key("twin")
+            .with_child(VuerComponent::new("box").with_key("twin"));
+        assert_eq!(twins.find_by_key("twin").unwrap().tag, "group");
+    }
+
+    #[test]
+    fn test_component_key_wire_format() {
+        // The layout written before `key` was a field
+        let old_layout = json!({
+            "tag": "group",
+            "key": "arm",
+            "children": [{"tag": "box", "key": 7, "color": "red"}],
+            "visible": true,
+        });
+        let bytes = crate::serializer::serialize(&old_layout).unwrap();
+        let component = crate::deserializer::deserialize_component(&bytes).unwrap();
+        assert_eq!(component.key, Some(json!("arm")));
+        assert!(!component.props.contains_key("key"));
+        assert_eq!(component.props.len(), 1);
+        let child = &component.children.as_ref().unwrap()[0];
+        assert_eq!(child.key, Some(json!(7)));
+        assert_eq!(child.key_text().as_deref(), Some("7"));
+        assert_eq!(child.props, HashMap::from([("color".to_string(), json!("red"))]));
+        assert_eq!(component.find_by_key("7").unwrap().tag, "box");
+
+        // Keys go back out with the type they came in with
+        assert_eq!(serde_json::to_value(&component).unwrap(), old_layout);
+        let bytes = crate::serializer::serialize_component(&component).unwrap();
+        let wire: serde_json::Value = crate::deserializer::deserialize(&bytes).unwrap();
+        assert_eq!(wire, old_layout);
+
+        // A `key` prop is the key, never a second `key` entry
+        let via_prop = VuerComponent::new("box").with_prop("key", json!(3)).with_key("b");
+        assert!(via_prop.props.is_empty());
+        assert_eq!(serde_json::to_value(&via_prop).unwrap(), json!({"tag": "box", "key": "b"}));
+        let mut edited = VuerComponent::new("box").with_key("b");
+        edited.props.insert("key".to_string(), json!("stale"));
+        let bytes = crate::serializer::serialize_component(&edited).unwrap();
+        let wire: serde_json::Value = crate::deserializer::deserialize(&bytes).unwrap();
+        assert_eq!(wire, json!({"tag": "box", "key": "b"}));
+
+        let keyless = VuerComponent::new("light");
+        assert_eq!(serde_json::to_value(&keyless).unwrap(), json!({"tag": "light"}));
+        let null_key: VuerComponent =
+            serde_json::from_value(json!({"tag": "light", "key": null})).unwrap();
+        assert_eq!(null_key, keyless);
+        assert!(serde_json::from_value::<VuerComponent>(json!({"tag": "x", "key": [1]})).is_err());
+    }
+
+    #[test]
+    fn test_component_flatten_roundtrip() {
+        let scene = VuerComponent::new("scene").with_child(
+            VuerComponent::new("group")
+                .with_key("arm")
+                .with_child(
+                    VuerComponent::new("group")
+                        .with_child(VuerComponent::new("box").with_prop("color", json!("red")))
+                        .with_child(VuerComponent::new("sphere")),
+                )
+                .with_child(VuerComponent::new("light")),
+        );
+
+        let mut flat = scene.flatten();
+        assert_eq!(flat.len(), 6);
+        assert_eq!(flat[0].parent_key, None);
+        assert_eq!(flat[1].key, "arm");
+        assert_eq!(flat[2].parent_key.as_deref(), Some("arm"));
+        assert_eq!(flat[3].parent_key.as_ref(), Some(&flat[2].key));
+        // Only keys the component had are kept in props
+        assert_eq!(flat[1].props["key"], "arm");
+        assert!(!flat[3].props.contains_key("key"));
+
+        assert_eq!(VuerComponent::from_flat(&flat).unwrap(), scene);
+
+        flat[3].props.insert("color".to_string(), json!("green"));
+        let rebuilt = VuerComponent::from_flat(&flat).unwrap();
+        assert_eq!(rebuilt.find_by_tag("box")[0].props["color"], "green");
+        assert_eq!(rebuilt.find_by_tag("sphere").len(), 1);
+        assert_eq!(rebuilt.children.as_ref().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_component_from_flat_invalid() {
+        let node = |key: &str, parent: Option<&str>| FlatComponent {
+            tag: "group".to_string(),
+            props: HashMap::new(),
+            key: key.to_string(),
+            parent_key: parent.map(str::to_string),
+        };
+
+        assert!(VuerComponent::from_flat(&[]).is_err());
+        assert!(VuerComponent::from_flat(&[node("a", None), node("b", None)]).is_err());
+        assert!(VuerComponent::from_flat(&[node("a", None), node("a", Some("a"))]).is_err());
+        assert!(VuerComponent::from_flat(&[node("a", None), node("b", Some("x"))]).is_err());
+        // A cycle detached from the root
+        let cycle = [node("a", None), node("b", Some("c")), node("c", Some("b"))];
+        assert!(VuerComponent::from_flat(&cycle).is_err());
+    }
+
+    fn base_material() -> VuerComponent {
+        VuerComponent::new("mesh")
+            .with_prop("color", json!("red"))
+            .with_prop("material", json!({"roughness": 0.5, "map": {"repeat": [1, 1]}}))
+    }
+
+    #[test]
+    fn test_merge_props() {
+        let overrides = HashMap::from([
+            ("color".to_string(), json!("blue")),
+            ("visible".to_string(), json!(false)),
+            ("material".to_string(), json!({"metalness": 1.0})),
+        ]);
+        let merged = base_material().merge_props(&overrides);
+
+        assert_eq!(merged.props.len(), 3);
+        assert_eq!(merged.props["color"], json!("blue"));
+        assert_eq!(merged.props["visible"], json!(false));
+        // Shallow merge replaces nested objects wholesale
+        assert_eq!(merged.props["material"], json!({"metalness": 1.0}));
+
+        assert_eq!(base_material().merge_props(&HashMap::new()), base_material());
+    }
+
+    #[test]
+    fn test_deep_merge_props() {
+        let overrides = HashMap::from([
+            ("color".to_string(), json!("blue")),
+            (
+                "material".to_string(),
+                json!({"metalness": 1.0, "map": {"repeat": [2, 2], "offset": [0, 1]}}),
+            ),
+        ]);
+        let merged = base_material().deep_merge_props(&overrides);
+
+        assert_eq!(merged.props["color"], json!("blue"));
+        assert_eq!(
+            merged.props["material"],
+            json!({
+                "roughness": 0.5,
+                "metalness": 1.0,
+                "map": {"repeat": [2, 2], "offset": [0, 1]}
+            })
+        );
+
+        // Non-object values replace objects and vice versa
+        let overrides = HashMap::from([("material".to_string(), json!("standard"))]);
+        let merged = base_material().deep_merge_props(&overrides);
+        assert_eq!(merged.props["material"], json!("standard"));
+    }
+
+    #[test]
+    fn test_without_prop() {
+        let component = base_material().without_prop("color").without_prop("missing");
+        assert_eq!(component.props.len(), 1);
+        assert!(component.props.contains_key("material"));
+    }
+
+    /// Check the draft-07 keywords used by `to_json_schema` recursively
+    fn assert_draft07(schema: &serde_json::Value) {
+        const TYPES: [&str; 7] =
+            ["null", "boolean", "number", "integer", "string", "array", "object"];
+
+        let schema = schema.as_object().expect("schemas are objects");
+        if let Some(ty) = schema.get("type") {
+            assert!(TYPES.contains(&ty.as_str().unwrap()), "invalid type {}", ty);
+        }
+        for sub in schema.get("properties").and_then(|p| p.as_object()).into_iter().flatten() {
+            assert_draft07(sub.1);
+        }
+        match schema.get("items") {
+            Some(serde_json::Value::Array(items)) => items.iter().for_each(assert_draft07),
+            Some(items) => assert_draft07(items),
+            None => {}
+        }
+        if let Some(required) = schema.get("required") {
+            assert!(required.as_array().unwrap().iter().all(|r| r.is_string()));
+        }
+    }
+
+    #[test]
+    fn test_to_json_schema() {
+        let scene = VuerComponent::new("scene")
+            .with_prop("background", json!("#000"))
+            .with_child(
+                VuerComponent::new("mesh")
+                    .with_prop("position", json!([0.0, 1.0, 2.0]))
+                    .with_prop("visible", json!(true))
+                    .with_prop("material", json!({"color": "red", "opacity": 0.5}))
+                    .with_prop("tags", json!(["a", 1]))
+                    .with_prop("parent", json!(null))
+                    .with_prop("mode", json!("wireframe"))
+                    .with_schema_hint("mode", json!({"enum": ["solid", "wireframe"]})),
+            )
+            .with_child(VuerComponent::new("light"));
+
+        let schema = scene.to_json_schema();
+        assert_eq!(schema["$schema"], "http://json-schema.org/draft-07/schema#");
+        assert_eq!(schema["type"], "object");
+        assert_eq!(schema["properties"]["tag"], json!({"const": "scene"}));
+        assert_eq!(schema["properties"]["background"], json!({"type": "string"}));
+        assert_draft07(&schema);
+
+        let children = schema["properties"]["children"]["items"].as_array().unwrap();
+        assert_eq!(children.len(), 2);
+        assert!(children[0].get("$schema").is_none());
+        let mesh = &children[0]["properties"];
+        assert_eq!(mesh["position"], json!({"type": "array", "items": {"type": "number"}}));
+        assert_eq!(mesh["visible"], json!({"type": "boolean"}));
+        assert_eq!(mesh["material"]["properties"]["opacity"], json!({"type": "number"}));
+        assert_eq!(mesh["tags"], json!({"type": "array"}));
+        assert_eq!(mesh["parent"], json!({"type": "null"}));
+        assert_eq!(mesh["mode"]["enum"], json!(["solid", "wireframe"]));
+        assert!(mesh.get(SCHEMA_HINT_PROP).is_none());
+        assert_eq!(children[1]["title"], "light");
+    }
+
+    #[test]
+    fn test_display_events() {
+        let mut msg = Message::new("CLICK").with_data(json!({"x": 1}));
+        msg.ts = 1234567890;
+        assert_eq!(msg.to_string(), r#"Message[CLICK @ 1234567890ms, data={"x":1}]"#);
+
+        msg.kwargs = Some(HashMap::from([
+            ("b".to_string(), json!(2)),
+            ("a".to_string(), json!("s")),
+        ]));
+        msg.correlation_id = Some("req-1".to_string());
+        assert_eq!(
+            msg.to_string(),
+            concat!(
+                r#"Message[CLICK @ 1234567890ms, data={"x":1}, kwargs={"a":"s","b":2}, "#,
+                "correlation_id=req-1]"
+            )
+        );
+
+        let mut event = ClientEvent::new("KEY", json!("a")).with_rtype("KEY_ACK");
+        event.ts = 5;
+        assert_eq!(event.to_string(), r#"ClientEvent[KEY @ 5ms, rtype=KEY_ACK, value="a"]"#);
+
+        let mut event = ServerEvent::new("SET", json!(null));
+        event.ts = 5;
+        assert_eq!(event.to_string(), "ServerEvent[SET @ 5ms, data=null]");
+
+        let mut request = RpcRequest::new("render", "rpc-1").with_args(vec![json!(1), json!(2)]);
+        request.ts = 5;
+        assert_eq!(request.to_string(), "RpcRequest[render @ 5ms, rtype=rpc-1, args=[1,2]]");
+
+        let mut response = RpcResponse::error("rpc-1", "timed out");
+        response.ts = 5;
+        assert_eq!(response.to_string(), "RpcResponse[rpc-1 @ 5ms, ok=false, error=timed out]");
+        let mut response = RpcResponse::success("rpc-1", json!([1]));
+        response.ts = 5;
+        assert_eq!(response.to_string(), "RpcResponse[rpc-1 @ 5ms, ok=true, data=[1]]");
+    }
+
+    #[test]
+    fn test_display_component_tree() {
+        let scene = VuerComponent::new("scene")
+            .with_prop("background", json!("#000"))
+            .with_child(
+                VuerComponent::new("group")
+                    .with_key("g")
+                    .with_child(VuerComponent::new("box").with_prop("size", json!(1))),
+            )
+            .with_child(VuerComponent::new("light"));
+
+        assert_eq!(scene.to_string(), "<scene props=1 children=2>");
+        assert_eq!(scene.children.as_ref().unwrap()[1].to_string(), "<light props=0 children=0>");
+        assert_eq!(
+            scene.display_tree(2),
+            "<scene props=1 children=2>\n  <group props=1 children=1>\n    \
+             <box props=1 children=0>\n  <light props=0 children=0>"
+        );
+    }
+
+    #[test]
+    fn test_tolerant_timestamp() {
+        use crate::deserializer::{deserialize, peek_fields};
+        use crate::serializer::serialize;
+
+        let decode = |ts: serde_json::Value| -> crate::error::Result<Timestamp> {
+            let bytes = serialize(&json!({"etype": "TICK", "ts": ts})).unwrap();
+            let ts = deserialize::<Message>(&bytes)?.ts;
+            assert_eq!(peek_fields(&bytes).unwrap().ts, ts);
+            Ok(ts)
+        };
+
+        let millis = 1_737_374_400_123;
+        assert_eq!(decode(json!(millis)).unwrap(), millis);
+        assert_eq!(decode(json!(1_737_374_400)).unwrap(), 1_737_374_400_000);
+        assert_eq!(decode(json!(1_737_374_400.123)).unwrap(), millis);
+        assert_eq!(decode(json!(1_737_374_400_123.4)).unwrap(), millis);
+        assert_eq!(decode(json!("1737374400123")).unwrap(), millis);
+        assert_eq!(decode(json!(" 1737374400.123 ")).unwrap(), millis);
+
+        // Epoch and the boundary between seconds and milliseconds
+        assert_eq!(decode(json!(0)).unwrap(), 0);
+        assert_eq!(decode(json!(0.0005)).unwrap(), 1);
+        assert_eq!(decode(json!(-1.5)).unwrap(), -1_500);
+        assert_eq!(
+            decode(json!(SECONDS_TIMESTAMP_LIMIT - 1)).unwrap(),
+            (SECONDS_TIMESTAMP_LIMIT - 1) * 1000
+        );
+        assert_eq!(decode(json!(SECONDS_TIMESTAMP_LIMIT)).unwrap(), SECONDS_TIMESTAMP_LIMIT);
+        assert_eq!(decode(json!(-SECONDS_TIMESTAMP_LIMIT)).unwrap(), -SECONDS_TIMESTAMP_LIMIT);
+
+        for bad in [json!("soon"), json!(u64::MAX), json!(1e300), json!(null)] {
+            assert!(decode(bad).is_err());
+        }
+
+        // Every event type reads `ts` the same way
+        let seconds = json!({"etype": "E", "rtype": "R", "ts": 1_737_374_400.5});
+        let expected = 1_737_374_400_500;
+        assert_eq!(serde_json::from_value::<ClientEvent>(seconds.clone()).unwrap().ts, expected);
+        assert_eq!(serde_json::from_value::<ServerEvent>(seconds.clone()).unwrap().ts, expected);
+        assert_eq!(serde_json::from_value::<RpcRequest>(seconds.clone()).unwrap().ts, expected);
+        assert_eq!(serde_json::from_value::<RpcResponse>(seconds).unwrap().ts, expected);
+
+        // Timestamps we write come back unchanged
+        let msg = Message::new("NOW");
+        assert_eq!(deserialize::<Message>(&serialize(&msg).unwrap()).unwrap().ts, msg.ts);
+    }
+
+    #[cfg(feature = "opentelemetry")]
+    fn remote_span_context() -> opentelemetry::trace::SpanContext {
+        use opentelemetry::trace::{SpanContext, SpanId, TraceFlags, TraceId, TraceState};
+
+        SpanContext::new(
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap(),
+            SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::from_key_value([("vendor", "v1")]).unwrap(),
+        )
+    }
+
+    #[cfg(feature = "opentelemetry")]
+    #[test]
+    fn test_trace_context_roundtrip() {
+        use crate::deserializer::deserialize_message;
+        use crate::serializer::serialize_message;
+        use opentelemetry::trace::TraceContextExt;
+        use opentelemetry_sdk::propagation::TraceContextPropagator;
+
+        opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+
+        // Nothing to propagate outside a span
+        let mut untraced = Message::new("CLICK");
+        untraced.inject_trace_context();
+        assert_eq!(untraced.metadata, None);
+        assert!(untraced.extract_trace_context().is_none());
+
+        let span_context = remote_span_context();
+        let guard = opentelemetry::Context::new()
+            .with_remote_span_context(span_context.clone())
+            .attach();
+        let mut msg = Message::new("CLICK").with_metadata("user", json!("ge"));
+        msg.inject_trace_context();
+        drop(guard);
+
+        assert_eq!(
+            msg.get_meta("traceparent"),
+            Some(&json!("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"))
+        );
+        assert_eq!(msg.get_meta("tracestate"), Some(&json!("vendor=v1")));
+        assert_eq!(msg.get_meta("user"), Some(&json!("ge")));
+
+        let restored = deserialize_message(&serialize_message(&msg).unwrap()).unwrap();
+        let context = restored.extract_trace_context().unwrap();
+        assert_eq!(context.span().span_context(), &span_context);
+
+        let mut request = RpcRequest::new("render", "rpc-1");
+        assert!(request.extract_trace_context().is_none());
+        let _guard = context.attach();
+        request.inject_trace_context();
+        assert_eq!(
+            request.extract_trace_context().unwrap().span().span_context(),
+            &span_context
+        );
+    }
 }
@@ -0,0 +1,217 @@
+//! Configurable message validation rules
+//!
+//! Author: Ge Yang
+
+use crate::error::{Result, VmpError};
+use crate::types::Message;
+
+/// A validation rule: returns an error describing the violation, if any
+pub type ValidationRule = Box<dyn Fn(&Message) -> Result<()> + Send + Sync>;
+
+/// Default limit for [`RULE_MAX_ETYPE_LEN`] in [`MessageValidator::default`]
+pub const DEFAULT_MAX_ETYPE_LEN: usize = 256;
+
+/// Rejects messages with an empty `etype`
+pub const RULE_NONEMPTY_ETYPE: fn(&Message) -> Result<()> = nonempty_etype;
+
+/// Rejects messages with `args` or `kwargs` but no `rtype`
+pub const RULE_RPC_HAS_RTYPE: fn(&Message) -> Result<()> = rpc_has_rtype;
+
+/// Rejects messages whose `ts` is zero or negative
+pub const RULE_POSITIVE_TIMESTAMP: fn(&Message) -> Result<()> = positive_timestamp;
+
+/// Builds a rule rejecting messages whose `etype` is longer than `n` bytes
+pub const RULE_MAX_ETYPE_LEN: fn(usize) -> ValidationRule = max_etype_len;
+
+fn nonempty_etype(msg: &Message) -> Result<()> {
+    if msg.etype.is_empty() {
+        return Err(VmpError::InvalidMessage(
+            "Message etype cannot be empty".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn rpc_has_rtype(msg: &Message) -> Result<()> {
+    if (msg.args.is_some() || msg.kwargs.is_some()) && msg.rtype.is_none() {
+        return Err(VmpError::InvalidMessage(
+            "RPC request must have rtype field".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn positive_timestamp(msg: &Message) -> Result<()> {
+    if msg.ts <= 0 {
+        return Err(VmpError::InvalidMessage(format!(
+            "Message ts must be positive, got {}",
+            msg.ts
+        )));
+    }
+    Ok(())
+}
+
+fn max_etype_len(n: usize) -> ValidationRule {
+    Box::new(move |msg| {
+        if msg.etype.len() > n {
+            return Err(VmpError::InvalidMessage(format!(
+                "Message etype of {} bytes exceeds the limit of {}",
+                msg.etype.len(),
+                n
+            )));
+        }
+        Ok(())
+    })
+}
+
+/// Checks messages against a set of rules, reporting every violation
+///
+/// The default validator applies all built-in rules, with
+/// [`DEFAULT_MAX_ETYPE_LEN`] as the etype limit. Use [`MessageValidator::new`]
+/// to start from no rules.
+///
+/// # Example
+///
+/// ```rust
+/// use vuer_rpc::validator::{MessageValidator, RULE_NONEMPTY_ETYPE};
+/// use vuer_rpc::{Message, VmpError};
+///
+/// let mut validator = MessageValidator::new();
+/// validator.add_rule(RULE_NONEMPTY_ETYPE).add_rule(|msg: &Message| {
+///     if msg.etype.starts_with("INTERNAL_") {
+///         return Err(VmpError::InvalidMessage("reserved etype".to_string()));
+///     }
+///     Ok(())
+/// });
+///
+/// assert!(validator.validate(&Message::new("CLICK")).is_empty());
+/// assert_eq!(validator.validate(&Message::new("INTERNAL_SYNC")).len(), 1);
+/// ```
+pub struct MessageValidator {
+    rules: Vec<ValidationRule>,
+}
+
+impl Default for MessageValidator {
+    fn default() -> Self {
+        let mut validator = Self::new();
+        validator
+            .add_rule(RULE_NONEMPTY_ETYPE)
+            .add_rule(RULE_RPC_HAS_RTYPE)
+            .add_rule(RULE_POSITIVE_TIMESTAMP)
+            .add_rule(RULE_MAX_ETYPE_LEN(DEFAULT_MAX_ETYPE_LEN));
+        validator
+    }
+}
+
+impl MessageValidator {
+    /// Create a validator with no rules
+    pub fn new() -> Self {
+        Self { rules: Vec::new() }
+    }
+
+    /// Add a rule, run after those already added
+    pub fn add_rule(
+        &mut self,
+        rule: impl Fn(&Message) -> Result<()> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    /// Run every rule, returning the violations in rule order
+    /// (empty if the message is valid)
+    pub fn validate(&self, msg: &Message) -> Vec<VmpError> {
+        self.rules.iter().filter_map(|rule| rule(msg).err()).collect()
+    }
+
+    /// Number of rules
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Whether the validator has no rules
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_nonempty_etype() {
+        assert!(RULE_NONEMPTY_ETYPE(&Message::new("CLICK")).is_ok());
+        assert!(matches!(
+            RULE_NONEMPTY_ETYPE(&Message::new("")),
+            Err(VmpError::InvalidMessage(m)) if m.contains("etype")
+        ));
+    }
+
+    #[test]
+    fn test_rpc_has_rtype() {
+        let mut msg = Message::new("render");
+        assert!(RULE_RPC_HAS_RTYPE(&msg).is_ok());
+
+        msg.kwargs = Some(Default::default());
+        assert!(RULE_RPC_HAS_RTYPE(&msg).is_err());
+
+        msg.rtype = Some("render_done".to_string());
+        assert!(RULE_RPC_HAS_RTYPE(&msg).is_ok());
+    }
+
+    #[test]
+    fn test_positive_timestamp() {
+        let mut msg = Message::new("TICK");
+        assert!(RULE_POSITIVE_TIMESTAMP(&msg).is_ok());
+
+        for ts in [0, -5] {
+            msg.ts = ts;
+            assert!(RULE_POSITIVE_TIMESTAMP(&msg).is_err());
+        }
+    }
+
+    #[test]
+    fn test_max_etype_len() {
+        let rule = RULE_MAX_ETYPE_LEN(5);
+        assert!(rule(&Message::new("CLICK")).is_ok());
+        assert!(matches!(
+            rule(&Message::new("CLICKS")),
+            Err(VmpError::InvalidMessage(m)) if m.contains("limit of 5")
+        ));
+    }
+
+    #[test]
+    fn test_collects_all_violations() {
+        let validator = MessageValidator::default();
+        assert_eq!(validator.len(), 4);
+        assert!(validator.validate(&Message::new("CLICK")).is_empty());
+
+        let mut msg = Message::new("");
+        msg.ts = 0;
+        msg.args = Some(vec![json!(1)]);
+        assert_eq!(validator.validate(&msg).len(), 3);
+
+        let long = Message::new("X".repeat(DEFAULT_MAX_ETYPE_LEN + 1));
+        assert_eq!(validator.validate(&long).len(), 1);
+    }
+
+    #[test]
+    fn test_custom_rules() {
+        let mut validator = MessageValidator::new();
+        assert!(validator.is_empty());
+        assert!(validator.validate(&Message::new("")).is_empty());
+
+        validator
+            .add_rule(|msg: &Message| match msg.data {
+                Some(_) => Ok(()),
+                None => Err(VmpError::MissingField("data".to_string())),
+            })
+            .add_rule(RULE_MAX_ETYPE_LEN(3));
+
+        let errors = validator.validate(&Message::new("LONG"));
+        assert!(matches!(errors[..], [VmpError::MissingField(_), VmpError::InvalidMessage(_)]));
+        assert!(validator.validate(&Message::new("SET").with_data(json!(1))).is_empty());
+    }
+}
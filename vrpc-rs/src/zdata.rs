@@ -3,24 +3,37 @@
 //! Author: Ge Yang
 
 use crate::error::{Result, VmpError};
+use bytes::Bytes;
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+/// Keys of a serialized ZData that are not extra fields
+pub(crate) const ZDATA_RESERVED_KEYS: [&str; 7] =
+    ["ztype", "b", "dtype", "shape", "strides", "vmp_version", "checksum"];
 
 /// ZData wrapper format for custom data types
 ///
 /// This struct provides a generic container for encoding custom types
 /// that may not have native Rust equivalents. It uses a type discriminator
 /// (`ztype`) and flexible fields to support various data formats.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+///
+/// `Debug` output shows the binary length and extra field names rather
+/// than their contents, so large payloads can be logged safely.
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
 pub struct ZData {
     /// Type identifier (e.g., "numpy.ndarray", "torch.Tensor", "image")
     pub ztype: String,
 
     /// Binary data (for arrays, images, etc.)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    #[serde(with = "serde_bytes")]
-    pub b: Option<Vec<u8>>,
+    ///
+    /// Reference counted, so cloning a ZData shares the buffer instead of
+    /// copying it. Serialized as a MessagePack bin field.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub b: Option<Bytes>,
 
     /// Element data type (for arrays/tensors)
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -30,11 +43,98 @@ pub struct ZData {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub shape: Option<Vec<usize>>,
 
+    /// Byte strides per dimension, as in NumPy (C order when absent)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strides: Option<Vec<isize>>,
+
+    /// Protocol version the sender encoded this ZData with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vmp_version: Option<String>,
+
+    /// Checksum of the binary data, checked by [`ZData::verify_checksum`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<ZDataChecksum>,
+
     /// Additional fields for custom types
     #[serde(flatten)]
     pub extra: IndexMap<String, Value>,
 }
 
+/// Checksum of a ZData binary payload
+///
+/// Serialized as a single-key map such as `{"crc32": 3632233996}`.
+/// Checksums this build cannot compute, such as SHA-256 without the
+/// `crypto` feature or algorithms it does not know, still decode and are
+/// kept as received, but [`ZData::verify_checksum`] leaves them unverified.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ZDataChecksum {
+    /// CRC32 (IEEE) of the binary data
+    Crc32(u32),
+
+    /// SHA-256 digest of the binary data, verified with the `crypto` feature
+    Sha256([u8; 32]),
+
+    /// A checksum of another algorithm, never verified
+    Other {
+        /// Name of the algorithm, as the single map key
+        algorithm: String,
+        /// Checksum value as received
+        value: Value,
+    },
+}
+
+impl ZDataChecksum {
+    /// Compute a checksum of `data` using the same algorithm as `self`, or
+    /// `None` if this build cannot compute it
+    fn recompute(&self, data: &[u8]) -> Option<Self> {
+        match self {
+            ZDataChecksum::Crc32(_) => Some(ZDataChecksum::Crc32(crc32fast::hash(data))),
+            #[cfg(feature = "crypto")]
+            ZDataChecksum::Sha256(_) => {
+                use sha2::{Digest, Sha256};
+                Some(ZDataChecksum::Sha256(Sha256::digest(data).into()))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Serialize for ZDataChecksum {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+
+        let mut map = serializer.serialize_map(Some(1))?;
+        match self {
+            ZDataChecksum::Crc32(crc) => map.serialize_entry("crc32", crc)?,
+            ZDataChecksum::Sha256(digest) => map.serialize_entry("sha256", digest)?,
+            ZDataChecksum::Other { algorithm, value } => map.serialize_entry(algorithm, value)?,
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for ZDataChecksum {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let mut entries = serde_json::Map::<String, Value>::deserialize(deserializer)?.into_iter();
+        let (Some((algorithm, value)), None) = (entries.next(), entries.next()) else {
+            return Err(D::Error::custom("checksum must be a map with a single entry"));
+        };
+        match algorithm.as_str() {
+            "crc32" => serde_json::from_value(value).map(ZDataChecksum::Crc32),
+            "sha256" => serde_json::from_value(value).map(ZDataChecksum::Sha256),
+            _ => Ok(ZDataChecksum::Other { algorithm, value }),
+        }
+        .map_err(D::Error::custom)
+    }
+}
+
 impl ZData {
     /// Create a new ZData with the given type identifier
     pub fn new(ztype: impl Into<String>) -> Self {
@@ -43,16 +143,74 @@ impl ZData {
             b: None,
             dtype: None,
             shape: None,
+            strides: None,
+            vmp_version: None,
+            checksum: None,
             extra: IndexMap::new(),
         }
     }
 
     /// Set binary data
-    pub fn with_binary(mut self, data: Vec<u8>) -> Self {
+    pub fn with_binary(mut self, data: impl Into<Bytes>) -> Self {
+        self.b = Some(data.into());
+        self
+    }
+
+    /// Set binary data from a shared buffer, without copying it
+    pub fn with_bytes(mut self, data: Bytes) -> Self {
         self.b = Some(data);
         self
     }
 
+    /// Take a byte range of the binary data as a new ZData sharing the same
+    /// buffer
+    ///
+    /// With a known dtype the range must fall on element boundaries, and the
+    /// result is a 1-D array of the elements in it. Checksums, strides and
+    /// other shape information describe the whole buffer, so they are
+    /// dropped. Compressed data cannot be sliced.
+    pub fn slice(&self, range: std::ops::Range<usize>) -> Result<ZData> {
+        let bytes = self.b.as_ref().ok_or_else(|| {
+            VmpError::MissingField("Binary data missing from ZData".to_string())
+        })?;
+        if let Some(compression) = self.get_field("compression") {
+            return Err(VmpError::TypeConversion(format!(
+                "Cannot slice data compressed with {}",
+                compression
+            )));
+        }
+        if range.start > range.end || range.end > bytes.len() {
+            return Err(VmpError::TypeConversion(format!(
+                "Slice {:?} is out of bounds for {} bytes",
+                range,
+                bytes.len()
+            )));
+        }
+
+        let shape = match self.dtype.as_deref() {
+            Some(dtype) => {
+                let size = element_size_bytes(dtype).ok_or_else(|| {
+                    VmpError::TypeConversion(format!("Unknown dtype: {}", dtype))
+                })?;
+                if !range.start.is_multiple_of(size) || !range.end.is_multiple_of(size) {
+                    return Err(VmpError::TypeConversion(format!(
+                        "Slice {:?} does not fall on {} element boundaries",
+                        range, dtype
+                    )));
+                }
+                Some(vec![range.len() / size])
+            }
+            None => None,
+        };
+
+        let mut sliced = self.clone();
+        sliced.b = Some(bytes.slice(range));
+        sliced.shape = shape;
+        sliced.strides = None;
+        sliced.checksum = None;
+        Ok(sliced)
+    }
+
     /// Set data type
     pub fn with_dtype(mut self, dtype: impl Into<String>) -> Self {
         self.dtype = Some(dtype.into());
@@ -65,6 +223,107 @@ impl ZData {
         self
     }
 
+    /// Set byte strides
+    pub fn with_strides(mut self, strides: Vec<isize>) -> Self {
+        self.strides = Some(strides);
+        self
+    }
+
+    /// Reinterpret the binary data as another dtype, like NumPy's `view`
+    ///
+    /// The last dimension of the shape is rescaled so the total byte count
+    /// is unchanged; the buffer itself is shared, not copied.
+    pub fn view_as_dtype(&self, new_dtype: &str) -> Result<ZData> {
+        let new_size = element_size_bytes(new_dtype).ok_or_else(|| {
+            VmpError::TypeConversion(format!("Unknown dtype: {}", new_dtype))
+        })?;
+        let old_dtype = self.dtype.as_deref().ok_or_else(|| {
+            VmpError::MissingField("Dtype missing from ZData".to_string())
+        })?;
+        let old_size = element_size_bytes(old_dtype).ok_or_else(|| {
+            VmpError::TypeConversion(format!("Unknown dtype: {}", old_dtype))
+        })?;
+        if !self.is_c_contiguous() {
+            return Err(VmpError::TypeConversion(
+                "Only C-contiguous arrays can be viewed as another dtype".to_string(),
+            ));
+        }
+
+        let mut shape = self.shape.clone().ok_or_else(|| {
+            VmpError::MissingField("Shape missing from ZData".to_string())
+        })?;
+        let last_bytes = match shape.last() {
+            Some(&dim) => dim.checked_mul(old_size).ok_or_else(|| {
+                VmpError::TypeConversion(format!("Shape {:?} is too large", shape))
+            })?,
+            None => old_size,
+        };
+        if !last_bytes.is_multiple_of(new_size) || (shape.is_empty() && old_size != new_size) {
+            return Err(VmpError::TypeConversion(format!(
+                "Cannot view {:?} {} as {}: last dimension is {} bytes",
+                shape, old_dtype, new_dtype, last_bytes
+            )));
+        }
+        if let Some(last) = shape.last_mut() {
+            *last = last_bytes / new_size;
+        }
+
+        let mut view = self.clone();
+        view.strides = self
+            .strides
+            .as_ref()
+            .map(|_| contiguous_strides(&shape, new_size, false));
+        view.shape = Some(shape);
+        view.dtype = Some(new_dtype.to_string());
+        Ok(view)
+    }
+
+    /// Whether the binary data is laid out in C (row-major) order
+    ///
+    /// True when no strides are stored. Stored strides can only be checked
+    /// for known dtypes.
+    pub fn is_c_contiguous(&self) -> bool {
+        self.has_contiguous_strides(false)
+    }
+
+    /// Whether the binary data is laid out in Fortran (column-major) order
+    pub fn is_fortran_contiguous(&self) -> bool {
+        self.has_contiguous_strides(true)
+    }
+
+    fn has_contiguous_strides(&self, fortran: bool) -> bool {
+        let shape = self.shape.as_deref().unwrap_or_default();
+        let Some(item_size) = self.dtype.as_deref().and_then(element_size_bytes).or_else(|| {
+            // Without strides only the implied C layout has to be checked,
+            // which does not depend on the element size
+            self.strides.is_none().then_some(1)
+        }) else {
+            return false;
+        };
+
+        let strides = match &self.strides {
+            Some(strides) if strides.len() != shape.len() => return false,
+            Some(strides) => strides.clone(),
+            None => contiguous_strides(shape, item_size, false),
+        };
+        if shape.contains(&0) {
+            return true;
+        }
+
+        // As in NumPy, the stride of a dimension of length 1 is irrelevant
+        let expected = contiguous_strides(shape, item_size, fortran);
+        shape
+            .iter()
+            .zip(strides.iter().zip(&expected))
+            .all(|(&dim, (stride, expected))| dim == 1 || stride == expected)
+    }
+
+    /// Set the protocol version, usually [`crate::types::PROTOCOL_VERSION`]
+    pub fn with_vmp_version(mut self, version: impl Into<String>) -> Self {
+        self.vmp_version = Some(version.into());
+        self
+    }
+
     /// Add an extra field
     pub fn with_field(mut self, key: impl Into<String>, value: Value) -> Self {
         self.extra.insert(key.into(), value);
@@ -80,6 +339,400 @@ impl ZData {
     pub fn is_type(&self, ztype: &str) -> bool {
         self.ztype == ztype
     }
+
+    /// Approximate payload size in bytes
+    ///
+    /// Counts the binary data, the dtype string, 8 bytes per shape
+    /// dimension, and for extra fields the key length plus the size of the
+    /// value (string length, 8 bytes per number, 1 per bool, containers
+    /// summed recursively including object keys).
+    #[inline]
+    pub fn byte_size(&self) -> usize {
+        self.b.as_ref().map_or(0, |b| b.len())
+            + self.dtype.as_ref().map_or(0, |d| d.len())
+            + self.shape.as_ref().map_or(0, |s| s.len() * 8)
+            + self
+                .extra
+                .iter()
+                .map(|(key, value)| key.len() + value_byte_size(value))
+                .sum::<usize>()
+    }
+
+    /// Estimated memory held by this ZData, including heap allocations
+    ///
+    /// Adds the struct itself, allocated string and vector capacity, and
+    /// the `IndexMap` entry and index storage for extra fields. The shared
+    /// binary buffer is counted in full.
+    #[inline]
+    pub fn total_memory_bytes(&self) -> usize {
+        const MAP_ENTRY: usize =
+            2 * size_of::<usize>() + size_of::<String>() + size_of::<Value>();
+
+        size_of::<Self>()
+            + self.ztype.capacity()
+            + self.b.as_ref().map_or(0, |b| b.len())
+            + self.dtype.as_ref().map_or(0, |d| d.capacity())
+            + self.shape.as_ref().map_or(0, |s| s.capacity() * size_of::<usize>())
+            + self.vmp_version.as_ref().map_or(0, |v| v.capacity())
+            + self.extra.capacity() * MAP_ENTRY
+            + self
+                .extra
+                .iter()
+                .map(|(key, value)| key.capacity() + value_heap_bytes(value))
+                .sum::<usize>()
+    }
+
+    /// Store a CRC32 of the binary data
+    ///
+    /// The checksum covers `b` as transmitted, so add it after compression.
+    pub fn with_crc32_checksum(mut self) -> Self {
+        self.checksum = Some(ZDataChecksum::Crc32(crc32fast::hash(self.binary())));
+        self
+    }
+
+    /// Store a SHA-256 digest of the binary data
+    #[cfg(feature = "crypto")]
+    pub fn with_sha256_checksum(mut self) -> Self {
+        use sha2::{Digest, Sha256};
+
+        self.checksum = Some(ZDataChecksum::Sha256(Sha256::digest(self.binary()).into()));
+        self
+    }
+
+    /// Check the binary data against the stored checksum
+    ///
+    /// Succeeds when the checksum matches, when none is stored, or when this
+    /// build cannot compute it. Without a `checksum`, a `crc32` extra field
+    /// as written by earlier releases is checked instead.
+    pub fn verify_checksum(&self) -> Result<()> {
+        let matches = if let Some(stored) = &self.checksum {
+            stored.recompute(self.binary()).is_none_or(|actual| actual == *stored)
+        } else if let Some(legacy) = self.get_field("crc32") {
+            let stored = legacy
+                .as_u64()
+                .and_then(|crc| u32::try_from(crc).ok())
+                .ok_or_else(|| {
+                    VmpError::TypeConversion(format!("Invalid crc32 checksum: {}", legacy))
+                })?;
+            crc32fast::hash(self.binary()) == stored
+        } else {
+            true
+        };
+        if matches {
+            Ok(())
+        } else {
+            Err(VmpError::InvalidMessage("checksum mismatch".to_string()))
+        }
+    }
+
+    fn binary(&self) -> &[u8] {
+        self.b.as_deref().unwrap_or_default()
+    }
+
+    /// Compress the binary data with "zstd" or "gzip"
+    ///
+    /// The algorithm is recorded in the `compression` extra field so that
+    /// [`ZData::decompressed`] can undo it.
+    #[cfg(feature = "compression")]
+    pub fn with_compression(mut self, algo: &str) -> Result<Self> {
+        if let Some(existing) = self.get_field("compression") {
+            return Err(VmpError::TypeConversion(format!(
+                "ZData is already compressed with {}",
+                existing
+            )));
+        }
+
+        let raw = self.b.as_deref().ok_or_else(|| {
+            VmpError::MissingField("Binary data missing from ZData".to_string())
+        })?;
+
+        let compressed = match algo {
+            "zstd" => zstd::encode_all(raw, 0)
+                .map_err(|e| VmpError::TypeConversion(format!("zstd compression failed: {}", e)))?,
+            "gzip" => {
+                use std::io::Write;
+
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder
+                    .write_all(raw)
+                    .and_then(|_| encoder.finish())
+                    .map_err(|e| {
+                        VmpError::TypeConversion(format!("gzip compression failed: {}", e))
+                    })?
+            }
+            _ => {
+                return Err(VmpError::TypeConversion(format!(
+                    "Unsupported compression algorithm: {}",
+                    algo
+                )));
+            }
+        };
+
+        self.b = Some(compressed.into());
+        Ok(self.with_field("compression", Value::String(algo.to_string())))
+    }
+
+    /// Get the binary data, decompressing it if the `compression` extra
+    /// field is set
+    ///
+    /// Uncompressed data is borrowed without copying. Decompressed output is
+    /// capped at [`crate::deserializer::DEFAULT_MAX_BINARY_BYTES`]; use
+    /// [`ZData::decompressed_with_limit`] to choose another limit.
+    pub fn decompressed(&self) -> Result<Cow<'_, [u8]>> {
+        self.decompressed_with_limit(Some(crate::deserializer::DEFAULT_MAX_BINARY_BYTES))
+    }
+
+    /// Like [`ZData::decompressed`], but failing with
+    /// `VmpError::InvalidMessage` as soon as the decompressed output grows
+    /// past `limit` bytes (`None` for no limit)
+    ///
+    /// Only `limit + 1` bytes are ever decompressed, so a small payload that
+    /// expands enormously is rejected without being inflated.
+    #[cfg_attr(not(feature = "compression"), allow(unused_variables))]
+    pub fn decompressed_with_limit(&self, limit: Option<usize>) -> Result<Cow<'_, [u8]>> {
+        let bytes = self.b.as_deref().ok_or_else(|| {
+            VmpError::MissingField("Binary data missing from ZData".to_string())
+        })?;
+
+        let Some(compression) = self.get_field("compression") else {
+            return Ok(Cow::Borrowed(bytes));
+        };
+
+        match compression.as_str() {
+            #[cfg(feature = "compression")]
+            Some("zstd") => {
+                let decoder = zstd::stream::read::Decoder::new(bytes).map_err(|e| {
+                    VmpError::TypeConversion(format!("zstd decompression failed: {}", e))
+                })?;
+                read_decompressed(decoder, "zstd", limit).map(Cow::Owned)
+            }
+            #[cfg(feature = "compression")]
+            Some("gzip") => {
+                read_decompressed(flate2::read::GzDecoder::new(bytes), "gzip", limit)
+                    .map(Cow::Owned)
+            }
+            #[cfg(not(feature = "compression"))]
+            Some(algo @ ("zstd" | "gzip")) => Err(VmpError::TypeConversion(format!(
+                "{} decompression requires the 'compression' feature. \
+                 Add 'features = [\"compression\"]' to your Cargo.toml dependency.",
+                algo
+            ))),
+            _ => Err(VmpError::TypeConversion(format!(
+                "Unsupported compression algorithm: {}",
+                compression
+            ))),
+        }
+    }
+
+    /// Check the ZData for internal consistency, rejecting unknown dtypes
+    pub fn validate(&self) -> Result<()> {
+        self.validate_with(false)
+    }
+
+    /// Check the ZData for internal consistency
+    ///
+    /// Strides, if any, must have one entry per shape dimension. For known
+    /// dtypes the binary length must equal the shape's element count times
+    /// the element size; compressed data is not measured.
+    pub fn validate_with(&self, allow_unknown_dtype: bool) -> Result<()> {
+        let invalid = |msg: String| Err(VmpError::InvalidMessage(msg));
+
+        if self.ztype.is_empty() {
+            return invalid("ZData ztype cannot be empty".to_string());
+        }
+
+        if let Some(version) = &self.vmp_version {
+            crate::types::check_protocol_version(version)?;
+        }
+
+        if self.shape.is_some() && self.b.is_none() {
+            return invalid(format!("{} ZData has a shape but no binary data", self.ztype));
+        }
+
+        if let Some(strides) = &self.strides {
+            let ndim = self.shape.as_ref().map_or(0, Vec::len);
+            if strides.len() != ndim {
+                return invalid(format!(
+                    "{} ZData has {} strides for {} shape dimensions",
+                    self.ztype,
+                    strides.len(),
+                    ndim
+                ));
+            }
+        }
+
+        let Some(dtype) = self.dtype.as_deref() else {
+            return Ok(());
+        };
+        let Some(item_size) = element_size_bytes(dtype) else {
+            if allow_unknown_dtype {
+                return Ok(());
+            }
+            return invalid(format!("{} ZData has unknown dtype '{}'", self.ztype, dtype));
+        };
+
+        if let (Some(shape), Some(b)) = (&self.shape, &self.b)
+            && self.get_field("compression").is_none()
+        {
+            let expected = shape
+                .iter()
+                .try_fold(item_size, |acc, &dim| acc.checked_mul(dim));
+            if expected != Some(b.len()) {
+                return invalid(format!(
+                    "{} ZData binary length {} does not match shape {:?} of {}",
+                    self.ztype,
+                    b.len(),
+                    shape,
+                    dtype
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Read all of a decompressing reader, failing once more than `limit`
+/// bytes come out
+#[cfg(feature = "compression")]
+fn read_decompressed(
+    reader: impl std::io::Read,
+    algo: &str,
+    limit: Option<usize>,
+) -> Result<Vec<u8>> {
+    use std::io::Read;
+
+    let mut raw = Vec::new();
+    let cap = limit.map_or(u64::MAX, |limit| limit as u64 + 1);
+    reader
+        .take(cap)
+        .read_to_end(&mut raw)
+        .map_err(|e| VmpError::TypeConversion(format!("{} decompression failed: {}", algo, e)))?;
+    match limit {
+        Some(limit) if raw.len() > limit => Err(VmpError::InvalidMessage(format!(
+            "{} data decompresses to more than {} bytes",
+            algo, limit
+        ))),
+        _ => Ok(raw),
+    }
+}
+
+fn value_byte_size(value: &Value) -> usize {
+    match value {
+        Value::Null => 0,
+        Value::Bool(_) => 1,
+        Value::Number(_) => 8,
+        Value::String(s) => s.len(),
+        Value::Array(items) => items.iter().map(value_byte_size).sum(),
+        Value::Object(map) => map.iter().map(|(k, v)| k.len() + value_byte_size(v)).sum(),
+    }
+}
+
+fn value_heap_bytes(value: &Value) -> usize {
+    match value {
+        Value::Null | Value::Bool(_) | Value::Number(_) => 0,
+        Value::String(s) => s.capacity(),
+        Value::Array(items) => {
+            let elements: usize = items.iter().map(value_heap_bytes).sum();
+            items.capacity() * size_of::<Value>() + elements
+        }
+        Value::Object(map) => map
+            .iter()
+            .map(|(k, v)| {
+                size_of::<String>() + size_of::<Value>() + k.capacity() + value_heap_bytes(v)
+            })
+            .sum(),
+    }
+}
+
+impl std::fmt::Debug for ZData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut s = f.debug_struct("ZData");
+        s.field("ztype", &self.ztype);
+        if let Some(b) = &self.b {
+            s.field("b", &format_args!("<{} bytes>", group_digits(b.len())));
+        }
+        s.field("dtype", &self.dtype).field("shape", &self.shape);
+        if let Some(strides) = &self.strides {
+            s.field("strides", strides);
+        }
+        if let Some(version) = &self.vmp_version {
+            s.field("vmp_version", version);
+        }
+        if let Some(checksum) = &self.checksum {
+            s.field("checksum", checksum);
+        }
+        s.field("extra", &self.extra.keys().collect::<Vec<_>>()).finish()
+    }
+}
+
+impl ZData {
+    /// One-line description for logs, without any payload contents
+    ///
+    /// For example `numpy.ndarray dtype=float32 shape=[1080, 1920]
+    /// b=<8_294_400 bytes> extra=[name]`.
+    pub fn summary(&self) -> String {
+        let mut summary = self.ztype.clone();
+        if let Some(dtype) = &self.dtype {
+            summary.push_str(&format!(" dtype={}", dtype));
+        }
+        if let Some(shape) = &self.shape {
+            summary.push_str(&format!(" shape={:?}", shape));
+        }
+        if let Some(b) = &self.b {
+            summary.push_str(&format!(" b=<{} bytes>", group_digits(b.len())));
+        }
+        if !self.extra.is_empty() {
+            let keys: Vec<&str> = self.extra.keys().map(String::as_str).collect();
+            summary.push_str(&format!(" extra=[{}]", keys.join(", ")));
+        }
+        summary
+    }
+}
+
+/// Format a number with `_` between groups of three digits
+fn group_digits(n: usize) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push('_');
+        }
+        grouped.push(c);
+    }
+    grouped
+}
+
+/// Byte strides of a contiguous array in C or Fortran order
+///
+/// Strides saturate at `isize::MAX` for shapes too large to address.
+pub(crate) fn contiguous_strides(shape: &[usize], item_size: usize, fortran: bool) -> Vec<isize> {
+    let to_isize = |n: usize| isize::try_from(n).unwrap_or(isize::MAX);
+    let mut strides = vec![0isize; shape.len()];
+    let mut step = to_isize(item_size);
+    let mut assign = |i: usize| {
+        strides[i] = step;
+        step = step.saturating_mul(to_isize(shape[i].max(1)));
+    };
+    if fortran {
+        (0..shape.len()).for_each(&mut assign);
+    } else {
+        (0..shape.len()).rev().for_each(&mut assign);
+    }
+    strides
+}
+
+/// Element size in bytes of a NumPy dtype name, or `None` if unknown
+pub fn element_size_bytes(dtype: &str) -> Option<usize> {
+    match dtype {
+        "bool" | "uint8" | "int8" => Some(1),
+        "uint16" | "int16" | "float16" => Some(2),
+        "uint32" | "int32" | "float32" => Some(4),
+        "uint64" | "int64" | "float64" | "complex64" => Some(8),
+        "complex128" => Some(16),
+        _ => None,
+    }
 }
 
 /// Type conversion trait for custom types
@@ -109,6 +762,13 @@ pub trait ZDataConversion: Sized {
     }
 }
 
+/// Sole key of the object that wraps an undecodable ZData when
+/// `UnknownTypePolicy::Wrap` is used
+///
+/// The value is the original ZData map, which the serializer emits
+/// unchanged.
+pub const UNKNOWN_TYPE_KEY: &str = "$vmp_unknown";
+
 /// Fallback type for when a ZData type is not available
 ///
 /// This allows the system to preserve unknown types without failing.
@@ -133,6 +793,67 @@ impl UnknownType {
     pub fn as_zdata(&self) -> &ZData {
         &self.zdata
     }
+
+    /// Recover an unknown type from the wrapper produced while decoding
+    ///
+    /// Returns `None` if `value` is not such a wrapper.
+    pub fn from_wrapped(value: &Value) -> Option<Self> {
+        let inner = unwrap_unknown(value)?;
+        serde_json::from_value(Value::Object(inner.clone())).ok().map(Self::new)
+    }
+}
+
+/// Wrap the map of an undecodable ZData under [`UNKNOWN_TYPE_KEY`]
+pub(crate) fn wrap_unknown(map: serde_json::Map<String, Value>) -> Value {
+    let mut wrapper = serde_json::Map::new();
+    wrapper.insert(UNKNOWN_TYPE_KEY.to_string(), Value::Object(map));
+    Value::Object(wrapper)
+}
+
+/// The original ZData map inside a wrapper made by [`wrap_unknown`]
+pub(crate) fn unwrap_unknown(value: &Value) -> Option<&serde_json::Map<String, Value>> {
+    match value {
+        Value::Object(map) if map.len() == 1 => map.get(UNKNOWN_TYPE_KEY)?.as_object(),
+        _ => None,
+    }
+}
+
+/// ZData decoded into one of the built-in types
+///
+/// Returned by [`ZData::decode_known`]. Types whose feature is disabled
+/// decode as `Unknown`.
+pub enum DecodedZData {
+    #[cfg(feature = "ndarray")]
+    NumpyArray(crate::builtin_types::NumpyArray<f32>),
+    #[cfg(feature = "image")]
+    Image(crate::builtin_types::ImageData),
+    #[cfg(feature = "video")]
+    VideoFrame(crate::builtin_types::VideoFrame),
+    Unknown(UnknownType),
+}
+
+impl ZData {
+    /// Decode into the matching built-in type based on `ztype`
+    ///
+    /// Unrecognized types are returned as `DecodedZData::Unknown`; an error
+    /// is only returned when a recognized type fails to decode.
+    pub fn decode_known(&self) -> Result<DecodedZData> {
+        #[cfg(feature = "ndarray")]
+        if self.is_type(crate::builtin_types::NumpyArray::<f32>::ztype()) {
+            return crate::builtin_types::NumpyArray::from_zdata(self)
+                .map(DecodedZData::NumpyArray);
+        }
+        #[cfg(feature = "image")]
+        if self.is_type(crate::builtin_types::ImageData::ztype()) {
+            return crate::builtin_types::ImageData::from_zdata(self).map(DecodedZData::Image);
+        }
+        #[cfg(feature = "video")]
+        if self.is_type(crate::builtin_types::VideoFrame::ztype()) {
+            return crate::builtin_types::VideoFrame::from_zdata(self)
+                .map(DecodedZData::VideoFrame);
+        }
+        Ok(DecodedZData::Unknown(UnknownType::new(self.clone())))
+    }
 }
 
 /// Helper function to encode a value to ZData if it implements the trait
@@ -159,45 +880,1494 @@ pub fn decode_from_zdata<T: ZDataConversion>(zdata: &ZData) -> Result<T> {
     T::from_zdata(zdata)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use serde_json::json;
+/// Type identifier of the pieces produced by [`split_chunks`]
+pub const CHUNK_ZTYPE: &str = "vmp.chunk";
 
-    #[test]
-    fn test_zdata_creation() {
-        let zdata = ZData::new("test.Type")
-            .with_binary(vec![1, 2, 3, 4])
-            .with_dtype("float32")
-            .with_shape(vec![2, 2])
-            .with_field("custom", json!("value"));
+/// Split a ZData into `vmp.chunk` pieces carrying at most `max_bytes` of
+/// binary data each
+///
+/// Every chunk records its `chunk_id`, `chunk_index`, `chunk_count`, the
+/// `parent_ztype` and a CRC32 `content_hash` of the whole binary. The first
+/// chunk also carries the original metadata under `parent`. The binary
+/// buffer is shared, not copied. A ZData without binary data yields a
+/// single chunk.
+pub fn split_chunks(zdata: &ZData, max_bytes: usize) -> Vec<ZData> {
+    let max_bytes = max_bytes.max(1);
+    let data = zdata.b.clone().unwrap_or_default();
+    let count = data.len().div_ceil(max_bytes).max(1);
+    let id = uuid::Uuid::new_v4().to_string();
+    let hash = crc32fast::hash(&data);
 
-        assert_eq!(zdata.ztype, "test.Type");
-        assert_eq!(zdata.b, Some(vec![1, 2, 3, 4]));
-        assert_eq!(zdata.dtype, Some("float32".to_string()));
-        assert_eq!(zdata.shape, Some(vec![2, 2]));
-        assert_eq!(zdata.get_field("custom"), Some(&json!("value")));
+    let mut parent = zdata.clone();
+    parent.b = None;
+    let parent = serde_json::to_value(&parent).unwrap_or_default();
+
+    (0..count)
+        .map(|index| {
+            let mut chunk = ZData::new(CHUNK_ZTYPE)
+                .with_field("chunk_id", Value::from(id.clone()))
+                .with_field("chunk_index", Value::from(index))
+                .with_field("chunk_count", Value::from(count))
+                .with_field("parent_ztype", Value::from(zdata.ztype.clone()))
+                .with_field("content_hash", Value::from(hash));
+            if index == 0 {
+                chunk = chunk.with_field("parent", parent.clone());
+            }
+            if zdata.b.is_some() {
+                let start = index * max_bytes;
+                let end = (start + max_bytes).min(data.len());
+                chunk = chunk.with_binary(data.slice(start..end));
+            }
+            chunk
+        })
+        .collect()
+}
+
+/// Default for [`ChunkAssembler::with_max_chunk_count`]
+pub const DEFAULT_MAX_CHUNK_COUNT: usize = 65_536;
+
+/// Default for [`ChunkAssembler::with_max_pending`]
+pub const DEFAULT_MAX_PENDING_CHUNKED: usize = 64;
+
+/// Default for [`ChunkAssembler::with_ttl`]
+pub const DEFAULT_CHUNK_TTL: Duration = Duration::from_secs(30);
+
+struct PartialChunks {
+    count: usize,
+    hash: u32,
+    parent_ztype: String,
+    parent: Option<ZData>,
+    parts: BTreeMap<usize, Option<Bytes>>,
+    last_seen: Instant,
+}
+
+/// Reassembles ZData split by [`split_chunks`]
+///
+/// Chunks of several objects may be interleaved and arrive in any order.
+/// Memory is bounded: objects announcing more than `max_chunk_count`
+/// chunks are rejected, objects that received no chunk for `ttl` are
+/// dropped, and once `max_pending` objects are incomplete the one that
+/// least recently received a chunk is dropped to make room.
+pub struct ChunkAssembler {
+    pending: HashMap<String, PartialChunks>,
+    max_pending: usize,
+    max_chunk_count: usize,
+    ttl: Duration,
+}
+
+impl Default for ChunkAssembler {
+    fn default() -> Self {
+        Self {
+            pending: HashMap::new(),
+            max_pending: DEFAULT_MAX_PENDING_CHUNKED,
+            max_chunk_count: DEFAULT_MAX_CHUNK_COUNT,
+            ttl: DEFAULT_CHUNK_TTL,
+        }
     }
+}
 
-    #[test]
-    fn test_unknown_type() {
-        let zdata = ZData::new("unknown.Type");
-        let unknown = UnknownType::new(zdata.clone());
+impl ChunkAssembler {
+    /// Create an empty assembler with the default limits
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-        assert_eq!(unknown.ztype(), "unknown.Type");
-        assert_eq!(unknown.as_zdata(), &zdata);
+    /// Keep at most `max_pending` incomplete objects (at least one)
+    pub fn with_max_pending(mut self, max_pending: usize) -> Self {
+        self.max_pending = max_pending.max(1);
+        self
     }
 
-    #[test]
-    fn test_zdata_serialization() {
-        let zdata = ZData::new("numpy.ndarray")
-            .with_binary(vec![0, 1, 2, 3])
-            .with_dtype("uint8")
-            .with_shape(vec![2, 2]);
+    /// Reject objects split into more than `max_chunk_count` chunks
+    pub fn with_max_chunk_count(mut self, max_chunk_count: usize) -> Self {
+        self.max_chunk_count = max_chunk_count;
+        self
+    }
 
-        let json = serde_json::to_string(&zdata).unwrap();
-        let deserialized: ZData = serde_json::from_str(&json).unwrap();
+    /// Drop incomplete objects that received no chunk for `ttl`
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
 
-        assert_eq!(zdata, deserialized);
+    /// Number of objects with chunks still missing
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Drop incomplete objects that received no chunk for the TTL,
+    /// returning how many were dropped
+    ///
+    /// [`ChunkAssembler::push`] does this when a new object starts; call it
+    /// directly to free memory while no chunks arrive.
+    pub fn evict_expired(&mut self) -> usize {
+        let before = self.pending.len();
+        let ttl = self.ttl;
+        self.pending.retain(|_, partial| partial.last_seen.elapsed() < ttl);
+        before - self.pending.len()
+    }
+
+    /// Make room for one more incomplete object by dropping the one that
+    /// least recently received a chunk
+    fn evict_for_insert(&mut self) {
+        self.evict_expired();
+        while self.pending.len() >= self.max_pending {
+            let oldest = self
+                .pending
+                .iter()
+                .min_by_key(|(_, partial)| partial.last_seen)
+                .map(|(id, _)| id.clone());
+            match oldest {
+                Some(id) => self.pending.remove(&id),
+                None => break,
+            };
+        }
+    }
+
+    /// Add a chunk, returning the original ZData once all its chunks arrived
+    ///
+    /// Duplicate chunks are ignored. Chunks that disagree with earlier ones
+    /// about the count, hash or parent type, whose count exceeds
+    /// `max_chunk_count`, or whose reassembled data does not match the hash,
+    /// are rejected with `VmpError::InvalidMessage`.
+    pub fn push(&mut self, chunk: ZData) -> Result<Option<ZData>> {
+        if !chunk.is_type(CHUNK_ZTYPE) {
+            return Err(VmpError::InvalidMessage(format!(
+                "Expected a {} ZData, got {}",
+                CHUNK_ZTYPE, chunk.ztype
+            )));
+        }
+
+        let id = chunk_str(&chunk, "chunk_id")?.to_string();
+        let index = chunk_uint(&chunk, "chunk_index")?;
+        let count = chunk_uint(&chunk, "chunk_count")?;
+        let hash = u32::try_from(chunk_uint(&chunk, "content_hash")?)
+            .map_err(|_| VmpError::InvalidMessage("Invalid chunk content_hash".to_string()))?;
+        let parent_ztype = chunk_str(&chunk, "parent_ztype")?;
+
+        if count == 0 || index >= count {
+            return Err(VmpError::InvalidMessage(format!(
+                "Chunk index {} out of range for {} chunks",
+                index, count
+            )));
+        }
+        if count > self.max_chunk_count as u64 {
+            return Err(VmpError::InvalidMessage(format!(
+                "Chunk count {} exceeds max_chunk_count ({})",
+                count, self.max_chunk_count
+            )));
+        }
+        let (index, count) = (index as usize, count as usize);
+
+        if !self.pending.contains_key(&id) {
+            self.evict_for_insert();
+        }
+        let partial = self.pending.entry(id.clone()).or_insert_with(|| PartialChunks {
+            count,
+            hash,
+            parent_ztype: parent_ztype.to_string(),
+            parent: None,
+            parts: BTreeMap::new(),
+            last_seen: Instant::now(),
+        });
+        if partial.count != count || partial.hash != hash || partial.parent_ztype != parent_ztype {
+            return Err(VmpError::InvalidMessage(format!(
+                "Chunk {} of {} does not match earlier chunks",
+                index, id
+            )));
+        }
+        partial.last_seen = Instant::now();
+        if partial.parts.contains_key(&index) {
+            return Ok(None);
+        }
+
+        if let Some(parent) = chunk.get_field("parent") {
+            partial.parent = Some(serde_json::from_value(parent.clone())?);
+        }
+        partial.parts.insert(index, chunk.b);
+        if partial.parts.len() < partial.count {
+            return Ok(None);
+        }
+
+        let partial = self.pending.remove(&id).expect("pending entry was just updated");
+        let mut zdata = partial.parent.ok_or_else(|| {
+            VmpError::InvalidMessage(format!("First chunk of {} carries no parent", id))
+        })?;
+
+        let parts: Vec<Option<Bytes>> = partial.parts.into_values().collect();
+        if parts.iter().any(Option::is_some) {
+            let total = parts.iter().flatten().map(Bytes::len).sum();
+            let mut data = Vec::with_capacity(total);
+            for part in parts.iter().flatten() {
+                data.extend_from_slice(part);
+            }
+            if crc32fast::hash(&data) != partial.hash {
+                return Err(VmpError::InvalidMessage(format!(
+                    "Content hash mismatch for {}",
+                    id
+                )));
+            }
+            zdata.b = Some(data.into());
+        }
+
+        Ok(Some(zdata))
+    }
+}
+
+fn chunk_str<'a>(chunk: &'a ZData, field: &str) -> Result<&'a str> {
+    chunk
+        .get_field(field)
+        .and_then(Value::as_str)
+        .ok_or_else(|| VmpError::MissingField(format!("Chunk is missing {}", field)))
+}
+
+fn chunk_uint(chunk: &ZData, field: &str) -> Result<u64> {
+    chunk
+        .get_field(field)
+        .and_then(Value::as_u64)
+        .ok_or_else(|| VmpError::MissingField(format!("Chunk is missing {}", field)))
+}
+
+impl ZData {
+    /// Split the binary data into copies of this ZData carrying at most
+    /// `chunk_size` bytes each
+    ///
+    /// Each chunk keeps the ztype, dtype and other fields, and adds
+    /// `chunk_index` and `chunk_total`. The shape and strides of the whole
+    /// data move to `full_shape` and `full_strides`; when the dtype has a
+    /// known size and the data is not compressed, `chunk_size` is rounded
+    /// down to whole elements and each chunk gets its own flat shape, so it
+    /// passes ZData validation on its own. A checksum is recomputed for each
+    /// chunk with the same algorithm. Unlike [`split_chunks`] the chunks
+    /// carry no id, so they must be collected per object and passed to
+    /// [`ZData::from_chunks`].
+    pub fn chunked_encode(&self, chunk_size: usize) -> Result<Vec<ZData>> {
+        if chunk_size == 0 {
+            return Err(VmpError::InvalidMessage("chunk_size must be positive".to_string()));
+        }
+        let item_size = self
+            .dtype
+            .as_deref()
+            .and_then(element_size_bytes)
+            .filter(|_| self.shape.is_some() && self.get_field("compression").is_none());
+        let chunk_size = item_size.map_or(chunk_size, |size| (chunk_size / size).max(1) * size);
+        let data = self.b.clone().unwrap_or_default();
+        let total = data.len().div_ceil(chunk_size).max(1);
+
+        let mut template = self.clone();
+        if let Some(shape) = template.shape.take() {
+            template.extra.insert(CHUNK_FULL_SHAPE.to_string(), Value::from(shape));
+        }
+        if let Some(strides) = template.strides.take() {
+            template.extra.insert(CHUNK_FULL_STRIDES.to_string(), Value::from(strides));
+        }
+
+        Ok((0..total)
+            .map(|index| {
+                let mut chunk = template
+                    .clone()
+                    .with_field("chunk_index", Value::from(index))
+                    .with_field("chunk_total", Value::from(total));
+                if self.b.is_some() {
+                    let start = index * chunk_size;
+                    let piece = data.slice(start..(start + chunk_size).min(data.len()));
+                    chunk.checksum = self.checksum.as_ref().and_then(|c| c.recompute(&piece));
+                    chunk.shape = item_size.map(|size| vec![piece.len() / size]);
+                    chunk.b = Some(piece);
+                }
+                chunk
+            })
+            .collect())
+    }
+
+    /// Reassemble chunks made by [`ZData::chunked_encode`], in any order
+    ///
+    /// Fails if a chunk is missing or duplicated, if the chunks disagree on
+    /// their metadata, or if a chunk does not match its checksum.
+    pub fn from_chunks(mut chunks: Vec<ZData>) -> Result<ZData> {
+        let first = chunks
+            .first()
+            .ok_or_else(|| VmpError::InvalidMessage("No chunks to reassemble".to_string()))?;
+        let total = chunk_uint(first, "chunk_total")?;
+        let metadata = chunk_metadata(first)?;
+        if chunks.len() as u64 != total {
+            return Err(VmpError::InvalidMessage(format!(
+                "Expected {} chunks, got {}",
+                total,
+                chunks.len()
+            )));
+        }
+
+        let mut indexed = Vec::with_capacity(chunks.len());
+        for chunk in chunks.drain(..) {
+            if chunk_uint(&chunk, "chunk_total")? != total || chunk_metadata(&chunk)? != metadata {
+                return Err(VmpError::InvalidMessage(
+                    "Chunks have mismatched metadata".to_string(),
+                ));
+            }
+            chunk.verify_checksum()?;
+            indexed.push((chunk_uint(&chunk, "chunk_index")?, chunk));
+        }
+        indexed.sort_by_key(|(index, _)| *index);
+        for (expected, (index, _)) in indexed.iter().enumerate() {
+            if *index != expected as u64 {
+                return Err(VmpError::InvalidMessage(format!("Chunk {} is missing", expected)));
+            }
+        }
+
+        let mut zdata = metadata;
+        if indexed.iter().any(|(_, chunk)| chunk.b.is_some()) {
+            let len = indexed.iter().filter_map(|(_, c)| c.b.as_ref()).map(Bytes::len).sum();
+            let mut data = Vec::with_capacity(len);
+            for (_, chunk) in &indexed {
+                data.extend_from_slice(chunk.b.as_deref().unwrap_or_default());
+            }
+            zdata.checksum = indexed[0].1.checksum.as_ref().and_then(|c| c.recompute(&data));
+            zdata.b = Some(data.into());
+        }
+        Ok(zdata)
+    }
+}
+
+/// Extra field holding the shape of the whole data on each chunk made by
+/// [`ZData::chunked_encode`]
+pub const CHUNK_FULL_SHAPE: &str = "full_shape";
+
+/// Extra field holding the strides of the whole data on each chunk made by
+/// [`ZData::chunked_encode`]
+pub const CHUNK_FULL_STRIDES: &str = "full_strides";
+
+/// A chunk without its binary data, checksum and chunk fields, with the
+/// shape and strides of the whole data restored
+fn chunk_metadata(chunk: &ZData) -> Result<ZData> {
+    let mut metadata = chunk.clone();
+    metadata.b = None;
+    metadata.checksum = None;
+    metadata.extra.shift_remove("chunk_index");
+    metadata.extra.shift_remove("chunk_total");
+    metadata.shape = take_chunk_field(&mut metadata, CHUNK_FULL_SHAPE)?;
+    metadata.strides = take_chunk_field(&mut metadata, CHUNK_FULL_STRIDES)?;
+    Ok(metadata)
+}
+
+fn take_chunk_field<T: serde::de::DeserializeOwned>(
+    metadata: &mut ZData,
+    field: &str,
+) -> Result<Option<T>> {
+    metadata
+        .extra
+        .shift_remove(field)
+        .map(|value| {
+            serde_json::from_value(value)
+                .map_err(|_| VmpError::InvalidMessage(format!("Invalid chunk {}", field)))
+        })
+        .transpose()
+}
+
+/// Extra field holding the source URI of a [`ZDataHandle`]
+pub const HANDLE_SOURCE_KEY: &str = "source";
+
+/// ZData whose binary payload lives outside the message
+///
+/// Holds the metadata (ztype, dtype, shape, ...) and a `file://` or
+/// `http(s)://` URI in the `source` extra field, and serializes as a ZData
+/// map without `b`. The payload is only fetched by [`ZDataHandle::load`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "ZData", into = "ZData")]
+pub struct ZDataHandle {
+    meta: ZData,
+}
+
+impl ZDataHandle {
+    /// Create a handle to data of type `ztype` stored at `uri`
+    pub fn new(ztype: impl Into<String>, uri: impl Into<String>) -> Self {
+        Self {
+            meta: ZData::new(ztype).with_field(HANDLE_SOURCE_KEY, Value::String(uri.into())),
+        }
+    }
+
+    /// Set data type
+    pub fn with_dtype(mut self, dtype: impl Into<String>) -> Self {
+        self.meta.dtype = Some(dtype.into());
+        self
+    }
+
+    /// Set shape
+    pub fn with_shape(mut self, shape: Vec<usize>) -> Self {
+        self.meta.shape = Some(shape);
+        self
+    }
+
+    /// Set a custom field
+    pub fn with_field(mut self, key: impl Into<String>, value: Value) -> Self {
+        self.meta.extra.insert(key.into(), value);
+        self
+    }
+
+    /// URI the binary payload is loaded from
+    pub fn uri(&self) -> &str {
+        self.meta
+            .get_field(HANDLE_SOURCE_KEY)
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+    }
+
+    /// Metadata of the referenced ZData, without binary data
+    pub fn metadata(&self) -> &ZData {
+        &self.meta
+    }
+
+    /// Fetch the binary payload, blocking until it has arrived
+    ///
+    /// The URI comes from the sender, so it is only followed when `access`
+    /// allows it. HTTP sources need the `http` feature, and must not be
+    /// loaded this way from inside an async runtime; use
+    /// [`ZDataHandle::load_async`] there.
+    pub fn load(&self, access: &HandleAccess) -> Result<ZData> {
+        let data = match self.source()? {
+            HandleSource::File(path) => {
+                let path = access.resolve_file(path)?;
+                read_capped(std::fs::File::open(path)?, self.uri(), access.max_bytes)?
+            }
+            HandleSource::Http(url) => {
+                access.check_host(url)?;
+                fetch_blocking(url, access.max_bytes)?
+            }
+        };
+        self.with_payload(data)
+    }
+
+    /// Fetch the binary payload asynchronously, under the same rules as
+    /// [`ZDataHandle::load`]
+    #[cfg(feature = "tokio")]
+    pub async fn load_async(&self, access: &HandleAccess) -> Result<ZData> {
+        use tokio::io::AsyncReadExt;
+
+        let data = match self.source()? {
+            HandleSource::File(path) => {
+                let path = access.resolve_file(path)?;
+                let file = tokio::fs::File::open(path).await?;
+                let mut data = Vec::new();
+                file.take(access.max_bytes as u64 + 1).read_to_end(&mut data).await?;
+                check_capped(data, self.uri(), access.max_bytes)?
+            }
+            HandleSource::Http(url) => {
+                access.check_host(url)?;
+                fetch_async(url, access.max_bytes).await?
+            }
+        };
+        self.with_payload(data)
+    }
+
+    fn source(&self) -> Result<HandleSource<'_>> {
+        let uri = self.uri();
+        if let Some(path) = uri.strip_prefix("file://") {
+            Ok(HandleSource::File(path))
+        } else if uri.starts_with("http://") || uri.starts_with("https://") {
+            Ok(HandleSource::Http(uri))
+        } else {
+            Err(VmpError::InvalidMessage(format!("Unsupported ZData source: {}", uri)))
+        }
+    }
+
+    /// The loaded ZData, checked against the metadata and any checksum
+    fn with_payload(&self, data: Vec<u8>) -> Result<ZData> {
+        let mut zdata = self.meta.clone();
+        zdata.extra.shift_remove(HANDLE_SOURCE_KEY);
+        zdata.b = Some(data.into());
+        zdata.validate_with(true)?;
+        zdata.verify_checksum()?;
+        Ok(zdata)
+    }
+}
+
+impl TryFrom<ZData> for ZDataHandle {
+    type Error = VmpError;
+
+    fn try_from(meta: ZData) -> Result<Self> {
+        if meta.b.is_some() {
+            return Err(VmpError::InvalidMessage(
+                "ZData handle must not carry binary data".to_string(),
+            ));
+        }
+        if !meta.get_field(HANDLE_SOURCE_KEY).is_some_and(Value::is_string) {
+            return Err(VmpError::MissingField(format!(
+                "ZData handle is missing {}",
+                HANDLE_SOURCE_KEY
+            )));
+        }
+        Ok(Self { meta })
+    }
+}
+
+impl From<ZDataHandle> for ZData {
+    fn from(handle: ZDataHandle) -> Self {
+        handle.meta
+    }
+}
+
+/// Sources a [`ZDataHandle`] may be loaded from, and how much it may read
+///
+/// The default allows nothing: files need a `base_dir` and HTTP sources a
+/// host in `allowed_hosts`.
+#[derive(Debug, Clone)]
+pub struct HandleAccess {
+    /// Directory `file://` sources must lie inside, after resolving
+    /// symlinks and `..`; files are refused when unset
+    pub base_dir: Option<std::path::PathBuf>,
+
+    /// Hosts `http(s)://` sources may name; redirects are not followed
+    pub allowed_hosts: Vec<String>,
+
+    /// Largest payload read, in bytes
+    pub max_bytes: usize,
+}
+
+impl Default for HandleAccess {
+    fn default() -> Self {
+        Self {
+            base_dir: None,
+            allowed_hosts: Vec::new(),
+            max_bytes: crate::deserializer::DEFAULT_MAX_BINARY_BYTES,
+        }
+    }
+}
+
+impl HandleAccess {
+    /// Allow files inside `base_dir`
+    pub fn with_base_dir(mut self, base_dir: impl Into<std::path::PathBuf>) -> Self {
+        self.base_dir = Some(base_dir.into());
+        self
+    }
+
+    /// Allow HTTP sources on `host`
+    pub fn with_allowed_host(mut self, host: impl Into<String>) -> Self {
+        self.allowed_hosts.push(host.into());
+        self
+    }
+
+    /// Set the largest payload read
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// The canonical form of `path`, if it lies inside `base_dir`
+    fn resolve_file(&self, path: &str) -> Result<std::path::PathBuf> {
+        let base_dir = self.base_dir.as_ref().ok_or_else(|| {
+            VmpError::InvalidMessage(format!("Loading file://{} needs a base_dir", path))
+        })?;
+        let resolved = std::fs::canonicalize(path)?;
+        if !resolved.starts_with(std::fs::canonicalize(base_dir)?) {
+            return Err(VmpError::InvalidMessage(format!(
+                "ZData source file://{} is outside {}",
+                path,
+                base_dir.display()
+            )));
+        }
+        Ok(resolved)
+    }
+
+    fn check_host(&self, url: &str) -> Result<()> {
+        let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+        let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+        // Drop any credentials, then the port
+        let host_port = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+        let host = match host_port.strip_prefix('[') {
+            Some(v6) => v6.split(']').next().unwrap_or_default(),
+            None => host_port.split(':').next().unwrap_or_default(),
+        };
+        if self.allowed_hosts.iter().any(|allowed| allowed.eq_ignore_ascii_case(host)) {
+            Ok(())
+        } else {
+            Err(VmpError::InvalidMessage(format!(
+                "ZData source host `{}` is not allowed",
+                host
+            )))
+        }
+    }
+}
+
+enum HandleSource<'a> {
+    File(&'a str),
+    Http(&'a str),
+}
+
+/// Read at most `max_bytes` from `reader`, failing if there is more
+fn read_capped(reader: impl std::io::Read, uri: &str, max_bytes: usize) -> Result<Vec<u8>> {
+    use std::io::Read;
+
+    let mut data = Vec::new();
+    reader.take(max_bytes as u64 + 1).read_to_end(&mut data)?;
+    check_capped(data, uri, max_bytes)
+}
+
+fn check_capped(data: Vec<u8>, uri: &str, max_bytes: usize) -> Result<Vec<u8>> {
+    if data.len() > max_bytes {
+        return Err(VmpError::InvalidMessage(format!(
+            "ZData source {} holds more than {} bytes",
+            uri, max_bytes
+        )));
+    }
+    Ok(data)
+}
+
+#[cfg(feature = "http")]
+fn fetch_blocking(url: &str, max_bytes: usize) -> Result<Vec<u8>> {
+    let client = reqwest::blocking::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .map_err(http_error)?;
+    let response = client
+        .get(url)
+        .send()
+        .and_then(|r| r.error_for_status())
+        .map_err(http_error)?;
+    read_capped(response, url, max_bytes)
+}
+
+#[cfg(feature = "http")]
+async fn fetch_async(url: &str, max_bytes: usize) -> Result<Vec<u8>> {
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .map_err(http_error)?;
+    let mut response = client
+        .get(url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(http_error)?;
+    let mut data = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(http_error)? {
+        data.extend_from_slice(&chunk);
+        if data.len() > max_bytes {
+            break;
+        }
+    }
+    check_capped(data, url, max_bytes)
+}
+
+#[cfg(feature = "http")]
+fn http_error(e: reqwest::Error) -> VmpError {
+    VmpError::Io(std::io::Error::other(e))
+}
+
+#[cfg(not(feature = "http"))]
+fn fetch_blocking(url: &str, _max_bytes: usize) -> Result<Vec<u8>> {
+    Err(VmpError::InvalidMessage(format!(
+        "Loading {} requires the `http` feature",
+        url
+    )))
+}
+
+#[cfg(all(feature = "tokio", not(feature = "http")))]
+async fn fetch_async(url: &str, max_bytes: usize) -> Result<Vec<u8>> {
+    fetch_blocking(url, max_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_zdata_creation() {
+        let zdata = ZData::new("test.Type")
+            .with_binary(vec![1, 2, 3, 4])
+            .with_dtype("float32")
+            .with_shape(vec![2, 2])
+            .with_field("custom", json!("value"));
+
+        assert_eq!(zdata.ztype, "test.Type");
+        assert_eq!(zdata.b.as_deref(), Some(&[1, 2, 3, 4][..]));
+        assert_eq!(zdata.dtype, Some("float32".to_string()));
+        assert_eq!(zdata.shape, Some(vec![2, 2]));
+        assert_eq!(zdata.get_field("custom"), Some(&json!("value")));
+    }
+
+    #[test]
+    fn test_byte_size() {
+        assert_eq!(ZData::new("empty").byte_size(), 0);
+
+        let zdata = ZData::new("numpy.ndarray")
+            .with_binary(vec![0u8; 24])
+            .with_dtype("float32")
+            .with_shape(vec![2, 3])
+            .with_field("name", json!("points"))
+            .with_field("meta", json!({"scale": 0.5, "tags": [true, null]}));
+
+        let binary = 24;
+        let dtype = "float32".len();
+        let shape = 2 * 8;
+        let name = "name".len() + "points".len();
+        let meta = "meta".len() + "scale".len() + 8 + "tags".len() + 1;
+        assert_eq!(zdata.byte_size(), binary + dtype + shape + name + meta);
+    }
+
+    #[test]
+    fn test_total_memory_bytes() {
+        let mut ztype = String::with_capacity(32);
+        ztype.push_str("blob");
+        let mut zdata = ZData::new(ztype).with_binary(vec![0u8; 100]);
+        assert_eq!(zdata.total_memory_bytes(), size_of::<ZData>() + 32 + 100);
+
+        zdata.shape = Some(Vec::with_capacity(4));
+        assert_eq!(
+            zdata.total_memory_bytes(),
+            size_of::<ZData>() + 32 + 100 + 4 * size_of::<usize>()
+        );
+
+        let shape = 4 * size_of::<usize>();
+        let with_extra = zdata.with_field("k", json!("v"));
+        let entry = 2 * size_of::<usize>() + size_of::<String>() + size_of::<Value>();
+        assert_eq!(
+            with_extra.total_memory_bytes(),
+            size_of::<ZData>() + 32 + 100 + shape + with_extra.extra.capacity() * entry + 2
+        );
+    }
+
+    #[test]
+    fn test_summary_and_debug() {
+        let zdata = ZData::new("numpy.ndarray")
+            .with_binary(vec![0u8; 2_073_600])
+            .with_dtype("uint8")
+            .with_shape(vec![1080, 1920])
+            .with_field("name", json!("frame"))
+            .with_field("camera", json!({"id": 2}));
+
+        assert_eq!(
+            zdata.summary(),
+            "numpy.ndarray dtype=uint8 shape=[1080, 1920] b=<2_073_600 bytes> extra=[name, camera]"
+        );
+        assert_eq!(ZData::new("marker").summary(), "marker");
+
+        let debug = format!("{:?}", zdata);
+        assert!(debug.contains("b: <2_073_600 bytes>"));
+        assert!(debug.contains(r#"extra: ["name", "camera"]"#));
+        assert!(debug.len() < 200, "debug output too long: {}", debug.len());
+        assert!(format!("{:#?}", zdata).len() < 400);
+
+        assert_eq!(group_digits(0), "0");
+        assert_eq!(group_digits(999), "999");
+        assert_eq!(group_digits(1000), "1_000");
+    }
+
+    #[test]
+    fn test_view_as_dtype() {
+        let bytes = ZData::new("numpy.ndarray")
+            .with_binary(vec![0u8; 48])
+            .with_dtype("uint8")
+            .with_shape(vec![2, 24]);
+
+        let floats = bytes.view_as_dtype("float32").unwrap();
+        assert_eq!(floats.dtype.as_deref(), Some("float32"));
+        assert_eq!(floats.shape, Some(vec![2, 6]));
+        assert_eq!(floats.b.as_ref().unwrap().as_ptr(), bytes.b.as_ref().unwrap().as_ptr());
+        assert!(floats.validate().is_ok());
+
+        let back = floats.with_strides(vec![24, 4]).view_as_dtype("uint16").unwrap();
+        assert_eq!(back.shape, Some(vec![2, 12]));
+        assert_eq!(back.strides, Some(vec![24, 2]));
+
+        let err = |zdata: &ZData, dtype: &str| zdata.view_as_dtype(dtype).unwrap_err();
+        assert!(matches!(err(&bytes, "float128"), VmpError::TypeConversion(_)));
+        let odd = bytes.clone().with_shape(vec![3, 5]);
+        assert!(matches!(err(&odd, "int32"), VmpError::TypeConversion(_)));
+        let scalar = bytes.clone().with_shape(vec![]);
+        assert!(matches!(err(&scalar, "float32"), VmpError::TypeConversion(_)));
+        let fortran = bytes.clone().with_strides(vec![1, 2]);
+        assert!(matches!(err(&fortran, "uint16"), VmpError::TypeConversion(_)));
+        let mut untyped = bytes.clone();
+        untyped.dtype = None;
+        assert!(matches!(err(&untyped, "uint16"), VmpError::MissingField(_)));
+
+        assert_eq!(element_size_bytes("complex128"), Some(16));
+        assert_eq!(element_size_bytes("float128"), None);
+    }
+
+    #[test]
+    fn test_contiguity() {
+        let array = ZData::new("numpy.ndarray")
+            .with_binary(vec![0u8; 24])
+            .with_dtype("float32")
+            .with_shape(vec![2, 3]);
+        assert!(array.is_c_contiguous());
+        assert!(!array.is_fortran_contiguous());
+
+        let c_order = array.clone().with_strides(vec![12, 4]);
+        assert!(c_order.is_c_contiguous() && !c_order.is_fortran_contiguous());
+
+        let f_order = array.clone().with_strides(vec![4, 8]);
+        assert!(!f_order.is_c_contiguous() && f_order.is_fortran_contiguous());
+
+        // Shapes too large to address must not overflow the stride computation
+        let huge = ZData::new("numpy.ndarray").with_shape(vec![3, 3074457345618258603]);
+        assert!(huge.is_c_contiguous());
+
+        let sliced = array.clone().with_strides(vec![24, 4]);
+        assert!(!sliced.is_c_contiguous() && !sliced.is_fortran_contiguous());
+
+        // Vectors are both C and Fortran contiguous
+        let vector = array.with_shape(vec![6]).with_strides(vec![4]);
+        assert!(vector.is_c_contiguous() && vector.is_fortran_contiguous());
+
+        let unknown = ZData::new("x").with_dtype("<U5").with_shape(vec![2]).with_strides(vec![20]);
+        assert!(!unknown.is_c_contiguous());
+    }
+
+    #[test]
+    fn test_validate() {
+        let valid = ZData::new("numpy.ndarray")
+            .with_binary(vec![0u8; 24])
+            .with_dtype("float32")
+            .with_shape(vec![2, 3]);
+        assert!(valid.validate().is_ok());
+        assert!(ZData::new("custom.Marker").validate().is_ok());
+
+        let message = |zdata: ZData| match zdata.validate() {
+            Err(VmpError::InvalidMessage(msg)) => msg,
+            other => panic!("expected InvalidMessage, got {:?}", other),
+        };
+
+        let mut empty = valid.clone();
+        empty.ztype.clear();
+        assert!(message(empty).contains("ztype cannot be empty"));
+
+        let mut missing = valid.clone();
+        missing.b = None;
+        assert!(message(missing).contains("shape but no binary data"));
+
+        let mismatched = valid.clone().with_dtype("float64");
+        assert!(message(mismatched).contains("does not match shape"));
+
+        let overflow = valid.clone().with_shape(vec![usize::MAX, 2]);
+        assert!(message(overflow).contains("does not match shape"));
+
+        let strided = valid.clone().with_strides(vec![4]);
+        assert!(message(strided).contains("1 strides for 2 shape dimensions"));
+
+        let unknown = valid.clone().with_dtype("<U5");
+        assert!(message(unknown.clone()).contains("unknown dtype"));
+        assert!(unknown.validate_with(true).is_ok());
+
+        // Compressed payloads are not measured against the shape
+        let compressed = valid.with_binary(vec![1]).with_field("compression", json!("zstd"));
+        assert!(compressed.validate().is_ok());
+    }
+
+    #[test]
+    fn test_unknown_type() {
+        let zdata = ZData::new("unknown.Type");
+        let unknown = UnknownType::new(zdata.clone());
+
+        assert_eq!(unknown.ztype(), "unknown.Type");
+        assert_eq!(unknown.as_zdata(), &zdata);
+    }
+
+    #[test]
+    fn test_decode_known_unknown_fallback() {
+        let zdata = ZData::new("custom.Mesh").with_binary(vec![1, 2, 3]);
+        match zdata.decode_known().unwrap() {
+            DecodedZData::Unknown(unknown) => assert_eq!(unknown.as_zdata(), &zdata),
+            #[allow(unreachable_patterns)]
+            _ => panic!("expected Unknown"),
+        }
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn test_decode_known_numpy() {
+        use crate::builtin_types::NumpyArray;
+
+        let array = ndarray::ArrayD::from_shape_vec(vec![2], vec![1.0f32, 2.0]).unwrap();
+        let zdata = NumpyArray::new(array.clone()).to_zdata().unwrap();
+        let DecodedZData::NumpyArray(decoded) = zdata.decode_known().unwrap() else {
+            panic!("expected NumpyArray");
+        };
+        assert_eq!(decoded.array, array);
+
+        // A recognized type with a corrupt payload is an error, not Unknown
+        let corrupt = ZData::new("numpy.ndarray").with_dtype("float32");
+        assert!(corrupt.decode_known().is_err());
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_decode_known_image() {
+        use crate::builtin_types::ImageData;
+
+        let image = image::DynamicImage::new_rgb8(2, 2);
+        let zdata = ImageData::new(image, image::ImageFormat::Png).to_zdata().unwrap();
+        let DecodedZData::Image(decoded) = zdata.decode_known().unwrap() else {
+            panic!("expected Image");
+        };
+        assert_eq!(decoded.image.width(), 2);
+    }
+
+    #[cfg(feature = "video")]
+    #[test]
+    fn test_decode_known_video() {
+        use crate::builtin_types::VideoFrame;
+
+        let frame = VideoFrame::new("h264", 7, vec![0, 0, 1]);
+        let zdata = frame.to_zdata().unwrap();
+        let DecodedZData::VideoFrame(decoded) = zdata.decode_known().unwrap() else {
+            panic!("expected VideoFrame");
+        };
+        assert_eq!(decoded, frame);
+    }
+
+    #[test]
+    fn test_zdata_serialization() {
+        let zdata = ZData::new("numpy.ndarray")
+            .with_binary(vec![0, 1, 2, 3])
+            .with_dtype("uint8")
+            .with_shape(vec![2, 2]);
+
+        let json = serde_json::to_string(&zdata).unwrap();
+        let deserialized: ZData = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(zdata, deserialized);
+    }
+
+    #[test]
+    fn test_decompressed_borrows_uncompressed() {
+        let zdata = ZData::new("test.Blob").with_binary(vec![1, 2, 3]);
+        let bytes = zdata.decompressed().unwrap();
+        assert!(matches!(bytes, Cow::Borrowed(_)));
+        assert_eq!(bytes.as_ptr(), zdata.b.as_ref().unwrap().as_ptr());
+
+        let unknown = zdata.with_field("compression", json!("lz4"));
+        assert!(matches!(unknown.decompressed(), Err(VmpError::TypeConversion(_))));
+    }
+
+    #[test]
+    #[cfg(feature = "compression")]
+    fn test_compression_roundtrip() {
+        let raw: Vec<u8> = (0..4096).map(|i| (i % 7) as u8).collect();
+
+        for algo in ["zstd", "gzip"] {
+            let zdata = ZData::new("test.Blob")
+                .with_binary(raw.clone())
+                .with_compression(algo)
+                .unwrap();
+            assert_eq!(zdata.get_field("compression"), Some(&json!(algo)));
+            assert!(zdata.b.as_ref().unwrap().len() < raw.len());
+
+            let bytes = crate::serializer::zdata_to_bytes(&zdata).unwrap();
+            let restored: ZData = crate::deserializer::deserialize(&bytes).unwrap();
+            assert_eq!(restored.decompressed().unwrap().as_ref(), raw.as_slice());
+        }
+
+        let zdata = ZData::new("test.Blob").with_binary(raw);
+        assert!(matches!(
+            zdata.clone().with_compression("lz4"),
+            Err(VmpError::TypeConversion(_))
+        ));
+        let compressed = zdata.with_compression("zstd").unwrap();
+        assert!(compressed.with_compression("gzip").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "compression")]
+    fn test_decompression_limit() {
+        let raw = vec![0u8; 1 << 20];
+
+        for algo in ["zstd", "gzip"] {
+            let bomb = ZData::new("test.Blob")
+                .with_binary(raw.clone())
+                .with_compression(algo)
+                .unwrap();
+            assert!(bomb.b.as_ref().unwrap().len() < 4096);
+
+            let err = bomb.decompressed_with_limit(Some(1024)).unwrap_err();
+            assert!(matches!(err, VmpError::InvalidMessage(msg) if msg.contains("1024")));
+            assert_eq!(bomb.decompressed_with_limit(Some(raw.len())).unwrap().len(), raw.len());
+            assert_eq!(bomb.decompressed_with_limit(None).unwrap().len(), raw.len());
+        }
+    }
+
+    #[test]
+    fn test_checksum() {
+        let zdata = ZData::new("test.Blob")
+            .with_binary(vec![1, 2, 3, 4])
+            .with_crc32_checksum();
+        assert_eq!(zdata.checksum, Some(ZDataChecksum::Crc32(0xb63c_fbcd)));
+        assert!(zdata.verify_checksum().is_ok());
+        assert!(zdata.extra.is_empty());
+
+        let value = serde_json::to_value(&zdata).unwrap();
+        assert_eq!(value["checksum"], json!({"crc32": 0xb63c_fbcd_u32}));
+        assert_eq!(serde_json::from_value::<ZData>(value).unwrap(), zdata);
+
+        let mut corrupted = zdata.clone();
+        let mut bytes = corrupted.b.unwrap().to_vec();
+        bytes[2] ^= 0x10;
+        corrupted.b = Some(bytes.into());
+        assert!(matches!(
+            corrupted.verify_checksum(),
+            Err(VmpError::InvalidMessage(msg)) if msg == "checksum mismatch"
+        ));
+
+        let unchecked = ZData::new("test.Blob").with_binary(vec![1, 2, 3, 4]);
+        assert!(unchecked.verify_checksum().is_ok());
+    }
+
+    #[test]
+    fn test_legacy_crc32_field() {
+        let legacy = ZData::new("test.Blob")
+            .with_binary(vec![1, 2, 3, 4])
+            .with_field("crc32", json!(0xb63c_fbcd_u32));
+        assert!(legacy.verify_checksum().is_ok());
+
+        let wrong = legacy.clone().with_field("crc32", json!(1));
+        assert!(matches!(wrong.verify_checksum(), Err(VmpError::InvalidMessage(_))));
+
+        let invalid = legacy.with_field("crc32", json!("abc"));
+        assert!(matches!(invalid.verify_checksum(), Err(VmpError::TypeConversion(_))));
+    }
+
+    #[test]
+    fn test_unknown_checksum_algorithm() {
+        let value = json!({"ztype": "test.Blob", "checksum": {"xxh3": "9f86d081"}});
+        let zdata: ZData = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(
+            zdata.checksum,
+            Some(ZDataChecksum::Other {
+                algorithm: "xxh3".to_string(),
+                value: json!("9f86d081"),
+            })
+        );
+        assert!(zdata.verify_checksum().is_ok());
+        assert_eq!(serde_json::to_value(&zdata).unwrap(), value);
+
+        // SHA-256 always decodes, and is only verified with the crypto feature
+        let digest = [7u8; 32];
+        let sha = ZData::new("test.Blob").with_binary(vec![1, 2, 3]);
+        let sha = ZData {
+            checksum: Some(ZDataChecksum::Sha256(digest)),
+            ..sha
+        };
+        let bytes = crate::serializer::serialize(&sha).unwrap();
+        let restored: ZData = crate::deserializer::deserialize(&bytes).unwrap();
+        assert_eq!(restored, sha);
+        assert_eq!(restored.verify_checksum().is_ok(), !cfg!(feature = "crypto"));
+
+        let malformed = json!({"ztype": "test.Blob", "checksum": {"crc32": "abc"}});
+        assert!(serde_json::from_value::<ZData>(malformed).is_err());
+        let two = json!({"ztype": "test.Blob", "checksum": {"crc32": 1, "sha256": [0]}});
+        assert!(serde_json::from_value::<ZData>(two).is_err());
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn test_sha256_checksum() {
+        let zdata = ZData::new("test.Blob")
+            .with_binary(vec![0u8; 4096])
+            .with_sha256_checksum();
+        assert!(matches!(zdata.checksum, Some(ZDataChecksum::Sha256(_))));
+        assert!(zdata.verify_checksum().is_ok());
+
+        let bytes = crate::serializer::serialize(&zdata).unwrap();
+        let restored: ZData = crate::deserializer::deserialize(&bytes).unwrap();
+        assert!(restored.verify_checksum().is_ok());
+
+        let mut corrupted = zdata;
+        corrupted.b = Some(vec![1u8; 4096].into());
+        assert!(corrupted.verify_checksum().is_err());
+    }
+
+    #[test]
+    fn test_clone_shares_binary() {
+        let zdata = ZData::new("test.PointCloud").with_binary(vec![7u8; 1024]);
+        let copy = zdata.clone();
+
+        assert_eq!(copy.b.as_ref().unwrap().as_ptr(), zdata.b.as_ref().unwrap().as_ptr());
+
+        // Still encoded as a MessagePack bin field
+        #[derive(Serialize)]
+        struct Reference {
+            ztype: &'static str,
+            #[serde(with = "serde_bytes")]
+            b: Vec<u8>,
+        }
+        let reference = Reference {
+            ztype: "test.PointCloud",
+            b: vec![7u8; 1024],
+        };
+        assert_eq!(
+            crate::serializer::zdata_to_bytes(&zdata).unwrap(),
+            crate::serializer::serialize(&reference).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_with_bytes_and_slice() {
+        let buffer = Bytes::from((0..16u32).flat_map(u32::to_le_bytes).collect::<Vec<_>>());
+        let zdata = ZData::new("numpy.ndarray")
+            .with_bytes(buffer.clone())
+            .with_dtype("uint32")
+            .with_shape(vec![4, 4])
+            .with_crc32_checksum();
+        assert_eq!(zdata.b.as_ref().unwrap().as_ptr(), buffer.as_ptr());
+
+        // The second row, still pointing into the original allocation
+        let row = zdata.slice(16..32).unwrap();
+        let row_bytes = row.b.as_ref().unwrap();
+        assert_eq!(row_bytes.as_ptr(), buffer[16..].as_ptr());
+        assert_eq!(row.shape, Some(vec![4]));
+        assert_eq!(row.checksum, None);
+        let values: Vec<u32> = row_bytes
+            .chunks_exact(4)
+            .map(|c| u32::from_le_bytes(c.try_into().unwrap()))
+            .collect();
+        assert_eq!(values, [4, 5, 6, 7]);
+        assert!(row.validate().is_ok());
+
+        let empty = zdata.slice(64..64).unwrap();
+        assert_eq!(empty.shape, Some(vec![0]));
+
+        assert!(zdata.slice(2..8).is_err());
+        assert!(zdata.slice(0..68).is_err());
+        assert!(ZData::new("custom.Empty").slice(0..0).is_err());
+
+        let untyped = ZData::new("custom.Blob").with_binary(vec![1, 2, 3, 4, 5]);
+        let middle = untyped.slice(1..4).unwrap();
+        assert_eq!(middle.b.as_deref(), Some(&[2, 3, 4][..]));
+        assert_eq!(middle.shape, None);
+    }
+
+    fn blob() -> ZData {
+        ZData::new("custom.Mesh")
+            .with_binary((0..=255u8).cycle().take(1000).collect::<Vec<_>>())
+            .with_dtype("uint8")
+            .with_shape(vec![1000])
+            .with_field("name", json!("terrain"))
+    }
+
+    #[test]
+    fn test_chunks_out_of_order_with_duplicates() {
+        let original = blob();
+        let chunks = split_chunks(&original, 300);
+        assert_eq!(chunks.len(), 4);
+        for chunk in &chunks {
+            assert!(chunk.is_type(CHUNK_ZTYPE));
+            assert!(chunk.b.as_ref().unwrap().len() <= 300);
+        }
+
+        let mut assembler = ChunkAssembler::new();
+        for index in [3, 1, 1, 0, 3] {
+            assert!(assembler.push(chunks[index].clone()).unwrap().is_none());
+        }
+        assert_eq!(assembler.pending(), 1);
+
+        let restored = assembler.push(chunks[2].clone()).unwrap().unwrap();
+        assert_eq!(restored, original);
+        assert_eq!(assembler.pending(), 0);
+    }
+
+    #[test]
+    fn test_single_chunk() {
+        let original = blob();
+        let chunks = split_chunks(&original, 4096);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(ChunkAssembler::new().push(chunks[0].clone()).unwrap(), Some(original));
+
+        let marker = ZData::new("custom.Marker").with_field("id", json!(7));
+        let chunks = split_chunks(&marker, 16);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(ChunkAssembler::new().push(chunks[0].clone()).unwrap(), Some(marker));
+    }
+
+    #[test]
+    fn test_chunks_rejected() {
+        let chunks = split_chunks(&blob(), 500);
+
+        // Same chunk_id but a different count
+        let mut wrong_count = chunks[1].clone();
+        wrong_count.extra.insert("chunk_count".to_string(), json!(3));
+        let mut assembler = ChunkAssembler::new();
+        assembler.push(chunks[0].clone()).unwrap();
+        assert!(matches!(assembler.push(wrong_count), Err(VmpError::InvalidMessage(_))));
+
+        // Corrupted data is caught by the content hash
+        let mut corrupted = chunks[1].clone();
+        corrupted.b = Some(Bytes::from(vec![0u8; 500]));
+        let mut assembler = ChunkAssembler::new();
+        assembler.push(chunks[0].clone()).unwrap();
+        assert!(matches!(assembler.push(corrupted), Err(VmpError::InvalidMessage(_))));
+
+        let mut out_of_range = chunks[0].clone();
+        out_of_range.extra.insert("chunk_index".to_string(), json!(2));
+        assert!(ChunkAssembler::new().push(out_of_range).is_err());
+        assert!(ChunkAssembler::new().push(blob()).is_err());
+    }
+
+    #[test]
+    fn test_chunk_assembler_limits() {
+        // A bogus count is rejected before anything is allocated for it
+        let mut bogus = split_chunks(&blob(), 500).remove(1);
+        bogus.extra.insert("chunk_count".to_string(), json!(u64::MAX));
+        let mut assembler = ChunkAssembler::new();
+        let err = assembler.push(bogus).unwrap_err();
+        assert!(matches!(err, VmpError::InvalidMessage(msg) if msg.contains("max_chunk_count")));
+        assert_eq!(assembler.pending(), 0);
+
+        let chunks = split_chunks(&blob(), 300);
+        let mut small = ChunkAssembler::new().with_max_chunk_count(3);
+        assert!(small.push(chunks[0].clone()).is_err());
+
+        // The object that least recently received a chunk makes room
+        let objects: Vec<Vec<ZData>> = (0..3).map(|_| split_chunks(&blob(), 500)).collect();
+        let mut assembler = ChunkAssembler::new().with_max_pending(2);
+        assembler.push(objects[0][0].clone()).unwrap();
+        assembler.push(objects[1][0].clone()).unwrap();
+        assembler.push(objects[2][0].clone()).unwrap();
+        assert_eq!(assembler.pending(), 2);
+        assert!(assembler.push(objects[0][1].clone()).unwrap().is_none());
+        assert_eq!(assembler.push(objects[2][1].clone()).unwrap(), Some(blob()));
+
+        // Stale objects expire
+        let mut assembler = ChunkAssembler::new().with_ttl(Duration::ZERO);
+        assembler.push(objects[1][0].clone()).unwrap();
+        assert_eq!(assembler.evict_expired(), 1);
+        assert_eq!(assembler.pending(), 0);
+    }
+
+    mod fuzz {
+        use super::*;
+        use proptest::prelude::*;
+
+        fn dtype() -> impl Strategy<Value = Option<String>> {
+            prop::option::of(prop::sample::select(vec!["uint8", "float32", "complex128", "<U5"]))
+                .prop_map(|d| d.map(str::to_string))
+        }
+
+        proptest! {
+            #[test]
+            fn test_validate_never_panics_on_random_bytes(
+                bytes in prop::collection::vec(any::<u8>(), 0..256)
+            ) {
+                if let Ok(zdata) = crate::deserializer::deserialize::<ZData>(&bytes) {
+                    let _ = zdata.validate();
+                    let _ = zdata.validate_with(true);
+                }
+            }
+
+            #[test]
+            fn test_validate_never_panics_on_random_fields(
+                ztype in ".{0,8}",
+                b in prop::option::of(prop::collection::vec(any::<u8>(), 0..64)),
+                dtype in dtype(),
+                shape in prop::option::of(prop::collection::vec(any::<usize>(), 0..4)),
+                strides in prop::option::of(prop::collection::vec(any::<isize>(), 0..4)),
+            ) {
+                let mut zdata = ZData::new(ztype);
+                zdata.b = b.map(Bytes::from);
+                zdata.dtype = dtype;
+                zdata.shape = shape;
+                zdata.strides = strides;
+
+                let _ = zdata.validate();
+                let _ = zdata.is_c_contiguous();
+                let _ = zdata.is_fortran_contiguous();
+                let _ = zdata.view_as_dtype("uint16");
+            }
+        }
+    }
+
+    fn temp_file(data: &[u8]) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("vmp-handle-{}.bin", uuid::Uuid::new_v4()));
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_handle_load_file() {
+        let data: Vec<u8> = (0..24).collect();
+        let path = temp_file(&data);
+        let handle = ZDataHandle::new("numpy.ndarray", format!("file://{}", path.display()))
+            .with_dtype("uint8")
+            .with_shape(vec![4, 6]);
+
+        // The handle travels without the payload
+        let bytes = crate::serializer::serialize(&handle).unwrap();
+        assert!(crate::deserializer::deserialize::<ZData>(&bytes).unwrap().b.is_none());
+        let restored: ZDataHandle = crate::deserializer::deserialize(&bytes).unwrap();
+        assert_eq!(restored, handle);
+
+        let access = HandleAccess::default().with_base_dir(std::env::temp_dir());
+        let zdata = restored.load(&access).unwrap();
+        assert_eq!(zdata.b.as_deref(), Some(&data[..]));
+        assert_eq!(zdata.shape, Some(vec![4, 6]));
+        assert!(zdata.get_field(HANDLE_SOURCE_KEY).is_none());
+
+        let wrong_shape = handle.clone().with_shape(vec![5, 5]);
+        assert!(wrong_shape.load(&access).is_err());
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(handle.load(&access), Err(VmpError::Io(_))));
+    }
+
+    #[test]
+    fn test_handle_access() {
+        let path = temp_file(&[7; 16]);
+        let handle = ZDataHandle::new("blob", format!("file://{}", path.display()));
+
+        // Nothing is allowed by default
+        let err = handle.load(&HandleAccess::default()).unwrap_err();
+        assert!(matches!(err, VmpError::InvalidMessage(m) if m.contains("base_dir")));
+
+        // Files outside the base directory, also through `..`
+        let base = std::env::temp_dir().join(format!("vmp-base-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir(&base).unwrap();
+        let access = HandleAccess::default().with_base_dir(&base);
+        assert!(matches!(handle.load(&access), Err(VmpError::InvalidMessage(_))));
+        let file_name = path.file_name().unwrap().to_str().unwrap();
+        let escape =
+            ZDataHandle::new("blob", format!("file://{}/../{}", base.display(), file_name));
+        assert!(matches!(escape.load(&access), Err(VmpError::InvalidMessage(_))));
+
+        // Payloads past the limit
+        let access = HandleAccess::default().with_base_dir(std::env::temp_dir());
+        assert!(handle.load(&access.clone().with_max_bytes(16)).is_ok());
+        let err = handle.load(&access.with_max_bytes(15)).unwrap_err();
+        assert!(matches!(err, VmpError::InvalidMessage(m) if m.contains("more than 15 bytes")));
+
+        // Hosts not on the allowlist, however the URL is dressed up
+        let access = HandleAccess::default().with_allowed_host("data.example.com");
+        for url in [
+            "http://evil.example.com/x.bin",
+            "https://data.example.com@evil.example.com/x.bin",
+            "http://169.254.169.254/latest",
+            "http://[::1]:8080/x.bin",
+        ] {
+            let err = ZDataHandle::new("blob", url).load(&access).unwrap_err();
+            let refused = matches!(err, VmpError::InvalidMessage(m) if m.contains("not allowed"));
+            assert!(refused, "{url}");
+        }
+        assert!(access.check_host("https://DATA.example.com:443/x.bin?q=1").is_ok());
+
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_dir(&base).unwrap();
+    }
+
+    #[test]
+    fn test_handle_invalid() {
+        let ftp = ZDataHandle::new("blob", "ftp://example.com/data.bin");
+        let access = HandleAccess::default().with_allowed_host("example.com");
+        assert!(matches!(ftp.load(&access), Err(VmpError::InvalidMessage(_))));
+
+        let inline = ZData::new("blob").with_binary(vec![1, 2, 3]);
+        assert!(ZDataHandle::try_from(inline).is_err());
+        assert!(ZDataHandle::try_from(ZData::new("blob")).is_err());
+
+        let bytes = crate::serializer::serialize(&ZData::new("blob")).unwrap();
+        assert!(crate::deserializer::deserialize::<ZDataHandle>(&bytes).is_err());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_handle_load_async() {
+        let path = temp_file(b"abcdefgh");
+        let handle = ZDataHandle::new("blob", format!("file://{}", path.display()));
+        let access = HandleAccess::default().with_base_dir(std::env::temp_dir());
+        let zdata = handle.load_async(&access).await.unwrap();
+        assert_eq!(zdata.b.as_deref(), Some(&b"abcdefgh"[..]));
+        let err = handle.load_async(&access.with_max_bytes(4)).await.unwrap_err();
+        assert!(matches!(err, VmpError::InvalidMessage(_)));
+        assert!(handle.load_async(&HandleAccess::default()).await.is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_chunked_encode_roundtrip() {
+        let zdata = ZData::new("numpy.ndarray")
+            .with_binary((0..12).collect::<Vec<u8>>())
+            .with_dtype("uint8")
+            .with_shape(vec![3, 4])
+            .with_field("name", json!("grid"))
+            .with_crc32_checksum();
+
+        // Exact multiple of the chunk size
+        let chunks = zdata.chunked_encode(4).unwrap();
+        assert_eq!(chunks.len(), 3);
+        for (index, chunk) in chunks.iter().enumerate() {
+            assert_eq!(chunk.ztype, "numpy.ndarray");
+            assert_eq!(chunk.shape, Some(vec![4]));
+            assert_eq!(chunk.get_field(CHUNK_FULL_SHAPE), Some(&json!([3, 4])));
+            assert_eq!(chunk.get_field("chunk_index"), Some(&json!(index)));
+            assert_eq!(chunk.get_field("chunk_total"), Some(&json!(3)));
+            assert_eq!(chunk.b.as_ref().unwrap().len(), 4);
+            assert!(chunk.verify_checksum().is_ok());
+            assert!(chunk.validate().is_ok());
+        }
+        assert_eq!(ZData::from_chunks(chunks).unwrap(), zdata);
+
+        // Remainder in the last chunk, reassembled out of order
+        let mut chunks = zdata.chunked_encode(5).unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[2].b.as_deref(), Some(&[10, 11][..]));
+        chunks.reverse();
+        assert_eq!(ZData::from_chunks(chunks).unwrap(), zdata);
+
+        let whole = zdata.chunked_encode(100).unwrap();
+        assert_eq!(whole.len(), 1);
+        assert_eq!(ZData::from_chunks(whole).unwrap(), zdata);
+    }
+
+    #[test]
+    fn test_chunked_encode_message_roundtrip() {
+        let zdata = ZData::new("test.Grid")
+            .with_binary((0..48).collect::<Vec<u8>>())
+            .with_dtype("float32")
+            .with_shape(vec![3, 4])
+            .with_strides(vec![16, 4])
+            .with_crc32_checksum();
+
+        // Chunks hold whole elements, so 10 bytes become 2 floats
+        let chunks = zdata.chunked_encode(10).unwrap();
+        assert_eq!(chunks.len(), 6);
+        assert!(chunks.iter().all(|chunk| chunk.shape == Some(vec![2])));
+
+        // Each chunk travels in its own message under default validation
+        let received: Vec<ZData> = chunks
+            .iter()
+            .map(|chunk| {
+                let msg = crate::types::Message::new("CHUNK")
+                    .with_data(serde_json::to_value(chunk).unwrap());
+                let bytes = crate::serializer::serialize_message(&msg).unwrap();
+                let decoded = crate::deserializer::deserialize_message(&bytes).unwrap();
+                serde_json::from_value(decoded.data.unwrap()).unwrap()
+            })
+            .collect();
+        assert_eq!(received, chunks);
+        assert_eq!(ZData::from_chunks(received).unwrap(), zdata);
+    }
+
+    #[test]
+    fn test_chunked_encode_rejected() {
+        let zdata = ZData::new("test.Blob").with_binary(vec![1u8; 10]).with_dtype("uint8");
+        assert!(zdata.chunked_encode(0).is_err());
+        assert!(ZData::from_chunks(Vec::new()).is_err());
+
+        let mut missing = zdata.chunked_encode(3).unwrap();
+        missing.remove(1);
+        assert!(ZData::from_chunks(missing).is_err());
+
+        let mut duplicated = zdata.chunked_encode(3).unwrap();
+        duplicated[2] = duplicated[1].clone();
+        assert!(ZData::from_chunks(duplicated).is_err());
+
+        let mut mismatched = zdata.chunked_encode(3).unwrap();
+        mismatched[0].dtype = Some("int8".to_string());
+        assert!(ZData::from_chunks(mismatched).is_err());
+
+        let mut unindexed = zdata.chunked_encode(3).unwrap();
+        unindexed[3].extra.shift_remove("chunk_index");
+        assert!(matches!(
+            ZData::from_chunks(unindexed),
+            Err(VmpError::MissingField(_))
+        ));
     }
 }